@@ -1,4 +1,5 @@
 use std::convert::TryFrom;
+use std::fmt;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use super::{InvalidAddress, MacAddr};
@@ -69,6 +70,16 @@ impl Address {
     }
 }
 
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Address::Ipv4(ip) => ip.fmt(f),
+            Address::Ipv6(ip) => ip.fmt(f),
+            Address::Mac(mac) => mac.fmt(f),
+        }
+    }
+}
+
 impl From<Ipv4Addr> for Address {
     fn from(ip: Ipv4Addr) -> Self {
         Address::Ipv4(ip)
@@ -186,3 +197,64 @@ impl TryFrom<Address> for MacAddr {
         }
     }
 }
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Address {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Address::Ipv4(ip) => serializer.collect_str(ip),
+            Address::Ipv6(ip) => serializer.collect_str(ip),
+            Address::Mac(mac) => serializer.collect_str(mac),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Address {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AddressVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for AddressVisitor {
+            type Value = Address;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an IPv4, IPv6 or MAC address string")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                if let Ok(ip) = v.parse::<IpAddr>() {
+                    return Ok(ip.into());
+                }
+                v.parse::<MacAddr>().map(Address::from).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(AddressVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Address;
+
+    #[test]
+    fn test_display() {
+        for s in ["192.0.2.1", "2001:db8::1"] {
+            let addr = Address::from(s.parse::<std::net::IpAddr>().unwrap());
+            assert_eq!(addr.to_string(), s);
+        }
+        let mac = Address::from([0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        assert_eq!(mac.to_string(), "00:11:22:33:44:55");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_roundtrip() {
+        for s in ["192.0.2.1", "2001:db8::1", "00:11:22:33:44:55"] {
+            let json = format!("\"{}\"", s);
+            let addr: Address = serde_json::from_str(&json).unwrap();
+            assert_eq!(serde_json::to_string(&addr).unwrap(), json);
+        }
+        assert!(serde_json::from_str::<Address>("\"foo\"").is_err());
+    }
+}
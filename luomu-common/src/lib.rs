@@ -17,6 +17,9 @@ use std::fmt;
 mod address;
 pub use address::Address;
 
+#[cfg(any(unix, windows))]
+mod sockaddr;
+
 mod macaddr;
 pub use macaddr::MacAddr;
 
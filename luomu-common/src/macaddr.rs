@@ -14,6 +14,14 @@ use super::InvalidAddress;
 pub struct MacAddr([u8; 6]);
 
 impl MacAddr {
+    /// The broadcast address `ff:ff:ff:ff:ff:ff`.
+    pub const BROADCAST: MacAddr = MacAddr([0xff; 6]);
+
+    /// checks if this address is the broadcast address `ff:ff:ff:ff:ff:ff`.
+    pub fn is_broadcast(&self) -> bool {
+        *self == Self::BROADCAST
+    }
+
     /// checks if this address is multicast address.
     pub fn is_multicast(&self) -> bool {
         // https://en.wikipedia.org/wiki/MAC_address#Unicast_vs._multicast_(I/G_bit)
@@ -22,6 +30,24 @@ impl MacAddr {
         // the frame is meant to reach only one receiving NIC
         (self.0[0] & 0x01) == 0x01
     }
+
+    /// checks if this address is locally administered address.
+    pub fn is_locally_administered(&self) -> bool {
+        // https://en.wikipedia.org/wiki/MAC_address#Universal_vs._local_(U/L_bit)
+        // The second-least-significant bit of the first octet is referred to
+        // as the U/L, or Universal/Local, bit. When this bit is 1 the address
+        // is locally administered.
+        (self.0[0] & 0x02) == 0x02
+    }
+}
+
+// Parse one group of hex digits from MAC address string. Group must be
+// non-empty and consist only of hex digits.
+fn parse_hex_group(s: &str, max_len: usize) -> Result<u16, InvalidAddress> {
+    if s.is_empty() || s.len() > max_len || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(InvalidAddress);
+    }
+    u16::from_str_radix(s, 16).map_err(|_e| InvalidAddress)
 }
 
 impl From<[u8; 6]> for MacAddr {
@@ -67,6 +93,11 @@ impl TryFrom<u64> for MacAddr {
     }
 }
 
+/// Parse MAC address from string.
+///
+/// Supported formats are colon separated (`00:11:22:33:44:55`), dash separated
+/// (`00-11-22-33-44-55`) and dot separated groups of four hex digits
+/// (`0011.2233.4455`).
 impl FromStr for MacAddr {
     type Err = InvalidAddress;
 
@@ -74,13 +105,32 @@ impl FromStr for MacAddr {
         if s.is_empty() || s.len() > 17 {
             return Err(InvalidAddress);
         }
-        let parts = s.split(':').collect::<Vec<&str>>();
+
+        let mut val = [0u8; 6];
+
+        if s.contains('.') {
+            let parts = s.split('.').collect::<Vec<&str>>();
+            if parts.len() != 3 {
+                return Err(InvalidAddress);
+            }
+            for (idx, v) in parts.iter().enumerate() {
+                if v.len() != 4 {
+                    return Err(InvalidAddress);
+                }
+                let [hi, lo] = parse_hex_group(v, 4)?.to_be_bytes();
+                val[idx * 2] = hi;
+                val[idx * 2 + 1] = lo;
+            }
+            return Ok(MacAddr(val));
+        }
+
+        let sep = if s.contains('-') { '-' } else { ':' };
+        let parts = s.split(sep).collect::<Vec<&str>>();
         if parts.len() != 6 {
             return Err(InvalidAddress);
         }
-        let mut val = [0u8; 6];
-        for (idx, v) in parts.iter().take(6).enumerate() {
-            val[idx] = u8::from_str_radix(v, 16).map_err(|_e| InvalidAddress {})?;
+        for (idx, v) in parts.iter().enumerate() {
+            val[idx] = parse_hex_group(v, 2)? as u8;
         }
         Ok(MacAddr(val))
     }
@@ -114,6 +164,34 @@ impl std::ops::Deref for MacAddr {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for MacAddr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MacAddr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MacAddrVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for MacAddrVisitor {
+            type Value = MacAddr;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a MAC address string")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(MacAddrVisitor)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::convert::{TryFrom, TryInto};
@@ -173,6 +251,23 @@ mod tests {
         assert!(MacAddr::try_from("00:11:22:zz:44:55").is_err()); // invalid bytes
         assert!(MacAddr::try_from("00::22:33:44:55").is_err()); // omitted value
     }
+    #[test]
+    fn test_parse_formats() {
+        let expected: MacAddr = [0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc].into();
+        assert_eq!("00:11:22:aa:bb:cc".parse::<MacAddr>().unwrap(), expected);
+        assert_eq!("00-11-22-AA-BB-CC".parse::<MacAddr>().unwrap(), expected);
+        assert_eq!("0011.22aa.bbcc".parse::<MacAddr>().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_formats_invalid() {
+        assert!("00-11-22:33-44-55".parse::<MacAddr>().is_err()); // mixed
+        assert!("0011.2233".parse::<MacAddr>().is_err()); // short
+        assert!("011.2233.4455".parse::<MacAddr>().is_err()); // short group
+        assert!("0011.2233.44zz".parse::<MacAddr>().is_err()); // invalid bytes
+        assert!("+0:11:22:33:44:55".parse::<MacAddr>().is_err()); // sign
+    }
+
     #[test]
     fn test_parse_invalid() {
         assert!("".parse::<MacAddr>().is_err());
@@ -200,6 +295,34 @@ mod tests {
         assert!(addr3.is_multicast());
     }
 
+    #[test]
+    fn test_is_broadcast() {
+        let addr1: MacAddr = "00:11:22:33:44:55".parse().unwrap();
+        assert!(!addr1.is_broadcast());
+        let addr2: MacAddr = "ff:ff:ff:ff:ff:ff".parse().unwrap();
+        assert!(addr2.is_broadcast());
+        assert_eq!(addr2, MacAddr::BROADCAST);
+    }
+
+    #[test]
+    fn test_is_locally_administered() {
+        let addr1: MacAddr = "00:11:22:33:44:55".parse().unwrap();
+        assert!(!addr1.is_locally_administered());
+        let addr2: MacAddr = "02:00:00:00:00:01".parse().unwrap();
+        assert!(addr2.is_locally_administered());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_roundtrip() {
+        let mac: MacAddr = "00:11:22:33:44:55".parse().unwrap();
+        let json = serde_json::to_string(&mac).unwrap();
+        assert_eq!(json, "\"00:11:22:33:44:55\"");
+        let parsed: MacAddr = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, mac);
+        assert!(serde_json::from_str::<MacAddr>("\"00:11\"").is_err());
+    }
+
     quickcheck! {
         fn prop_macaddr_to_from(xs: (u8, u8, u8, u8, u8, u8)) -> bool {
             let b1 = &[xs.0, xs.1, xs.2, xs.3, xs.4, xs.5];
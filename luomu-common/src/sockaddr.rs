@@ -0,0 +1,170 @@
+//! Conversions from raw `sockaddr` structures into [Address].
+#![allow(unsafe_code)]
+
+#[cfg(unix)]
+use std::convert::TryFrom;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use super::Address;
+#[cfg(unix)]
+use super::InvalidAddress;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use super::MacAddr;
+#[cfg(unix)]
+use libc as sys;
+
+// Winsock address structures, which libc doesn't have on Windows.
+#[cfg(windows)]
+#[allow(non_camel_case_types)]
+mod sys {
+    pub const AF_INET: i32 = 2;
+    pub const AF_INET6: i32 = 23;
+
+    #[repr(C)]
+    pub struct in_addr {
+        pub s_addr: u32,
+    }
+
+    #[repr(C)]
+    pub struct sockaddr_in {
+        pub sin_family: u16,
+        pub sin_port: u16,
+        pub sin_addr: in_addr,
+        pub sin_zero: [u8; 8],
+    }
+
+    #[repr(C)]
+    pub struct in6_addr {
+        pub s6_addr: [u8; 16],
+    }
+
+    #[repr(C)]
+    pub struct sockaddr_in6 {
+        pub sin6_family: u16,
+        pub sin6_port: u16,
+        pub sin6_flowinfo: u32,
+        pub sin6_addr: in6_addr,
+        pub sin6_scope_id: u32,
+    }
+}
+
+impl Address {
+    /// Construct an `Address` from raw `sockaddr`.
+    ///
+    /// IPv4 (`AF_INET`), IPv6 (`AF_INET6`) and link-layer addresses
+    /// (`AF_PACKET` on Linux, `AF_LINK` on macOS) are supported. Returns `None`
+    /// if `addr` is null or the address family is not supported.
+    ///
+    /// # Safety
+    ///
+    /// `addr` must be null or point to a valid `sockaddr` which is large enough
+    /// for the address family given in its `sa_family` field.
+    pub unsafe fn from_sockaddr(addr: *const libc::sockaddr) -> Option<Address> {
+        if addr.is_null() {
+            return None;
+        }
+
+        let family = (*addr).sa_family;
+
+        match i32::from(family) {
+            sys::AF_INET => {
+                let inet4: *const sys::sockaddr_in = addr as *const sys::sockaddr_in;
+                let s_addr: u32 = (*inet4).sin_addr.s_addr;
+                Some(Ipv4Addr::from(u32::from_be(s_addr)).into())
+            }
+
+            sys::AF_INET6 => {
+                let inet6: *const sys::sockaddr_in6 = addr as *const sys::sockaddr_in6;
+                let s6_addr: [u8; 16] = (*inet6).sin6_addr.s6_addr;
+                Some(Ipv6Addr::from(s6_addr).into())
+            }
+
+            #[cfg(target_os = "macos")]
+            libc::AF_LINK => {
+                let dl_sock: *const libc::sockaddr_dl = addr as *const libc::sockaddr_dl;
+                let start = (*dl_sock).sdl_nlen as usize;
+                let sdl_data = &(*dl_sock).sdl_data;
+                let dl_addr: [u8; 6] = [
+                    *sdl_data.get(start)? as u8,
+                    *sdl_data.get(start + 1)? as u8,
+                    *sdl_data.get(start + 2)? as u8,
+                    *sdl_data.get(start + 3)? as u8,
+                    *sdl_data.get(start + 4)? as u8,
+                    *sdl_data.get(start + 5)? as u8,
+                ];
+                Some(MacAddr::from(dl_addr).into())
+            }
+
+            #[cfg(target_os = "linux")]
+            libc::AF_PACKET => {
+                let ll_sock: *const libc::sockaddr_ll = addr as *const libc::sockaddr_ll;
+                let mut ll_addr = [0u8; 6];
+                ll_addr.copy_from_slice(&(&(*ll_sock).sll_addr)[0..6]);
+                Some(MacAddr::from(ll_addr).into())
+            }
+
+            _ => None,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl TryFrom<&libc::sockaddr_storage> for Address {
+    type Error = InvalidAddress;
+
+    fn try_from(storage: &libc::sockaddr_storage) -> Result<Self, Self::Error> {
+        // sockaddr_storage is large enough to hold any of the supported
+        // address families.
+        let addr = storage as *const libc::sockaddr_storage as *const libc::sockaddr;
+        unsafe { Address::from_sockaddr(addr) }.ok_or(InvalidAddress)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::convert::TryFrom;
+    use std::mem::MaybeUninit;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::Address;
+
+    fn storage() -> libc::sockaddr_storage {
+        unsafe { MaybeUninit::zeroed().assume_init() }
+    }
+
+    #[test]
+    fn test_from_sockaddr_null() {
+        assert!(unsafe { Address::from_sockaddr(std::ptr::null()) }.is_none());
+    }
+
+    #[test]
+    fn test_try_from_sockaddr_storage_ipv4() {
+        let ip = Ipv4Addr::new(192, 0, 2, 1);
+        let mut storage = storage();
+        let sin = &mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in;
+        unsafe {
+            (*sin).sin_family = libc::AF_INET as libc::sa_family_t;
+            (*sin).sin_addr.s_addr = u32::from(ip).to_be();
+        }
+        assert_eq!(Address::try_from(&storage).unwrap(), Address::from(ip));
+    }
+
+    #[test]
+    fn test_try_from_sockaddr_storage_ipv6() {
+        let ip: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let mut storage = storage();
+        let sin6 = &mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in6;
+        unsafe {
+            (*sin6).sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            (*sin6).sin6_addr.s6_addr = ip.octets();
+        }
+        assert_eq!(Address::try_from(&storage).unwrap(), Address::from(ip));
+    }
+
+    #[test]
+    fn test_try_from_sockaddr_storage_unsupported() {
+        let mut storage = storage();
+        storage.ss_family = libc::AF_UNIX as libc::sa_family_t;
+        assert!(Address::try_from(&storage).is_err());
+    }
+}
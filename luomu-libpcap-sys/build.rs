@@ -10,6 +10,36 @@ use tar::Archive;
 static VERSION: &str = "1.10.4";
 
 fn main() -> io::Result<()> {
+    let dynamic = env::var_os("CARGO_FEATURE_DYNAMIC").is_some();
+    let static_ = env::var_os("CARGO_FEATURE_STATIC").is_some();
+    let dlopen = env::var_os("CARGO_FEATURE_DLOPEN").is_some();
+    if [dynamic, static_, dlopen].iter().filter(|f| **f).count() > 1 {
+        panic!("only one of features \"dynamic\", \"static\" and \"dlopen\" can be enabled");
+    }
+    if dlopen {
+        let out_dir = env::var("OUT_DIR").expect("environment variable OUT_DIR");
+        for bindings in ["pcap.rs", "windows.rs"] {
+            println!("cargo:rerun-if-changed=src/{}", bindings);
+            let source = fs::read_to_string(Path::new("src").join(bindings))?;
+            fs::write(Path::new(&out_dir).join(bindings), dlopen_bindings(&source))?;
+        }
+        return Ok(());
+    }
+    if dynamic {
+        link_system("dylib");
+        return Ok(());
+    }
+    if static_ {
+        link_system("static");
+        return Ok(());
+    }
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=LIBPCAP_CONFIGURE_ARGS");
+    println!("cargo:rerun-if-env-changed=LIBPCAP_CPPFLAGS");
+    println!("cargo:rerun-if-env-changed=LIBPCAP_LDFLAGS");
+    println!("cargo:rerun-if-changed=libpcap-{}.tar.gz", VERSION);
+
     let out_dir = env::var("OUT_DIR").expect("environment variable OUT_DIR");
     let source_dir = unpack_libpcap(&out_dir)?;
     compile(&out_dir, &source_dir)?;
@@ -22,6 +52,125 @@ fn main() -> io::Result<()> {
     Ok(())
 }
 
+// Link with libpcap of the system instead of building the bundled source.
+// The library is looked up from LIBPCAP_LIB_DIR, if set, in addition to the
+// default search path of the linker.
+fn link_system(kind: &str) {
+    println!("cargo:rerun-if-env-changed=LIBPCAP_LIB_DIR");
+    if let Some(libdir) = env::var_os("LIBPCAP_LIB_DIR") {
+        println!(
+            "cargo:rustc-link-search=native={}",
+            Path::new(&libdir).display()
+        );
+    }
+    // Npcap and WinPcap name the library wpcap.
+    let name = match env::var("CARGO_CFG_TARGET_OS").as_deref() {
+        Ok("windows") => "wpcap",
+        _ => "pcap",
+    };
+    println!("cargo:rustc-link-lib={}={}", kind, name);
+}
+
+// Rewrite declarations of functions in bindings into functions calling the
+// function looked up from libpcap loaded at runtime. Everything else is kept
+// as is. Each function is declared in an extern block of its own, as bindgen
+// writes them.
+fn dlopen_bindings(source: &str) -> String {
+    let mut out = String::new();
+    let mut lines = source.lines();
+    while let Some(line) = lines.next() {
+        if line != "extern \"C\" {" {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        }
+        let mut decl = String::new();
+        for line in lines.by_ref() {
+            if line == "}" {
+                break;
+            }
+            decl.push_str(line.trim());
+            decl.push(' ');
+        }
+        out.push_str(&dlopen_function(&decl));
+    }
+    out
+}
+
+// Function calling `decl`, such as "pub fn pcap_close(arg1: *mut pcap_t);",
+// through a pointer looked up at runtime.
+fn dlopen_function(decl: &str) -> String {
+    let decl = decl
+        .trim()
+        .strip_prefix("pub fn ")
+        .and_then(|decl| decl.strip_suffix(';'))
+        .unwrap_or_else(|| panic!("unexpected declaration in bindings: {}", decl));
+    let (name, rest) = decl.split_once('(').expect("arguments");
+
+    // Split arguments at commas outside of nested types.
+    let mut depth = 0;
+    let mut end = 0;
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    for (i, c) in rest.char_indices() {
+        match c {
+            '(' | '<' | '[' => depth += 1,
+            ')' if depth == 0 => {
+                end = i;
+                break;
+            }
+            ')' | '>' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(std::mem::take(&mut arg));
+                continue;
+            }
+            _ => (),
+        }
+        arg.push(c);
+    }
+    args.push(arg);
+    let args: Vec<&str> = args
+        .iter()
+        .map(|arg| arg.trim())
+        .filter(|arg| !arg.is_empty())
+        .collect();
+    let names: Vec<&str> = args
+        .iter()
+        .map(|arg| arg.split(':').next().unwrap().trim())
+        .collect();
+    let ret = rest[end + 1..].trim();
+
+    // Functions taking an error buffer get the reason for failing in it. In
+    // libpcap the buffer is the last `char *` argument.
+    let set_error = args
+        .iter()
+        .zip(&names)
+        .rev()
+        .find(|(arg, _)| arg.ends_with(": *mut ::std::os::raw::c_char"))
+        .map(|(_, name)| format!("SYMBOL.set_error({});\n            ", name))
+        .unwrap_or_default();
+
+    format!(
+        "pub unsafe extern \"C\" fn {name}({args}) {ret} {{\n    \
+             static SYMBOL: crate::dynamic::Symbol = crate::dynamic::Symbol::new(\"{name}\\0\");\n    \
+             match SYMBOL.get() {{\n        \
+                 Some(ptr) => {{\n            \
+                     let f: unsafe extern \"C\" fn({args}) {ret} = ::std::mem::transmute(ptr);\n            \
+                     f({names})\n        \
+                 }}\n        \
+                 None => {{\n            \
+                     {set_error}crate::dynamic::Fallback::fallback()\n        \
+                 }}\n    \
+             }}\n\
+         }}\n",
+        name = name,
+        args = args.join(", "),
+        ret = ret,
+        names = names.join(", "),
+        set_error = set_error,
+    )
+}
+
 fn unpack_libpcap(out_dir: &str) -> io::Result<PathBuf> {
     eprintln!("*** UNPACK_LIBPCAP");
     let dest = format!("{}/src", out_dir);
@@ -47,17 +196,29 @@ fn unpack_libpcap(out_dir: &str) -> io::Result<PathBuf> {
 
 fn compile(out_dir: &str, source_dir: &Path) -> io::Result<()> {
     eprintln!("*** COMPILE");
-    let target_arg = format!("--target={}", env::var("TARGET").unwrap());
-    let host_arg = format!("--host={}", env::var("HOST").unwrap());
+    // In autoconf terms the build system is Cargo's host and the host system
+    // is Cargo's target.
+    let build_arg = format!("--build={}", env::var("HOST").unwrap());
+    let host_arg = format!("--host={}", env::var("TARGET").unwrap());
     let j_arg = format!("-j{}", env::var("NUM_JOBS").unwrap());
 
-    let compiler = cc::Build::new().get_compiler();
+    // Use the same toolchain as cc crate, which knows the compilers and flags
+    // of cross targets and honors CC_<target>, CFLAGS_<target>, AR_<target>
+    // and so on.
+    let build = cc::Build::new();
+    let compiler = build.get_compiler();
     let cc = compiler.path().to_string_lossy();
-    // panic!("cc = {}", cc);
+    let cflags = compiler.cflags_env();
+    let ar = build.get_archiver();
+    let ranlib = build.get_ranlib();
 
-    let output = Command::new(source_dir.join("configure"))
+    let mut configure = Command::new(source_dir.join("configure"));
+    configure
         .current_dir(source_dir)
         .arg(format!("CC={}", cc))
+        .arg(format!("CFLAGS={}", cflags.to_string_lossy()))
+        .arg(format!("AR={}", ar.get_program().to_string_lossy()))
+        .arg(format!("RANLIB={}", ranlib.get_program().to_string_lossy()))
         .arg("--prefix")
         .arg(out_dir)
         .arg("--disable-universal")
@@ -65,9 +226,29 @@ fn compile(out_dir: &str, source_dir: &Path) -> io::Result<()> {
         .arg("--enable-usb=no")
         .arg("--without-libnl")
         .arg("--disable-dbus")
-        .arg(target_arg)
-        .arg(host_arg)
-        .output()?;
+        .arg(build_arg)
+        .arg(host_arg);
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("android") {
+        // configure can't guess the capture mechanism of Android, and the NDK
+        // lacks headers of Bluetooth and RDMA capture.
+        configure
+            .arg("--with-pcap=linux")
+            .arg("--disable-bluetooth")
+            .arg("--disable-rdma");
+    }
+    // Flags with paths to headers and libraries of a sysroot, which may
+    // contain spaces.
+    if let Ok(cppflags) = env::var("LIBPCAP_CPPFLAGS") {
+        configure.arg(format!("CPPFLAGS={}", cppflags));
+    }
+    if let Ok(ldflags) = env::var("LIBPCAP_LDFLAGS") {
+        configure.arg(format!("LDFLAGS={}", ldflags));
+    }
+    // Other extra arguments, separated by whitespace.
+    if let Ok(args) = env::var("LIBPCAP_CONFIGURE_ARGS") {
+        configure.args(args.split_whitespace());
+    }
+    let output = configure.output()?;
     if !output.status.success() {
         panic!(
             "\nSTDOUT:\n{}\n\nSTDERR:\n{}\n",
@@ -13,6 +13,7 @@ bindgen \
     "${DEST}/${LIBPCAP}/pcap/pcap.h" \
     -o src/pcap.rs \
     --allowlist-function='^pcap_.*' \
+    --allowlist-function='^bpf_image$' \
     --allowlist-type='^pcap_.*' \
     --allowlist-var='^PCAP_.*' \
     --allowlist-var='^DLT_.*' \
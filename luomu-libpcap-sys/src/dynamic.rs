@@ -0,0 +1,235 @@
+// Loading libpcap at runtime for the "dlopen" feature.
+//
+// With the feature the bindings are functions which look up the function of
+// the same name from libpcap on first call, instead of declarations resolved
+// when linking. libpcap is loaded when the first function is called or with
+// `load()`. If libpcap can't be loaded or lacks the function, such as one
+// added in a later version, the function returns PCAP_ERROR or null, and
+// writes the reason into its errbuf argument if it has one.
+
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::OnceLock;
+
+// Environment variable with the path of the library to load.
+const PATH_VAR: &str = "LIBPCAP_PATH";
+
+#[cfg(target_os = "macos")]
+const NAMES: &[&str] = &["libpcap.A.dylib", "libpcap.dylib"];
+#[cfg(windows)]
+const NAMES: &[&str] = &["wpcap.dll"];
+#[cfg(not(any(target_os = "macos", windows)))]
+const NAMES: &[&str] = &["libpcap.so.1", "libpcap.so", "libpcap.so.0.8"];
+
+struct Library(*mut c_void);
+
+// The handle is only used to look up symbols, which is thread safe.
+unsafe impl Send for Library {}
+unsafe impl Sync for Library {}
+
+static LIBRARY: OnceLock<Result<Library, String>> = OnceLock::new();
+
+/// Load libpcap.
+///
+/// The library is loaded from the path in `LIBPCAP_PATH` environment
+/// variable if set, otherwise by its usual names from the default search
+/// path: `libpcap.so.1` on Linux and other Unixes, `libpcap.A.dylib` on macOS
+/// and `wpcap.dll` of Npcap on Windows. The library is loaded only once; later
+/// calls return the outcome of the first.
+///
+/// Returns an error describing why the library couldn't be loaded, in which
+/// case the functions of this crate fail without calling into libpcap.
+pub fn load() -> Result<(), String> {
+    match LIBRARY.get_or_init(open) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(err.clone()),
+    }
+}
+
+fn open() -> Result<Library, String> {
+    let names: Vec<String> = match std::env::var(PATH_VAR) {
+        Ok(path) => vec![path],
+        Err(_) => NAMES.iter().map(|name| name.to_string()).collect(),
+    };
+    let mut errors = Vec::new();
+    for name in &names {
+        match sys::open(name) {
+            Ok(handle) => return Ok(Library(handle)),
+            Err(err) => errors.push(err),
+        }
+    }
+    Err(format!("libpcap not installed: {}", errors.join("; ")))
+}
+
+/// Function looked up from libpcap. Used by the bindings.
+#[doc(hidden)]
+pub struct Symbol {
+    name: &'static str,
+    ptr: AtomicPtr<c_void>,
+}
+
+impl Symbol {
+    /// Symbol for function `name`, which must end with a NUL.
+    pub const fn new(name: &'static str) -> Symbol {
+        Symbol {
+            name,
+            ptr: AtomicPtr::new(std::ptr::null_mut()),
+        }
+    }
+
+    /// Address of the function, or `None` if libpcap can't be loaded or it
+    /// doesn't have the function.
+    pub fn get(&self) -> Option<*mut c_void> {
+        let ptr = self.ptr.load(Ordering::Relaxed);
+        if !ptr.is_null() {
+            return Some(ptr);
+        }
+        let library = LIBRARY.get_or_init(open).as_ref().ok()?;
+        let name = CStr::from_bytes_with_nul(self.name.as_bytes()).ok()?;
+        let ptr = unsafe { sys::symbol(library.0, name) };
+        if ptr.is_null() {
+            return None;
+        }
+        self.ptr.store(ptr, Ordering::Relaxed);
+        Some(ptr)
+    }
+
+    /// Write the reason the function can't be called into `errbuf` of
+    /// `PCAP_ERRBUF_SIZE` bytes.
+    ///
+    /// # Safety
+    ///
+    /// `errbuf` must be null or point to at least `PCAP_ERRBUF_SIZE` bytes.
+    pub unsafe fn set_error(&self, errbuf: *mut c_char) {
+        if errbuf.is_null() {
+            return;
+        }
+        let msg = match LIBRARY.get_or_init(open) {
+            Ok(_) => format!(
+                "libpcap has no function {}",
+                self.name.trim_end_matches('\0')
+            ),
+            Err(err) => err.clone(),
+        };
+        let len = msg.len().min(crate::PCAP_ERRBUF_SIZE as usize - 1);
+        std::ptr::copy_nonoverlapping(msg.as_ptr() as *const c_char, errbuf, len);
+        *errbuf.add(len) = 0;
+    }
+}
+
+/// Value returned by a function which can't be called: `PCAP_ERROR` for
+/// status codes and null for pointers. Used by the bindings.
+#[doc(hidden)]
+pub trait Fallback {
+    fn fallback() -> Self;
+}
+
+impl Fallback for () {
+    fn fallback() -> Self {}
+}
+
+impl Fallback for i32 {
+    fn fallback() -> Self {
+        crate::PCAP_ERROR
+    }
+}
+
+impl Fallback for i64 {
+    fn fallback() -> Self {
+        crate::PCAP_ERROR as i64
+    }
+}
+
+impl<T> Fallback for *mut T {
+    fn fallback() -> Self {
+        std::ptr::null_mut()
+    }
+}
+
+impl<T> Fallback for *const T {
+    fn fallback() -> Self {
+        std::ptr::null()
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::ffi::{c_void, CStr};
+
+    pub(super) fn open(name: &str) -> Result<*mut c_void, String> {
+        let cname = super::cstring(name)?;
+        let handle = unsafe { libc::dlopen(cname.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            let err = unsafe { libc::dlerror() };
+            if err.is_null() {
+                return Err(format!("could not load {}", name));
+            }
+            return Err(unsafe { CStr::from_ptr(err) }
+                .to_string_lossy()
+                .into_owned());
+        }
+        Ok(handle)
+    }
+
+    pub(super) unsafe fn symbol(handle: *mut c_void, name: &CStr) -> *mut c_void {
+        libc::dlsym(handle, name.as_ptr())
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::{c_char, c_void, CStr};
+
+    const LOAD_WITH_ALTERED_SEARCH_PATH: u32 = 0x8;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn LoadLibraryExA(name: *const c_char, file: *mut c_void, flags: u32) -> *mut c_void;
+        fn GetProcAddress(module: *mut c_void, name: *const c_char) -> *mut c_void;
+        fn GetSystemDirectoryA(buffer: *mut c_char, size: u32) -> u32;
+        fn GetLastError() -> u32;
+    }
+
+    // Npcap installs wpcap.dll into System32\Npcap, which is not in the
+    // search path, unless installed in WinPcap compatible mode.
+    fn npcap_path(name: &str) -> Option<String> {
+        let mut buffer = [0 as c_char; 260];
+        let len = unsafe { GetSystemDirectoryA(buffer.as_mut_ptr(), buffer.len() as u32) };
+        if len == 0 || len as usize >= buffer.len() {
+            return None;
+        }
+        let dir = unsafe { CStr::from_ptr(buffer.as_ptr()) };
+        Some(format!("{}\\Npcap\\{}", dir.to_string_lossy(), name))
+    }
+
+    fn load(path: &str, flags: u32) -> Result<*mut c_void, String> {
+        let cpath = super::cstring(path)?;
+        let handle = unsafe { LoadLibraryExA(cpath.as_ptr(), std::ptr::null_mut(), flags) };
+        if handle.is_null() {
+            return Err(format!("could not load {}: error {}", path, unsafe {
+                GetLastError()
+            }));
+        }
+        Ok(handle)
+    }
+
+    pub(super) fn open(name: &str) -> Result<*mut c_void, String> {
+        let err = match load(name, 0) {
+            Ok(handle) => return Ok(handle),
+            Err(err) => err,
+        };
+        // Load Packet.dll of Npcap from the same directory.
+        match npcap_path(name) {
+            Some(path) if !name.contains('\\') => load(&path, LOAD_WITH_ALTERED_SEARCH_PATH),
+            _ => Err(err),
+        }
+    }
+
+    pub(super) unsafe fn symbol(handle: *mut c_void, name: &CStr) -> *mut c_void {
+        GetProcAddress(handle, name.as_ptr())
+    }
+}
+
+fn cstring(name: &str) -> Result<CString, String> {
+    CString::new(name).map_err(|_| format!("invalid library name {:?}", name))
+}
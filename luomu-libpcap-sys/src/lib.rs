@@ -1,3 +1,18 @@
 #![allow(non_camel_case_types, non_snake_case, non_upper_case_globals)]
+// The bindings are unsafe functions instead of extern declarations.
+#![cfg_attr(feature = "dlopen", allow(clippy::missing_safety_doc))]
 
+#[cfg(not(feature = "dlopen"))]
 include!("pcap.rs");
+#[cfg(feature = "dlopen")]
+include!(concat!(env!("OUT_DIR"), "/pcap.rs"));
+
+#[cfg(all(windows, not(feature = "dlopen")))]
+include!("windows.rs");
+#[cfg(all(windows, feature = "dlopen"))]
+include!(concat!(env!("OUT_DIR"), "/windows.rs"));
+
+#[cfg(feature = "dlopen")]
+mod dynamic;
+#[cfg(feature = "dlopen")]
+pub use dynamic::load;
@@ -385,6 +385,12 @@ fn bindgen_test_layout_bpf_insn() {
         )
     );
 }
+extern "C" {
+    pub fn bpf_image(
+        arg1: *const bpf_insn,
+        arg2: ::std::os::raw::c_int,
+    ) -> *mut ::std::os::raw::c_char;
+}
 // pub type FILE = [u64; 19usize];
 pub use libc::FILE;
 #[repr(C)]
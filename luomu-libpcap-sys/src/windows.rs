@@ -0,0 +1,20 @@
+// Functions declared in pcap.h only on Windows. generate.sh runs bindgen on
+// Unix where these are not visible, so they are declared here by hand.
+
+extern "C" {
+    pub fn pcap_live_dump(
+        p: *mut pcap_t,
+        filename: *mut ::std::os::raw::c_char,
+        maxsize: ::std::os::raw::c_int,
+        maxpacks: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn pcap_live_dump_ended(
+        p: *mut pcap_t,
+        sync: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn pcap_getevent(p: *mut pcap_t) -> *mut ::std::os::raw::c_void;
+}
@@ -1,11 +1,10 @@
-use luomu_libpcap::{PcapIfT, Result};
+use luomu_libpcap::Result;
 
 fn main() -> Result<()> {
     env_logger::init();
 
-    let pcap_ifs = PcapIfT::new()?;
-    for interface in pcap_ifs.get_interfaces() {
-        println!("{:#?}", interface);
+    for (n, interface) in luomu_libpcap::interfaces()?.iter().enumerate() {
+        println!("{}.{}", n + 1, interface);
     }
 
     Ok(())
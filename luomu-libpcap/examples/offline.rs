@@ -13,6 +13,12 @@ fn main() {
     };
 
     let pcap = Pcap::offline(&fname).unwrap();
+    if let Some(filter) = env::args().nth(2) {
+        pcap.set_filter(&filter).unwrap();
+    }
+    if let Some(info) = pcap.savefile_info() {
+        println!("{:?}", info);
+    }
     for (count, pkt) in pcap.capture().enumerate() {
         let packet = pkt.packet();
         let mut hex = String::new();
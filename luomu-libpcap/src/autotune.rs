@@ -0,0 +1,161 @@
+use crate::{Error, Pcap, Result};
+
+/// Outcome of `BufferTuner::check()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TuneAction {
+    /// Nothing was done.
+    Unchanged,
+    /// Capture was reopened with a larger buffer.
+    Resized {
+        /// Buffer size before reopening.
+        old: usize,
+        /// Buffer size after reopening.
+        new: usize,
+    },
+    /// Packets are dropped but the buffer is already at the maximum size.
+    AtLimit,
+}
+
+/// Buffer size auto-tuner based on drop statistics.
+///
+/// Call `BufferTuner::check()` periodically, for example once every few
+/// seconds from the capture loop. If libpcap reports packets dropped because
+/// of lack of buffer space for `patience` consecutive checks, the capture is
+/// reopened with a buffer `growth` times larger, up to `max_buffer_size`.
+///
+/// The capture is reopened from `Pcap::config()`, so options and filter are
+/// preserved, but only captures created with `PcapBuilder` can be tuned.
+/// Packets still waiting in the buffer of the old capture when it's replaced
+/// are lost.
+#[derive(Debug)]
+pub struct BufferTuner {
+    max_buffer_size: usize,
+    growth: usize,
+    patience: u32,
+    last_drop: u32,
+    strikes: u32,
+}
+
+impl BufferTuner {
+    /// Construct a new `BufferTuner` growing buffer up to `max_buffer_size`
+    /// bytes.
+    ///
+    /// By default the buffer size is doubled after drops are seen in 3
+    /// consecutive checks.
+    pub fn new(max_buffer_size: usize) -> BufferTuner {
+        BufferTuner {
+            max_buffer_size,
+            growth: 2,
+            patience: 3,
+            last_drop: 0,
+            strikes: 0,
+        }
+    }
+
+    /// Set the factor by which the buffer size is grown. Values less than 2
+    /// are treated as 2.
+    pub fn with_growth(mut self, growth: usize) -> BufferTuner {
+        self.growth = growth.max(2);
+        self
+    }
+
+    /// Set the number of consecutive checks with drops before the buffer is
+    /// grown. Values less than 1 are treated as 1.
+    pub fn with_patience(mut self, patience: u32) -> BufferTuner {
+        self.patience = patience.max(1);
+        self
+    }
+
+    /// Check drop statistics of `pcap` and reopen it with a larger buffer if
+    /// drops persist.
+    ///
+    /// On `TuneAction::Resized` the `pcap` has been replaced with a new
+    /// capture handle.
+    pub fn check(&mut self, pcap: &mut Pcap) -> Result<TuneAction> {
+        let stats = pcap.stats()?;
+        let new_drops = match self.record_drops(stats.packets_dropped()) {
+            Some(new_drops) => new_drops,
+            None => return Ok(TuneAction::Unchanged),
+        };
+
+        let old = pcap.buffer_size()?;
+        let new = match self.grown_size(old) {
+            Some(new) => new,
+            None => return Ok(TuneAction::AtLimit),
+        };
+
+        let mut config = pcap.config().ok_or_else(|| {
+            Error::PcapError("capture was not created with PcapBuilder".to_string())
+        })?;
+        config.buffer_size = Some(new);
+        log::info!(
+            "{}: {} packets dropped, growing buffer from {} to {} bytes",
+            config.source,
+            new_drops,
+            old,
+            new
+        );
+
+        // Open the new handle before closing the old one, so the old one is
+        // still usable if reopening fails.
+        *pcap = config.open()?;
+
+        self.last_drop = 0;
+        self.strikes = 0;
+        Ok(TuneAction::Resized { old, new })
+    }
+
+    // Record the drop counter `dropped` of a check. Returns the packets
+    // dropped since the previous check if drops have persisted for
+    // `patience` checks.
+    fn record_drops(&mut self, dropped: u32) -> Option<u32> {
+        let new_drops = dropped.wrapping_sub(self.last_drop);
+        self.last_drop = dropped;
+
+        if new_drops == 0 {
+            self.strikes = 0;
+            return None;
+        }
+
+        self.strikes += 1;
+        if self.strikes < self.patience {
+            return None;
+        }
+        Some(new_drops)
+    }
+
+    // Buffer size to grow buffer of `old` bytes to, or `None` if it is at the
+    // maximum size.
+    fn grown_size(&self, old: usize) -> Option<usize> {
+        let new = old.saturating_mul(self.growth).min(self.max_buffer_size);
+        (new > old).then_some(new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BufferTuner;
+
+    #[test]
+    fn test_record_drops() {
+        let mut tuner = BufferTuner::new(1 << 20).with_patience(2);
+        assert_eq!(tuner.record_drops(0), None);
+        assert_eq!(tuner.record_drops(10), None);
+        assert_eq!(tuner.record_drops(15), Some(5));
+        // A check without drops resets the patience.
+        assert_eq!(tuner.record_drops(15), None);
+        assert_eq!(tuner.record_drops(20), None);
+        // The drop counter of libpcap wraps around.
+        tuner.last_drop = u32::MAX - 1;
+        assert_eq!(tuner.record_drops(2), Some(4));
+    }
+
+    #[test]
+    fn test_grown_size() {
+        let tuner = BufferTuner::new(3000).with_growth(1);
+        assert_eq!(tuner.grown_size(1000), Some(2000));
+        assert_eq!(tuner.grown_size(2000), Some(3000));
+        assert_eq!(tuner.grown_size(3000), None);
+        assert_eq!(tuner.grown_size(usize::MAX), None);
+    }
+}
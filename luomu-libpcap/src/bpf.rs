@@ -0,0 +1,130 @@
+//! Controls of BPF devices on BSDs and macOS.
+//!
+//! libpcap captures on these platforms through a `/dev/bpf` device. Some of
+//! the device settings libpcap doesn't expose, or allows changing only before
+//! the capture is activated. [BpfDevice] sets them with `ioctl()` on the
+//! device of an active capture.
+
+use std::io;
+
+use crate::{Error, PcapT, Result};
+
+// Same values on all the BSDs and macOS, unless noted.
+const BIOCGBLEN: libc::c_ulong = 0x4004_4266;
+const BIOCFLUSH: libc::c_ulong = 0x2000_4268;
+const BIOCIMMEDIATE: libc::c_ulong = 0x8004_4270;
+const BIOCGHDRCMPLT: libc::c_ulong = 0x4004_4274;
+const BIOCSHDRCMPLT: libc::c_ulong = 0x8004_4275;
+// Only on FreeBSD, DragonFly and macOS. On NetBSD the same number is
+// BIOCGDLTLIST, and OpenBSD has BIOCSDIRFILT instead.
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly"
+))]
+const BIOCSSEESENT: libc::c_ulong = 0x8004_4277;
+
+/// BPF device of a live capture.
+///
+/// Returned by `Pcap::bpf()`. The settings take effect immediately and stay
+/// in effect until the capture is closed.
+pub struct BpfDevice<'p> {
+    pcap_t: &'p PcapT,
+    fd: libc::c_int,
+}
+
+impl<'p> BpfDevice<'p> {
+    /// BPF device of `pcap_t`. Fails for savefiles and other handles not
+    /// capturing from a BPF device.
+    pub fn new(pcap_t: &'p PcapT) -> Result<BpfDevice<'p>> {
+        let fd = match (pcap_t.fileno(), pcap_t.file()) {
+            (Some(fd), None) => fd,
+            _ => {
+                return Err(Error::PcapError(format!(
+                    "{} is not captured from a BPF device",
+                    pcap_t.get_inteface()
+                )))
+            }
+        };
+        Ok(BpfDevice { pcap_t, fd })
+    }
+
+    /// Set immediate mode. In immediate mode reads return as soon as a
+    /// packet arrives instead of waiting for the buffer to fill or the read
+    /// timeout to pass.
+    ///
+    /// Unlike `PcapBuilder::set_immediate()`, this can be changed while
+    /// capturing.
+    pub fn set_immediate(&self, immediate: bool) -> Result<()> {
+        self.set(BIOCIMMEDIATE, immediate.into())
+    }
+
+    /// Set "header complete" mode. When set, the source address of Ethernet
+    /// frames sent with `Pcap::inject()` is sent as is. Otherwise the kernel
+    /// replaces it with the address of the interface.
+    pub fn set_header_complete(&self, complete: bool) -> Result<()> {
+        self.set(BIOCSHDRCMPLT, complete.into())
+    }
+
+    /// Returns true if "header complete" mode is set.
+    pub fn header_complete(&self) -> Result<bool> {
+        Ok(self.get(BIOCGHDRCMPLT)? != 0)
+    }
+
+    /// Set whether packets sent by the host are captured. This is the
+    /// device setting behind `functions::pcap_setdirection()`, for systems
+    /// where setting the direction is not supported.
+    ///
+    /// Available on FreeBSD, DragonFly, macOS and iOS.
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly"
+    ))]
+    pub fn set_see_sent(&self, see_sent: bool) -> Result<()> {
+        self.set(BIOCSSEESENT, see_sent.into())
+    }
+
+    /// Size of the kernel buffer in bytes. The kernel may use a smaller
+    /// buffer than asked with `PcapBuilder::set_buffer_size()`. A read from
+    /// the device returns at most a buffer of packets.
+    pub fn buffer_len(&self) -> Result<usize> {
+        Ok(self.get(BIOCGBLEN)? as usize)
+    }
+
+    /// Discard the packets in the kernel buffer.
+    pub fn flush(&self) -> Result<()> {
+        self.ioctl(BIOCFLUSH, std::ptr::null_mut())
+    }
+
+    /// The capture of the device.
+    pub fn pcap_t(&self) -> &PcapT {
+        self.pcap_t
+    }
+
+    fn set(&self, request: libc::c_ulong, value: libc::c_uint) -> Result<()> {
+        let mut value = value;
+        self.ioctl(request, &mut value)
+    }
+
+    fn get(&self, request: libc::c_ulong) -> Result<libc::c_uint> {
+        let mut value: libc::c_uint = 0;
+        self.ioctl(request, &mut value)?;
+        Ok(value)
+    }
+
+    fn ioctl(&self, request: libc::c_ulong, arg: *mut libc::c_uint) -> Result<()> {
+        if unsafe { libc::ioctl(self.fd, request as _, arg) } < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for BpfDevice<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BpfDevice").field("fd", &self.fd).finish()
+    }
+}
@@ -0,0 +1,368 @@
+//! Bridge between Rust I/O objects and C `FILE *` streams.
+//!
+//! libpcap reads savefiles from and writes them into standard I/O streams.
+//! Functions in this module create `FILE *` streams backed by any [Read] or
+//! [Write] object using `fopencookie()` on Linux and `funopen()` on BSDs and
+//! macOS. The stream owns the Rust object; it is dropped when the stream is
+//! closed with `fclose()`.
+//!
+//! The streams are not seekable, but `ftell()` works as the current position
+//! is tracked.
+
+use std::ffi::c_void;
+use std::io::{self, Read, Write};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+// State behind the cookie pointer.
+struct Cookie<T> {
+    inner: T,
+    pos: u64,
+}
+
+/// Open a `FILE *` stream reading from `reader`.
+pub(crate) fn open_reader<R: Read + Send + 'static>(reader: R) -> io::Result<*mut libc::FILE> {
+    let cookie = Box::into_raw(Box::new(Cookie {
+        inner: reader,
+        pos: 0,
+    }));
+    let fp = unsafe { sys::open_reader::<R>(cookie as *mut c_void) };
+    if fp.is_null() {
+        let err = io::Error::last_os_error();
+        drop(unsafe { Box::from_raw(cookie) });
+        return Err(err);
+    }
+    Ok(fp)
+}
+
+/// Open a `FILE *` stream writing into `writer`. `writer` is flushed when the
+/// stream is closed.
+pub(crate) fn open_writer<W: Write + Send + 'static>(writer: W) -> io::Result<*mut libc::FILE> {
+    let cookie = Box::into_raw(Box::new(Cookie {
+        inner: writer,
+        pos: 0,
+    }));
+    let fp = unsafe { sys::open_writer::<W>(cookie as *mut c_void) };
+    if fp.is_null() {
+        let err = io::Error::last_os_error();
+        drop(unsafe { Box::from_raw(cookie) });
+        return Err(err);
+    }
+    Ok(fp)
+}
+
+// Callbacks shared by both implementations. They return -1 and set errno on
+// error like the C functions they stand for. Panics must not unwind into C, so
+// they are caught and reported as I/O errors.
+
+unsafe fn read_cb<R: Read>(cookie: *mut c_void, buf: *mut u8, len: usize) -> isize {
+    let cookie = &mut *(cookie as *mut Cookie<R>);
+    let buf = std::slice::from_raw_parts_mut(buf, len);
+    let ret = catch_unwind(AssertUnwindSafe(|| loop {
+        match cookie.inner.read(buf) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            res => return res,
+        }
+    }));
+    complete(cookie, ret)
+}
+
+unsafe fn write_cb<W: Write>(cookie: *mut c_void, buf: *const u8, len: usize) -> isize {
+    let cookie = &mut *(cookie as *mut Cookie<W>);
+    let buf = std::slice::from_raw_parts(buf, len);
+    let ret = catch_unwind(AssertUnwindSafe(|| {
+        cookie.inner.write_all(buf).map(|()| buf.len())
+    }));
+    complete(cookie, ret)
+}
+
+fn complete<T>(cookie: &mut Cookie<T>, ret: std::thread::Result<io::Result<usize>>) -> isize {
+    match ret {
+        Ok(Ok(n)) => {
+            cookie.pos += n as u64;
+            n as isize
+        }
+        Ok(Err(e)) => {
+            set_errno(e.raw_os_error().unwrap_or(libc::EIO));
+            -1
+        }
+        Err(_) => {
+            set_errno(libc::EIO);
+            -1
+        }
+    }
+}
+
+// Only reporting of the current position is supported.
+unsafe fn seek_cb<T>(cookie: *mut c_void, offset: i64, whence: libc::c_int) -> i64 {
+    let cookie = &*(cookie as *const Cookie<T>);
+    if offset == 0 && whence == libc::SEEK_CUR {
+        cookie.pos as i64
+    } else {
+        set_errno(libc::ESPIPE);
+        -1
+    }
+}
+
+unsafe fn close_reader_cb<R>(cookie: *mut c_void) -> libc::c_int {
+    let cookie = Box::from_raw(cookie as *mut Cookie<R>);
+    match catch_unwind(AssertUnwindSafe(move || drop(cookie))) {
+        Ok(()) => 0,
+        Err(_) => {
+            set_errno(libc::EIO);
+            -1
+        }
+    }
+}
+
+unsafe fn close_writer_cb<W: Write>(cookie: *mut c_void) -> libc::c_int {
+    let mut cookie = Box::from_raw(cookie as *mut Cookie<W>);
+    match catch_unwind(AssertUnwindSafe(move || cookie.inner.flush())) {
+        Ok(Ok(())) => 0,
+        Ok(Err(e)) => {
+            set_errno(e.raw_os_error().unwrap_or(libc::EIO));
+            -1
+        }
+        Err(_) => {
+            set_errno(libc::EIO);
+            -1
+        }
+    }
+}
+
+fn set_errno(errno: libc::c_int) {
+    unsafe {
+        #[cfg(target_os = "linux")]
+        let location = libc::__errno_location();
+        #[cfg(any(target_os = "openbsd", target_os = "netbsd"))]
+        let location = libc::__errno();
+        #[cfg(any(
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "dragonfly"
+        ))]
+        let location = libc::__error();
+
+        *location = errno;
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::ffi::c_void;
+    use std::io::{Read, Write};
+
+    #[repr(C)]
+    struct CookieIoFunctions {
+        read: Option<ReadFn>,
+        write: Option<WriteFn>,
+        seek: Option<SeekFn>,
+        close: Option<CloseFn>,
+    }
+
+    type ReadFn =
+        unsafe extern "C" fn(*mut c_void, *mut libc::c_char, libc::size_t) -> libc::ssize_t;
+    type WriteFn =
+        unsafe extern "C" fn(*mut c_void, *const libc::c_char, libc::size_t) -> libc::ssize_t;
+    type SeekFn = unsafe extern "C" fn(*mut c_void, *mut libc::off64_t, libc::c_int) -> libc::c_int;
+    type CloseFn = unsafe extern "C" fn(*mut c_void) -> libc::c_int;
+
+    extern "C" {
+        fn fopencookie(
+            cookie: *mut c_void,
+            mode: *const libc::c_char,
+            funcs: CookieIoFunctions,
+        ) -> *mut libc::FILE;
+    }
+
+    unsafe extern "C" fn read<R: Read>(
+        cookie: *mut c_void,
+        buf: *mut libc::c_char,
+        len: libc::size_t,
+    ) -> libc::ssize_t {
+        super::read_cb::<R>(cookie, buf as *mut u8, len)
+    }
+
+    unsafe extern "C" fn write<W: Write>(
+        cookie: *mut c_void,
+        buf: *const libc::c_char,
+        len: libc::size_t,
+    ) -> libc::ssize_t {
+        super::write_cb::<W>(cookie, buf as *const u8, len)
+    }
+
+    unsafe extern "C" fn seek<T>(
+        cookie: *mut c_void,
+        offset: *mut libc::off64_t,
+        whence: libc::c_int,
+    ) -> libc::c_int {
+        match super::seek_cb::<T>(cookie, *offset, whence) {
+            -1 => -1,
+            pos => {
+                *offset = pos;
+                0
+            }
+        }
+    }
+
+    unsafe extern "C" fn close_reader<R>(cookie: *mut c_void) -> libc::c_int {
+        super::close_reader_cb::<R>(cookie)
+    }
+
+    unsafe extern "C" fn close_writer<W: Write>(cookie: *mut c_void) -> libc::c_int {
+        super::close_writer_cb::<W>(cookie)
+    }
+
+    pub(super) unsafe fn open_reader<R: Read>(cookie: *mut c_void) -> *mut libc::FILE {
+        let funcs = CookieIoFunctions {
+            read: Some(read::<R>),
+            write: None,
+            seek: Some(seek::<R>),
+            close: Some(close_reader::<R>),
+        };
+        fopencookie(cookie, b"rb\0".as_ptr() as *const libc::c_char, funcs)
+    }
+
+    pub(super) unsafe fn open_writer<W: Write>(cookie: *mut c_void) -> *mut libc::FILE {
+        let funcs = CookieIoFunctions {
+            read: None,
+            write: Some(write::<W>),
+            seek: Some(seek::<W>),
+            close: Some(close_writer::<W>),
+        };
+        fopencookie(cookie, b"wb\0".as_ptr() as *const libc::c_char, funcs)
+    }
+}
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    target_os = "netbsd"
+))]
+mod sys {
+    use std::ffi::c_void;
+    use std::io::{Read, Write};
+
+    type ReadFn = unsafe extern "C" fn(*mut c_void, *mut libc::c_char, libc::c_int) -> libc::c_int;
+    type WriteFn =
+        unsafe extern "C" fn(*mut c_void, *const libc::c_char, libc::c_int) -> libc::c_int;
+    type SeekFn = unsafe extern "C" fn(*mut c_void, libc::off_t, libc::c_int) -> libc::off_t;
+    type CloseFn = unsafe extern "C" fn(*mut c_void) -> libc::c_int;
+
+    extern "C" {
+        fn funopen(
+            cookie: *const c_void,
+            readfn: Option<ReadFn>,
+            writefn: Option<WriteFn>,
+            seekfn: Option<SeekFn>,
+            closefn: Option<CloseFn>,
+        ) -> *mut libc::FILE;
+    }
+
+    unsafe extern "C" fn read<R: Read>(
+        cookie: *mut c_void,
+        buf: *mut libc::c_char,
+        len: libc::c_int,
+    ) -> libc::c_int {
+        super::read_cb::<R>(cookie, buf as *mut u8, len as usize) as libc::c_int
+    }
+
+    unsafe extern "C" fn write<W: Write>(
+        cookie: *mut c_void,
+        buf: *const libc::c_char,
+        len: libc::c_int,
+    ) -> libc::c_int {
+        super::write_cb::<W>(cookie, buf as *const u8, len as usize) as libc::c_int
+    }
+
+    unsafe extern "C" fn seek<T>(
+        cookie: *mut c_void,
+        offset: libc::off_t,
+        whence: libc::c_int,
+    ) -> libc::off_t {
+        super::seek_cb::<T>(cookie, offset as i64, whence) as libc::off_t
+    }
+
+    unsafe extern "C" fn close_reader<R>(cookie: *mut c_void) -> libc::c_int {
+        super::close_reader_cb::<R>(cookie)
+    }
+
+    unsafe extern "C" fn close_writer<W: Write>(cookie: *mut c_void) -> libc::c_int {
+        super::close_writer_cb::<W>(cookie)
+    }
+
+    pub(super) unsafe fn open_reader<R: Read>(cookie: *mut c_void) -> *mut libc::FILE {
+        funopen(
+            cookie,
+            Some(read::<R>),
+            None,
+            Some(seek::<R>),
+            Some(close_reader::<R>),
+        )
+    }
+
+    pub(super) unsafe fn open_writer<W: Write>(cookie: *mut c_void) -> *mut libc::FILE {
+        funopen(
+            cookie,
+            None,
+            Some(write::<W>),
+            Some(seek::<W>),
+            Some(close_writer::<W>),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+    use std::sync::{Arc, Mutex};
+
+    use super::{open_reader, open_writer};
+
+    // Writer sharing its buffer so contents can be checked after fclose().
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_writer_stream() {
+        let buf = SharedBuf::default();
+        let fp = open_writer(buf.clone()).unwrap();
+        unsafe {
+            let data = b"Hello, world";
+            assert_eq!(
+                libc::fwrite(data.as_ptr() as *const libc::c_void, 1, data.len(), fp),
+                data.len()
+            );
+            libc::fflush(fp);
+            assert_eq!(libc::ftell(fp), data.len() as libc::c_long);
+            assert_eq!(libc::fclose(fp), 0);
+        }
+        assert_eq!(&buf.0.lock().unwrap()[..], b"Hello, world");
+    }
+
+    #[test]
+    fn test_reader_stream() {
+        let fp = open_reader(Cursor::new(b"Hello, world".to_vec())).unwrap();
+        let mut data = [0u8; 32];
+        unsafe {
+            let n = libc::fread(data.as_mut_ptr() as *mut libc::c_void, 1, data.len(), fp);
+            assert_eq!(&data[..n], b"Hello, world");
+            assert_eq!(libc::feof(fp), 1);
+            assert_eq!(libc::fclose(fp), 0);
+        }
+    }
+}
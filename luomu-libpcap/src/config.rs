@@ -0,0 +1,218 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::{Error, Pcap, PcapBuilder, Result};
+
+/// Options of a live capture.
+///
+/// `PcapBuilder` records the options set on it and `Pcap::config()` returns
+/// them together with the filter expression in effect. The configuration can
+/// be used to open an identical capture again, for example after the
+/// interface has been down or to change options which can't be changed on an
+/// activated capture.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CaptureConfig {
+    /// Capture source, usually the name of a network interface.
+    pub source: String,
+    /// Buffer size of the capture in bytes.
+    pub buffer_size: Option<usize>,
+    /// Promiscuous mode.
+    pub promiscuous: Option<bool>,
+    /// Immediate mode.
+    pub immediate: Option<bool>,
+    /// Packet buffer timeout.
+    pub timeout: Option<Duration>,
+    /// Snapshot length.
+    pub snaplen: Option<usize>,
+    /// Filter expression set after activation.
+    pub filter: Option<String>,
+}
+
+impl CaptureConfig {
+    /// Construct a configuration with libpcap's defaults for `source`.
+    pub fn new(source: &str) -> CaptureConfig {
+        CaptureConfig {
+            source: source.to_string(),
+            buffer_size: None,
+            promiscuous: None,
+            immediate: None,
+            timeout: None,
+            snaplen: None,
+            filter: None,
+        }
+    }
+
+    /// Create a `PcapBuilder` with the options applied.
+    ///
+    /// The filter can't be set before the capture is activated, so it's not
+    /// applied. Use `CaptureConfig::open()` to get an activated capture with
+    /// filter.
+    pub fn builder(&self) -> Result<PcapBuilder> {
+        let mut builder = Pcap::builder(&self.source)?;
+        if let Some(buffer_size) = self.buffer_size {
+            builder = builder.set_buffer_size(buffer_size)?;
+        }
+        if let Some(promiscuous) = self.promiscuous {
+            builder = builder.set_promiscuous(promiscuous)?;
+        }
+        if let Some(immediate) = self.immediate {
+            builder = builder.set_immediate(immediate)?;
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.set_timeout(timeout)?;
+        }
+        if let Some(snaplen) = self.snaplen {
+            builder = builder.set_snaplen(snaplen)?;
+        }
+        Ok(builder)
+    }
+
+    /// Create and activate a capture with the options and set the filter.
+    pub fn open(&self) -> Result<Pcap> {
+        let pcap = self.builder()?.activate()?;
+        if let Some(filter) = &self.filter {
+            pcap.set_filter(filter)?;
+        }
+        Ok(pcap)
+    }
+}
+
+impl FromStr for CaptureConfig {
+    type Err = Error;
+
+    /// Parses a capture specification with the source and options, like
+    /// `"eth0?promisc=1&snaplen=256&immediate=1"`.
+    ///
+    /// Options follow the source after `?` as `name=value` pairs separated by
+    /// `&`:
+    ///
+    ///   * `promisc`: promiscuous mode, `1`/`0`, `true`/`false`, `on`/`off`
+    ///     or `yes`/`no`
+    ///   * `immediate`: immediate mode, as above
+    ///   * `snaplen`: snapshot length
+    ///   * `buffer`: buffer size in bytes
+    ///   * `timeout`: packet buffer timeout in milliseconds
+    ///   * `filter`: filter expression, with `+` for spaces and `%XX` for
+    ///     other special characters, like in URLs
+    ///
+    /// Unknown options and invalid values are errors.
+    ///
+    /// ```
+    /// use luomu_libpcap::CaptureConfig;
+    ///
+    /// let config: CaptureConfig = "eth0?promisc=1&filter=udp+port+53".parse()?;
+    /// assert_eq!(config.source, "eth0");
+    /// assert_eq!(config.promiscuous, Some(true));
+    /// assert_eq!(config.filter.as_deref(), Some("udp port 53"));
+    /// # Ok::<(), luomu_libpcap::Error>(())
+    /// ```
+    fn from_str(s: &str) -> Result<Self> {
+        let (source, options) = match s.split_once('?') {
+            Some((source, options)) => (source, options),
+            None => (s, ""),
+        };
+        if source.is_empty() {
+            return Err(spec_error("no capture source"));
+        }
+        let mut config = CaptureConfig::new(source);
+        for option in options.split('&').filter(|option| !option.is_empty()) {
+            let (name, value) = option
+                .split_once('=')
+                .ok_or_else(|| spec_error(&format!("no value for {}", option)))?;
+            match name {
+                "promisc" => config.promiscuous = Some(parse_bool(name, value)?),
+                "immediate" => config.immediate = Some(parse_bool(name, value)?),
+                "snaplen" => config.snaplen = Some(parse_number(name, value)?),
+                "buffer" => config.buffer_size = Some(parse_number(name, value)?),
+                "timeout" => {
+                    config.timeout = Some(Duration::from_millis(parse_number(name, value)?))
+                }
+                "filter" => config.filter = Some(decode(value)?),
+                _ => return Err(spec_error(&format!("unknown option {}", name))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn spec_error(reason: &str) -> Error {
+    Error::PcapError(format!("invalid capture specification: {}", reason))
+}
+
+fn parse_bool(name: &str, value: &str) -> Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "on" | "yes" => Ok(true),
+        "0" | "false" | "off" | "no" => Ok(false),
+        _ => Err(spec_error(&format!(
+            "invalid value {:?} for {}",
+            value, name
+        ))),
+    }
+}
+
+fn parse_number<T: FromStr>(name: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| spec_error(&format!("invalid value {:?} for {}", value, name)))
+}
+
+// Decode `+` and `%XX` escapes of a URL query value.
+fn decode(value: &str) -> Result<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut iter = value.bytes();
+    while let Some(b) = iter.next() {
+        match b {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = [iter.next(), iter.next()];
+                let byte = match hex {
+                    [Some(hi), Some(lo)] => std::str::from_utf8(&[hi, lo])
+                        .ok()
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+                    _ => None,
+                };
+                bytes.push(byte.ok_or_else(|| spec_error("invalid escape in filter"))?);
+            }
+            b => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| spec_error("filter is not UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::CaptureConfig;
+
+    #[test]
+    fn test_capture_config_from_str() {
+        let config: CaptureConfig = "eth0?promisc=1&snaplen=256&immediate=on&timeout=50"
+            .parse()
+            .unwrap();
+        assert_eq!(config.source, "eth0");
+        assert_eq!(config.promiscuous, Some(true));
+        assert_eq!(config.immediate, Some(true));
+        assert_eq!(config.snaplen, Some(256));
+        assert_eq!(config.timeout, Some(Duration::from_millis(50)));
+        assert_eq!(config.buffer_size, None);
+
+        let config: CaptureConfig = "any".parse().unwrap();
+        assert_eq!(config, CaptureConfig::new("any"));
+
+        let config: CaptureConfig = "lo?filter=tcp%5B13%5D+%26+2%20!%3D+0".parse().unwrap();
+        assert_eq!(config.filter.as_deref(), Some("tcp[13] & 2 != 0"));
+
+        for spec in [
+            "",
+            "?promisc=1",
+            "eth0?promisc=2",
+            "eth0?snaplen=-1",
+            "eth0?promisc",
+            "eth0?monitor=1",
+            "eth0?filter=%zz",
+        ] {
+            assert!(spec.parse::<CaptureConfig>().is_err(), "{}", spec);
+        }
+    }
+}
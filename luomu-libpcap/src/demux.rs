@@ -0,0 +1,199 @@
+use crate::functions::{pcap_compile_nopcap, pcap_datalink, pcap_snapshot};
+use crate::{Error, Packet, PcapFilter, PcapT, Result};
+
+/// Classifier of packets by a set of named filters.
+///
+/// `Demux` compiles filters for the link-layer header type of a capture and
+/// tells for each packet which of them matched, using
+/// `pcap_offline_filter()`. This allows splitting traffic of a single
+/// capture into classes, like "dns" or "tls", without opening a capture for
+/// each.
+///
+/// Filters are evaluated in the order they were added.
+pub struct Demux {
+    linktype: i32,
+    snaplen: usize,
+    filters: Vec<(String, PcapFilter)>,
+}
+
+impl Demux {
+    /// Construct a `Demux` without filters for packets captured with
+    /// `pcap_t`.
+    pub fn new(pcap_t: &PcapT) -> Result<Demux> {
+        Ok(Demux {
+            linktype: pcap_datalink(pcap_t)?,
+            snaplen: pcap_snapshot(pcap_t)?,
+            filters: Vec::new(),
+        })
+    }
+
+    /// Add a filter named `name`.
+    ///
+    /// See [pcap-filter(7)](https://www.tcpdump.org/manpages/pcap-filter.7.html)
+    /// for the syntax of `filter`. Fails if the filter doesn't compile or a
+    /// filter with the same name exists.
+    pub fn add(&mut self, name: &str, filter: &str) -> Result<()> {
+        if self.filters.iter().any(|(n, _)| n == name) {
+            return Err(Error::PcapError(format!("filter {} already exists", name)));
+        }
+        let compiled = pcap_compile_nopcap(self.snaplen, self.linktype, filter)?;
+        self.filters.push((name.to_string(), compiled));
+        Ok(())
+    }
+
+    /// Add a filter named `name` and return the `Demux`.
+    pub fn with(mut self, name: &str, filter: &str) -> Result<Demux> {
+        self.add(name, filter)?;
+        Ok(self)
+    }
+
+    /// Names of the filters in the order they were added.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.filters.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Number of filters.
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Returns true if there are no filters.
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Names of the filters matching `packet`.
+    pub fn classify<P: Packet>(&self, packet: &P) -> Vec<&str> {
+        self.filters
+            .iter()
+            .filter(|(_, filter)| filter.matches(packet))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Indices of the filters matching `packet`, in the order of `names()`.
+    ///
+    /// Indices are appended into `matches`, which allows reusing its
+    /// allocation between packets.
+    pub fn classify_into<P: Packet>(&self, packet: &P, matches: &mut Vec<usize>) {
+        matches.extend(
+            self.filters
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, filter))| filter.matches(packet))
+                .map(|(index, _)| index),
+        );
+    }
+
+    /// Name of the first filter matching `packet`, if any.
+    pub fn first_match<P: Packet>(&self, packet: &P) -> Option<&str> {
+        self.filters
+            .iter()
+            .find(|(_, filter)| filter.matches(packet))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+impl std::fmt::Debug for Demux {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Demux")
+            .field("linktype", &self.linktype)
+            .field("filters", &self.names().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use luomu_libpcap_sys::pcap_pkthdr;
+
+    use super::Demux;
+    use crate::{FilterFixture, LinkType, OwnedPacket};
+
+    // IPv4 packet from 10.0.0.1 to 10.0.0.2 with protocol `proto` and
+    // transport header `header`.
+    fn ipv4(proto: u8, header: &[u8]) -> OwnedPacket {
+        let len = 20 + header.len();
+        let mut bytes = vec![
+            0x45, 0x00, 0x00, len as u8, 0x00, 0x00, 0x00, 0x00, 0x40, proto, 0x00, 0x00, 10, 0, 0,
+            1, 10, 0, 0, 2,
+        ];
+        bytes.extend_from_slice(header);
+        let pkthdr = pcap_pkthdr {
+            ts: libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            caplen: len as u32,
+            len: len as u32,
+        };
+        OwnedPacket::new(pkthdr, bytes)
+    }
+
+    fn udp(port: u16) -> OwnedPacket {
+        let [hi, lo] = port.to_be_bytes();
+        ipv4(17, &[0x04, 0xd2, hi, lo, 0x00, 0x08, 0x00, 0x00])
+    }
+
+    fn tcp(port: u16) -> OwnedPacket {
+        let [hi, lo] = port.to_be_bytes();
+        let mut header = vec![0x04, 0xd2, hi, lo];
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(&[0x50, 0x02, 0xff, 0xff, 0, 0, 0, 0]);
+        ipv4(6, &header)
+    }
+
+    fn demux() -> Demux {
+        let fixture = FilterFixture::new(LinkType::RAW, 65535).unwrap();
+        Demux::new(fixture.pcap_t())
+            .unwrap()
+            .with("dns", "udp port 53")
+            .unwrap()
+            .with("udp", "udp")
+            .unwrap()
+            .with("tls", "tcp port 443")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_demux_routing() {
+        let demux = demux();
+        assert_eq!(demux.names().collect::<Vec<_>>(), vec!["dns", "udp", "tls"]);
+
+        assert_eq!(demux.classify(&udp(53)), vec!["dns", "udp"]);
+        assert_eq!(demux.first_match(&udp(53)), Some("dns"));
+        assert_eq!(demux.classify(&udp(123)), vec!["udp"]);
+        assert_eq!(demux.first_match(&tcp(443)), Some("tls"));
+
+        let mut matches = vec![7];
+        demux.classify_into(&udp(53), &mut matches);
+        assert_eq!(matches, vec![7, 0, 1]);
+    }
+
+    #[test]
+    fn test_demux_no_match() {
+        let demux = demux();
+        // Packets matching no filter are left for the caller to route.
+        let icmp = ipv4(1, &[8, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(demux.classify(&icmp).is_empty());
+        assert_eq!(demux.first_match(&icmp), None);
+        assert!(demux.classify(&tcp(80)).is_empty());
+
+        let mut matches = Vec::new();
+        demux.classify_into(&icmp, &mut matches);
+        assert!(matches.is_empty());
+
+        let fixture = FilterFixture::new(LinkType::RAW, 65535).unwrap();
+        let empty = Demux::new(fixture.pcap_t()).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.first_match(&udp(53)), None);
+    }
+
+    #[test]
+    fn test_demux_add_errors() {
+        let mut demux = demux();
+        assert!(demux.add("dns", "udp port 5353").is_err());
+        assert!(demux.add("bad", "foo").is_err());
+        assert_eq!(demux.len(), 3);
+    }
+}
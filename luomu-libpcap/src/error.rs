@@ -21,6 +21,12 @@ pub enum Error {
     OnlySupportedInMonitorMode,
     /// The process doesn't have permission to open the capture source
     /// (PCAP_ERROR_PERM_DENIED).
+    ///
+    /// Capturing on Linux needs `CAP_NET_RAW` capability. On Android only
+    /// processes running as root have it, and apps can't list network
+    /// interfaces either, in which case `PcapIfT::new()` returns this error
+    /// for the "any" device, followed by the error message of libpcap in
+    /// parentheses.
     PermissionDenied(String),
     /// The capture source device is not up (PCAP_ERROR_IFACE_NOT_UP).
     InterfaceNotUp(String),
@@ -50,6 +56,53 @@ pub enum Error {
     IO(io::Error),
 }
 
+/// Error in a filter expression, returned by `PcapFilter::validate()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FilterError {
+    /// Error message from libpcap.
+    pub message: String,
+    /// Byte offset of the offending token in the filter expression, if it
+    /// could be located.
+    pub position: Option<usize>,
+}
+
+impl FilterError {
+    /// Construct a `FilterError` from libpcap's error message about
+    /// `filter`, locating the token the message refers to.
+    pub(crate) fn new(filter: &str, message: String) -> FilterError {
+        let position = error_token(&message).and_then(|token| filter.find(token));
+        FilterError { message, position }
+    }
+}
+
+// Find the token an error message of libpcap refers to, such as "foo" in
+// "unknown host 'foo'" or "illegal token: @".
+fn error_token(message: &str) -> Option<&str> {
+    if let Some((_, token)) = message.split_once("illegal token: ") {
+        return Some(token.trim()).filter(|token| !token.is_empty());
+    }
+    let (_, rest) = message.split_once('\'')?;
+    let (token, _) = rest.split_once('\'')?;
+    Some(token).filter(|token| !token.is_empty())
+}
+
+impl error::Error for FilterError {}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some(position) => write!(f, "{} (at offset {})", self.message, position),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl From<FilterError> for Error {
+    fn from(err: FilterError) -> Self {
+        Error::PcapError(err.message)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum CStringError {
     Utf8(std::str::Utf8Error),
@@ -138,6 +191,12 @@ impl From<std::ffi::FromBytesWithNulError> for Error {
     }
 }
 
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::IO(err)
+    }
+}
+
 impl From<Error> for io::Error {
     fn from(err: Error) -> io::Error {
         match err {
@@ -167,3 +226,30 @@ impl From<Error> for io::Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::FilterError;
+
+    #[test]
+    fn test_filter_error_position() {
+        let err = FilterError::new(
+            "tcp and host foo.invalid",
+            "unknown host 'foo.invalid'".into(),
+        );
+        assert_eq!(err.position, Some(13));
+
+        let err = FilterError::new("tcp @ 1", "illegal token: @".into());
+        assert_eq!(err.position, Some(4));
+
+        let err = FilterError::new(
+            "tcp and",
+            "can't parse filter expression: syntax error".into(),
+        );
+        assert_eq!(err.position, None);
+        assert_eq!(
+            err.to_string(),
+            "can't parse filter expression: syntax error"
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! Streaming export of captured packets
+//!
+//! [PacketEncoder] writes packets into any [Write] (for example a
+//! `TcpStream`) and [PacketDecoder] reads them back from any [Read] as
+//! [OwnedPacket]s. This allows a capture agent to stream packets to a remote
+//! collector.
+//!
+//! Each packet is written as a frame prefixed with its length. All integers
+//! are in network (big endian) byte order:
+//!
+//! ```text
+//! +----------------+----------------+-----------------+------------+-------------+
+//! | frame len: u32 | ts_sec: i64    | ts_usec: u32    | len: u32   | packet data |
+//! +----------------+----------------+-----------------+------------+-------------+
+//! ```
+//!
+//! `frame len` is the number of bytes following it, `len` is the original
+//! length of the packet on the wire and captured length is `frame len` minus
+//! the 16 bytes of header. [PacketDecoder] rejects frames with a negative
+//! `ts_sec`, `ts_usec` of a second or more, or `len` less than the captured
+//! length.
+
+use std::io::{self, Read, Write};
+
+use luomu_libpcap_sys::pcap_pkthdr;
+
+use crate::{Error, OwnedPacket, Packet, Result};
+
+/// Size of the frame header following the length prefix.
+const HEADER_LEN: usize = 16;
+
+/// Maximum captured length of the packet accepted by [PacketDecoder]. This is
+/// the same as libpcap's `MAXIMUM_SNAPLEN`.
+pub const MAX_CAPLEN: usize = 262144;
+
+/// Encoder writing length-prefixed packet frames into [Write].
+#[derive(Debug)]
+pub struct PacketEncoder<W> {
+    writer: W,
+}
+
+impl<W: Write> PacketEncoder<W> {
+    /// Construct new `PacketEncoder` writing into `writer`.
+    pub fn new(writer: W) -> Self {
+        PacketEncoder { writer }
+    }
+
+    /// Encode a [Packet] and write it into underlying writer.
+    pub fn encode<P: Packet>(&mut self, packet: &P) -> Result<()> {
+        self.encode_raw(packet.pkthdr(), packet.packet())
+    }
+
+    /// Encode a header and bytes and write them into underlying writer.
+    //
+    // time_t is not i64 on all platforms.
+    #[allow(clippy::unnecessary_cast)]
+    pub fn encode_raw(&mut self, pkthdr: &pcap_pkthdr, bytes: &[u8]) -> Result<()> {
+        if bytes.len() > MAX_CAPLEN {
+            return Err(invalid_data("packet too large"));
+        }
+
+        let mut frame = Vec::with_capacity(4 + HEADER_LEN + bytes.len());
+        frame.extend_from_slice(&((HEADER_LEN + bytes.len()) as u32).to_be_bytes());
+        frame.extend_from_slice(&(pkthdr.ts.tv_sec as i64).to_be_bytes());
+        frame.extend_from_slice(&(pkthdr.ts.tv_usec as u32).to_be_bytes());
+        frame.extend_from_slice(&pkthdr.len.to_be_bytes());
+        frame.extend_from_slice(bytes);
+        self.writer.write_all(&frame)?;
+        Ok(())
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Return the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Decoder reading length-prefixed packet frames from [Read].
+///
+/// The decoder can also be used as an [Iterator] which ends when the reader
+/// reaches end of file between frames.
+#[derive(Debug)]
+pub struct PacketDecoder<R> {
+    reader: R,
+}
+
+impl<R: Read> PacketDecoder<R> {
+    /// Construct new `PacketDecoder` reading from `reader`.
+    pub fn new(reader: R) -> Self {
+        PacketDecoder { reader }
+    }
+
+    /// Read and decode next packet from the underlying reader.
+    ///
+    /// Returns `Ok(None)` if end of file is reached before next frame.
+    pub fn decode(&mut self) -> Result<Option<OwnedPacket>> {
+        let mut prefix = [0u8; 4];
+        if !read_prefix(&mut self.reader, &mut prefix)? {
+            return Ok(None);
+        }
+
+        let frame_len = u32::from_be_bytes(prefix) as usize;
+        if frame_len < HEADER_LEN || frame_len - HEADER_LEN > MAX_CAPLEN {
+            return Err(invalid_data("invalid frame length"));
+        }
+
+        let mut header = [0u8; HEADER_LEN];
+        self.reader.read_exact(&mut header)?;
+        let mut packet = vec![0u8; frame_len - HEADER_LEN];
+        self.reader.read_exact(&mut packet)?;
+
+        let ts_sec = i64::from_be_bytes(header[0..8].try_into().unwrap());
+        let ts_usec = u32::from_be_bytes(header[8..12].try_into().unwrap());
+        let len = u32::from_be_bytes(header[12..16].try_into().unwrap());
+
+        // The type of tv_sec is not i64 on all platforms.
+        #[allow(clippy::useless_conversion)]
+        let tv_sec = match ts_sec.try_into() {
+            Ok(tv_sec) if ts_sec >= 0 => tv_sec,
+            _ => return Err(invalid_data("invalid timestamp seconds")),
+        };
+        if ts_usec >= 1_000_000 {
+            return Err(invalid_data("invalid timestamp microseconds"));
+        }
+        if (len as usize) < packet.len() {
+            return Err(invalid_data("packet length less than captured length"));
+        }
+
+        let pkthdr = pcap_pkthdr {
+            ts: libc::timeval {
+                tv_sec,
+                tv_usec: ts_usec as _,
+            },
+            caplen: packet.len() as u32,
+            len,
+        };
+
+        Ok(Some(OwnedPacket::new(pkthdr, packet)))
+    }
+
+    /// Return the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for PacketDecoder<R> {
+    type Item = Result<OwnedPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.decode().transpose()
+    }
+}
+
+// Read the length prefix. Returns false if reader was at end of file before
+// any bytes of the prefix were read.
+fn read_prefix<R: Read>(reader: &mut R, prefix: &mut [u8; 4]) -> Result<bool> {
+    let mut read = 0;
+    while read < prefix.len() {
+        match reader.read(&mut prefix[read..]) {
+            Ok(0) if read == 0 => return Ok(false),
+            Ok(0) => return Err(Error::IO(io::ErrorKind::UnexpectedEof.into())),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(Error::IO(e)),
+        }
+    }
+    Ok(true)
+}
+
+fn invalid_data(msg: &str) -> Error {
+    Error::IO(io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use luomu_libpcap_sys::pcap_pkthdr;
+
+    use super::{PacketDecoder, PacketEncoder};
+    use crate::{OwnedPacket, Packet};
+
+    fn packet(ts_sec: i64, bytes: &[u8]) -> OwnedPacket {
+        let pkthdr = pcap_pkthdr {
+            ts: libc::timeval {
+                tv_sec: ts_sec as _,
+                tv_usec: 42,
+            },
+            caplen: bytes.len() as u32,
+            len: bytes.len() as u32 + 10,
+        };
+        OwnedPacket::new(pkthdr, bytes.to_vec())
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let packets = vec![packet(1, b"Hello"), packet(2, b""), packet(3, b"world")];
+
+        let mut encoder = PacketEncoder::new(Vec::new());
+        for p in &packets {
+            encoder.encode(p).unwrap();
+        }
+        let buf = encoder.into_inner();
+
+        let decoded = PacketDecoder::new(Cursor::new(buf))
+            .collect::<crate::Result<Vec<OwnedPacket>>>()
+            .unwrap();
+        assert_eq!(decoded.len(), packets.len());
+        for (a, b) in packets.iter().zip(decoded.iter()) {
+            assert_eq!(a.packet(), b.packet());
+            assert_eq!(a.timestamp(), b.timestamp());
+            assert_eq!(a.pkthdr().len, b.pkthdr().len);
+            assert_eq!(a.pkthdr().caplen, b.pkthdr().caplen);
+        }
+    }
+
+    #[test]
+    fn test_decode_truncated() {
+        let mut encoder = PacketEncoder::new(Vec::new());
+        encoder.encode(&packet(1, b"Hello")).unwrap();
+        let mut buf = encoder.into_inner();
+        buf.pop();
+
+        let mut decoder = PacketDecoder::new(Cursor::new(buf));
+        assert!(decoder.decode().is_err());
+    }
+
+    #[test]
+    fn test_decode_invalid_length() {
+        let buf = 3u32.to_be_bytes().to_vec();
+        let mut decoder = PacketDecoder::new(Cursor::new(buf));
+        assert!(decoder.decode().is_err());
+    }
+
+    // Frame with header fields `ts_sec`, `ts_usec` and `len` and 4 bytes of
+    // packet data.
+    fn frame(ts_sec: i64, ts_usec: u32, len: u32) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&20u32.to_be_bytes());
+        buf.extend_from_slice(&ts_sec.to_be_bytes());
+        buf.extend_from_slice(&ts_usec.to_be_bytes());
+        buf.extend_from_slice(&len.to_be_bytes());
+        buf.extend_from_slice(b"data");
+        buf
+    }
+
+    #[test]
+    fn test_decode_invalid_header() {
+        let decode = |buf| PacketDecoder::new(Cursor::new(buf)).decode();
+        assert!(decode(frame(1, 999_999, 4)).unwrap().is_some());
+        assert!(decode(frame(1, 1_000_000, 4)).is_err());
+        assert!(decode(frame(1, u32::MAX, 4)).is_err());
+        assert!(decode(frame(-1, 0, 4)).is_err());
+        assert!(decode(frame(1, 0, 3)).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+use crate::{Error, OwnedPacket, Packet, PacketSource, Result};
+
+/// Probabilities of faults injected by [FaultInjector], between 0 and 1.
+///
+/// Faults are decided for each read of the source in the order of the
+/// fields: a read may time out or fail without reading a packet, or a packet
+/// read may be dropped or truncated.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Faults {
+    /// Return `Error::Timeout` instead of reading a packet.
+    pub timeout: f64,
+    /// Return an error instead of reading a packet.
+    pub error: f64,
+    /// Drop the packet read and read the next one.
+    pub drop: f64,
+    /// Truncate the packet read to a random length shorter than it is,
+    /// like a capture with a small snapshot length would.
+    pub truncate: f64,
+}
+
+/// Numbers of faults injected by [FaultInjector].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FaultCounts {
+    /// Timeouts returned.
+    pub timeouts: u64,
+    /// Errors returned.
+    pub errors: u64,
+    /// Packets dropped.
+    pub dropped: u64,
+    /// Packets truncated.
+    pub truncated: u64,
+}
+
+/// Adapter injecting faults into a [PacketSource].
+///
+/// Applications should cope with captures timing out, failing, losing
+/// packets and returning truncated packets. `FaultInjector` makes any source,
+/// such as a [MockSource](crate::MockSource) or a savefile, behave like that
+/// with given probabilities, so resilience to flaky capture conditions can be
+/// tested. Faults are chosen by a pseudorandom generator with a seed, so a
+/// failing test can be repeated.
+///
+/// Injected errors are `Error::PcapError` with message "injected fault".
+/// Errors of the source are returned as they are.
+#[derive(Debug)]
+pub struct FaultInjector<S> {
+    source: S,
+    faults: Faults,
+    // State of xorshift64* generator.
+    rng: u64,
+    counts: FaultCounts,
+}
+
+impl<S: PacketSource> FaultInjector<S> {
+    /// Construct a new `FaultInjector` over `source` injecting `faults`,
+    /// with pseudorandom generator seeded with `seed`.
+    pub fn new(source: S, faults: Faults, seed: u64) -> FaultInjector<S> {
+        FaultInjector {
+            source,
+            faults,
+            // xorshift gets stuck at zero.
+            rng: seed | 1,
+            counts: FaultCounts::default(),
+        }
+    }
+
+    /// Numbers of faults injected so far.
+    pub fn counts(&self) -> FaultCounts {
+        self.counts
+    }
+
+    /// Get a reference to the underlying source.
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Get the underlying source back.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    // Next pseudorandom number between 0 and 1.
+    fn random(&mut self) -> f64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let random = self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+        random as f64 / (1u64 << 53) as f64
+    }
+
+    fn happens(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.random() < probability
+    }
+}
+
+impl<S: PacketSource> PacketSource for FaultInjector<S> {
+    type Packet = OwnedPacket;
+
+    fn next_packet(&mut self) -> Result<OwnedPacket> {
+        if self.happens(self.faults.timeout) {
+            self.counts.timeouts += 1;
+            return Err(Error::Timeout);
+        }
+        if self.happens(self.faults.error) {
+            self.counts.errors += 1;
+            return Err(Error::PcapError(String::from("injected fault")));
+        }
+        loop {
+            let packet = self.source.next_packet()?;
+            if self.happens(self.faults.drop) {
+                self.counts.dropped += 1;
+                continue;
+            }
+            let mut owned = OwnedPacket::new(*packet.pkthdr(), packet.packet().to_vec());
+            if !owned.is_empty() && self.happens(self.faults.truncate) {
+                let len = (self.random() * owned.len() as f64) as usize;
+                owned.truncate(len);
+                self.counts.truncated += 1;
+            }
+            return Ok(owned);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FaultInjector, Faults};
+    use crate::{Error, MockSource, Packet, PacketSource};
+
+    #[test]
+    fn test_fault_injector() {
+        let source = MockSource::new().packets(vec![[0u8; 100]; 1000]);
+        let faults = Faults {
+            timeout: 0.1,
+            error: 0.1,
+            drop: 0.2,
+            truncate: 0.5,
+        };
+        let mut injector = FaultInjector::new(source, faults, 42);
+        let mut packets = 0;
+        let mut short = 0;
+        loop {
+            match injector.next_packet() {
+                Ok(packet) => {
+                    packets += 1;
+                    assert!(packet.len() <= 100);
+                    assert_eq!(packet.pkthdr().len, 100);
+                    if packet.len() < 100 {
+                        short += 1;
+                    }
+                }
+                Err(Error::Break) => break,
+                Err(_) => (),
+            }
+        }
+
+        let counts = injector.counts();
+        assert_eq!(packets + counts.dropped, 1000);
+        assert_eq!(short, counts.truncated);
+        assert!((100..300).contains(&counts.dropped), "{:?}", counts);
+        assert!((300..500).contains(&counts.truncated), "{:?}", counts);
+        assert!(counts.timeouts > 50 && counts.errors > 50, "{:?}", counts);
+    }
+
+    #[test]
+    fn test_no_faults() {
+        let source = MockSource::new().packets([[1u8; 10], [2u8; 10]]);
+        let mut injector = FaultInjector::new(source, Faults::default(), 0);
+        assert_eq!(injector.next_packet().unwrap().packet(), [1u8; 10]);
+        assert_eq!(injector.next_packet().unwrap().packet(), [2u8; 10]);
+        assert!(matches!(injector.next_packet(), Err(Error::Break)));
+    }
+}
@@ -0,0 +1,234 @@
+use std::fmt;
+use std::net::IpAddr;
+
+use luomu_common::MacAddr;
+
+use crate::{Error, FilterValue, Result};
+
+/// Filter expression built from parts.
+///
+/// `FilterExpr`s can be combined with `and()`, `or()` and `not()`, or in
+/// groups with `all()` and `any()`, and are rendered with parentheses where
+/// needed to keep the meaning of each part.
+/// This matters as in the filter language `and` and `or` have the same
+/// precedence, so `"a or b"` and `"c"` can't be joined simply as
+/// `"a or b and c"`.
+///
+/// ```
+/// use luomu_libpcap::FilterExpr;
+///
+/// let dns = FilterExpr::port(53).and(FilterExpr::new("udp or tcp"));
+/// let filter = dns.or(FilterExpr::new("icmp")).not();
+/// assert_eq!(filter.to_string(), "not ((port 53 and (udp or tcp)) or (icmp))");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FilterExpr(Expr);
+
+// Groups built by `FilterExpr` always have at least two expressions, as an
+// empty group would render as an empty filter matching every packet.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Expr {
+    // Filter expression as a string, for example from configuration.
+    Raw(String),
+    // Primitive expression generated from a typed value, such as
+    // `host 192.0.2.1`.
+    Primitive(String),
+    // All the expressions match.
+    And(Vec<FilterExpr>),
+    // Any of the expressions matches.
+    Or(Vec<FilterExpr>),
+    // The expression doesn't match.
+    Not(Box<FilterExpr>),
+}
+
+impl FilterExpr {
+    /// Filter expression from a string. The string is always parenthesized
+    /// when combined with other expressions.
+    pub fn new(expr: &str) -> FilterExpr {
+        FilterExpr(Expr::Raw(expr.to_string()))
+    }
+
+    /// Packets to or from host `ip`.
+    pub fn host(ip: IpAddr) -> FilterExpr {
+        FilterExpr(Expr::Primitive(format!("host {}", FilterValue::Ip(ip))))
+    }
+
+    /// Packets to or from network `ip`/`prefix`.
+    pub fn net(ip: IpAddr, prefix: u8) -> Result<FilterExpr> {
+        let net = FilterValue::Net(ip, prefix);
+        net.validate()?;
+        Ok(FilterExpr(Expr::Primitive(format!("net {}", net))))
+    }
+
+    /// Packets to or from port `port`.
+    pub fn port(port: u16) -> FilterExpr {
+        FilterExpr(Expr::Primitive(format!("port {}", port)))
+    }
+
+    /// Packets to or from Ethernet address `mac`.
+    pub fn ether_host(mac: MacAddr) -> FilterExpr {
+        FilterExpr(Expr::Primitive(format!("ether host {}", mac)))
+    }
+
+    /// Expression matching when all of `exprs` match. Fails if `exprs` is
+    /// empty.
+    pub fn all<I: IntoIterator<Item = FilterExpr>>(exprs: I) -> Result<FilterExpr> {
+        FilterExpr::group(exprs, FilterExpr::and)
+    }
+
+    /// Expression matching when any of `exprs` matches. Fails if `exprs` is
+    /// empty, as the empty filter would match every packet instead of none.
+    pub fn any<I: IntoIterator<Item = FilterExpr>>(exprs: I) -> Result<FilterExpr> {
+        FilterExpr::group(exprs, FilterExpr::or)
+    }
+
+    fn group<I, F>(exprs: I, join: F) -> Result<FilterExpr>
+    where
+        I: IntoIterator<Item = FilterExpr>,
+        F: Fn(FilterExpr, FilterExpr) -> FilterExpr,
+    {
+        let mut exprs = exprs.into_iter();
+        let first = exprs
+            .next()
+            .ok_or_else(|| Error::PcapError("empty group of filter expressions".to_string()))?;
+        Ok(exprs.fold(first, join))
+    }
+
+    /// Expression matching when both this and `other` match.
+    pub fn and(self, other: FilterExpr) -> FilterExpr {
+        match self.0 {
+            Expr::And(mut exprs) => {
+                exprs.push(other);
+                FilterExpr(Expr::And(exprs))
+            }
+            expr => FilterExpr(Expr::And(vec![FilterExpr(expr), other])),
+        }
+    }
+
+    /// Expression matching when this or `other` matches.
+    pub fn or(self, other: FilterExpr) -> FilterExpr {
+        match self.0 {
+            Expr::Or(mut exprs) => {
+                exprs.push(other);
+                FilterExpr(Expr::Or(exprs))
+            }
+            expr => FilterExpr(Expr::Or(vec![FilterExpr(expr), other])),
+        }
+    }
+
+    /// Expression matching when this doesn't match.
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> FilterExpr {
+        match self.0 {
+            Expr::Not(expr) => *expr,
+            expr => FilterExpr(Expr::Not(Box::new(FilterExpr(expr)))),
+        }
+    }
+
+    // Write expression as an operand of `and`, `or` or `not`.
+    fn fmt_operand(&self, f: &mut fmt::Formatter<'_>, parent: &FilterExpr) -> fmt::Result {
+        let parenthesize = match (&self.0, &parent.0) {
+            (Expr::Primitive(_), _) => false,
+            (Expr::Not(_), Expr::Not(_)) => true,
+            (Expr::Not(_), _) => false,
+            _ => true,
+        };
+        if parenthesize {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+impl fmt::Display for FilterExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (op, exprs) = match &self.0 {
+            Expr::Raw(expr) | Expr::Primitive(expr) => return f.write_str(expr),
+            Expr::Not(expr) => {
+                f.write_str("not ")?;
+                return expr.fmt_operand(f, self);
+            }
+            Expr::And(exprs) => (" and ", exprs),
+            Expr::Or(exprs) => (" or ", exprs),
+        };
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                f.write_str(op)?;
+            }
+            expr.fmt_operand(f, self)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&str> for FilterExpr {
+    fn from(expr: &str) -> Self {
+        FilterExpr::new(expr)
+    }
+}
+
+impl From<String> for FilterExpr {
+    fn from(expr: String) -> Self {
+        FilterExpr(Expr::Raw(expr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::FilterExpr;
+
+    #[test]
+    fn test_filter_expr_render() {
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+        let expr = FilterExpr::host(ip)
+            .and(FilterExpr::port(80))
+            .and(FilterExpr::new("tcp"));
+        assert_eq!(expr.to_string(), "host 192.0.2.1 and port 80 and (tcp)");
+
+        let expr = FilterExpr::port(53)
+            .or(FilterExpr::port(853))
+            .and(FilterExpr::host(ip).not());
+        assert_eq!(
+            expr.to_string(),
+            "(port 53 or port 853) and not host 192.0.2.1"
+        );
+
+        assert_eq!(FilterExpr::new("udp").not().not(), FilterExpr::new("udp"));
+        assert_eq!(
+            FilterExpr::port(1)
+                .or(FilterExpr::port(2))
+                .not()
+                .to_string(),
+            "not (port 1 or port 2)"
+        );
+        let net = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0));
+        assert!(FilterExpr::net(net, 33).is_err());
+        assert_eq!(
+            FilterExpr::net(net, 24).unwrap().to_string(),
+            "net 192.0.2.0/24"
+        );
+    }
+
+    #[test]
+    fn test_filter_expr_groups() {
+        let ports = [53, 853].map(FilterExpr::port);
+        assert_eq!(
+            FilterExpr::any(ports.clone()).unwrap().to_string(),
+            "port 53 or port 853"
+        );
+        assert_eq!(
+            FilterExpr::all(ports).unwrap().to_string(),
+            "port 53 and port 853"
+        );
+        assert_eq!(
+            FilterExpr::any([FilterExpr::new("udp")]).unwrap(),
+            FilterExpr::new("udp")
+        );
+        assert!(FilterExpr::any([]).is_err());
+        assert!(FilterExpr::all([]).is_err());
+    }
+}
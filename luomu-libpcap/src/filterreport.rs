@@ -0,0 +1,68 @@
+use std::path::Path;
+
+use crate::functions::{pcap_next_ex, pcap_open_offline};
+use crate::{Error, OwnedPacket, PcapFilter, Result};
+
+/// Result of running a filter over a capture file with
+/// `PcapFilter::test_on_file()`.
+#[derive(Clone, Debug)]
+pub struct FilterReport {
+    /// Number of packets matching the filter.
+    pub matched: u64,
+    /// Number of packets in the file.
+    pub total: u64,
+    /// The first matching packets, as many as were asked for.
+    pub packets: Vec<OwnedPacket>,
+}
+
+impl FilterReport {
+    /// Number of packets not matching the filter.
+    pub fn unmatched(&self) -> u64 {
+        self.total - self.matched
+    }
+
+    /// Share of packets matching the filter, from 0.0 to 1.0. Zero for a file
+    /// without packets.
+    pub fn ratio(&self) -> f64 {
+        match self.total {
+            0 => 0.0,
+            total => self.matched as f64 / total as f64,
+        }
+    }
+}
+
+// Run `filter` over packets of savefile `path`, keeping the first `keep`
+// matching packets.
+pub(crate) fn test_on_file<P: AsRef<Path>>(
+    filter: &PcapFilter,
+    path: P,
+    keep: usize,
+) -> Result<FilterReport> {
+    let pcap_t = pcap_open_offline(path)?;
+    let mut report = FilterReport {
+        matched: 0,
+        total: 0,
+        packets: Vec::new(),
+    };
+    loop {
+        let packet = match pcap_next_ex(&pcap_t) {
+            Ok(packet) => packet,
+            Err(Error::Break) => break,
+            Err(Error::Timeout) => continue,
+            Err(err) => return Err(err),
+        };
+        report.total += 1;
+        if filter.matches(&packet) {
+            report.matched += 1;
+            if report.packets.len() < keep {
+                report.packets.push(packet.to_owned());
+            }
+        }
+    }
+    log::debug!(
+        "filter matched {} of {} packets",
+        report.matched,
+        report.total
+    );
+    Ok(report)
+}
@@ -0,0 +1,155 @@
+use luomu_libpcap_sys::pcap_pkthdr;
+
+use crate::functions::{pcap_offline_filter, pcap_open_dead_with_tstamp_precision};
+use crate::{LinkType, PcapFilter, PcapT, Result, TimestampPrecision};
+
+/// Dead capture handle for testing filters against packets in memory.
+///
+/// Filters are compiled for a link-layer header type and snapshot length.
+/// `FilterFixture` compiles them for the given ones with a handle made by
+/// `pcap_open_dead()`, and runs them against packets given as bytes, as if
+/// the packets were captured with the snapshot length. No network access or
+/// privileges are needed, so filter logic can be asserted in CI:
+///
+/// ```no_run
+/// use luomu_libpcap::generator::FrameBuilder;
+/// use luomu_libpcap::{FilterFixture, LinkType};
+///
+/// let fixture = FilterFixture::new(LinkType::RAW, 96)?;
+/// let src = "192.0.2.1:49152".parse().unwrap();
+/// let dst = "192.0.2.53:53".parse().unwrap();
+/// let packet = FrameBuilder::udp(src, dst).payload_len(32).build_ip()?;
+/// assert!(fixture.matches("udp port 53", &packet)?);
+/// assert!(!fixture.matches("tcp", &packet)?);
+/// # Ok::<(), luomu_libpcap::Error>(())
+/// ```
+pub struct FilterFixture {
+    pcap_t: PcapT,
+    linktype: LinkType,
+    snaplen: usize,
+}
+
+impl FilterFixture {
+    /// Fixture for link-layer header type `linktype` and snapshot length
+    /// `snaplen`.
+    pub fn new(linktype: LinkType, snaplen: usize) -> Result<FilterFixture> {
+        let pcap_t = pcap_open_dead_with_tstamp_precision(
+            linktype.value(),
+            snaplen,
+            TimestampPrecision::Micro,
+        )?;
+        Ok(FilterFixture {
+            pcap_t,
+            linktype,
+            snaplen,
+        })
+    }
+
+    /// Fixture for Ethernet with snapshot length of 65535.
+    pub fn ethernet() -> Result<FilterFixture> {
+        FilterFixture::new(LinkType::ETHERNET, 65535)
+    }
+
+    /// Link-layer header type of the fixture.
+    pub fn linktype(&self) -> LinkType {
+        self.linktype
+    }
+
+    /// Snapshot length of the fixture.
+    pub fn snaplen(&self) -> usize {
+        self.snaplen
+    }
+
+    /// The dead handle of the fixture.
+    pub fn pcap_t(&self) -> &PcapT {
+        &self.pcap_t
+    }
+
+    /// Compile `filter` for the link-layer header type and snapshot length
+    /// of the fixture.
+    pub fn compile(&self, filter: &str) -> Result<PcapFilter> {
+        PcapFilter::compile_with_pcap_t(&self.pcap_t, filter)
+    }
+
+    /// Returns true if `packet` matches `filter`. Fails if `filter` doesn't
+    /// compile.
+    pub fn matches(&self, filter: &str, packet: &[u8]) -> Result<bool> {
+        let filter = self.compile(filter)?;
+        Ok(pcap_offline_filter(&filter, &self.pkthdr(packet), packet))
+    }
+
+    /// Check each of `packets` against `filter`. The result has true for
+    /// each matching packet.
+    pub fn matching(&self, filter: &str, packets: &[&[u8]]) -> Result<Vec<bool>> {
+        let filter = self.compile(filter)?;
+        let packets: Vec<(pcap_pkthdr, &[u8])> = packets
+            .iter()
+            .map(|packet| (self.pkthdr(packet), *packet))
+            .collect();
+        Ok(filter.filter_batch(&packets))
+    }
+
+    // Header of `packet` as if it had been captured with the snapshot length
+    // of the fixture.
+    fn pkthdr(&self, packet: &[u8]) -> pcap_pkthdr {
+        pcap_pkthdr {
+            ts: libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            caplen: packet.len().min(self.snaplen) as u32,
+            len: packet.len() as u32,
+        }
+    }
+}
+
+impl std::fmt::Debug for FilterFixture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FilterFixture")
+            .field("linktype", &self.linktype)
+            .field("snaplen", &self.snaplen)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FilterFixture;
+    use crate::LinkType;
+
+    // IPv4 header of a UDP packet from 10.0.0.1 to 10.0.0.2 and UDP header
+    // from port 1234 to 53.
+    fn udp_dns() -> Vec<u8> {
+        let mut packet = vec![
+            0x45, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 10, 0, 0, 1,
+            10, 0, 0, 2,
+        ];
+        packet.extend_from_slice(&[0x04, 0xd2, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00]);
+        packet
+    }
+
+    #[test]
+    fn test_filter_fixture() {
+        let fixture = FilterFixture::new(LinkType::RAW, 65535).unwrap();
+        let packet = udp_dns();
+        assert!(fixture.matches("udp port 53", &packet).unwrap());
+        assert!(fixture.matches("src host 10.0.0.1", &packet).unwrap());
+        assert!(!fixture.matches("tcp", &packet).unwrap());
+        assert!(fixture.matches("foo", &packet).is_err());
+
+        let mut ethernet = vec![0u8; 12];
+        ethernet.extend_from_slice(&[0x08, 0x00]);
+        ethernet.extend_from_slice(&packet);
+        let fixture = FilterFixture::ethernet().unwrap();
+        assert_eq!(
+            fixture
+                .matching("udp dst port 53", &[&ethernet, &packet])
+                .unwrap(),
+            [true, false]
+        );
+
+        // The UDP header is cut off by the snapshot length.
+        let fixture = FilterFixture::new(LinkType::RAW, 20).unwrap();
+        assert!(!fixture.matches("udp port 53", &packet).unwrap());
+    }
+}
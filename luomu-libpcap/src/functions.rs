@@ -9,21 +9,42 @@
 //! <https://www.tcpdump.org/manpages/> and modified as needed to fit the
 //! implementations.
 //!
+//! All functions of libpcap 1.10 are wrapped, except:
+//!
+//!   * the remote capture API (`pcap_open()`, `pcap_findalldevs_ex()`,
+//!     `pcap_createsrcstr()`, `pcap_parsesrcstr()`, `pcap_setsampling()` and
+//!     `pcap_remoteact_*()`), as libpcap is built without remote capture
+//!     support,
+//!   * `pcap_lookupdev()`, which is deprecated and doesn't work once
+//!     `pcap_init()` has been called,
+//!   * `pcap_next()`, as `pcap_next_ex()` does the same with proper error
+//!     reporting.
+//!
+//! On Windows the WinPcap/Npcap extensions `pcap_getevent()`,
+//! `pcap_live_dump()` and `pcap_live_dump_ended()` are wrapped too.
+//!
 //! Log level of "trace" (see <https://docs.rs/log/>) is used to log invocations
-//! of these functions.
+//! of these functions. With `tracing` feature enabled the invocations are
+//! emitted as `tracing` events (see <https://docs.rs/tracing/>) instead and
+//! some of the functions are wrapped in spans.
 
 use std::collections::BTreeSet;
 use std::ffi::{c_void, CStr, CString};
 use std::mem::MaybeUninit;
-use std::net::{Ipv4Addr, Ipv6Addr};
-use std::os::fd::AsRawFd;
+use std::net::Ipv4Addr;
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, IntoRawFd};
 use std::path::Path;
 
+#[cfg(not(feature = "tracing"))]
 use log::trace;
+#[cfg(feature = "tracing")]
+use tracing::trace;
 
 use crate::{
-    Address, AddressIter, BorrowedPacket, Error, Interface, InterfaceAddress, InterfaceFlag,
-    MacAddr, PcapDumper, PcapFilter, PcapIfT, PcapStat, PcapT, Result,
+    Address, AddressIter, BorrowedPacket, Direction, Error, Interface, InterfaceAddress,
+    InterfaceFlag, InterfaceFlags, PcapDumper, PcapFilter, PcapIfT, PcapStat, PcapT, Result,
+    TimestampPrecision,
 };
 
 use luomu_libpcap_sys as libpcap;
@@ -33,6 +54,41 @@ use luomu_libpcap_sys as libpcap;
 const PCAP_SUCCESS: i32 = 0;
 const PCAP_ERROR: i32 = libpcap::PCAP_ERROR;
 
+/// initialize the library
+///
+/// `pcap_init()` is used to initialize the Packet Capture library. `opts`
+/// specifies options for the library; currently, the options are:
+///
+/// * `PCAP_CHAR_ENC_LOCAL`: Treat all strings supplied as arguments, and
+///   return all strings to the caller, as being in the local character
+///   encoding.
+/// * `PCAP_CHAR_ENC_UTF_8`: Treat all strings supplied as arguments, and
+///   return all strings to the caller, as being in UTF-8.
+///
+/// On Windows `pcap_init()` also initializes Winsock.
+///
+/// Usually there is no need to call this directly as the library is
+/// initialized when first handle is created. See [crate::init()].
+///
+/// <https://www.tcpdump.org/manpages/pcap_init.3pcap.html>
+pub fn pcap_init(opts: u32) -> Result<()> {
+    let mut errbuf: Vec<u8> = vec![0; libpcap::PCAP_ERRBUF_SIZE as usize];
+
+    let ret = unsafe { libpcap::pcap_init(opts, errbuf.as_mut_ptr() as *mut libc::c_char) };
+
+    trace!("pcap_init({}) => {}", opts, ret);
+
+    match ret {
+        PCAP_SUCCESS => Ok(()),
+        PCAP_ERROR => {
+            let cstr = unsafe { CStr::from_ptr(errbuf.as_ptr() as *const libc::c_char) };
+            let err = cstr.to_str()?.to_owned();
+            Err(Error::PcapError(err))
+        }
+        n => Err(Error::PcapErrorCode(n)),
+    }
+}
+
 /// Create a live capture handle
 ///
 /// `pcap_create()` is used to create a packet capture handle to look at packets
@@ -41,6 +97,7 @@ const PCAP_ERROR: i32 = libpcap::PCAP_ERROR;
 ///
 /// <https://www.tcpdump.org/manpages/pcap_create.3pcap.html>
 pub fn pcap_create(source: &str) -> Result<PcapT> {
+    crate::init::ensure_init()?;
     let mut errbuf: Vec<u8> = vec![0; libpcap::PCAP_ERRBUF_SIZE as usize];
     let interface = Some(source.to_string());
     let source = CString::new(source)?;
@@ -60,6 +117,9 @@ pub fn pcap_create(source: &str) -> Result<PcapT> {
         pcap_t,
         errbuf,
         interface,
+        activity: Default::default(),
+        id: PcapT::next_id(),
+        savefile_size: None,
     })
 }
 
@@ -69,6 +129,7 @@ pub fn pcap_create(source: &str) -> Result<PcapT> {
 ///
 /// <https://www.tcpdump.org/manpages/pcap_open_offline.3pcap.html>
 pub fn pcap_open_offline<P: AsRef<Path>>(savefile: P) -> Result<PcapT> {
+    crate::init::ensure_init()?;
     let mut errbuf: Vec<u8> = vec![0; libpcap::PCAP_ERRBUF_SIZE as usize];
 
     let fname = CString::new(savefile.as_ref().to_string_lossy().as_ref())?;
@@ -87,9 +148,24 @@ pub fn pcap_open_offline<P: AsRef<Path>>(savefile: P) -> Result<PcapT> {
         pcap_t,
         errbuf,
         interface: None,
+        activity: Default::default(),
+        id: PcapT::next_id(),
+        savefile_size: savefile_size(savefile.as_ref()),
     })
 }
 
+// Size of savefile `path`, or None if it's not a regular file. libpcap reads
+// standard input for "-", so it is not looked up as a file.
+fn savefile_size(path: &Path) -> Option<u64> {
+    if path == Path::new("-") {
+        return None;
+    }
+    std::fs::metadata(path)
+        .ok()
+        .filter(|meta| meta.is_file())
+        .map(|meta| meta.len())
+}
+
 /// Close a capture device or savefile
 ///
 /// `pcap_close()` closes the files associated with `PcapT` and deallocates
@@ -177,6 +253,8 @@ pub fn pcap_set_timeout(pcap_t: &PcapT, to_ms: i32) -> Result<()> {
 ///
 /// <https://www.tcpdump.org/manpages/pcap_activate.3pcap.html>
 pub fn pcap_activate(pcap_t: &PcapT) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("pcap_activate", device = %pcap_t.get_inteface()).entered();
     trace!("pcap_activate({:p})", pcap_t.pcap_t);
     let ret = unsafe { libpcap::pcap_activate(pcap_t.pcap_t) };
     check_pcap_error(pcap_t, ret)?;
@@ -191,6 +269,170 @@ pub fn pcap_activate(pcap_t: &PcapT) -> Result<()> {
     Ok(())
 }
 
+/// get the file descriptor for a live capture
+///
+/// `pcap_fileno()` returns the file descriptor from which captured packets are
+/// read. If `PcapT` refers to a savefile that was opened using
+/// `pcap_open_offline()`, it returns the file descriptor for the savefile.
+///
+/// Returns `None` if the handle has no file descriptor, for example when it
+/// was created with `pcap_open_dead()` or the capture is not yet activated.
+///
+/// <https://www.tcpdump.org/manpages/pcap_fileno.3pcap.html>
+pub fn pcap_fileno(pcap_t: &PcapT) -> Option<libc::c_int> {
+    let ret = unsafe { libpcap::pcap_fileno(pcap_t.pcap_t) };
+    trace!("pcap_fileno({:p}) => {}", pcap_t.pcap_t, ret);
+    if ret == PCAP_ERROR {
+        None
+    } else {
+        Some(ret)
+    }
+}
+
+/// get the standard I/O stream for a savefile being read
+///
+/// `pcap_file()` returns the standard I/O stream of the savefile, if a savefile
+/// was opened with `pcap_open_offline()`, or `None`, if a network device was
+/// opened with `pcap_create()` and `pcap_activate()`.
+///
+/// The stream is owned by libpcap and must not be closed.
+///
+/// <https://www.tcpdump.org/manpages/pcap_file.3pcap.html>
+pub fn pcap_file(pcap_t: &PcapT) -> Option<*mut libpcap::FILE> {
+    let ret = unsafe { libpcap::pcap_file(pcap_t.pcap_t) };
+    trace!("pcap_file({:p}) => {:p}", pcap_t.pcap_t, ret);
+    if ret.is_null() {
+        None
+    } else {
+        Some(ret)
+    }
+}
+
+/// find out whether a savefile has the native byte order
+///
+/// `pcap_is_swapped()` returns true if `PcapT` refers to a savefile that uses
+/// a different byte order than the current system. For a live capture, it
+/// always returns false.
+///
+/// <https://www.tcpdump.org/manpages/pcap_is_swapped.3pcap.html>
+pub fn pcap_is_swapped(pcap_t: &PcapT) -> Result<bool> {
+    trace!("pcap_is_swapped({:p})", pcap_t.pcap_t);
+    let ret = unsafe { libpcap::pcap_is_swapped(pcap_t.pcap_t) };
+    if ret < 0 {
+        check_pcap_error(pcap_t, ret)?;
+    }
+    Ok(ret != 0)
+}
+
+/// get the major version of the file format of a savefile
+///
+/// If `PcapT` refers to a savefile, `pcap_major_version()` returns the major
+/// number of the file format of the savefile. If `PcapT` refers to a live
+/// capture, the value returned is meaningless.
+///
+/// <https://www.tcpdump.org/manpages/pcap_major_version.3pcap.html>
+pub fn pcap_major_version(pcap_t: &PcapT) -> i32 {
+    trace!("pcap_major_version({:p})", pcap_t.pcap_t);
+    unsafe { libpcap::pcap_major_version(pcap_t.pcap_t) }
+}
+
+/// get the minor version of the file format of a savefile
+///
+/// If `PcapT` refers to a savefile, `pcap_minor_version()` returns the minor
+/// number of the file format of the savefile. If `PcapT` refers to a live
+/// capture, the value returned is meaningless.
+///
+/// <https://www.tcpdump.org/manpages/pcap_major_version.3pcap.html>
+pub fn pcap_minor_version(pcap_t: &PcapT) -> i32 {
+    trace!("pcap_minor_version({:p})", pcap_t.pcap_t);
+    unsafe { libpcap::pcap_minor_version(pcap_t.pcap_t) }
+}
+
+/// get the snapshot length
+///
+/// `pcap_snapshot()` returns the snapshot length specified when
+/// `pcap_set_snaplen()` or `pcap_open_live()` was called, for a live capture,
+/// or the snapshot length from the capture file, for a savefile.
+///
+/// <https://www.tcpdump.org/manpages/pcap_snapshot.3pcap.html>
+pub fn pcap_snapshot(pcap_t: &PcapT) -> Result<usize> {
+    trace!("pcap_snapshot({:p})", pcap_t.pcap_t);
+    let ret = unsafe { libpcap::pcap_snapshot(pcap_t.pcap_t) };
+    if ret < 0 {
+        check_pcap_error(pcap_t, ret)?;
+    }
+    Ok(ret as usize)
+}
+
+/// get the link-layer header type
+///
+/// `pcap_datalink()` returns the link-layer header type for the live capture
+/// or savefile specified by `PcapT`.
+///
+/// <https://www.tcpdump.org/manpages/pcap_datalink.3pcap.html>
+pub fn pcap_datalink(pcap_t: &PcapT) -> Result<i32> {
+    trace!("pcap_datalink({:p})", pcap_t.pcap_t);
+    let ret = unsafe { libpcap::pcap_datalink(pcap_t.pcap_t) };
+    if ret < 0 {
+        check_pcap_error(pcap_t, ret)?;
+    }
+    Ok(ret)
+}
+
+/// get the size of the capture buffer
+///
+/// `pcap_bufsize()` returns the size of the buffer in bytes that is used for
+/// capture on an activated `PcapT`.
+///
+/// <https://www.tcpdump.org/manpages/pcap.3pcap.html>
+pub fn pcap_bufsize(pcap_t: &PcapT) -> Result<usize> {
+    trace!("pcap_bufsize({:p})", pcap_t.pcap_t);
+    let ret = unsafe { libpcap::pcap_bufsize(pcap_t.pcap_t) };
+    if ret < 0 {
+        check_pcap_error(pcap_t, ret)?;
+    }
+    Ok(ret as usize)
+}
+
+/// get the time stamp precision returned in captures
+///
+/// `pcap_get_tstamp_precision()` returns the precision of the time stamp
+/// returned in packet captures on the pcap descriptor.
+///
+/// <https://www.tcpdump.org/manpages/pcap_get_tstamp_precision.3pcap.html>
+pub fn pcap_get_tstamp_precision(pcap_t: &PcapT) -> Result<TimestampPrecision> {
+    trace!("pcap_get_tstamp_precision({:p})", pcap_t.pcap_t);
+    let ret = unsafe { libpcap::pcap_get_tstamp_precision(pcap_t.pcap_t) };
+    match ret as u32 {
+        libpcap::PCAP_TSTAMP_PRECISION_MICRO => Ok(TimestampPrecision::Micro),
+        libpcap::PCAP_TSTAMP_PRECISION_NANO => Ok(TimestampPrecision::Nano),
+        _ => Err(Error::PcapErrorCode(ret)),
+    }
+}
+
+/// get the state of non-blocking mode on a capture device
+///
+/// `pcap_getnonblock()` returns the current "non-blocking" state of the
+/// capture descriptor; it always returns false on savefiles.
+///
+/// <https://www.tcpdump.org/manpages/pcap_getnonblock.3pcap.html>
+pub fn pcap_getnonblock(pcap_t: &PcapT) -> Result<bool> {
+    trace!("pcap_getnonblock({:p})", pcap_t.pcap_t);
+    let mut errbuf: Vec<u8> = vec![0; libpcap::PCAP_ERRBUF_SIZE as usize];
+    let ret = unsafe {
+        libpcap::pcap_getnonblock(pcap_t.pcap_t, errbuf.as_mut_ptr() as *mut libc::c_char)
+    };
+    match ret {
+        PCAP_ERROR => {
+            let cstr = unsafe { CStr::from_ptr(errbuf.as_ptr() as *const libc::c_char) };
+            let err = cstr.to_str()?.to_owned();
+            Err(Error::PcapError(err))
+        }
+        n if n < 0 => Err(Error::PcapErrorCode(n)),
+        n => Ok(n != 0),
+    }
+}
+
 /// get libpcap error message text
 ///
 /// `pcap_geterr()` returns the error pertaining to the last pcap library error.
@@ -203,9 +445,7 @@ pub fn pcap_activate(pcap_t: &PcapT) -> Result<()> {
 pub fn get_error(pcap_t: &PcapT) -> Result<Error> {
     trace!("get_error({:p})", pcap_t.pcap_t);
     let ptr = unsafe { libpcap::pcap_geterr(pcap_t.pcap_t) };
-    let cstr = unsafe { CStr::from_ptr(ptr) };
-    let err = cstr.to_str()?.to_owned();
-    Ok(Error::PcapError(err))
+    Ok(Error::PcapError(libpcap_str("pcap_geterr", ptr)?))
 }
 
 /// get capture statistics
@@ -217,7 +457,20 @@ pub fn get_error(pcap_t: &PcapT) -> Result<Error> {
 /// <https://www.tcpdump.org/manpages/pcap_stats.3pcap.html>
 pub fn pcap_stats(pcap_t: &PcapT, stat: &mut PcapStat) -> Result<()> {
     let ret = unsafe { libpcap::pcap_stats(pcap_t.pcap_t, &mut stat.stats) };
-    check_pcap_error(pcap_t, ret)
+    check_pcap_error(pcap_t, ret)?;
+
+    #[cfg(feature = "tracing")]
+    if stat.stats.ps_drop > 0 || stat.stats.ps_ifdrop > 0 {
+        tracing::warn!(
+            device = %pcap_t.get_inteface(),
+            received = stat.stats.ps_recv,
+            dropped = stat.stats.ps_drop,
+            dropped_interface = stat.stats.ps_ifdrop,
+            "packet drops detected"
+        );
+    }
+
+    Ok(())
 }
 
 /// transmit a packet
@@ -254,15 +507,44 @@ pub fn pcap_inject(pcap_t: &PcapT, buf: &[u8]) -> Result<usize> {
 /// Optimization of the filter turned on by default.
 ///
 /// C library's `pcap_compile()` supports specifying IPv4 netmask, but we use
-/// `PCAP_NETMASK_UNKNOWN` by default.
+/// `PCAP_NETMASK_UNKNOWN` by default. Use `pcap_compile_with_netmask()` for
+/// filters testing for IPv4 broadcast addresses.
 ///
 /// <https://www.tcpdump.org/manpages/pcap_compile.3pcap.html>
 pub fn pcap_compile(pcap_t: &PcapT, filter: &str) -> Result<PcapFilter> {
-    trace!("pcap_compile({:p}, {})", pcap_t.pcap_t, filter);
+    compile(pcap_t, filter, libpcap::PCAP_NETMASK_UNKNOWN)
+}
+
+/// compile a filter expression with IPv4 netmask
+///
+/// Like `pcap_compile()`, but with the IPv4 netmask of the network on which
+/// packets are being captured, as returned by `pcap_lookupnet()`. The netmask
+/// is needed by the `ip broadcast` primitive.
+///
+/// <https://www.tcpdump.org/manpages/pcap_compile.3pcap.html>
+pub fn pcap_compile_with_netmask(
+    pcap_t: &PcapT,
+    filter: &str,
+    netmask: Ipv4Addr,
+) -> Result<PcapFilter> {
+    // pcap_lookupnet() gives the netmask in network byte order and
+    // pcap_compile() takes it the same way.
+    compile(pcap_t, filter, u32::from(netmask).to_be())
+}
+
+fn compile(pcap_t: &PcapT, filter: &str, netmask: libpcap::bpf_u_int32) -> Result<PcapFilter> {
+    #[cfg(feature = "tracing")]
+    let _span =
+        tracing::debug_span!("pcap_compile", device = %pcap_t.get_inteface(), filter).entered();
+    trace!(
+        "pcap_compile({:p}, {}, {:x})",
+        pcap_t.pcap_t,
+        filter,
+        netmask
+    );
     let mut bpf_program: MaybeUninit<libpcap::bpf_program> = MaybeUninit::zeroed();
     let filter = CString::new(filter)?;
     let optimize = 1;
-    let netmask = libpcap::PCAP_NETMASK_UNKNOWN;
 
     let ret = unsafe {
         libpcap::pcap_compile(
@@ -290,6 +572,13 @@ pub fn pcap_compile(pcap_t: &PcapT, filter: &str) -> Result<PcapFilter> {
 ///
 /// <https://www.tcpdump.org/manpages/pcap_setfilter.3pcap.html>
 pub fn pcap_setfilter(pcap_t: &PcapT, pcap_filter: &mut PcapFilter) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!(
+        "pcap_setfilter",
+        device = %pcap_t.get_inteface(),
+        instructions = pcap_filter.bpf_program.bf_len
+    )
+    .entered();
     trace!(
         "pcap_setfilter({:p}, {:p})",
         pcap_t.pcap_t,
@@ -332,12 +621,18 @@ pub fn pcap_next_ex(pcap_t: &PcapT) -> Result<BorrowedPacket> {
     match ret {
         1 => (),
         0 => return Err(Error::Timeout),
-        n => check_pcap_error(pcap_t, n)?,
+        n => {
+            if n == PCAP_ERROR {
+                pcap_t.activity.fail();
+            }
+            check_pcap_error(pcap_t, n)?
+        }
     }
 
     if header.is_null() || packet.is_null() {
         panic!("header or packet NULL.");
     }
+    pcap_t.activity.packet();
 
     Ok(BorrowedPacket::new(header, packet))
 }
@@ -353,6 +648,7 @@ pub fn pcap_next_ex(pcap_t: &PcapT) -> Result<BorrowedPacket> {
 ///
 /// <https://www.tcpdump.org/manpages/pcap_open_dead.3pcap.html>
 pub fn pcap_open_dead() -> Result<PcapT> {
+    crate::init::ensure_init()?;
     let pcap_t = unsafe { libpcap::pcap_open_dead(libpcap::DLT_EN10MB as libc::c_int, 65535) };
 
     // pcap_open_dead return value is not documented.
@@ -363,6 +659,9 @@ pub fn pcap_open_dead() -> Result<PcapT> {
         pcap_t,
         errbuf,
         interface: None,
+        activity: Default::default(),
+        id: PcapT::next_id(),
+        savefile_size: None,
     })
 }
 
@@ -378,9 +677,11 @@ pub fn pcap_open_dead() -> Result<PcapT> {
 // Rust compiler doesn't require it, but we want to take `&mut PcapDumper`
 // because we write into a file pointed by `PcapDumper`.
 #[allow(clippy::needless_pass_by_value)]
+#[cfg(unix)]
 pub fn pcap_dump_fopen(pcap_t: &PcapT, file: &mut std::fs::File) -> Result<PcapDumper> {
     trace!("pcap_dump_fopen({:p}, {:?})", pcap_t.pcap_t, file);
     let mode = b"wb\0";
+    let precision = pcap_get_tstamp_precision(pcap_t)?;
 
     let filedesc = unsafe { libc::fdopen(file.as_raw_fd(), mode.as_ptr() as *const libc::c_char) };
     if filedesc.is_null() {
@@ -392,7 +693,40 @@ pub fn pcap_dump_fopen(pcap_t: &PcapT, file: &mut std::fs::File) -> Result<PcapD
         return Err(get_error(pcap_t)?);
     }
 
-    Ok(PcapDumper { pcap_dumper_t: ret })
+    Ok(PcapDumper::new(ret, precision))
+}
+
+/// open a writer to which to write packets
+///
+/// Like `pcap_dump_fopen()` but packets are written into `writer`, which is
+/// wrapped into a C stream. The writer is owned by the returned `PcapDumper`
+/// and flushed and dropped when the dumper is dropped.
+///
+/// <https://www.tcpdump.org/manpages/pcap_dump_open.3pcap.html>
+#[cfg(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    target_os = "netbsd"
+))]
+pub fn pcap_dump_fopen_writer<W>(pcap_t: &PcapT, writer: W) -> Result<PcapDumper>
+where
+    W: std::io::Write + Send + 'static,
+{
+    trace!("pcap_dump_fopen_writer({:p})", pcap_t.pcap_t);
+    let precision = pcap_get_tstamp_precision(pcap_t)?;
+    let fp = crate::cfile::open_writer(writer)?;
+
+    let ret = unsafe { libpcap::pcap_dump_fopen(pcap_t.pcap_t, fp) };
+    if ret.is_null() {
+        unsafe { libc::fclose(fp) };
+        return Err(get_error(pcap_t)?);
+    }
+
+    Ok(PcapDumper::new(ret, precision))
 }
 
 /// flush to a savefile packets dumped
@@ -408,6 +742,7 @@ pub fn pcap_dump_fopen(pcap_t: &PcapT, file: &mut std::fs::File) -> Result<PcapD
 #[allow(clippy::needless_pass_by_value)]
 pub fn pcap_dump_flush(dumper: &mut PcapDumper) -> Result<()> {
     trace!("pcap_dump_flush({:p})", dumper.pcap_dumper_t);
+    dumper.write_buffered()?;
     let ret = unsafe { libpcap::pcap_dump_flush(dumper.pcap_dumper_t) };
     if ret == 0 {
         Ok(())
@@ -434,6 +769,10 @@ pub fn pcap_dump_flush(dumper: &mut PcapDumper) -> Result<()> {
 #[allow(clippy::needless_pass_by_value)]
 pub fn pcap_dump(dumper: &mut PcapDumper, pkthdr: &libpcap::pcap_pkthdr, bytes: &[u8]) {
     trace!("pcap_dump({:p}, {:?})", dumper.pcap_dumper_t, pkthdr);
+    // Keep packets in order with those in the write buffer of PcapDumper.
+    if let Err(err) = dumper.write_buffered() {
+        log::warn!("pcap_dump(): {}", err);
+    }
     unsafe {
         libpcap::pcap_dump(
             dumper.pcap_dumper_t as *mut libc::c_uchar,
@@ -443,150 +782,1133 @@ pub fn pcap_dump(dumper: &mut PcapDumper, pkthdr: &libpcap::pcap_pkthdr, bytes:
     }
 }
 
-/// get a list of capture devices
+/// open a device for capturing
 ///
-/// `pcap_findalldevs()` constructs a list of network devices that can be opened
-/// with `pcap_create()` and `pcap_activate()` or with `pcap_open_live()`. Note
-/// that there may be network devices that cannot be opened by the process
-/// calling `pcap_findalldevs()`, because, for example, that process does not
-/// have sufficient privileges to open them for capturing; if so, those devices
-/// will not appear on the list.
+/// `pcap_open_live()` is used to obtain a packet capture handle to look at
+/// packets on the network. `device` is a string that specifies the network
+/// device to open. This is the same as calling `pcap_create()`, setting the
+/// options and calling `pcap_activate()`.
 ///
-/// <https://www.tcpdump.org/manpages/pcap_findalldevs.3pcap.html>
-pub fn pcap_findalldevs() -> Result<PcapIfT> {
-    let mut pcap_if_t: *mut libpcap::pcap_if_t = std::ptr::null_mut();
+/// <https://www.tcpdump.org/manpages/pcap_open_live.3pcap.html>
+pub fn pcap_open_live(device: &str, snaplen: usize, promisc: bool, to_ms: i32) -> Result<PcapT> {
+    crate::init::ensure_init()?;
     let mut errbuf: Vec<u8> = vec![0; libpcap::PCAP_ERRBUF_SIZE as usize];
+    let interface = Some(device.to_string());
+    let device = CString::new(device)?;
 
-    let ret = unsafe {
-        libpcap::pcap_findalldevs(&mut pcap_if_t, errbuf.as_mut_ptr() as *mut libc::c_char)
+    let pcap_t = unsafe {
+        libpcap::pcap_open_live(
+            device.as_ptr(),
+            snaplen as libc::c_int,
+            promisc.into(),
+            to_ms as libc::c_int,
+            errbuf.as_mut_ptr() as *mut libc::c_char,
+        )
     };
 
-    match ret {
-        PCAP_SUCCESS => {
-            trace!("pcap_findalldevs() => {:p}", pcap_if_t);
-            Ok(PcapIfT { pcap_if_t })
-        }
-        PCAP_ERROR => {
-            let cstr = unsafe { CStr::from_ptr(errbuf.as_ptr() as *const libc::c_char) };
-            let err = cstr.to_str()?.to_owned();
-            Err(Error::PcapError(err))
-        }
-        n => Err(Error::PcapErrorCode(n)),
+    trace!("pcap_open_live({:?}) => {:p}", device, pcap_t);
+    if pcap_t.is_null() {
+        return Err(errbuf_error(&errbuf)?);
     }
+
+    Ok(PcapT {
+        pcap_t,
+        errbuf,
+        interface,
+        activity: Default::default(),
+        id: PcapT::next_id(),
+        savefile_size: None,
+    })
 }
 
-/// free a list of capture devices
+/// open a fake `PcapT` for compiling filters with given time stamp precision
 ///
-/// Free a list of network devices produced by `pcap_findalldevs()`.
+/// `pcap_open_dead_with_tstamp_precision()` is like `pcap_open_dead()`, but
+/// lets the link-layer header type, snapshot length and time stamp precision
+/// to be specified.
 ///
-/// <https://www.tcpdump.org/manpages/pcap_findalldevs.3pcap.html>
-pub fn pcap_freealldevs(pcap_if_t: PcapIfT) {
-    trace!("pcap_freealldevs({:p})", pcap_if_t.pcap_if_t);
-    // PcapIfT is owned by this function and dropped at this point since it's no
-    // longer needed. Dropping frees the allocated resources.
-}
-
-pub(crate) fn try_interface_from(pcap_if_t: *mut libpcap::pcap_if_t) -> Result<Interface> {
-    trace!("try_interface_from({:p})", pcap_if_t);
-    let name: String = {
-        let name = unsafe { (*pcap_if_t).name };
-        if name.is_null() {
-            panic!("pcap_if_t.name is null");
-        } else {
-            let s = unsafe { CStr::from_ptr(name) };
-            s.to_str()?.to_owned()
-        }
+/// <https://www.tcpdump.org/manpages/pcap_open_dead.3pcap.html>
+pub fn pcap_open_dead_with_tstamp_precision(
+    linktype: i32,
+    snaplen: usize,
+    precision: TimestampPrecision,
+) -> Result<PcapT> {
+    crate::init::ensure_init()?;
+    let pcap_t = unsafe {
+        libpcap::pcap_open_dead_with_tstamp_precision(
+            linktype as libc::c_int,
+            snaplen as libc::c_int,
+            precision.as_raw(),
+        )
     };
 
-    let description: Option<String> = {
-        let descr = unsafe { (*pcap_if_t).description };
-        if descr.is_null() {
-            None
-        } else {
-            let s = unsafe { CStr::from_ptr(descr) };
-            Some(s.to_str()?.to_owned())
-        }
-    };
+    trace!(
+        "pcap_open_dead_with_tstamp_precision({}, {}, {:?}) => {:p}",
+        linktype,
+        snaplen,
+        precision,
+        pcap_t
+    );
+    if pcap_t.is_null() {
+        return Err(Error::PcapError(
+            "pcap_open_dead_with_tstamp_precision() failed".to_string(),
+        ));
+    }
 
-    let addresses = {
-        let pcap_addr_t: *mut libpcap::pcap_addr_t = unsafe { (*pcap_if_t).addresses };
-        let addrs = AddressIter {
-            start: pcap_addr_t,
-            next: Some(pcap_addr_t),
-        };
+    let errbuf: Vec<u8> = vec![0; libpcap::PCAP_ERRBUF_SIZE as usize];
+    Ok(PcapT {
+        pcap_t,
+        errbuf,
+        interface: None,
+        activity: Default::default(),
+        id: PcapT::next_id(),
+        savefile_size: None,
+    })
+}
 
-        let mut addresses = BTreeSet::new();
-        for addr in addrs {
-            addresses.insert(addr);
-        }
-        addresses
-    };
+/// open a savefile for reading with given time stamp precision
+///
+/// `pcap_open_offline_with_tstamp_precision()` is like `pcap_open_offline()`,
+/// but time stamps of packets read are returned with the given precision.
+///
+/// <https://www.tcpdump.org/manpages/pcap_open_offline.3pcap.html>
+pub fn pcap_open_offline_with_tstamp_precision<P: AsRef<Path>>(
+    savefile: P,
+    precision: TimestampPrecision,
+) -> Result<PcapT> {
+    crate::init::ensure_init()?;
+    let mut errbuf: Vec<u8> = vec![0; libpcap::PCAP_ERRBUF_SIZE as usize];
+    let fname = CString::new(savefile.as_ref().to_string_lossy().as_ref())?;
 
-    let flags = {
-        let f = unsafe { (*pcap_if_t).flags };
-        get_interface_flags(f)
+    let pcap_t = unsafe {
+        libpcap::pcap_open_offline_with_tstamp_precision(
+            fname.as_ptr(),
+            precision.as_raw(),
+            errbuf.as_mut_ptr() as *mut libc::c_char,
+        )
     };
 
-    Ok(Interface {
-        name,
-        description,
-        addresses,
-        flags,
+    trace!(
+        "pcap_open_offline_with_tstamp_precision({:?}, {:?}) => {:p}",
+        fname,
+        precision,
+        pcap_t
+    );
+    if pcap_t.is_null() {
+        return Err(errbuf_error(&errbuf)?);
+    }
+
+    Ok(PcapT {
+        pcap_t,
+        errbuf,
+        interface: None,
+        activity: Default::default(),
+        id: PcapT::next_id(),
+        savefile_size: None,
     })
 }
 
-fn from_sockaddr(addr: *const libc::sockaddr) -> Option<Address> {
-    trace!("from_sockaddr({:p})", addr);
+/// open an already opened file for reading as savefile
+///
+/// `pcap_fopen_offline()` is like `pcap_open_offline()` but reads the savefile
+/// from already opened `file`. The file is owned by the returned `PcapT` and
+/// closed when it is dropped.
+///
+/// <https://www.tcpdump.org/manpages/pcap_open_offline.3pcap.html>
+#[cfg(unix)]
+pub fn pcap_fopen_offline(file: std::fs::File) -> Result<PcapT> {
+    fopen_offline(file, None)
+}
 
-    if addr.is_null() {
-        return None;
-    }
+/// open an already opened file for reading as savefile with given time stamp
+/// precision
+///
+/// `pcap_fopen_offline_with_tstamp_precision()` is like
+/// `pcap_fopen_offline()`, but time stamps of packets read are returned with
+/// the given precision.
+///
+/// <https://www.tcpdump.org/manpages/pcap_open_offline.3pcap.html>
+#[cfg(unix)]
+pub fn pcap_fopen_offline_with_tstamp_precision(
+    file: std::fs::File,
+    precision: TimestampPrecision,
+) -> Result<PcapT> {
+    fopen_offline(file, Some(precision))
+}
 
-    let family = unsafe { (*addr).sa_family };
+#[cfg(unix)]
+fn fopen_offline(file: std::fs::File, precision: Option<TimestampPrecision>) -> Result<PcapT> {
+    crate::init::ensure_init()?;
+    trace!("pcap_fopen_offline({:?}, {:?})", file, precision);
+    let mode = b"rb\0";
+
+    let fd = file.into_raw_fd();
+    let fp = unsafe { libc::fdopen(fd, mode.as_ptr() as *const libc::c_char) };
+    if fp.is_null() {
+        let err = std::io::Error::last_os_error();
+        unsafe { libc::close(fd) };
+        return Err(Error::IO(err));
+    }
 
-    match i32::from(family) {
-        libc::AF_INET => {
-            let inet4: *const libc::sockaddr_in = addr as *const libc::sockaddr_in;
-            let s_addr: u32 = unsafe { (*inet4).sin_addr.s_addr };
-            Some(Ipv4Addr::from(u32::from_be(s_addr)).into())
-        }
+    unsafe { fopen_offline_fp(fp, precision) }
+}
 
-        libc::AF_INET6 => {
-            let inet6: *const libc::sockaddr_in6 = addr as *const libc::sockaddr_in6;
-            let s6_addr: [u8; 16] = unsafe { (*inet6).sin6_addr.s6_addr };
-            Some(Ipv6Addr::from(s6_addr).into())
-        }
+// Open savefile from `fp`. `fp` is owned by the returned PcapT, or closed on
+// failure.
+#[cfg(unix)]
+unsafe fn fopen_offline_fp(
+    fp: *mut libpcap::FILE,
+    precision: Option<TimestampPrecision>,
+) -> Result<PcapT> {
+    let mut errbuf: Vec<u8> = vec![0; libpcap::PCAP_ERRBUF_SIZE as usize];
+    let pcap_t = match precision {
+        None => libpcap::pcap_fopen_offline(fp, errbuf.as_mut_ptr() as *mut libc::c_char),
+        Some(precision) => libpcap::pcap_fopen_offline_with_tstamp_precision(
+            fp,
+            precision.as_raw(),
+            errbuf.as_mut_ptr() as *mut libc::c_char,
+        ),
+    };
 
-        #[cfg(target_os = "macos")]
-        libc::AF_LINK => {
-            let dl_sock: *const libc::sockaddr_dl = addr as *const libc::sockaddr_dl;
-            let start = unsafe { (*dl_sock).sdl_nlen } as usize;
-            let dl_addr: [u8; 6] = unsafe {
-                [
-                    *(*dl_sock).sdl_data.get(start)? as u8,
-                    *(*dl_sock).sdl_data.get(start + 1)? as u8,
-                    *(*dl_sock).sdl_data.get(start + 2)? as u8,
-                    *(*dl_sock).sdl_data.get(start + 3)? as u8,
-                    *(*dl_sock).sdl_data.get(start + 4)? as u8,
-                    *(*dl_sock).sdl_data.get(start + 5)? as u8,
-                ]
-            };
-            Some(MacAddr::from(dl_addr).into())
+    if pcap_t.is_null() {
+        libc::fclose(fp);
+        return Err(errbuf_error(&errbuf)?);
+    }
+
+    Ok(PcapT {
+        pcap_t,
+        errbuf,
+        interface: None,
+        activity: Default::default(),
+        id: PcapT::next_id(),
+        savefile_size: None,
+    })
+}
+
+/// open a savefile for reading from any reader
+///
+/// Like `pcap_fopen_offline()` but the savefile is read from `reader`, which
+/// is wrapped into a C stream. The reader is owned by the returned `PcapT`
+/// and dropped when it is dropped.
+///
+/// <https://www.tcpdump.org/manpages/pcap_open_offline.3pcap.html>
+#[cfg(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    target_os = "netbsd"
+))]
+pub fn pcap_fopen_offline_reader<R>(reader: R) -> Result<PcapT>
+where
+    R: std::io::Read + Send + 'static,
+{
+    crate::init::ensure_init()?;
+    trace!("pcap_fopen_offline_reader()");
+    let fp = crate::cfile::open_reader(reader)?;
+    unsafe { fopen_offline_fp(fp, None) }
+}
+
+/// set monitor mode for a not-yet-activated capture handle
+///
+/// `pcap_set_rfmon()` sets whether monitor mode should be set on a capture
+/// handle when the handle is activated.
+///
+/// <https://www.tcpdump.org/manpages/pcap_set_rfmon.3pcap.html>
+pub fn pcap_set_rfmon(pcap_t: &PcapT, rfmon: bool) -> Result<()> {
+    trace!("pcap_set_rfmon({:p}, {})", pcap_t.pcap_t, rfmon);
+    let ret = unsafe { libpcap::pcap_set_rfmon(pcap_t.pcap_t, rfmon.into()) };
+    check_pcap_error(pcap_t, ret)
+}
+
+/// check whether monitor mode can be set for a not-yet-activated capture
+/// handle
+///
+/// `pcap_can_set_rfmon()` checks whether monitor mode could be set on a
+/// capture handle when the handle is activated.
+///
+/// <https://www.tcpdump.org/manpages/pcap_can_set_rfmon.3pcap.html>
+pub fn pcap_can_set_rfmon(pcap_t: &PcapT) -> Result<bool> {
+    trace!("pcap_can_set_rfmon({:p})", pcap_t.pcap_t);
+    let ret = unsafe { libpcap::pcap_can_set_rfmon(pcap_t.pcap_t) };
+    if ret < 0 {
+        check_pcap_error(pcap_t, ret)?;
+    }
+    Ok(ret == 1)
+}
+
+/// set the time stamp type to be used by a capture device
+///
+/// `pcap_set_tstamp_type()` sets the type of time stamp desired for packets
+/// captured on the pcap descriptor to the type specified by `tstamp_type`. It
+/// must be called on a pcap descriptor created by `pcap_create()` that has not
+/// yet been activated by `pcap_activate()`.
+///
+/// If the time stamp type is not supported by the capture device
+/// `Error::PcapWarning` is returned and the default type is used.
+///
+/// <https://www.tcpdump.org/manpages/pcap_set_tstamp_type.3pcap.html>
+pub fn pcap_set_tstamp_type(pcap_t: &PcapT, tstamp_type: i32) -> Result<()> {
+    trace!("pcap_set_tstamp_type({:p}, {})", pcap_t.pcap_t, tstamp_type);
+    let ret = unsafe { libpcap::pcap_set_tstamp_type(pcap_t.pcap_t, tstamp_type) };
+    check_pcap_error(pcap_t, ret)?;
+    if ret > 0 {
+        return Err(Error::PcapWarning(status_to_str(ret)?));
+    }
+    Ok(())
+}
+
+/// set the time stamp precision returned in captures
+///
+/// `pcap_set_tstamp_precision()` sets the precision of the time stamp desired
+/// for packets captured on the pcap descriptor. It must be called on a pcap
+/// descriptor created by `pcap_create()` that has not yet been activated by
+/// `pcap_activate()`.
+///
+/// <https://www.tcpdump.org/manpages/pcap_set_tstamp_precision.3pcap.html>
+pub fn pcap_set_tstamp_precision(pcap_t: &PcapT, precision: TimestampPrecision) -> Result<()> {
+    trace!(
+        "pcap_set_tstamp_precision({:p}, {:?})",
+        pcap_t.pcap_t,
+        precision
+    );
+    let ret =
+        unsafe { libpcap::pcap_set_tstamp_precision(pcap_t.pcap_t, precision.as_raw() as i32) };
+    check_pcap_error(pcap_t, ret)
+}
+
+/// get a list of time stamp types supported by a capture device
+///
+/// `pcap_list_tstamp_types()` is used to get a list of the supported time stamp
+/// types of the interface associated with the pcap descriptor.
+///
+/// <https://www.tcpdump.org/manpages/pcap_list_tstamp_types.3pcap.html>
+pub fn pcap_list_tstamp_types(pcap_t: &PcapT) -> Result<Vec<i32>> {
+    trace!("pcap_list_tstamp_types({:p})", pcap_t.pcap_t);
+    let mut types: *mut libc::c_int = std::ptr::null_mut();
+    let ret = unsafe { libpcap::pcap_list_tstamp_types(pcap_t.pcap_t, &mut types) };
+    if ret < 0 {
+        check_pcap_error(pcap_t, ret)?;
+        return Ok(Vec::new());
+    }
+
+    let list = if types.is_null() {
+        Vec::new()
+    } else {
+        let list = unsafe { std::slice::from_raw_parts(types, ret as usize) }.to_vec();
+        unsafe { libpcap::pcap_free_tstamp_types(types) };
+        list
+    };
+    Ok(list)
+}
+
+/// get the time stamp type value corresponding to a time stamp type name
+///
+/// `pcap_tstamp_type_name_to_val()` translates a time stamp type name to the
+/// corresponding time stamp type value. The translation is case-insensitive.
+///
+/// <https://www.tcpdump.org/manpages/pcap_tstamp_type_name_to_val.3pcap.html>
+pub fn pcap_tstamp_type_name_to_val(name: &str) -> Result<i32> {
+    trace!("pcap_tstamp_type_name_to_val({})", name);
+    let cname = CString::new(name)?;
+    let ret = unsafe { libpcap::pcap_tstamp_type_name_to_val(cname.as_ptr()) };
+    if ret < 0 {
+        return Err(Error::PcapError(format!(
+            "unknown time stamp type name {}",
+            name
+        )));
+    }
+    Ok(ret)
+}
+
+/// get a name for a time stamp type
+///
+/// `pcap_tstamp_type_val_to_name()` translates a time stamp type value to the
+/// corresponding time stamp type name. `None` is returned on failure.
+///
+/// <https://www.tcpdump.org/manpages/pcap_tstamp_type_val_to_name.3pcap.html>
+pub fn pcap_tstamp_type_val_to_name(tstamp_type: i32) -> Option<String> {
+    trace!("pcap_tstamp_type_val_to_name({})", tstamp_type);
+    let ptr = unsafe { libpcap::pcap_tstamp_type_val_to_name(tstamp_type) };
+    cstr_to_string(ptr)
+}
+
+/// get a description for a time stamp type
+///
+/// `pcap_tstamp_type_val_to_description()` translates a time stamp type value
+/// to a short description of that time stamp type. `None` is returned on
+/// failure.
+///
+/// <https://www.tcpdump.org/manpages/pcap_tstamp_type_val_to_name.3pcap.html>
+pub fn pcap_tstamp_type_val_to_description(tstamp_type: i32) -> Option<String> {
+    trace!("pcap_tstamp_type_val_to_description({})", tstamp_type);
+    let ptr = unsafe { libpcap::pcap_tstamp_type_val_to_description(tstamp_type) };
+    cstr_to_string(ptr)
+}
+
+/// set the state of non-blocking mode on a capture device
+///
+/// `pcap_setnonblock()` puts a capture handle into "non-blocking" mode, or
+/// takes it out of "non-blocking" mode, depending on whether `nonblock` is
+/// true or false. It has no effect on savefiles.
+///
+/// <https://www.tcpdump.org/manpages/pcap_setnonblock.3pcap.html>
+pub fn pcap_setnonblock(pcap_t: &PcapT, nonblock: bool) -> Result<()> {
+    trace!("pcap_setnonblock({:p}, {})", pcap_t.pcap_t, nonblock);
+    let mut errbuf: Vec<u8> = vec![0; libpcap::PCAP_ERRBUF_SIZE as usize];
+    let ret = unsafe {
+        libpcap::pcap_setnonblock(
+            pcap_t.pcap_t,
+            nonblock.into(),
+            errbuf.as_mut_ptr() as *mut libc::c_char,
+        )
+    };
+    match ret {
+        PCAP_ERROR => Err(errbuf_error(&errbuf)?),
+        n => check_pcap_error(pcap_t, n),
+    }
+}
+
+/// set the direction for which packets will be captured
+///
+/// `pcap_setdirection()` is used to specify a direction that packets will be
+/// captured. This is not necessarily fully supported on all platforms.
+///
+/// <https://www.tcpdump.org/manpages/pcap_setdirection.3pcap.html>
+pub fn pcap_setdirection(pcap_t: &PcapT, direction: Direction) -> Result<()> {
+    trace!("pcap_setdirection({:p}, {:?})", pcap_t.pcap_t, direction);
+    let ret = unsafe { libpcap::pcap_setdirection(pcap_t.pcap_t, direction.as_raw()) };
+    check_pcap_error(pcap_t, ret)
+}
+
+/// get the extended link-layer header type
+///
+/// `pcap_datalink_ext()` returns the link-layer header type for the live
+/// capture or savefile specified by `PcapT` including the extension bits.
+///
+/// <https://www.tcpdump.org/manpages/pcap_datalink.3pcap.html>
+pub fn pcap_datalink_ext(pcap_t: &PcapT) -> Result<i32> {
+    trace!("pcap_datalink_ext({:p})", pcap_t.pcap_t);
+    let ret = unsafe { libpcap::pcap_datalink_ext(pcap_t.pcap_t) };
+    if ret < 0 {
+        check_pcap_error(pcap_t, ret)?;
+    }
+    Ok(ret)
+}
+
+/// get a list of link-layer header types supported by a capture device
+///
+/// `pcap_list_datalinks()` is used to get a list of the supported link-layer
+/// header types of the interface associated with the pcap descriptor.
+///
+/// <https://www.tcpdump.org/manpages/pcap_list_datalinks.3pcap.html>
+pub fn pcap_list_datalinks(pcap_t: &PcapT) -> Result<Vec<i32>> {
+    trace!("pcap_list_datalinks({:p})", pcap_t.pcap_t);
+    let mut dlts: *mut libc::c_int = std::ptr::null_mut();
+    let ret = unsafe { libpcap::pcap_list_datalinks(pcap_t.pcap_t, &mut dlts) };
+    if ret < 0 {
+        check_pcap_error(pcap_t, ret)?;
+        return Ok(Vec::new());
+    }
+
+    let list = if dlts.is_null() {
+        Vec::new()
+    } else {
+        let list = unsafe { std::slice::from_raw_parts(dlts, ret as usize) }.to_vec();
+        unsafe { libpcap::pcap_free_datalinks(dlts) };
+        list
+    };
+    Ok(list)
+}
+
+/// set the link-layer header type to be used by a capture device
+///
+/// `pcap_set_datalink()` is used to set the current link-layer header type of
+/// the pcap descriptor to the type specified by `dlt`.
+///
+/// <https://www.tcpdump.org/manpages/pcap_set_datalink.3pcap.html>
+pub fn pcap_set_datalink(pcap_t: &PcapT, dlt: i32) -> Result<()> {
+    trace!("pcap_set_datalink({:p}, {})", pcap_t.pcap_t, dlt);
+    let ret = unsafe { libpcap::pcap_set_datalink(pcap_t.pcap_t, dlt) };
+    check_pcap_error(pcap_t, ret)
+}
+
+/// get the link-layer header type value corresponding to a header type name
+///
+/// `pcap_datalink_name_to_val()` translates a link-layer header type name,
+/// which is a `DLT_` name with the `DLT_` removed, to the corresponding
+/// link-layer header type value. The translation is case-insensitive.
+///
+/// <https://www.tcpdump.org/manpages/pcap_datalink_name_to_val.3pcap.html>
+pub fn pcap_datalink_name_to_val(name: &str) -> Result<i32> {
+    trace!("pcap_datalink_name_to_val({})", name);
+    let cname = CString::new(name)?;
+    let ret = unsafe { libpcap::pcap_datalink_name_to_val(cname.as_ptr()) };
+    if ret < 0 {
+        return Err(Error::PcapError(format!(
+            "unknown link-layer header type name {}",
+            name
+        )));
+    }
+    Ok(ret)
+}
+
+/// get a name for a link-layer header type value
+///
+/// `pcap_datalink_val_to_name()` translates a link-layer header type value to
+/// the corresponding link-layer header type name, which is the `DLT_` name for
+/// the link-layer header type value with the `DLT_` removed. `None` is
+/// returned if the type value does not correspond to a known `DLT_` value.
+///
+/// <https://www.tcpdump.org/manpages/pcap_datalink_val_to_name.3pcap.html>
+pub fn pcap_datalink_val_to_name(dlt: i32) -> Option<String> {
+    trace!("pcap_datalink_val_to_name({})", dlt);
+    let ptr = unsafe { libpcap::pcap_datalink_val_to_name(dlt) };
+    cstr_to_string(ptr)
+}
+
+/// get a description for a link-layer header type value
+///
+/// `pcap_datalink_val_to_description()` translates a link-layer header type
+/// value to a short description of that link-layer header type. `None` is
+/// returned if the type value does not correspond to a known `DLT_` value.
+///
+/// <https://www.tcpdump.org/manpages/pcap_datalink_val_to_name.3pcap.html>
+pub fn pcap_datalink_val_to_description(dlt: i32) -> Option<String> {
+    trace!("pcap_datalink_val_to_description({})", dlt);
+    let ptr = unsafe { libpcap::pcap_datalink_val_to_description(dlt) };
+    cstr_to_string(ptr)
+}
+
+/// get a description for a link-layer header type value or a "DLT n" string
+///
+/// `pcap_datalink_val_to_description_or_dlt()` is like
+/// `pcap_datalink_val_to_description()` but returns "DLT n" string for an
+/// unknown link-layer header type value `n`.
+///
+/// <https://www.tcpdump.org/manpages/pcap_datalink_val_to_name.3pcap.html>
+pub fn pcap_datalink_val_to_description_or_dlt(dlt: i32) -> String {
+    trace!("pcap_datalink_val_to_description_or_dlt({})", dlt);
+    let ptr = unsafe { libpcap::pcap_datalink_val_to_description_or_dlt(dlt) };
+    cstr_to_string(ptr).unwrap_or_else(|| format!("DLT {}", dlt))
+}
+
+/// compile a filter expression without a `PcapT`
+///
+/// `pcap_compile_nopcap()` is like `pcap_compile()` but takes the snapshot
+/// length and link-layer header type as arguments instead of `PcapT`.
+///
+/// This is implemented with `pcap_open_dead_with_tstamp_precision()` and
+/// `pcap_compile()`, exactly like libpcap does, but this way the error message
+/// is available on failure.
+///
+/// <https://www.tcpdump.org/manpages/pcap_compile.3pcap.html>
+pub fn pcap_compile_nopcap(snaplen: usize, linktype: i32, filter: &str) -> Result<PcapFilter> {
+    trace!("pcap_compile_nopcap({}, {}, {})", snaplen, linktype, filter);
+    let pcap_t =
+        pcap_open_dead_with_tstamp_precision(linktype, snaplen, TimestampPrecision::Micro)?;
+    pcap_compile(&pcap_t, filter)
+}
+
+/// check a filter against a packet
+///
+/// `pcap_offline_filter()` checks whether a filter matches a packet. Returns
+/// true if the packet matches the filter.
+///
+/// <https://www.tcpdump.org/manpages/pcap_offline_filter.3pcap.html>
+pub fn pcap_offline_filter(
+    pcap_filter: &PcapFilter,
+    pkthdr: &libpcap::pcap_pkthdr,
+    bytes: &[u8],
+) -> bool {
+    // libpcap reads caplen bytes from the packet, make sure it won't read past
+    // the end of given slice.
+    let mut pkthdr = *pkthdr;
+    pkthdr.caplen = pkthdr.caplen.min(bytes.len() as u32);
+    let ret =
+        unsafe { libpcap::pcap_offline_filter(&pcap_filter.bpf_program, &pkthdr, bytes.as_ptr()) };
+    trace!(
+        "pcap_offline_filter({:p}, {:?}) => {}",
+        &pcap_filter.bpf_program,
+        pkthdr,
+        ret
+    );
+    ret != 0
+}
+
+/// disassemble a filter instruction
+///
+/// `bpf_image()` returns a human-readable representation of instruction
+/// `insn` at index `n` of a filter program, in the format printed by
+/// `tcpdump -d`.
+///
+/// libpcap formats the instruction into a static buffer, so calls are
+/// serialized with a lock.
+pub fn bpf_image(insn: &libpcap::bpf_insn, n: usize) -> Result<String> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let _guard = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let image = unsafe { libpcap::bpf_image(insn, n as libc::c_int) };
+    trace!("bpf_image({:?}, {}) => {:p}", insn, n, image);
+    if image.is_null() {
+        return Err(Error::PcapError(format!("bpf_image() failed for {}", n)));
+    }
+    let image = unsafe { CStr::from_ptr(image) };
+    Ok(image.to_string_lossy().into_owned())
+}
+
+/// force a `pcap_dispatch()` or `pcap_loop()` call to return
+///
+/// `pcap_breakloop()` sets a flag that will force `pcap_dispatch()` or
+/// `pcap_loop()` to return rather than looping; they will return the number of
+/// packets that have been processed so far, or `Error::Break` if no packets
+/// have been processed so far. `pcap_next_ex()` also returns `Error::Break`.
+///
+/// <https://www.tcpdump.org/manpages/pcap_breakloop.3pcap.html>
+pub fn pcap_breakloop(pcap_t: &PcapT) {
+    trace!("pcap_breakloop({:p})", pcap_t.pcap_t);
+    unsafe { libpcap::pcap_breakloop(pcap_t.pcap_t) }
+}
+
+/// process packets from a live capture or savefile
+///
+/// `pcap_dispatch()` processes packets from a live capture or savefile until
+/// `cnt` packets are processed, the end of the current bufferful of packets
+/// is reached when doing a live capture, the end of the savefile is reached
+/// when reading from a savefile, `pcap_breakloop()` is called, or an error
+/// occurs. A value of -1 or 0 for `cnt` causes all the packets received in one
+/// buffer to be processed when reading a live capture, and causes all the
+/// packets in the file to be processed when reading a savefile.
+///
+/// `callback` is called for each packet. If `callback` panics the loop is
+/// broken and the panic is resumed after `pcap_dispatch()` returns.
+///
+/// Returns the number of packets processed on success; this can be 0 if no
+/// packets were read from a live capture or if no more packets are available
+/// in a savefile.
+///
+/// <https://www.tcpdump.org/manpages/pcap_dispatch.3pcap.html>
+pub fn pcap_dispatch<F>(pcap_t: &PcapT, cnt: i32, callback: F) -> Result<usize>
+where
+    F: FnMut(BorrowedPacket),
+{
+    trace!("pcap_dispatch({:p}, {})", pcap_t.pcap_t, cnt);
+    let ret = run_loop(pcap_t, cnt, callback, libpcap::pcap_dispatch);
+    #[cfg(feature = "tracing")]
+    if let Ok(packets) = ret {
+        tracing::debug!(device = %pcap_t.get_inteface(), packets, "pcap_dispatch batch");
+    }
+    ret
+}
+
+/// process packets from a live capture or savefile
+///
+/// `pcap_loop()` processes packets from a live capture or savefile until
+/// `cnt` packets are processed, the end of the savefile is reached when
+/// reading from a savefile, `pcap_breakloop()` is called, or an error occurs.
+/// It does not return when live packet buffer timeouts occur. A value of -1 or
+/// 0 for `cnt` is equivalent to infinity.
+///
+/// `callback` is called for each packet. If `callback` panics the loop is
+/// broken and the panic is resumed after `pcap_loop()` returns.
+///
+/// <https://www.tcpdump.org/manpages/pcap_loop.3pcap.html>
+pub fn pcap_loop<F>(pcap_t: &PcapT, cnt: i32, callback: F) -> Result<()>
+where
+    F: FnMut(BorrowedPacket),
+{
+    trace!("pcap_loop({:p}, {})", pcap_t.pcap_t, cnt);
+    run_loop(pcap_t, cnt, callback, libpcap::pcap_loop).map(|_| ())
+}
+
+// State passed through `u_char *user` to loop_callback().
+struct LoopState<F> {
+    pcap_t: *mut libpcap::pcap_t,
+    callback: F,
+    panic: Option<Box<dyn std::any::Any + Send + 'static>>,
+    // Number of packets passed to callback.
+    received: u64,
+}
+
+unsafe extern "C" fn loop_callback<F>(
+    user: *mut libc::c_uchar,
+    pkthdr: *const libpcap::pcap_pkthdr,
+    bytes: *const libc::c_uchar,
+) where
+    F: FnMut(BorrowedPacket),
+{
+    let state = &mut *(user as *mut LoopState<F>);
+    if state.panic.is_some() {
+        return;
+    }
+
+    state.received += 1;
+    let packet = BorrowedPacket::new(pkthdr, bytes);
+    let callback = &mut state.callback;
+    let ret = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| callback(packet)));
+    if let Err(payload) = ret {
+        // Unwinding through C is undefined behaviour. Stop the loop and
+        // resume the panic once libpcap has returned.
+        state.panic = Some(payload);
+        libpcap::pcap_breakloop(state.pcap_t);
+    }
+}
+
+type LoopFn = unsafe extern "C" fn(
+    *mut libpcap::pcap_t,
+    libc::c_int,
+    libpcap::pcap_handler,
+    *mut libc::c_uchar,
+) -> libc::c_int;
+
+fn run_loop<F>(pcap_t: &PcapT, cnt: i32, callback: F, loop_fn: LoopFn) -> Result<usize>
+where
+    F: FnMut(BorrowedPacket),
+{
+    let mut state = LoopState {
+        pcap_t: pcap_t.pcap_t,
+        callback,
+        panic: None,
+        received: 0,
+    };
+
+    let ret = unsafe {
+        loop_fn(
+            pcap_t.pcap_t,
+            cnt as libc::c_int,
+            Some(loop_callback::<F>),
+            &mut state as *mut LoopState<F> as *mut libc::c_uchar,
+        )
+    };
+
+    if state.received > 0 {
+        pcap_t.activity.packets(state.received);
+    }
+    if let Some(payload) = state.panic {
+        std::panic::resume_unwind(payload);
+    }
+
+    if ret < 0 {
+        if ret == PCAP_ERROR {
+            pcap_t.activity.fail();
+        }
+        check_pcap_error(pcap_t, ret)?;
+    }
+    Ok(ret as usize)
+}
+
+/// transmit a packet
+///
+/// `pcap_sendpacket()` sends a raw packet through the network interface; buf
+/// points to the data of the packet, including the link-layer header. It is
+/// like `pcap_inject()`, but it returns only success or failure.
+///
+/// <https://www.tcpdump.org/manpages/pcap_inject.3pcap.html>
+pub fn pcap_sendpacket(pcap_t: &PcapT, buf: &[u8]) -> Result<()> {
+    trace!(
+        "pcap_sendpacket({:p}, {:?}, {})",
+        pcap_t.pcap_t,
+        buf.as_ptr(),
+        buf.len()
+    );
+    let ret =
+        unsafe { libpcap::pcap_sendpacket(pcap_t.pcap_t, buf.as_ptr(), buf.len() as libc::c_int) };
+    check_pcap_error(pcap_t, ret)
+}
+
+/// open a file to which to write packets
+///
+/// `pcap_dump_open()` is called to open a "savefile" for writing. `path` is
+/// the name of the file to open. The file will have the same format as those
+/// used by tcpdump(1) and tcpdump(1).
+///
+/// <https://www.tcpdump.org/manpages/pcap_dump_open.3pcap.html>
+pub fn pcap_dump_open<P: AsRef<Path>>(pcap_t: &PcapT, path: P) -> Result<PcapDumper> {
+    let fname = CString::new(path.as_ref().to_string_lossy().as_ref())?;
+    trace!("pcap_dump_open({:p}, {:?})", pcap_t.pcap_t, fname);
+    let precision = pcap_get_tstamp_precision(pcap_t)?;
+    let ret = unsafe { libpcap::pcap_dump_open(pcap_t.pcap_t, fname.as_ptr()) };
+    if ret.is_null() {
+        return Err(get_error(pcap_t)?);
+    }
+    Ok(PcapDumper::new(ret, precision))
+}
+
+/// open a file to which to append packets
+///
+/// `pcap_dump_open_append()` is like `pcap_dump_open()` but, if the file
+/// already exists, and is a pcap file with the same byte order as the host
+/// opening the file, and has the same time stamp precision, link-layer header
+/// type, and snapshot length as `PcapT`, it will write new packets at the end
+/// of the file.
+///
+/// <https://www.tcpdump.org/manpages/pcap_dump_open.3pcap.html>
+pub fn pcap_dump_open_append<P: AsRef<Path>>(pcap_t: &PcapT, path: P) -> Result<PcapDumper> {
+    let fname = CString::new(path.as_ref().to_string_lossy().as_ref())?;
+    trace!("pcap_dump_open_append({:p}, {:?})", pcap_t.pcap_t, fname);
+    let precision = pcap_get_tstamp_precision(pcap_t)?;
+    let ret = unsafe { libpcap::pcap_dump_open_append(pcap_t.pcap_t, fname.as_ptr()) };
+    if ret.is_null() {
+        return Err(get_error(pcap_t)?);
+    }
+    Ok(PcapDumper::new(ret, precision))
+}
+
+/// get the standard I/O stream for a savefile being written
+///
+/// `pcap_dump_file()` returns the standard I/O stream of the savefile opened
+/// by `pcap_dump_open()`. The stream is owned by `PcapDumper`.
+///
+/// <https://www.tcpdump.org/manpages/pcap_dump_file.3pcap.html>
+pub fn pcap_dump_file(dumper: &PcapDumper) -> Option<*mut libpcap::FILE> {
+    let ret = unsafe { libpcap::pcap_dump_file(dumper.pcap_dumper_t) };
+    trace!("pcap_dump_file({:p}) => {:p}", dumper.pcap_dumper_t, ret);
+    if ret.is_null() {
+        None
+    } else {
+        Some(ret)
+    }
+}
+
+/// get the current file offset for a savefile being written
+///
+/// `pcap_dump_ftell64()` returns the current file position for the savefile.
+/// If any packets have been written to the savefile, this will be the position
+/// after the last packet's data or any data buffered for writing.
+///
+/// <https://www.tcpdump.org/manpages/pcap_dump_ftell.3pcap.html>
+pub fn pcap_dump_ftell(dumper: &PcapDumper) -> Result<u64> {
+    let ret = unsafe { libpcap::pcap_dump_ftell64(dumper.pcap_dumper_t) };
+    trace!("pcap_dump_ftell64({:p}) => {}", dumper.pcap_dumper_t, ret);
+    if ret < 0 {
+        return Err(Error::IO(std::io::Error::last_os_error()));
+    }
+    // Include packets still in the write buffer of PcapDumper.
+    Ok(ret as u64 + dumper.buf.len() as u64)
+}
+
+/// close a savefile being written
+///
+/// `pcap_dump_close()` closes the savefile associated with `PcapDumper`.
+///
+/// <https://www.tcpdump.org/manpages/pcap_dump_close.3pcap.html>
+pub fn pcap_dump_close(dumper: PcapDumper) {
+    trace!("pcap_dump_close({:p})", dumper.pcap_dumper_t);
+    // PcapDumper is owned by this function and dropped at this point since
+    // it's no longer needed. Dropping flushes and closes the savefile.
+}
+
+/// find the IPv4 network number and netmask for a device
+///
+/// `pcap_lookupnet()` is used to determine the IPv4 network number and mask
+/// associated with the network device `device`. Returns tuple of network and
+/// netmask.
+///
+/// <https://www.tcpdump.org/manpages/pcap_lookupnet.3pcap.html>
+pub fn pcap_lookupnet(device: &str) -> Result<(Ipv4Addr, Ipv4Addr)> {
+    crate::init::ensure_init()?;
+    let mut errbuf: Vec<u8> = vec![0; libpcap::PCAP_ERRBUF_SIZE as usize];
+    let cdevice = CString::new(device)?;
+    let mut net: libpcap::bpf_u_int32 = 0;
+    let mut mask: libpcap::bpf_u_int32 = 0;
+
+    let ret = unsafe {
+        libpcap::pcap_lookupnet(
+            cdevice.as_ptr(),
+            &mut net,
+            &mut mask,
+            errbuf.as_mut_ptr() as *mut libc::c_char,
+        )
+    };
+
+    trace!(
+        "pcap_lookupnet({}) => {}, {:x}/{:x}",
+        device,
+        ret,
+        net,
+        mask
+    );
+    match ret {
+        PCAP_SUCCESS => Ok((
+            Ipv4Addr::from(u32::from_be(net)),
+            Ipv4Addr::from(u32::from_be(mask)),
+        )),
+        PCAP_ERROR => Err(errbuf_error(&errbuf)?),
+        n => Err(Error::PcapErrorCode(n)),
+    }
+}
+
+/// get a file descriptor on which a select() can be done for a live capture
+///
+/// `pcap_get_selectable_fd()` returns a file descriptor on which a select(),
+/// poll(), epoll_wait() or kevent() call can be done to wait for packets to
+/// arrive. `None` is returned if no such descriptor is available.
+///
+/// <https://www.tcpdump.org/manpages/pcap_get_selectable_fd.3pcap.html>
+#[cfg(unix)]
+pub fn pcap_get_selectable_fd(pcap_t: &PcapT) -> Option<libc::c_int> {
+    let ret = unsafe { libpcap::pcap_get_selectable_fd(pcap_t.pcap_t) };
+    trace!("pcap_get_selectable_fd({:p}) => {}", pcap_t.pcap_t, ret);
+    if ret == PCAP_ERROR {
+        None
+    } else {
+        Some(ret)
+    }
+}
+
+/// get a timeout to be used when doing select() for a live capture
+///
+/// `pcap_get_required_select_timeout()` returns the timeout that should be
+/// used with select(), poll() et al. for devices on which
+/// `pcap_get_selectable_fd()` doesn't work reliably. `None` is returned if no
+/// timeout is required.
+///
+/// <https://www.tcpdump.org/manpages/pcap_get_required_select_timeout.3pcap.html>
+#[cfg(unix)]
+pub fn pcap_get_required_select_timeout(pcap_t: &PcapT) -> Option<std::time::Duration> {
+    let ret = unsafe { libpcap::pcap_get_required_select_timeout(pcap_t.pcap_t) };
+    trace!(
+        "pcap_get_required_select_timeout({:p}) => {:p}",
+        pcap_t.pcap_t,
+        ret
+    );
+    if ret.is_null() {
+        return None;
+    }
+    let tv = unsafe { *ret };
+    Some(std::time::Duration::new(
+        tv.tv_sec as u64,
+        (tv.tv_usec as u32) * 1000,
+    ))
+}
+
+/// get the event handle of a capture
+///
+/// `pcap_getevent()` returns the handle of an event which is signaled when
+/// packets are available to read, for use with `WaitForSingleObject()` or
+/// `WaitForMultipleObjects()`. This is the Windows counterpart of
+/// `pcap_get_selectable_fd()`. `None` is returned if the handle has no event,
+/// as is the case for savefiles. The event is owned by libpcap and must not
+/// be closed.
+#[cfg(windows)]
+pub fn pcap_getevent(pcap_t: &PcapT) -> Option<std::os::windows::io::RawHandle> {
+    let ret = unsafe { libpcap::pcap_getevent(pcap_t.pcap_t) };
+    trace!("pcap_getevent({:p}) => {:p}", pcap_t.pcap_t, ret);
+    if ret.is_null() {
+        None
+    } else {
+        Some(ret)
+    }
+}
+
+/// start a kernel dump of packets into a file
+///
+/// `pcap_live_dump()` makes the driver write captured packets directly into
+/// savefile `path`, without copying them to the application. Dumping stops
+/// when the file reaches `maxsize` bytes or `maxpacks` packets have been
+/// written; zero means no limit. Only available on Windows, and only with
+/// drivers supporting kernel dump mode.
+#[cfg(windows)]
+pub fn pcap_live_dump<P: AsRef<Path>>(
+    pcap_t: &PcapT,
+    path: P,
+    maxsize: usize,
+    maxpacks: usize,
+) -> Result<()> {
+    let fname = CString::new(path.as_ref().to_string_lossy().as_ref())?;
+    let maxsize = libc::c_int::try_from(maxsize).unwrap_or(libc::c_int::MAX);
+    let maxpacks = libc::c_int::try_from(maxpacks).unwrap_or(libc::c_int::MAX);
+    trace!(
+        "pcap_live_dump({:p}, {:?}, {}, {})",
+        pcap_t.pcap_t,
+        fname,
+        maxsize,
+        maxpacks
+    );
+    let ret = unsafe {
+        libpcap::pcap_live_dump(pcap_t.pcap_t, fname.as_ptr() as *mut _, maxsize, maxpacks)
+    };
+    check_pcap_error(pcap_t, ret)
+}
+
+/// check whether a kernel dump has ended
+///
+/// `pcap_live_dump_ended()` returns true when the kernel dump started with
+/// `pcap_live_dump()` has reached one of its limits. If `sync` is true, waits
+/// until that happens.
+#[cfg(windows)]
+pub fn pcap_live_dump_ended(pcap_t: &PcapT, sync: bool) -> Result<bool> {
+    let ret = unsafe { libpcap::pcap_live_dump_ended(pcap_t.pcap_t, sync.into()) };
+    trace!(
+        "pcap_live_dump_ended({:p}, {}) => {}",
+        pcap_t.pcap_t,
+        sync,
+        ret
+    );
+    match ret {
+        PCAP_ERROR => Err(get_error(pcap_t)?),
+        n => Ok(n != 0),
+    }
+}
+
+/// convert an error code value to a string
+///
+/// `pcap_statustostr()` converts a PCAP_ERROR_ or PCAP_WARNING_ value returned
+/// by a libpcap routine to an error string.
+///
+/// <https://www.tcpdump.org/manpages/pcap_statustostr.3pcap.html>
+pub fn pcap_statustostr(status: i32) -> Result<String> {
+    status_to_str(status)
+}
+
+/// convert an errno value to a string
+///
+/// `pcap_strerror()` is provided in case strerror(3) isn't available. It
+/// returns an error message string corresponding to `errnum`.
+///
+/// <https://www.tcpdump.org/manpages/pcap_strerror.3pcap.html>
+pub fn pcap_strerror(errnum: i32) -> Result<String> {
+    trace!("pcap_strerror({})", errnum);
+    let ptr = unsafe { libpcap::pcap_strerror(errnum) };
+    libpcap_str("pcap_strerror", ptr)
+}
+
+/// print libpcap error message text
+///
+/// `pcap_perror()` prints the text of the last pcap library error on stderr,
+/// prefixed by `prefix`.
+///
+/// <https://www.tcpdump.org/manpages/pcap_geterr.3pcap.html>
+pub fn pcap_perror(pcap_t: &PcapT, prefix: &str) -> Result<()> {
+    trace!("pcap_perror({:p}, {})", pcap_t.pcap_t, prefix);
+    let prefix = CString::new(prefix)?;
+    unsafe { libpcap::pcap_perror(pcap_t.pcap_t, prefix.as_ptr()) };
+    Ok(())
+}
+
+/// get a list of capture devices
+///
+/// `pcap_findalldevs()` constructs a list of network devices that can be opened
+/// with `pcap_create()` and `pcap_activate()` or with `pcap_open_live()`. Note
+/// that there may be network devices that cannot be opened by the process
+/// calling `pcap_findalldevs()`, because, for example, that process does not
+/// have sufficient privileges to open them for capturing; if so, those devices
+/// will not appear on the list.
+///
+/// If the process isn't allowed to list network interfaces at all,
+/// `Error::PermissionDenied` is returned.
+///
+/// <https://www.tcpdump.org/manpages/pcap_findalldevs.3pcap.html>
+pub fn pcap_findalldevs() -> Result<PcapIfT> {
+    crate::init::ensure_init()?;
+    let mut pcap_if_t: *mut libpcap::pcap_if_t = std::ptr::null_mut();
+    let mut errbuf: Vec<u8> = vec![0; libpcap::PCAP_ERRBUF_SIZE as usize];
+
+    let ret = unsafe {
+        libpcap::pcap_findalldevs(&mut pcap_if_t, errbuf.as_mut_ptr() as *mut libc::c_char)
+    };
+
+    match ret {
+        PCAP_SUCCESS => {
+            trace!("pcap_findalldevs() => {:p}", pcap_if_t);
+            Ok(PcapIfT { pcap_if_t })
+        }
+        PCAP_ERROR => {
+            let cstr = unsafe { CStr::from_ptr(errbuf.as_ptr() as *const libc::c_char) };
+            let err = cstr.to_str()?.to_owned();
+            Err(findalldevs_error(err))
         }
+        n => Err(Error::PcapErrorCode(n)),
+    }
+}
 
-        #[cfg(target_os = "linux")]
-        libc::AF_PACKET => {
-            let ll_sock: *const libc::sockaddr_ll = addr as *const libc::sockaddr_ll;
-            let mut ll_addr = [0u8; 6];
-            ll_addr.copy_from_slice(unsafe { &(*ll_sock).sll_addr[0..6] });
-            Some(MacAddr::from(ll_addr).into())
+// Listing devices fails if the process isn't allowed to query network
+// interfaces, as is the case for apps on Android which don't run as root.
+// Report that as `PermissionDenied` for the "any" device, like opening a
+// device without permission, instead of a generic error. The message from
+// libpcap follows the device name.
+fn findalldevs_error(err: String) -> Error {
+    if err.ends_with("Permission denied") || err.ends_with("Operation not permitted") {
+        trace!("pcap_findalldevs() => {}", err);
+        return Error::PermissionDenied(format!("any ({})", err));
+    }
+    Error::PcapError(err)
+}
+
+/// free a list of capture devices
+///
+/// Free a list of network devices produced by `pcap_findalldevs()`.
+///
+/// <https://www.tcpdump.org/manpages/pcap_findalldevs.3pcap.html>
+pub fn pcap_freealldevs(pcap_if_t: PcapIfT) {
+    trace!("pcap_freealldevs({:p})", pcap_if_t.pcap_if_t);
+    // PcapIfT is owned by this function and dropped at this point since it's no
+    // longer needed. Dropping frees the allocated resources.
+}
+
+pub(crate) fn try_interface_from(pcap_if_t: *mut libpcap::pcap_if_t) -> Result<Interface> {
+    trace!("try_interface_from({:p})", pcap_if_t);
+    let name: String = {
+        let name = unsafe { (*pcap_if_t).name };
+        if name.is_null() {
+            panic!("pcap_if_t.name is null");
+        } else {
+            let s = unsafe { CStr::from_ptr(name) };
+            s.to_str()?.to_owned()
         }
+    };
 
-        n => {
-            log::error!("Unsupported sa_family {}", n);
+    let description: Option<String> = {
+        let descr = unsafe { (*pcap_if_t).description };
+        if descr.is_null() {
             None
+        } else {
+            let s = unsafe { CStr::from_ptr(descr) };
+            Some(s.to_str()?.to_owned())
         }
+    };
+
+    let addresses = {
+        let pcap_addr_t: *mut libpcap::pcap_addr_t = unsafe { (*pcap_if_t).addresses };
+        let addrs = AddressIter {
+            start: pcap_addr_t,
+            next: Some(pcap_addr_t),
+        };
+
+        let mut addresses = BTreeSet::new();
+        for addr in addrs {
+            addresses.insert(addr);
+        }
+        addresses
+    };
+
+    let raw_flags = InterfaceFlags::from_bits_retain(unsafe { (*pcap_if_t).flags });
+    let flags = get_interface_flags(raw_flags);
+
+    Ok(Interface {
+        name,
+        description,
+        addresses,
+        flags,
+        raw_flags,
+    })
+}
+
+fn from_sockaddr(addr: *const libc::sockaddr) -> Option<Address> {
+    trace!("from_sockaddr({:p})", addr);
+
+    if addr.is_null() {
+        return None;
     }
+
+    // libpcap gives us valid sockaddr structures for the address family.
+    let ret = unsafe { Address::from_sockaddr(addr) };
+    if ret.is_none() {
+        let family = unsafe { (*addr).sa_family };
+        log::error!("Unsupported sa_family {}", family);
+    }
+    ret
 }
 
 pub(crate) fn try_address_from(pcap_addr_t: *mut libpcap::pcap_addr_t) -> Option<InterfaceAddress> {
@@ -597,6 +1919,11 @@ pub(crate) fn try_address_from(pcap_addr_t: *mut libpcap::pcap_addr_t) -> Option
         from_sockaddr(addr)?
     };
 
+    let scope_id = {
+        let addr = unsafe { (*pcap_addr_t).addr as *const libc::sockaddr };
+        scope_id_from_sockaddr(addr)
+    };
+
     let netmask = {
         let addr = unsafe { (*pcap_addr_t).netmask as *const libc::sockaddr };
         from_sockaddr(addr)
@@ -617,41 +1944,85 @@ pub(crate) fn try_address_from(pcap_addr_t: *mut libpcap::pcap_addr_t) -> Option
         netmask,
         broadaddr,
         dstaddr,
+        scope_id,
     })
 }
 
-fn get_interface_flags(val: u32) -> BTreeSet<InterfaceFlag> {
-    trace!("get_interface_flags({})", val);
-    let mut flags = BTreeSet::new();
-    use InterfaceFlag::*;
-    for flag in &[
-        libpcap::PCAP_IF_LOOPBACK,
-        libpcap::PCAP_IF_UP,
-        libpcap::PCAP_IF_RUNNING,
-    ] {
-        if (val & flag) > 0 {
-            match *flag {
-                libpcap::PCAP_IF_LOOPBACK => flags.insert(Loopback),
-                libpcap::PCAP_IF_UP => flags.insert(Up),
-                libpcap::PCAP_IF_RUNNING => flags.insert(Running),
-                _ => panic!("Unsupported InterfaceFlag field: {:b}", flag),
-            };
-        }
+// Scope id of IPv6 socket address `addr`, `None` for other addresses and
+// IPv6 addresses without a scope.
+#[cfg(unix)]
+fn scope_id_from_sockaddr(addr: *const libc::sockaddr) -> Option<u32> {
+    if addr.is_null() || i32::from(unsafe { (*addr).sa_family }) != libc::AF_INET6 {
+        return None;
     }
-    flags
+    // The address family says `addr` is a `sockaddr_in6`.
+    let scope_id = unsafe { (*(addr as *const libc::sockaddr_in6)).sin6_scope_id };
+    (scope_id != 0).then_some(scope_id)
+}
+
+// libc has no `sockaddr_in6` on Windows, so scope ids are not known there.
+#[cfg(windows)]
+fn scope_id_from_sockaddr(_addr: *const libc::sockaddr) -> Option<u32> {
+    None
+}
+
+fn get_interface_flags(val: InterfaceFlags) -> BTreeSet<InterfaceFlag> {
+    trace!("get_interface_flags({:?})", val);
+    val.iter().collect()
+}
+
+/// get the version information for libpcap
+///
+/// `pcap_lib_version()` returns a string giving information about the version
+/// of the libpcap library being used; note that it contains more information
+/// than just a version number.
+///
+/// <https://www.tcpdump.org/manpages/pcap_lib_version.3pcap.html>
+pub fn pcap_lib_version() -> Result<String> {
+    trace!("pcap_lib_version()");
+    let ptr = unsafe { libpcap::pcap_lib_version() };
+    libpcap_str("pcap_lib_version", ptr)
+}
+
+// Turn error message in errbuf into Error.
+fn errbuf_error(errbuf: &[u8]) -> Result<Error> {
+    let cstr = unsafe { CStr::from_ptr(errbuf.as_ptr() as *const libc::c_char) };
+    Ok(Error::PcapError(cstr.to_str()?.to_owned()))
+}
+
+// Copy C string returned by libpcap function `func` into String. The string
+// is null only if libpcap loaded at runtime doesn't have the function.
+fn libpcap_str(func: &str, ptr: *const libc::c_char) -> Result<String> {
+    if ptr.is_null() {
+        return Err(Error::PcapError(format!("{}() is not available", func)));
+    }
+    let cstr = unsafe { CStr::from_ptr(ptr) };
+    Ok(cstr.to_str()?.to_owned())
+}
+
+// Copy possibly null C string owned by libpcap into String.
+fn cstr_to_string(ptr: *const libc::c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    let cstr = unsafe { CStr::from_ptr(ptr) };
+    Some(cstr.to_string_lossy().into_owned())
 }
 
 fn status_to_str(error: libc::c_int) -> Result<String> {
     trace!("status_to_str({})", error);
     let ptr = unsafe { libpcap::pcap_statustostr(error) };
-    let cstr = unsafe { CStr::from_ptr(ptr) };
-    let status = cstr.to_str()?.to_owned();
-    Ok(status)
+    libpcap_str("pcap_statustostr", ptr)
 }
 
 /// Check for `libpcap` error.
 fn check_pcap_error(pcap_t: &PcapT, ret: i32) -> Result<()> {
-    if log::log_enabled!(log::Level::Trace) {
+    #[cfg(not(feature = "tracing"))]
+    let enabled = log::log_enabled!(log::Level::Trace);
+    #[cfg(feature = "tracing")]
+    let enabled = tracing::enabled!(tracing::Level::TRACE);
+
+    if enabled {
         let status = match ret {
             0 => "ok".to_string(),
             n => status_to_str(n).unwrap_or_default(),
@@ -692,6 +2063,8 @@ fn check_pcap_error(pcap_t: &PcapT, ret: i32) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tests::{interface, interface_address};
+    use std::net::IpAddr;
 
     // Test for https://github.com/sensorfu/luomu-libpcap/pull/10
     //
@@ -706,8 +2079,186 @@ mod tests {
         let _pcap_t: PcapT = pcap_create("").expect("pcap_create");
     }
 
+    #[test]
+    fn test_pcap_into_from_raw() {
+        let pcap = crate::Pcap::new("").expect("Pcap::new");
+        let ptr = pcap.into_raw();
+        let _pcap = unsafe { crate::Pcap::from_raw(ptr) };
+    }
+
+    #[test]
+    fn test_pcap_filter_clone() {
+        let insns = [
+            libpcap::bpf_insn {
+                code: 0x28,
+                jt: 0,
+                jf: 0,
+                k: 12,
+            },
+            libpcap::bpf_insn {
+                code: 0x06,
+                jt: 0,
+                jf: 0,
+                k: 262144,
+            },
+        ];
+        let filter = crate::PcapFilter::from_insns(&insns);
+        let clone = filter.clone();
+        drop(filter);
+        assert_eq!(clone.get_raw_filter_len(), 2);
+        assert_eq!(clone.insns()[1].k, 262144);
+
+        let empty = crate::PcapFilter::from_insns(&[]).clone();
+        assert_eq!(empty.get_raw_filter_len(), 0);
+    }
+
+    #[test]
+    fn test_pcap_filter_batch() {
+        let pkthdr = |len: usize| libpcap::pcap_pkthdr {
+            ts: libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            caplen: len as u32,
+            len: len as u32,
+        };
+
+        // Ethernet + IPv4 headers with protocol from argument.
+        let frame = |proto: u8| {
+            let mut frame = vec![0u8; 34];
+            frame[12] = 0x08;
+            frame[14] = 0x45;
+            frame[23] = proto;
+            frame
+        };
+        let udp = frame(17);
+        let tcp = frame(6);
+
+        let filter = crate::PcapFilter::compile("udp").expect("compile");
+        let packets = [
+            (pkthdr(udp.len()), &udp[..]),
+            (pkthdr(tcp.len()), &tcp[..]),
+            (pkthdr(udp.len()), &udp[..10]),
+        ];
+        assert_eq!(filter.filter_batch(&packets), vec![true, false, false]);
+    }
+
+    #[test]
+    fn test_savefile_size() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("luomu-libpcap-size-{}.pcap", std::process::id()));
+        std::fs::write(&path, [0u8; 24]).unwrap();
+        assert_eq!(savefile_size(&path), Some(24));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(savefile_size(&path), None);
+        assert_eq!(savefile_size(&dir), None);
+        // Standard input even if the current directory has a file named "-".
+        assert_eq!(savefile_size(Path::new("-")), None);
+    }
+
+    #[test]
+    fn test_dumper_timeval() {
+        let time = std::time::Duration::new(1_700_000_000, 123_456_789);
+        let ts = crate::timeval(time, TimestampPrecision::Micro);
+        assert_eq!((ts.tv_sec, ts.tv_usec), (1_700_000_000, 123_456));
+        let ts = crate::timeval(time, TimestampPrecision::Nano);
+        assert_eq!((ts.tv_sec, ts.tv_usec), (1_700_000_000, 123_456_789));
+    }
+
     #[test]
     fn test_pcap_if_t() {
         let _pcap_if_t: PcapIfT = pcap_findalldevs().expect("pcap_findalldevs");
     }
+
+    #[test]
+    fn test_findalldevs_error() {
+        let err = findalldevs_error("getifaddrs: Permission denied".to_string());
+        assert!(matches!(
+            err,
+            Error::PermissionDenied(ref dev) if dev == "any (getifaddrs: Permission denied)"
+        ));
+        let err = findalldevs_error("socket: Address family not supported".to_string());
+        assert!(matches!(err, Error::PcapError(_)));
+    }
+
+    #[test]
+    fn test_interface_address_subnet() {
+        let address = interface_address(
+            Address::Ipv4(Ipv4Addr::new(192, 168, 1, 20)),
+            Some(Address::Ipv4(Ipv4Addr::new(255, 255, 255, 0))),
+        );
+        assert_eq!(address.network(), Some("192.168.1.0".parse().unwrap()));
+        assert_eq!(address.broadcast(), Some("192.168.1.255".parse().unwrap()));
+        assert!(address.contains(&"192.168.1.200".parse().unwrap()));
+        assert!(!address.contains(&"192.168.2.1".parse().unwrap()));
+        assert!(!address.contains(&"::1".parse().unwrap()));
+
+        let address = InterfaceAddress {
+            scope_id: Some(3),
+            ..interface_address(
+                Address::Ipv6("fe80::1:2".parse().unwrap()),
+                Some(Address::Ipv6("ffff:ffff:ffff:ffff::".parse().unwrap())),
+            )
+        };
+        assert_eq!(address.scope_id(), Some(3));
+        assert_eq!(
+            address.socket_addr(546),
+            Some("[fe80::1:2%3]:546".parse().unwrap())
+        );
+        assert_eq!(address.network(), Some("fe80::".parse().unwrap()));
+        assert_eq!(address.broadcast(), None);
+        assert!(address.contains(&"fe80::abcd".parse().unwrap()));
+        assert!(!address.contains(&"fe81::1".parse().unwrap()));
+
+        let address = interface_address(Address::Ipv4(Ipv4Addr::new(10, 0, 0, 1)), None);
+        assert_eq!(address.network(), None);
+        assert!(address.contains(&"10.0.0.1".parse().unwrap()));
+        assert!(!address.contains(&"10.0.0.2".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_interface_peer_addresses() {
+        let mut interface = interface("tun0", InterfaceFlags::empty());
+        assert!(interface.peer_addresses().is_empty());
+
+        let address = InterfaceAddress {
+            dstaddr: Some(Address::Ipv4(Ipv4Addr::new(10, 8, 0, 1))),
+            ..interface_address(
+                Address::Ipv4(Ipv4Addr::new(10, 8, 0, 2)),
+                Some(Address::Ipv4(Ipv4Addr::new(255, 255, 255, 255))),
+            )
+        };
+        assert_eq!(
+            address.destination(),
+            Some(&Address::Ipv4(Ipv4Addr::new(10, 8, 0, 1)))
+        );
+        interface.addresses.insert(address);
+        let peers: Vec<IpAddr> = interface.peer_addresses().into_iter().collect();
+        assert_eq!(peers, vec!["10.8.0.1".parse::<IpAddr>().unwrap()]);
+    }
+
+    #[test]
+    fn test_interface_display() {
+        let mut interface = interface("eth0", InterfaceFlags::UP | InterfaceFlags::RUNNING);
+        interface.addresses.insert(InterfaceAddress {
+            broadaddr: Some(Address::Ipv4(Ipv4Addr::new(192, 168, 1, 255))),
+            ..interface_address(
+                Address::Ipv4(Ipv4Addr::new(192, 168, 1, 20)),
+                Some(Address::Ipv4(Ipv4Addr::new(255, 255, 255, 0))),
+            )
+        });
+        interface.addresses.insert(InterfaceAddress {
+            scope_id: Some(2),
+            ..interface_address(
+                Address::Ipv6("fe80::1".parse().unwrap()),
+                Some(Address::Ipv6("ffff:ffff:ffff:ffff::".parse().unwrap())),
+            )
+        });
+        assert_eq!(interface.to_string(), "eth0 [Up, Running]");
+        assert_eq!(
+            format!("{:#}", interface),
+            "eth0 [Up, Running]\n    192.168.1.20/24 broadcast 192.168.1.255\n    fe80::1%2/64"
+        );
+    }
 }
@@ -0,0 +1,102 @@
+//! Entry points for fuzzing
+//!
+//! Each function here feeds arbitrary bytes to one of the parsers of the
+//! crate, discarding the outcome. They must not panic, hang or allocate
+//! without bounds on any input. The functions are meant to be called from
+//! `cargo fuzz` targets:
+//!
+//! ```ignore
+//! #![no_main]
+//! use libfuzzer_sys::fuzz_target;
+//!
+//! fuzz_target!(|data: &[u8]| luomu_libpcap::fuzz::pcapng(data));
+//! ```
+//!
+//! Requires feature `fuzzing`.
+
+use std::io::Cursor;
+
+use crate::export::PacketDecoder;
+use crate::{golden, LinkType, PcapFilter};
+
+/// Parse `data` as a savefile in classic pcap format.
+pub fn savefile(data: &[u8]) {
+    let _ = golden::parse_pcap(data);
+}
+
+/// Parse `data` as a savefile in pcapng format.
+pub fn pcapng(data: &[u8]) {
+    let _ = golden::parse_pcapng(data);
+}
+
+/// Compare `data` as a savefile in either format with itself. Also
+/// exercises the comparison of [golden].
+pub fn golden_diff(data: &[u8]) {
+    if let Ok(diff) = golden::diff(data, data) {
+        assert_eq!(diff, None, "savefile differs from itself");
+    }
+}
+
+/// Decode `data` as a stream of packets written by
+/// [PacketEncoder](crate::export::PacketEncoder).
+pub fn export_stream(data: &[u8]) {
+    for packet in PacketDecoder::new(Cursor::new(data)) {
+        if packet.is_err() {
+            break;
+        }
+    }
+}
+
+/// Validate a filter expression. The first two bytes of `data` are the
+/// link-layer header type in big endian, the rest is the expression.
+///
+/// Calls into libpcap, so the filter compiler of libpcap is fuzzed too.
+pub fn filter(data: &[u8]) {
+    let (linktype, expression) = match data {
+        [a, b, rest @ ..] => (i32::from(u16::from_be_bytes([*a, *b])), rest),
+        _ => return,
+    };
+    let expression = String::from_utf8_lossy(expression);
+    // Expressions with NUL bytes can't be passed to libpcap.
+    if expression.contains('\0') {
+        return;
+    }
+    let _ = PcapFilter::validate(&expression, LinkType(linktype));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export_stream, golden_diff, pcapng, savefile};
+
+    #[test]
+    fn test_fuzz_parsers() {
+        let mut inputs: Vec<Vec<u8>> = vec![
+            Vec::new(),
+            vec![0x0a, 0x0d, 0x0d, 0x0a],
+            crate::savefile::tests::savefile(true, false, &[b"abc", b"defg"]),
+        ];
+        // Section header block followed by an enhanced packet block whose
+        // options would start past the end of the block.
+        let mut shb = vec![0x0a, 0x0d, 0x0d, 0x0a, 28, 0, 0, 0, 0x4d, 0x3c, 0x2b, 0x1a];
+        shb.extend_from_slice(&[1, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        shb.extend_from_slice(&[28, 0, 0, 0]);
+        let idb = [
+            1, 0, 0, 0, 20, 0, 0, 0, 1, 0, 0, 0, 0xff, 0xff, 0, 0, 20, 0, 0, 0,
+        ];
+        let mut epb = vec![6, 0, 0, 0, 36, 0, 0, 0];
+        epb.extend_from_slice(&[0; 12]);
+        epb.extend_from_slice(&[3, 0, 0, 0, 3, 0, 0, 0, 1, 2, 3]);
+        epb.push(0xff);
+        epb.extend_from_slice(&[36, 0, 0, 0]);
+        inputs.push([shb, idb.to_vec(), epb].concat());
+
+        for input in &inputs {
+            for len in 0..=input.len() {
+                savefile(&input[..len]);
+                pcapng(&input[..len]);
+                golden_diff(&input[..len]);
+                export_stream(&input[..len]);
+            }
+        }
+    }
+}
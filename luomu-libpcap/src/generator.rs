@@ -0,0 +1,330 @@
+//! Synthetic packets for tests and traffic generation
+//!
+//! [FrameBuilder] builds valid UDP and TCP packets over IPv4 or IPv6, with
+//! correct lengths and checksums, either as Ethernet frames for
+//! `Pcap::inject()` or as bare IP packets for `Pcap::inject_ip()` and
+//! captures with raw IP link-layer header type.
+//!
+//! ```
+//! use luomu_libpcap::generator::{FrameBuilder, TCP_SYN};
+//!
+//! let syn = FrameBuilder::tcp("10.0.0.1:40000".parse()?, "10.0.0.2:443".parse()?)
+//!     .tcp_flags(TCP_SYN)
+//!     .build()?;
+//! assert_eq!(syn.len(), 14 + 20 + 20);
+//!
+//! let query = FrameBuilder::udp("[fd00::1]:5353".parse()?, "[fd00::2]:53".parse()?)
+//!     .payload_len(32)
+//!     .build_ip()?;
+//! assert_eq!(query.len(), 40 + 8 + 32);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::net::{IpAddr, SocketAddr};
+
+use luomu_common::MacAddr;
+
+use crate::{Error, Result};
+
+/// TCP FIN flag.
+pub const TCP_FIN: u8 = 0x01;
+/// TCP SYN flag.
+pub const TCP_SYN: u8 = 0x02;
+/// TCP RST flag.
+pub const TCP_RST: u8 = 0x04;
+/// TCP PSH flag.
+pub const TCP_PSH: u8 = 0x08;
+/// TCP ACK flag.
+pub const TCP_ACK: u8 = 0x10;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const PROTO_TCP: u8 = 6;
+const PROTO_UDP: u8 = 17;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Transport {
+    Udp,
+    Tcp { flags: u8, seq: u32, ack: u32 },
+}
+
+/// Builder of UDP and TCP packets.
+///
+/// Ethernet addresses default to `02:00:00:00:00:01` for the source and
+/// broadcast for the destination, hop limit to 64 and payload to empty.
+/// TCP packets have ACK flag set and sequence and acknowledgement numbers
+/// of zero by default.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameBuilder {
+    src_mac: MacAddr,
+    dst_mac: MacAddr,
+    src: SocketAddr,
+    dst: SocketAddr,
+    ttl: u8,
+    transport: Transport,
+    payload: Vec<u8>,
+}
+
+impl FrameBuilder {
+    /// Builder of UDP packets from `src` to `dst`.
+    pub fn udp(src: SocketAddr, dst: SocketAddr) -> FrameBuilder {
+        FrameBuilder::new(src, dst, Transport::Udp)
+    }
+
+    /// Builder of TCP segments from `src` to `dst`.
+    pub fn tcp(src: SocketAddr, dst: SocketAddr) -> FrameBuilder {
+        FrameBuilder::new(
+            src,
+            dst,
+            Transport::Tcp {
+                flags: TCP_ACK,
+                seq: 0,
+                ack: 0,
+            },
+        )
+    }
+
+    fn new(src: SocketAddr, dst: SocketAddr, transport: Transport) -> FrameBuilder {
+        FrameBuilder {
+            src_mac: MacAddr::from([0x02, 0, 0, 0, 0, 0x01]),
+            dst_mac: MacAddr::BROADCAST,
+            src,
+            dst,
+            ttl: 64,
+            transport,
+            payload: Vec::new(),
+        }
+    }
+
+    /// Set the source and destination Ethernet addresses.
+    pub fn macs(mut self, src: MacAddr, dst: MacAddr) -> FrameBuilder {
+        self.src_mac = src;
+        self.dst_mac = dst;
+        self
+    }
+
+    /// Set the IPv4 time to live or IPv6 hop limit.
+    pub fn ttl(mut self, ttl: u8) -> FrameBuilder {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the TCP flags, such as `TCP_SYN | TCP_ACK`. Ignored for UDP.
+    pub fn tcp_flags(mut self, tcp_flags: u8) -> FrameBuilder {
+        if let Transport::Tcp { flags, .. } = &mut self.transport {
+            *flags = tcp_flags;
+        }
+        self
+    }
+
+    /// Set the TCP sequence and acknowledgement numbers. Ignored for UDP.
+    pub fn tcp_seq(mut self, tcp_seq: u32, tcp_ack: u32) -> FrameBuilder {
+        if let Transport::Tcp { seq, ack, .. } = &mut self.transport {
+            *seq = tcp_seq;
+            *ack = tcp_ack;
+        }
+        self
+    }
+
+    /// Set the payload.
+    pub fn payload<B: AsRef<[u8]>>(mut self, payload: B) -> FrameBuilder {
+        self.payload = payload.as_ref().to_vec();
+        self
+    }
+
+    /// Set a payload of `len` bytes counting up from 0, wrapping at 256.
+    pub fn payload_len(mut self, len: usize) -> FrameBuilder {
+        self.payload = (0..len).map(|n| n as u8).collect();
+        self
+    }
+
+    /// Build an Ethernet frame.
+    ///
+    /// Fails if the source and destination addresses are of different
+    /// families, or the packet is too large for IP.
+    pub fn build(&self) -> Result<Vec<u8>> {
+        let ethertype = match self.src.ip() {
+            IpAddr::V4(_) => ETHERTYPE_IPV4,
+            IpAddr::V6(_) => ETHERTYPE_IPV6,
+        };
+        let mut frame = Vec::with_capacity(14 + 60 + self.payload.len());
+        frame.extend_from_slice(&*self.dst_mac);
+        frame.extend_from_slice(&*self.src_mac);
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame.extend_from_slice(&self.build_ip()?);
+        Ok(frame)
+    }
+
+    /// Build an IP packet without link-layer header.
+    ///
+    /// Fails if the source and destination addresses are of different
+    /// families, or the packet is too large for IP.
+    pub fn build_ip(&self) -> Result<Vec<u8>> {
+        let (proto, mut transport) = match self.transport {
+            Transport::Udp => {
+                let mut header = Vec::with_capacity(8 + self.payload.len());
+                header.extend_from_slice(&self.src.port().to_be_bytes());
+                header.extend_from_slice(&self.dst.port().to_be_bytes());
+                header.extend_from_slice(&length(8 + self.payload.len())?.to_be_bytes());
+                header.extend_from_slice(&[0, 0]);
+                (PROTO_UDP, header)
+            }
+            Transport::Tcp { flags, seq, ack } => {
+                let mut header = Vec::with_capacity(20 + self.payload.len());
+                header.extend_from_slice(&self.src.port().to_be_bytes());
+                header.extend_from_slice(&self.dst.port().to_be_bytes());
+                header.extend_from_slice(&seq.to_be_bytes());
+                header.extend_from_slice(&ack.to_be_bytes());
+                // Data offset of 5 words, flags and window of 65535.
+                header.extend_from_slice(&[5 << 4, flags, 0xff, 0xff]);
+                // Checksum and urgent pointer.
+                header.extend_from_slice(&[0, 0, 0, 0]);
+                (PROTO_TCP, header)
+            }
+        };
+        transport.extend_from_slice(&self.payload);
+        let transport_len = length(transport.len())?;
+
+        let (mut packet, pseudo) = match (self.src.ip(), self.dst.ip()) {
+            (IpAddr::V4(src), IpAddr::V4(dst)) => {
+                let total_len = length(20 + transport.len())?;
+                let mut header = vec![0x45, 0];
+                header.extend_from_slice(&total_len.to_be_bytes());
+                // Identification, and no fragmentation.
+                header.extend_from_slice(&[0, 0, 0x40, 0]);
+                header.extend_from_slice(&[self.ttl, proto, 0, 0]);
+                header.extend_from_slice(&src.octets());
+                header.extend_from_slice(&dst.octets());
+                let checksum = checksum(0, &header);
+                header[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+                let mut pseudo = Vec::with_capacity(12);
+                pseudo.extend_from_slice(&src.octets());
+                pseudo.extend_from_slice(&dst.octets());
+                pseudo.extend_from_slice(&[0, proto]);
+                pseudo.extend_from_slice(&transport_len.to_be_bytes());
+                (header, pseudo)
+            }
+            (IpAddr::V6(src), IpAddr::V6(dst)) => {
+                let mut header = vec![0x60, 0, 0, 0];
+                header.extend_from_slice(&transport_len.to_be_bytes());
+                header.extend_from_slice(&[proto, self.ttl]);
+                header.extend_from_slice(&src.octets());
+                header.extend_from_slice(&dst.octets());
+
+                let mut pseudo = Vec::with_capacity(40);
+                pseudo.extend_from_slice(&src.octets());
+                pseudo.extend_from_slice(&dst.octets());
+                pseudo.extend_from_slice(&u32::from(transport_len).to_be_bytes());
+                pseudo.extend_from_slice(&[0, 0, 0, proto]);
+                (header, pseudo)
+            }
+            _ => {
+                return Err(Error::PcapError(format!(
+                    "addresses {} and {} are of different families",
+                    self.src, self.dst
+                )))
+            }
+        };
+
+        let offset = if proto == PROTO_UDP { 6 } else { 16 };
+        let mut checksum = checksum(sum(0, &pseudo), &transport);
+        if proto == PROTO_UDP && checksum == 0 {
+            checksum = 0xffff;
+        }
+        transport[offset..offset + 2].copy_from_slice(&checksum.to_be_bytes());
+        packet.extend_from_slice(&transport);
+        Ok(packet)
+    }
+}
+
+fn length(len: usize) -> Result<u16> {
+    u16::try_from(len)
+        .map_err(|_| Error::PcapError(format!("packet of {} bytes is too large", len)))
+}
+
+// One's complement sum of `bytes` as 16 bit words, added to `sum`.
+fn sum(mut sum: u32, bytes: &[u8]) -> u32 {
+    let mut chunks = bytes.chunks_exact(2);
+    for word in &mut chunks {
+        sum += u32::from(u16::from_be_bytes([word[0], word[1]]));
+    }
+    if let [last] = chunks.remainder() {
+        sum += u32::from(*last) << 8;
+    }
+    sum
+}
+
+// Internet checksum of `bytes` with `initial` sum of a pseudo header.
+fn checksum(initial: u32, bytes: &[u8]) -> u16 {
+    let mut sum = sum(initial, bytes);
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{checksum, sum, FrameBuilder, TCP_SYN};
+    use crate::{Layers, LinkType};
+
+    #[test]
+    fn test_udp_ipv4() {
+        let frame = FrameBuilder::udp(
+            "10.0.0.1:1234".parse().unwrap(),
+            "10.0.0.2:53".parse().unwrap(),
+        )
+        .payload_len(5)
+        .build()
+        .unwrap();
+        assert_eq!(frame.len(), 14 + 20 + 8 + 5);
+        assert_eq!(frame[12..14], [0x08, 0x00]);
+
+        let layers = Layers::new(LinkType::ETHERNET, &frame);
+        assert_eq!(layers.protocol(), Some(17));
+        assert_eq!(layers.payload(), [0, 1, 2, 3, 4]);
+        let ip = layers.network().unwrap();
+        assert_eq!(ip[2..4], 33u16.to_be_bytes());
+        // Checksums over a valid header or segment sum to zero.
+        assert_eq!(checksum(0, ip), 0);
+        let mut pseudo = vec![10, 0, 0, 1, 10, 0, 0, 2, 0, 17];
+        pseudo.extend_from_slice(&13u16.to_be_bytes());
+        assert_eq!(checksum(sum(0, &pseudo), &frame[34..]), 0);
+    }
+
+    #[test]
+    fn test_tcp_ipv6() {
+        let packet = FrameBuilder::tcp(
+            "[fd00::1]:40000".parse().unwrap(),
+            "[fd00::2]:443".parse().unwrap(),
+        )
+        .tcp_flags(TCP_SYN)
+        .tcp_seq(1000, 0)
+        .ttl(1)
+        .build_ip()
+        .unwrap();
+        assert_eq!(packet.len(), 40 + 20);
+
+        let layers = Layers::new(LinkType::IPV6, &packet);
+        assert_eq!(layers.protocol(), Some(6));
+        assert_eq!(packet[4..6], 20u16.to_be_bytes());
+        assert_eq!(packet[7], 1);
+        let tcp = layers.transport().unwrap();
+        assert_eq!(tcp[4..8], 1000u32.to_be_bytes());
+        assert_eq!(tcp[13], TCP_SYN);
+
+        let mut pseudo = packet[8..40].to_vec();
+        pseudo.extend_from_slice(&[0, 0, 0, 20, 0, 0, 0, 6]);
+        assert_eq!(checksum(sum(0, &pseudo), tcp), 0);
+    }
+
+    #[test]
+    fn test_mixed_families() {
+        let builder = FrameBuilder::udp(
+            "10.0.0.1:1234".parse().unwrap(),
+            "[fd00::2]:53".parse().unwrap(),
+        );
+        assert!(builder.build().is_err());
+    }
+}
@@ -0,0 +1,536 @@
+//! Comparing savefiles against golden files
+//!
+//! Tests of code writing savefiles compare the files written against
+//! expected ones. Comparing the bytes makes such tests fragile: the same
+//! packets can be written in either byte order, and options of pcapng blocks
+//! in any order. The functions here parse both files into their packets and
+//! compare those, ignoring the incidental differences:
+//!
+//! - byte order of the file
+//! - classic pcap or pcapng format, as long as the pcapng file has no
+//!   options the classic file can't have
+//! - time stamp precision, microseconds or nanoseconds, as long as the time
+//!   stamps are equal
+//! - order of the options of pcapng Interface Description Blocks and
+//!   Enhanced Packet Blocks
+//! - Section Header Block options, like the name of the writing application,
+//!   and Interface Statistics and other blocks not describing packets
+//!
+//! ```no_run
+//! use luomu_libpcap::golden;
+//!
+//! // ... write "out.pcap" ...
+//! golden::assert_files_eq("out.pcap", "tests/golden/expected.pcap");
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::savefile::{invalid_data, FileHeader, FILE_HEADER_LEN, RECORD_HEADER_LEN};
+use crate::Result;
+
+const SHB: u32 = 0x0a0d_0d0a;
+const IDB: u32 = 0x0000_0001;
+const SPB: u32 = 0x0000_0003;
+const EPB: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+
+// Option codes.
+const OPT_ENDOFOPT: u16 = 0;
+const IF_TSRESOL: u16 = 9;
+const IF_SPEED: u16 = 8;
+const IF_TSOFFSET: u16 = 14;
+const EPB_FLAGS: u16 = 2;
+const EPB_DROPCOUNT: u16 = 4;
+const EPB_PACKETID: u16 = 5;
+const EPB_QUEUE: u16 = 6;
+
+// Interface of packets, from the classic file header or a pcapng Interface
+// Description Block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Interface {
+    linktype: u32,
+    snaplen: u32,
+    // Sorted, numeric values in big endian, without if_tsresol.
+    options: Vec<(u16, Vec<u8>)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Record {
+    interface: Interface,
+    // Time stamp in nanoseconds since the epoch.
+    timestamp: u128,
+    len: u32,
+    data: Vec<u8>,
+    // Sorted, numeric values in big endian.
+    options: Vec<(u16, Vec<u8>)>,
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "linktype {}, snaplen {}, time stamp {} ns, {} of {} bytes",
+            self.interface.linktype,
+            self.interface.snaplen,
+            self.timestamp,
+            self.data.len(),
+            self.len
+        )?;
+        if !self.interface.options.is_empty() {
+            write!(f, ", interface options {:?}", self.interface.options)?;
+        }
+        if !self.options.is_empty() {
+            write!(f, ", options {:?}", self.options)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare the packets of savefiles `actual` and `expected`, both either in
+/// classic pcap or pcapng format.
+///
+/// Returns `None` if the files have the same packets, or a description of
+/// the first difference. Fails if either file can't be parsed.
+pub fn diff(actual: &[u8], expected: &[u8]) -> Result<Option<String>> {
+    let actual = parse(actual)?;
+    let expected = parse(expected)?;
+    for (n, (a, e)) in actual.iter().zip(expected.iter()).enumerate() {
+        if a.interface != e.interface
+            || a.timestamp != e.timestamp
+            || a.len != e.len
+            || a.options != e.options
+        {
+            return Ok(Some(format!("packet {} differs: {} != {}", n + 1, a, e)));
+        }
+        if a.data != e.data {
+            let offset = a
+                .data
+                .iter()
+                .zip(e.data.iter())
+                .position(|(a, e)| a != e)
+                .unwrap_or(a.data.len().min(e.data.len()));
+            return Ok(Some(format!(
+                "packet {} differs at byte {}: {} != {}",
+                n + 1,
+                offset,
+                a,
+                e
+            )));
+        }
+    }
+    if actual.len() != expected.len() {
+        return Ok(Some(format!(
+            "{} packets != {} packets",
+            actual.len(),
+            expected.len()
+        )));
+    }
+    Ok(None)
+}
+
+/// Compare the packets of savefiles at paths `actual` and `expected`. See
+/// [diff()].
+pub fn diff_files<P: AsRef<Path>, Q: AsRef<Path>>(
+    actual: P,
+    expected: Q,
+) -> Result<Option<String>> {
+    diff(&fs::read(actual)?, &fs::read(expected)?)
+}
+
+/// Assert that savefiles at paths `actual` and `expected` have the same
+/// packets. See [diff()].
+///
+/// Panics with the difference if they don't, or if either file can't be
+/// read.
+pub fn assert_files_eq<P: AsRef<Path>, Q: AsRef<Path>>(actual: P, expected: Q) {
+    let (actual, expected) = (actual.as_ref(), expected.as_ref());
+    match diff_files(actual, expected) {
+        Ok(None) => (),
+        Ok(Some(diff)) => panic!(
+            "{} differs from {}: {}",
+            actual.display(),
+            expected.display(),
+            diff
+        ),
+        Err(err) => panic!(
+            "comparing {} to {} failed: {}",
+            actual.display(),
+            expected.display(),
+            err
+        ),
+    }
+}
+
+fn parse(data: &[u8]) -> Result<Vec<Record>> {
+    if data.len() >= 4 && data[..4] == SHB.to_le_bytes() {
+        parse_pcapng(data)
+    } else {
+        parse_pcap(data)
+    }
+}
+
+pub(crate) fn parse_pcap(data: &[u8]) -> Result<Vec<Record>> {
+    let header = FileHeader::parse(data)?;
+    let interface = Interface {
+        linktype: header.linktype,
+        snaplen: header.snaplen,
+        options: Vec::new(),
+    };
+    let mut records = Vec::new();
+    let mut rest = &data[FILE_HEADER_LEN..];
+    while !rest.is_empty() {
+        if rest.len() < RECORD_HEADER_LEN {
+            return Err(invalid_data("truncated packet record header"));
+        }
+        let record = header.record(rest);
+        let end = RECORD_HEADER_LEN + record.caplen as usize;
+        if rest.len() < end {
+            return Err(invalid_data("truncated packet record"));
+        }
+        let frac = if header.nsec {
+            u128::from(record.ts_frac)
+        } else {
+            u128::from(record.ts_frac) * 1000
+        };
+        records.push(Record {
+            interface: interface.clone(),
+            timestamp: u128::from(record.ts_sec) * 1_000_000_000 + frac,
+            len: record.len,
+            data: rest[RECORD_HEADER_LEN..end].to_vec(),
+            options: Vec::new(),
+        });
+        rest = &rest[end..];
+    }
+    Ok(records)
+}
+
+// Byte order of a pcapng section.
+#[derive(Clone, Copy)]
+struct Section {
+    big_endian: bool,
+}
+
+impl Section {
+    fn u16_at(self, bytes: &[u8], i: usize) -> u16 {
+        let b = [bytes[i], bytes[i + 1]];
+        if self.big_endian {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        }
+    }
+
+    fn u32_at(self, bytes: &[u8], i: usize) -> u32 {
+        let b = [bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]];
+        if self.big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        }
+    }
+
+    // Parse options, converting numeric values of `numeric` codes into big
+    // endian, and sort them.
+    fn options(self, mut bytes: &[u8], numeric: &[u16]) -> Result<Vec<(u16, Vec<u8>)>> {
+        let mut options = Vec::new();
+        while bytes.len() >= 4 {
+            let code = self.u16_at(bytes, 0);
+            let len = self.u16_at(bytes, 2) as usize;
+            if code == OPT_ENDOFOPT {
+                break;
+            }
+            let end = 4 + len;
+            if bytes.len() < end {
+                return Err(invalid_data("truncated pcapng option"));
+            }
+            let mut value = bytes[4..end].to_vec();
+            if !self.big_endian && numeric.contains(&code) {
+                value.reverse();
+            }
+            options.push((code, value));
+            bytes = bytes.get(padded(end)..).unwrap_or_default();
+        }
+        options.sort();
+        Ok(options)
+    }
+}
+
+// Length padded to 32 bits.
+fn padded(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+// Interface of pcapng, with the resolution of its time stamps.
+struct PcapngInterface {
+    interface: Interface,
+    tsresol: u8,
+}
+
+impl PcapngInterface {
+    // Time stamp in nanoseconds.
+    fn timestamp(&self, ts: u64) -> u128 {
+        let ts = u128::from(ts);
+        if self.tsresol & 0x80 != 0 {
+            (ts * 1_000_000_000) >> (self.tsresol & 0x7f).min(127)
+        } else {
+            let exp = u32::from(self.tsresol).min(38);
+            if exp <= 9 {
+                ts * 10u128.pow(9 - exp)
+            } else {
+                ts / 10u128.pow(exp - 9)
+            }
+        }
+    }
+}
+
+pub(crate) fn parse_pcapng(data: &[u8]) -> Result<Vec<Record>> {
+    let mut records = Vec::new();
+    let mut section = Section { big_endian: false };
+    let mut interfaces: Vec<PcapngInterface> = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        if rest.len() < 12 {
+            return Err(invalid_data("truncated pcapng block"));
+        }
+        if rest[..4] == SHB.to_le_bytes() {
+            section.big_endian = match rest[8..12] {
+                [0x1a, 0x2b, 0x3c, 0x4d] => true,
+                [0x4d, 0x3c, 0x2b, 0x1a] => false,
+                _ => return Err(invalid_data("invalid pcapng byte-order magic")),
+            };
+            debug_assert_eq!(section.u32_at(rest, 8), BYTE_ORDER_MAGIC);
+            interfaces.clear();
+        }
+        let block_type = section.u32_at(rest, 0);
+        let len = section.u32_at(rest, 4) as usize;
+        if len < 12 || len % 4 != 0 || rest.len() < len {
+            return Err(invalid_data("invalid pcapng block length"));
+        }
+        let body = &rest[8..len - 4];
+        match block_type {
+            IDB => {
+                if body.len() < 8 {
+                    return Err(invalid_data("truncated interface description block"));
+                }
+                let mut options = section.options(&body[8..], &[IF_SPEED, IF_TSOFFSET])?;
+                let tsresol = match options.iter().position(|(code, _)| *code == IF_TSRESOL) {
+                    Some(i) => options.remove(i).1.first().copied().unwrap_or(6),
+                    None => 6,
+                };
+                interfaces.push(PcapngInterface {
+                    interface: Interface {
+                        linktype: section.u16_at(body, 0).into(),
+                        snaplen: section.u32_at(body, 4),
+                        options,
+                    },
+                    tsresol,
+                });
+            }
+            EPB => {
+                if body.len() < 20 {
+                    return Err(invalid_data("truncated enhanced packet block"));
+                }
+                let interface = interfaces
+                    .get(section.u32_at(body, 0) as usize)
+                    .ok_or_else(|| invalid_data("packet of unknown interface"))?;
+                let ts =
+                    (u64::from(section.u32_at(body, 4)) << 32) | u64::from(section.u32_at(body, 8));
+                let caplen = section.u32_at(body, 12) as usize;
+                let end = 20 + caplen;
+                if body.len() < end {
+                    return Err(invalid_data("truncated enhanced packet block"));
+                }
+                let options = section.options(
+                    body.get(padded(end)..).unwrap_or_default(),
+                    &[EPB_FLAGS, EPB_DROPCOUNT, EPB_PACKETID, EPB_QUEUE],
+                )?;
+                records.push(Record {
+                    interface: interface.interface.clone(),
+                    timestamp: interface.timestamp(ts),
+                    len: section.u32_at(body, 16),
+                    data: body[20..end].to_vec(),
+                    options,
+                });
+            }
+            SPB => {
+                if body.len() < 4 {
+                    return Err(invalid_data("truncated simple packet block"));
+                }
+                let interface = interfaces
+                    .first()
+                    .ok_or_else(|| invalid_data("packet of unknown interface"))?;
+                let packet_len = section.u32_at(body, 0);
+                let snaplen = match interface.interface.snaplen {
+                    0 => u32::MAX,
+                    snaplen => snaplen,
+                };
+                let caplen = packet_len.min(snaplen) as usize;
+                if body.len() < 4 + caplen {
+                    return Err(invalid_data("truncated simple packet block"));
+                }
+                records.push(Record {
+                    interface: interface.interface.clone(),
+                    timestamp: 0,
+                    len: packet_len,
+                    data: body[4..4 + caplen].to_vec(),
+                    options: Vec::new(),
+                });
+            }
+            _ => (),
+        }
+        rest = &rest[len..];
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff;
+    use crate::savefile::tests::savefile;
+
+    struct Pcapng {
+        big_endian: bool,
+        data: Vec<u8>,
+    }
+
+    impl Pcapng {
+        fn new(big_endian: bool) -> Pcapng {
+            let mut pcapng = Pcapng {
+                big_endian,
+                data: Vec::new(),
+            };
+            let mut body = Vec::new();
+            body.extend_from_slice(&pcapng.u32(0x1a2b_3c4d));
+            body.extend_from_slice(&pcapng.u16(1));
+            body.extend_from_slice(&pcapng.u16(0));
+            body.extend_from_slice(&[0xff; 8]);
+            // shb_userappl differs between writers.
+            body.extend_from_slice(&pcapng.option(4, if big_endian { b"a" } else { b"bb" }));
+            pcapng.block(0x0a0d_0d0a, &body);
+            pcapng
+        }
+
+        fn u16(&self, v: u16) -> [u8; 2] {
+            if self.big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        }
+
+        fn u32(&self, v: u32) -> [u8; 4] {
+            if self.big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        }
+
+        fn option(&self, code: u16, value: &[u8]) -> Vec<u8> {
+            let mut option = Vec::new();
+            option.extend_from_slice(&self.u16(code));
+            option.extend_from_slice(&self.u16(value.len() as u16));
+            option.extend_from_slice(value);
+            while option.len() % 4 != 0 {
+                option.push(0);
+            }
+            option
+        }
+
+        fn block(&mut self, block_type: u32, body: &[u8]) {
+            let len = 12 + body.len() as u32;
+            let (t, l) = (self.u32(block_type), self.u32(len));
+            self.data.extend_from_slice(&t);
+            self.data.extend_from_slice(&l);
+            self.data.extend_from_slice(body);
+            self.data.extend_from_slice(&l);
+        }
+
+        fn interface(&mut self, tsresol: u8, options: &[Vec<u8>]) {
+            let mut body = Vec::new();
+            body.extend_from_slice(&self.u16(1));
+            body.extend_from_slice(&[0, 0]);
+            body.extend_from_slice(&self.u32(65535));
+            body.extend_from_slice(&self.option(9, &[tsresol]));
+            for option in options {
+                body.extend_from_slice(option);
+            }
+            self.block(1, &body);
+        }
+
+        fn packet(&mut self, ts: u64, packet: &[u8], options: &[Vec<u8>]) {
+            let mut body = Vec::new();
+            body.extend_from_slice(&self.u32(0));
+            body.extend_from_slice(&self.u32((ts >> 32) as u32));
+            body.extend_from_slice(&self.u32(ts as u32));
+            body.extend_from_slice(&self.u32(packet.len() as u32));
+            body.extend_from_slice(&self.u32(packet.len() as u32 + 10));
+            body.extend_from_slice(packet);
+            while body.len() % 4 != 0 {
+                body.push(0);
+            }
+            for option in options {
+                body.extend_from_slice(option);
+            }
+            self.block(6, &body);
+        }
+    }
+
+    #[test]
+    fn test_diff_pcap() {
+        let expected = savefile(false, false, &[b"abc", b"defg"]);
+        assert_eq!(diff(&expected, &expected).unwrap(), None);
+        let swapped = savefile(true, false, &[b"abc", b"defg"]);
+        assert_eq!(diff(&swapped, &expected).unwrap(), None);
+
+        let other = savefile(true, false, &[b"abc", b"dxfg"]);
+        let diff_msg = diff(&other, &expected).unwrap().unwrap();
+        assert!(
+            diff_msg.starts_with("packet 2 differs at byte 1"),
+            "{}",
+            diff_msg
+        );
+
+        let fewer = savefile(false, false, &[b"abc"]);
+        assert_eq!(
+            diff(&fewer, &expected).unwrap().as_deref(),
+            Some("1 packets != 2 packets")
+        );
+        assert!(diff(b"not a savefile at all....", &expected).is_err());
+    }
+
+    #[test]
+    fn test_diff_pcapng() {
+        let build = |big_endian: bool, reversed: bool, tsresol: u8, ts: u64| {
+            let mut pcapng = Pcapng::new(big_endian);
+            let comment = pcapng.option(1, b"hello");
+            let flags = pcapng.option(2, &pcapng.u32(1));
+            let mut options = vec![comment, flags];
+            if reversed {
+                options.reverse();
+            }
+            pcapng.interface(tsresol, &[]);
+            pcapng.packet(ts, b"abc", &options);
+            pcapng.data
+        };
+
+        let expected = build(false, false, 6, 1_000_500);
+        assert_eq!(diff(&expected, &expected).unwrap(), None);
+        // Other byte order and option order, nanosecond time stamps.
+        let actual = build(true, true, 9, 1_000_500_000);
+        assert_eq!(diff(&actual, &expected).unwrap(), None);
+
+        let later = build(true, true, 9, 1_000_500_001);
+        assert!(diff(&later, &expected).unwrap().is_some());
+
+        // Same packet in classic format, but without options.
+        let pcap = savefile(false, false, &[b"abc"]);
+        assert!(diff(&pcap, &expected).unwrap().is_some());
+        let mut pcapng = Pcapng::new(true);
+        pcapng.interface(6, &[]);
+        pcapng.packet(1_000_500, b"abc", &[]);
+        assert_eq!(diff(&pcap, &pcapng.data).unwrap(), None);
+    }
+}
@@ -0,0 +1,103 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::{libpcap_available, loopback_device, Error, Pcap};
+
+static LIVE_CAPTURE: OnceLock<LiveCapture> = OnceLock::new();
+
+/// Whether live captures can be opened in the current environment.
+///
+/// Integration tests capturing live traffic can't run everywhere: CI
+/// containers often run without the privileges to capture, or even without
+/// a loopback device. [live_capture()] checks this once, so such tests can
+/// be skipped there with [require_live_capture!](crate::require_live_capture)
+/// instead of failing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LiveCapture {
+    /// Captures can be opened on the loopback device with the given name.
+    Available(String),
+    /// Captures can't be opened, for the given reason.
+    Unavailable(String),
+}
+
+impl LiveCapture {
+    /// Returns true if live captures can be opened.
+    pub fn is_available(&self) -> bool {
+        matches!(self, LiveCapture::Available(_))
+    }
+
+    /// Name of the loopback device, if live captures can be opened.
+    pub fn device(&self) -> Option<&str> {
+        match self {
+            LiveCapture::Available(device) => Some(device),
+            LiveCapture::Unavailable(_) => None,
+        }
+    }
+
+    fn detect() -> LiveCapture {
+        if !libpcap_available() {
+            return LiveCapture::Unavailable(String::from("libpcap is not installed"));
+        }
+        let device = match loopback_device() {
+            Ok(device) => device,
+            Err(Error::NoSuchDevice(_)) => {
+                return LiveCapture::Unavailable(String::from("no loopback device to capture on"))
+            }
+            Err(err) => {
+                return LiveCapture::Unavailable(format!("can't list capture devices: {}", err))
+            }
+        };
+        let activated = Pcap::builder(&device)
+            .and_then(|builder| builder.set_timeout(Duration::from_millis(10)))
+            .and_then(|builder| builder.activate());
+        match activated {
+            Ok(_) => LiveCapture::Available(device),
+            Err(Error::PermissionDenied(_)) => {
+                LiveCapture::Unavailable(format!("no permission to capture on {}", device))
+            }
+            Err(err) => LiveCapture::Unavailable(format!("can't capture on {}: {}", device, err)),
+        }
+    }
+}
+
+/// Check whether live captures can be opened in the current environment.
+///
+/// Opens a capture on the loopback device on the first call, and returns the
+/// outcome of it on later calls.
+pub fn live_capture() -> &'static LiveCapture {
+    LIVE_CAPTURE.get_or_init(LiveCapture::detect)
+}
+
+/// Skip a test if live captures can't be opened.
+///
+/// Evaluates to the name of the loopback device if live captures can be
+/// opened in the current environment, see [live_capture()]. Otherwise prints
+/// the reason why not and returns from the calling function, with the
+/// value given to the macro if any:
+///
+/// ```no_run
+/// use luomu_libpcap::{require_live_capture, Pcap, Result};
+///
+/// #[test]
+/// fn test_capture_loopback() -> Result<()> {
+///     let device = require_live_capture!(Ok(()));
+///     let pcap = Pcap::new(&device)?;
+///     // ...
+///     Ok(())
+/// }
+/// ```
+#[macro_export]
+macro_rules! require_live_capture {
+    () => {
+        $crate::require_live_capture!(())
+    };
+    ($ret:expr) => {
+        match $crate::live_capture() {
+            $crate::LiveCapture::Available(device) => device.clone(),
+            $crate::LiveCapture::Unavailable(reason) => {
+                eprintln!("skipping test without live capture: {}", reason);
+                return $ret;
+            }
+        }
+    };
+}
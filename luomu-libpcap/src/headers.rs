@@ -0,0 +1,351 @@
+use crate::{LinkType, OwnedPacket, Packet, PacketSource, Result};
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+
+/// Layers of a packet.
+///
+/// Returned by `Packet::layers()`. Splits a packet into link-layer, network
+/// layer and transport layer headers and the payload after them.
+///
+/// Headers of Ethernet (with VLAN tags), Linux cooked capture, BSD loopback
+/// and raw IP links, IPv4 and IPv6 (with extension headers), TCP, UDP, ICMP
+/// and ICMPv6 are recognized. Parsing stops at the first unrecognized or
+/// truncated header, and the rest of the packet is the payload.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layers<'a> {
+    packet: &'a [u8],
+    network: Option<usize>,
+    transport: Option<usize>,
+    payload: usize,
+    protocol: Option<u8>,
+}
+
+impl<'a> Layers<'a> {
+    /// Split `packet` with link-layer header type `linktype` into layers.
+    pub fn new(linktype: LinkType, packet: &'a [u8]) -> Layers<'a> {
+        let mut layers = Layers {
+            packet,
+            network: None,
+            transport: None,
+            payload: 0,
+            protocol: None,
+        };
+        let (link_len, ethertype) = match link_header(linktype, packet) {
+            Some(link) => link,
+            None => return layers,
+        };
+        layers.payload = link_len;
+        let network = &packet[link_len..];
+        let network_len = match ethertype {
+            Some(ETHERTYPE_IPV4) => ipv4_header(network),
+            Some(ETHERTYPE_IPV6) => ipv6_header(network),
+            // Raw IP, version is in the first nibble.
+            None => match network.first().map(|b| b >> 4) {
+                Some(4) => ipv4_header(network),
+                Some(6) => ipv6_header(network),
+                _ => None,
+            },
+            _ => None,
+        };
+        let (network_len, protocol) = match network_len {
+            Some(network) => network,
+            None => return layers,
+        };
+        layers.network = Some(link_len);
+        layers.payload = link_len + network_len;
+        let transport = &network[network_len..];
+        let transport_len = match protocol {
+            // TCP
+            6 => transport.get(12).map_or(transport.len(), |offset| {
+                ((offset >> 4) as usize * 4).max(20)
+            }),
+            // UDP, ICMP and ICMPv6
+            17 | 1 | 58 => 8,
+            _ => return layers,
+        };
+        layers.protocol = Some(protocol);
+        layers.transport = Some(layers.payload);
+        layers.payload += transport_len.min(transport.len());
+        layers
+    }
+
+    /// Link-layer header. Empty if the link-layer header type is not
+    /// recognized.
+    pub fn link(&self) -> &'a [u8] {
+        &self.packet[..self.network.unwrap_or(self.payload)]
+    }
+
+    /// Network layer header, if recognized.
+    pub fn network(&self) -> Option<&'a [u8]> {
+        let end = self.transport.unwrap_or(self.payload);
+        self.network.map(|start| &self.packet[start..end])
+    }
+
+    /// Transport layer header, if recognized.
+    pub fn transport(&self) -> Option<&'a [u8]> {
+        self.transport
+            .map(|start| &self.packet[start..self.payload])
+    }
+
+    /// Payload after the recognized headers.
+    pub fn payload(&self) -> &'a [u8] {
+        &self.packet[self.payload..]
+    }
+
+    /// Offset of the network layer header, if recognized.
+    pub fn network_offset(&self) -> Option<usize> {
+        self.network
+    }
+
+    /// Offset of the transport layer header, if recognized.
+    pub fn transport_offset(&self) -> Option<usize> {
+        self.transport
+    }
+
+    /// Offset of the payload, which is the length of the recognized headers.
+    pub fn payload_offset(&self) -> usize {
+        self.payload
+    }
+
+    /// IP protocol number of the transport layer, if recognized.
+    pub fn protocol(&self) -> Option<u8> {
+        self.protocol
+    }
+}
+
+/// Length of the link-layer, network and transport layer headers of a packet
+/// with link-layer header type `linktype`. See [Layers] for the recognized
+/// headers.
+pub fn header_len(linktype: LinkType, packet: &[u8]) -> usize {
+    Layers::new(linktype, packet).payload_offset()
+}
+
+/// Offset of the IPv4 or IPv6 header in a packet with link-layer header type
+/// `linktype`, or `None` if the packet is not an IP packet.
+///
+/// On raw IP links, such as tun devices, packets start with the IP header and
+/// the offset is 0. On other links the IP header follows the link-layer
+/// header, which is recognized as described in [Layers]. The IP header itself
+/// is not checked beyond its version.
+pub fn ip_offset(linktype: LinkType, packet: &[u8]) -> Option<usize> {
+    let (offset, ethertype) = link_header(linktype, packet)?;
+    let version = *packet.get(offset)? >> 4;
+    match ethertype {
+        Some(ETHERTYPE_IPV4) if version == 4 => Some(offset),
+        Some(ETHERTYPE_IPV6) if version == 6 => Some(offset),
+        None if version == 4 || version == 6 => Some(offset),
+        _ => None,
+    }
+}
+
+// Length of link-layer header and the EtherType of the network layer, or
+// `None` for raw IP.
+pub(crate) fn link_header(linktype: LinkType, packet: &[u8]) -> Option<(usize, Option<u16>)> {
+    let be16 = |offset: usize| {
+        packet
+            .get(offset..offset + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+    };
+    match linktype {
+        LinkType::ETHERNET => {
+            let mut offset = 12;
+            let mut ethertype = be16(offset)?;
+            while ethertype == ETHERTYPE_VLAN || ethertype == ETHERTYPE_QINQ {
+                offset += 4;
+                ethertype = be16(offset)?;
+            }
+            Some((offset + 2, Some(ethertype)))
+        }
+        LinkType::LINUX_SLL => Some((16, Some(be16(14)?))),
+        LinkType::LINUX_SLL2 => Some((20, Some(be16(0)?))),
+        LinkType::NULL | LinkType::LOOP if packet.len() >= 4 => Some((4, None)),
+        LinkType::IPV4 => Some((0, Some(ETHERTYPE_IPV4))),
+        LinkType::IPV6 => Some((0, Some(ETHERTYPE_IPV6))),
+        _ if linktype.is_raw_ip() => Some((0, None)),
+        _ => None,
+    }
+    .filter(|(len, _)| *len <= packet.len())
+}
+
+// Length of IPv4 header and the protocol of the payload.
+fn ipv4_header(packet: &[u8]) -> Option<(usize, u8)> {
+    let len = (*packet.first()? & 0x0f) as usize * 4;
+    let protocol = *packet.get(9)?;
+    // Only the first fragment has the transport header.
+    let fragment_offset = u16::from_be_bytes([*packet.get(6)?, *packet.get(7)?]) & 0x1fff;
+    if len < 20 || len > packet.len() {
+        return None;
+    }
+    Some((len, if fragment_offset == 0 { protocol } else { 0 }))
+}
+
+// Length of IPv6 header with extension headers and the protocol of the
+// payload.
+fn ipv6_header(packet: &[u8]) -> Option<(usize, u8)> {
+    let mut next = *packet.get(6)?;
+    let mut len = 40;
+    if len > packet.len() {
+        return None;
+    }
+    loop {
+        let header = &packet[len..];
+        let header_len = match next {
+            // Hop-by-hop, routing and destination options.
+            0 | 43 | 60 => (*header.get(1)? as usize + 1) * 8,
+            // Fragment
+            44 => {
+                let offset = u16::from_be_bytes([*header.get(2)?, *header.get(3)?]) >> 3;
+                if offset != 0 {
+                    return Some((len + 8, 0));
+                }
+                8
+            }
+            _ => return Some((len, next)),
+        };
+        next = *header.first()?;
+        if header_len > header.len() {
+            return None;
+        }
+        len += header_len;
+    }
+}
+
+/// Adapter truncating packets of a [PacketSource] to their headers.
+///
+/// Each packet is cut after its transport layer header, as computed by
+/// [header_len()], so that captures have only the metadata of the traffic.
+/// Such captures are much smaller than full captures and don't have the
+/// payloads, which may have sensitive data. The length of the packet on the
+/// wire is kept.
+#[derive(Debug)]
+pub struct HeadersOnly<S> {
+    source: S,
+    linktype: LinkType,
+}
+
+impl<S: PacketSource> HeadersOnly<S> {
+    /// Construct a new `HeadersOnly` over `source` with link-layer header
+    /// type `linktype`.
+    pub fn new(source: S, linktype: LinkType) -> HeadersOnly<S> {
+        HeadersOnly { source, linktype }
+    }
+
+    /// Get a reference to the underlying source.
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Get the underlying source back.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S: PacketSource> PacketSource for HeadersOnly<S> {
+    type Packet = OwnedPacket;
+
+    fn next_packet(&mut self) -> Result<OwnedPacket> {
+        let packet = self.source.next_packet()?;
+        let len = header_len(self.linktype, packet.packet());
+        let mut owned = OwnedPacket::new(*packet.pkthdr(), packet.packet()[..len].to_vec());
+        owned.truncate(len);
+        Ok(owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{header_len, ip_offset, Layers};
+    use crate::LinkType;
+
+    #[test]
+    fn test_header_len() {
+        // Ethernet with a VLAN tag, IPv4 and TCP with options, and payload.
+        let mut packet = vec![0u8; 12];
+        packet.extend_from_slice(&[0x81, 0x00, 0x00, 0x01, 0x08, 0x00]);
+        let mut ipv4 = vec![0u8; 20];
+        ipv4[0] = 0x45;
+        ipv4[9] = 6;
+        packet.extend_from_slice(&ipv4);
+        let mut tcp = vec![0u8; 24];
+        tcp[12] = 6 << 4;
+        packet.extend_from_slice(&tcp);
+        packet.extend_from_slice(b"payload");
+        assert_eq!(header_len(LinkType::ETHERNET, &packet), 18 + 20 + 24);
+
+        // Raw IPv6 with hop-by-hop options and UDP.
+        let mut packet = vec![0u8; 40];
+        packet[0] = 0x60;
+        packet[6] = 0;
+        packet.extend_from_slice(&[17, 0, 0, 0, 0, 0, 0, 0]);
+        packet.extend_from_slice(&[0u8; 8]);
+        packet.extend_from_slice(b"payload");
+        assert_eq!(header_len(LinkType::RAW, &packet), 40 + 8 + 8);
+
+        // Unknown EtherType keeps only the link-layer header.
+        let mut packet = vec![0u8; 12];
+        packet.extend_from_slice(&[0x88, 0xcc, 1, 2, 3]);
+        assert_eq!(header_len(LinkType::ETHERNET, &packet), 14);
+
+        // Truncated TCP header is kept as is.
+        let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 0, 6];
+        packet.resize(20 + 10, 0);
+        assert_eq!(header_len(LinkType::RAW, &packet), 30);
+    }
+
+    #[test]
+    fn test_ip_offset() {
+        let mut ipv6 = vec![0u8; 40];
+        ipv6[0] = 0x60;
+        assert_eq!(ip_offset(LinkType::RAW, &ipv6), Some(0));
+        assert_eq!(ip_offset(LinkType::IPV6, &ipv6), Some(0));
+        assert_eq!(ip_offset(LinkType::IPV4, &ipv6), None);
+        assert_eq!(ip_offset(LinkType::RAW, &[0x10, 0, 0, 0]), None);
+        // DLT_RAW only on OpenBSD
+        let expected = if cfg!(target_os = "openbsd") {
+            Some(0)
+        } else {
+            None
+        };
+        assert_eq!(ip_offset(LinkType(14), &ipv6), expected);
+
+        // BSD loopback
+        let mut packet = vec![2, 0, 0, 0];
+        packet.extend_from_slice(&ipv6);
+        assert_eq!(ip_offset(LinkType::NULL, &packet), Some(4));
+
+        // Ethernet with ARP
+        let mut packet = vec![0u8; 12];
+        packet.extend_from_slice(&[0x08, 0x06, 0x45]);
+        assert_eq!(ip_offset(LinkType::ETHERNET, &packet), None);
+    }
+
+    #[test]
+    fn test_layers() {
+        // Linux cooked capture, IPv4 and UDP, and payload.
+        let mut packet = vec![0u8; 14];
+        packet.extend_from_slice(&[0x08, 0x00]);
+        let mut ipv4 = vec![0u8; 20];
+        ipv4[0] = 0x45;
+        ipv4[9] = 17;
+        packet.extend_from_slice(&ipv4);
+        packet.extend_from_slice(&[0u8; 8]);
+        packet.extend_from_slice(b"payload");
+
+        let layers = Layers::new(LinkType::LINUX_SLL, &packet);
+        assert_eq!(layers.link().len(), 16);
+        assert_eq!(layers.network(), Some(&packet[16..36]));
+        assert_eq!(layers.transport_offset(), Some(36));
+        assert_eq!(layers.transport().map(|t| t.len()), Some(8));
+        assert_eq!(layers.payload(), b"payload");
+        assert_eq!(layers.protocol(), Some(17));
+
+        let layers = Layers::new(LinkType::IEEE802_11, &packet);
+        assert!(layers.link().is_empty());
+        assert_eq!(layers.network(), None);
+        assert_eq!(layers.payload(), &packet[..]);
+    }
+}
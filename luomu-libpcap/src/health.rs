@@ -0,0 +1,53 @@
+use std::cell::Cell;
+use std::time::Instant;
+
+/// Health of a live capture. See `Pcap::health()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Health {
+    /// Packets are arriving and none were dropped since the last check.
+    Healthy,
+    /// No packet has been read within the stall threshold, or packets were
+    /// dropped since the last check. The capture works but may need
+    /// attention, like a faster reader or a look at its filter.
+    Stalled,
+    /// Reading from the capture has failed or its statistics can't be read,
+    /// for example because the interface was removed. The capture should be
+    /// opened again.
+    Dead,
+}
+
+// Reading activity of a capture handle, updated by the functions reading
+// packets.
+#[derive(Debug, Default)]
+pub(crate) struct Activity {
+    last_packet: Cell<Option<Instant>>,
+    packets: Cell<u64>,
+    failed: Cell<bool>,
+}
+
+impl Activity {
+    pub(crate) fn packet(&self) {
+        self.packets(1);
+    }
+
+    pub(crate) fn packets(&self, n: u64) {
+        self.last_packet.set(Some(Instant::now()));
+        self.packets.set(self.packets.get() + n);
+    }
+
+    pub(crate) fn packets_read(&self) -> u64 {
+        self.packets.get()
+    }
+
+    pub(crate) fn fail(&self) {
+        self.failed.set(true);
+    }
+
+    pub(crate) fn last_packet(&self) -> Option<Instant> {
+        self.last_packet.get()
+    }
+
+    pub(crate) fn has_failed(&self) -> bool {
+        self.failed.get()
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+use crate::PcapStat;
+
+/// Statistics of a capture at one point in time. See [StatsHistory].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StatsSample {
+    /// Time the statistics were read.
+    pub time: SystemTime,
+    /// Packets received as reported by libpcap.
+    pub received: u64,
+    /// Packets dropped by the operating system as reported by libpcap.
+    pub dropped: u64,
+    /// Packets dropped by the network interface or its driver as reported by
+    /// libpcap.
+    pub dropped_interface: u64,
+}
+
+impl StatsSample {
+    /// Sample of `stats` read at `time`.
+    pub fn new(time: SystemTime, stats: &PcapStat) -> StatsSample {
+        StatsSample {
+            time,
+            received: stats.packets_received().into(),
+            dropped: stats.packets_dropped().into(),
+            dropped_interface: stats.packets_dropped_interface().into(),
+        }
+    }
+}
+
+/// Ring of the latest statistics samples of a capture.
+///
+/// Keeps the last `capacity` samples in memory, the oldest sample being
+/// replaced by the newest when full, so recent trends in drops can be shown
+/// e.g. on a debugging endpoint without storing statistics elsewhere.
+/// Sessions of [SessionManager](crate::SessionManager) keep one when enabled
+/// with `SessionManager::set_stats_history()`.
+#[derive(Clone, Debug, Default)]
+pub struct StatsHistory {
+    capacity: usize,
+    samples: VecDeque<StatsSample>,
+}
+
+impl StatsHistory {
+    /// Construct a new empty `StatsHistory` keeping `capacity` samples. With
+    /// zero capacity no samples are kept.
+    pub fn new(capacity: usize) -> StatsHistory {
+        StatsHistory {
+            capacity,
+            samples: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Add a sample of `stats` read now.
+    pub fn record(&mut self, stats: &PcapStat) {
+        self.push(StatsSample::new(SystemTime::now(), stats));
+    }
+
+    /// Add a sample, dropping the oldest one if the history is full.
+    pub fn push(&mut self, sample: StatsSample) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// The samples from the oldest to the newest.
+    pub fn samples(&self) -> impl Iterator<Item = &StatsSample> {
+        self.samples.iter()
+    }
+
+    /// Number of samples kept.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns true if there are no samples.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Maximum number of samples kept.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Packets dropped between the oldest and the newest sample, by the
+    /// operating system and by the interface.
+    pub fn dropped(&self) -> u64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some(first), Some(last)) => {
+                let total = |s: &StatsSample| s.dropped + s.dropped_interface;
+                total(last).saturating_sub(total(first))
+            }
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::{StatsHistory, StatsSample};
+
+    fn sample(dropped: u64) -> StatsSample {
+        StatsSample {
+            time: SystemTime::UNIX_EPOCH,
+            received: 1000,
+            dropped,
+            dropped_interface: 1,
+        }
+    }
+
+    #[test]
+    fn test_stats_history() {
+        let mut history = StatsHistory::new(3);
+        assert!(history.is_empty());
+        assert_eq!(history.dropped(), 0);
+        for dropped in [0, 5, 7, 20] {
+            history.push(sample(dropped));
+        }
+        assert_eq!(history.len(), 3);
+        let dropped: Vec<u64> = history.samples().map(|s| s.dropped).collect();
+        assert_eq!(dropped, vec![5, 7, 20]);
+        assert_eq!(history.dropped(), 15);
+
+        let mut disabled = StatsHistory::new(0);
+        disabled.push(sample(1));
+        assert!(disabled.is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::functions::{pcap_compile_nopcap, pcap_datalink, pcap_snapshot};
+use crate::{Packet, PcapFilter, PcapT, Result};
+
+struct Shared {
+    linktype: i32,
+    snaplen: usize,
+    filter: RwLock<Option<Arc<(String, PcapFilter)>>>,
+    generation: AtomicU64,
+}
+
+/// Filter which can be replaced while a capture is running.
+///
+/// The capture thread checks each packet with `FilterHandle::matches()` and
+/// other threads replace the filter with `FilterHandle::set()` through clones
+/// of the handle. Filtering is done in userspace with `pcap_offline_filter()`,
+/// so the replacement is atomic: each packet is evaluated by exactly one of
+/// the old or new filter. Replacing the filter installed in the kernel with
+/// `pcap_setfilter()` can't guarantee that, as libpcap filters packets
+/// already buffered again with the new filter.
+///
+/// The filter is applied on top of the filter set on the capture, if any.
+/// A handle without a filter matches all packets.
+#[derive(Clone)]
+pub struct FilterHandle {
+    inner: Arc<Shared>,
+}
+
+impl FilterHandle {
+    /// Construct a handle for filtering packets captured with `pcap_t`.
+    ///
+    /// Filters are compiled for the link-layer header type and snapshot
+    /// length of `pcap_t`.
+    pub fn new(pcap_t: &PcapT, filter: Option<&str>) -> Result<FilterHandle> {
+        let handle = FilterHandle::for_linktype(pcap_datalink(pcap_t)?, pcap_snapshot(pcap_t)?);
+        if let Some(filter) = filter {
+            handle.set(filter)?;
+        }
+        Ok(handle)
+    }
+
+    fn for_linktype(linktype: i32, snaplen: usize) -> FilterHandle {
+        FilterHandle {
+            inner: Arc::new(Shared {
+                linktype,
+                snaplen,
+                filter: RwLock::new(None),
+                generation: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Replace the filter.
+    ///
+    /// The filter is compiled before the replacement, so on error the old
+    /// filter stays in effect.
+    pub fn set(&self, filter: &str) -> Result<()> {
+        let compiled = pcap_compile_nopcap(self.inner.snaplen, self.inner.linktype, filter)?;
+        self.replace(Some(Arc::new((filter.to_string(), compiled))));
+        log::debug!("filter replaced with \"{}\"", filter);
+        Ok(())
+    }
+
+    /// Remove the filter, so that all packets match.
+    pub fn clear(&self) {
+        self.replace(None);
+    }
+
+    /// Expression of the filter in effect.
+    pub fn filter(&self) -> Option<String> {
+        self.current().map(|filter| filter.0.clone())
+    }
+
+    /// Number of times the filter has been replaced or removed.
+    pub fn generation(&self) -> u64 {
+        self.inner.generation.load(Ordering::SeqCst)
+    }
+
+    /// Check a packet against the filter in effect.
+    pub fn matches<P: Packet>(&self, packet: &P) -> bool {
+        match self.current() {
+            Some(filter) => filter.1.matches(packet),
+            None => true,
+        }
+    }
+
+    fn current(&self) -> Option<Arc<(String, PcapFilter)>> {
+        match self.inner.filter.read() {
+            Ok(filter) => filter.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    fn replace(&self, filter: Option<Arc<(String, PcapFilter)>>) {
+        let mut current = match self.inner.filter.write() {
+            Ok(current) => current,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *current = filter;
+        self.inner.generation.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl std::fmt::Debug for FilterHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FilterHandle")
+            .field("filter", &self.filter())
+            .field("generation", &self.generation())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use luomu_libpcap_sys as libpcap;
+
+    use super::FilterHandle;
+    use crate::{OwnedPacket, PcapFilter};
+
+    // Filter program accepting no packets.
+    fn reject_all() -> PcapFilter {
+        PcapFilter::from_insns(&[libpcap::bpf_insn {
+            code: 0x06,
+            jt: 0,
+            jf: 0,
+            k: 0,
+        }])
+    }
+
+    #[test]
+    fn test_filter_handle_swap() {
+        let packet = OwnedPacket::new(
+            libpcap::pcap_pkthdr {
+                ts: libc::timeval {
+                    tv_sec: 0,
+                    tv_usec: 0,
+                },
+                caplen: 1,
+                len: 1,
+            },
+            vec![0],
+        );
+        let handle = FilterHandle::for_linktype(1, 65535);
+        let clone = handle.clone();
+        assert_eq!(handle.filter(), None);
+        assert_eq!(handle.generation(), 0);
+        assert!(handle.matches(&packet));
+
+        // Replacing through a clone is seen by all clones.
+        clone.replace(Some(Arc::new(("none".to_string(), reject_all()))));
+        assert_eq!(handle.filter().as_deref(), Some("none"));
+        assert_eq!(handle.generation(), 1);
+        assert!(!handle.matches(&packet));
+
+        handle.clear();
+        assert_eq!(clone.filter(), None);
+        assert_eq!(clone.generation(), 2);
+        assert!(clone.matches(&packet));
+    }
+
+    #[test]
+    fn test_filter_handle_concurrent_swap() {
+        let handle = FilterHandle::for_linktype(1, 65535);
+        let swapper = {
+            let handle = handle.clone();
+            std::thread::spawn(move || {
+                for n in 0..100 {
+                    let expr = format!("filter {}", n);
+                    handle.replace(Some(Arc::new((expr, reject_all()))));
+                }
+            })
+        };
+        let mut last = 0;
+        while last < 100 {
+            // The generation only grows and the filter is always a whole one.
+            let generation = handle.generation();
+            assert!(generation >= last);
+            last = generation;
+            if let Some(filter) = handle.filter() {
+                assert!(filter.starts_with("filter "));
+            }
+        }
+        swapper.join().unwrap();
+        assert_eq!(handle.filter().as_deref(), Some("filter 99"));
+    }
+}
@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use crate::{PcapT, Result};
+
+/// Adaptive waiting strategy for idle captures.
+///
+/// Retrying `pcap_next_ex()` immediately after a packet buffer timeout keeps
+/// a CPU core busy on a quiet interface. `IdleBackoff` instead waits for the
+/// capture's file descriptor to become readable with `poll()`. The wait time
+/// starts from `min` and is doubled on each consecutive idle wait up to `max`,
+/// so latency stays low when traffic resumes while CPU usage of an idle
+/// capture drops to near zero.
+///
+/// Call `IdleBackoff::wait()` when no packet was available and
+/// `IdleBackoff::reset()` when a packet was received.
+#[derive(Clone, Debug)]
+pub struct IdleBackoff {
+    min: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl Default for IdleBackoff {
+    /// Backoff from 1 millisecond up to 100 milliseconds.
+    fn default() -> Self {
+        IdleBackoff::new(Duration::from_millis(1), Duration::from_millis(100))
+    }
+}
+
+impl IdleBackoff {
+    /// Construct a new `IdleBackoff` waiting from `min` up to `max`.
+    pub fn new(min: Duration, max: Duration) -> IdleBackoff {
+        let min = min.max(Duration::from_millis(1));
+        IdleBackoff {
+            min,
+            max: max.max(min),
+            current: min,
+        }
+    }
+
+    /// Time the next call of `wait()` waits at most.
+    pub fn current(&self) -> Duration {
+        self.current
+    }
+
+    /// Reset the wait time back to the minimum. Call when packets arrive.
+    pub fn reset(&mut self) {
+        self.current = self.min;
+    }
+
+    /// Wait until the capture has packets to read or the current wait time
+    /// passes, and grow the wait time.
+    ///
+    /// Returns true if the capture became readable. If the capture has no
+    /// selectable file descriptor, or event handle on Windows, this just
+    /// sleeps.
+    pub fn wait(&mut self, pcap_t: &PcapT) -> Result<bool> {
+        let timeout = self.grow();
+        wait_readable(pcap_t, timeout)
+    }
+
+    // Return current wait time and double it for the next wait.
+    fn grow(&mut self) -> Duration {
+        let timeout = self.current;
+        self.current = (self.current * 2).min(self.max);
+        timeout
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn wait_readable(pcap_t: &PcapT, timeout: Duration) -> Result<bool> {
+    use crate::functions::{pcap_get_required_select_timeout, pcap_get_selectable_fd};
+
+    let fd = match pcap_get_selectable_fd(pcap_t) {
+        Some(fd) => fd,
+        None => {
+            std::thread::sleep(timeout);
+            return Ok(false);
+        }
+    };
+
+    // Some devices need to be polled with a timeout even though they have a
+    // selectable descriptor.
+    let timeout = match pcap_get_required_select_timeout(pcap_t) {
+        Some(required) => timeout.min(required),
+        None => timeout,
+    };
+
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let to_ms = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+    let ret = unsafe { libc::poll(&mut pollfd, 1, to_ms) };
+    if ret < 0 {
+        let err = std::io::Error::last_os_error();
+        if err.kind() == std::io::ErrorKind::Interrupted {
+            return Ok(false);
+        }
+        return Err(err.into());
+    }
+    Ok(ret > 0)
+}
+
+#[cfg(windows)]
+pub(crate) fn wait_readable(pcap_t: &PcapT, timeout: Duration) -> Result<bool> {
+    const WAIT_OBJECT_0: u32 = 0;
+    const WAIT_FAILED: u32 = 0xffff_ffff;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn WaitForSingleObject(handle: *mut std::ffi::c_void, millis: u32) -> u32;
+    }
+
+    let event = match crate::functions::pcap_getevent(pcap_t) {
+        Some(event) => event,
+        None => {
+            std::thread::sleep(timeout);
+            return Ok(false);
+        }
+    };
+    // INFINITE is u32::MAX, stay below it.
+    let to_ms = timeout.as_millis().min(u32::MAX as u128 - 1) as u32;
+    match unsafe { WaitForSingleObject(event, to_ms) } {
+        WAIT_OBJECT_0 => Ok(true),
+        WAIT_FAILED => Err(std::io::Error::last_os_error().into()),
+        _ => Ok(false),
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn wait_readable(_pcap_t: &PcapT, timeout: Duration) -> Result<bool> {
+    std::thread::sleep(timeout);
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::IdleBackoff;
+
+    #[test]
+    fn test_idle_backoff_limits() {
+        let backoff = IdleBackoff::new(Duration::ZERO, Duration::ZERO);
+        assert_eq!(backoff.current(), Duration::from_millis(1));
+
+        let mut backoff = IdleBackoff::new(Duration::from_millis(2), Duration::from_millis(5));
+        assert_eq!(backoff.grow(), Duration::from_millis(2));
+        assert_eq!(backoff.grow(), Duration::from_millis(4));
+        assert_eq!(backoff.grow(), Duration::from_millis(5));
+        assert_eq!(backoff.current(), Duration::from_millis(5));
+        backoff.reset();
+        assert_eq!(backoff.current(), Duration::from_millis(2));
+    }
+}
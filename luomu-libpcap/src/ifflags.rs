@@ -0,0 +1,152 @@
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign};
+
+use luomu_libpcap_sys as libpcap;
+
+use crate::InterfaceFlag;
+
+/// Flags of a network interface as reported by libpcap.
+///
+/// Keeps the flag word of `pcap_if_t` as it is, so flags added by newer
+/// libpcap versions without an [InterfaceFlag] are not lost, and checking a
+/// flag is a bit operation. Flags are combined with `|`, like
+/// `InterfaceFlags::UP | InterfaceFlags::RUNNING`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InterfaceFlags(u32);
+
+impl InterfaceFlags {
+    /// Interface is a loopback interface (`PCAP_IF_LOOPBACK`).
+    pub const LOOPBACK: InterfaceFlags = InterfaceFlags(libpcap::PCAP_IF_LOOPBACK);
+    /// Interface is up (`PCAP_IF_UP`).
+    pub const UP: InterfaceFlags = InterfaceFlags(libpcap::PCAP_IF_UP);
+    /// Interface is running (`PCAP_IF_RUNNING`).
+    pub const RUNNING: InterfaceFlags = InterfaceFlags(libpcap::PCAP_IF_RUNNING);
+    /// Interface is wireless (`PCAP_IF_WIRELESS`).
+    pub const WIRELESS: InterfaceFlags = InterfaceFlags(libpcap::PCAP_IF_WIRELESS);
+    /// Bits of the connection status, see `connection_status()`
+    /// (`PCAP_IF_CONNECTION_STATUS`).
+    pub const CONNECTION_STATUS: InterfaceFlags =
+        InterfaceFlags(libpcap::PCAP_IF_CONNECTION_STATUS);
+
+    /// Flags from the raw flag word `bits`, keeping unknown bits.
+    pub const fn from_bits_retain(bits: u32) -> InterfaceFlags {
+        InterfaceFlags(bits)
+    }
+
+    /// The raw flag word.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// No flags set.
+    pub const fn empty() -> InterfaceFlags {
+        InterfaceFlags(0)
+    }
+
+    /// Returns true if no flags are set.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns true if all flags of `other` are set.
+    pub const fn contains(self, other: InterfaceFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Connection status of the interface, one of the
+    /// `PCAP_IF_CONNECTION_STATUS_*` values.
+    pub const fn connection_status(self) -> u32 {
+        self.0 & libpcap::PCAP_IF_CONNECTION_STATUS
+    }
+
+    /// The flags which have an [InterfaceFlag].
+    pub fn iter(self) -> impl Iterator<Item = InterfaceFlag> {
+        [
+            (InterfaceFlags::LOOPBACK, InterfaceFlag::Loopback),
+            (InterfaceFlags::UP, InterfaceFlag::Up),
+            (InterfaceFlags::RUNNING, InterfaceFlag::Running),
+        ]
+        .into_iter()
+        .filter(move |(bits, _)| self.contains(*bits))
+        .map(|(_, flag)| flag)
+    }
+}
+
+impl From<InterfaceFlag> for InterfaceFlags {
+    fn from(flag: InterfaceFlag) -> Self {
+        match flag {
+            InterfaceFlag::Loopback => InterfaceFlags::LOOPBACK,
+            InterfaceFlag::Up => InterfaceFlags::UP,
+            InterfaceFlag::Running => InterfaceFlags::RUNNING,
+        }
+    }
+}
+
+impl FromIterator<InterfaceFlag> for InterfaceFlags {
+    fn from_iter<I: IntoIterator<Item = InterfaceFlag>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(InterfaceFlags::empty(), |flags, flag| flags | flag.into())
+    }
+}
+
+impl BitOr for InterfaceFlags {
+    type Output = InterfaceFlags;
+
+    fn bitor(self, other: InterfaceFlags) -> InterfaceFlags {
+        InterfaceFlags(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for InterfaceFlags {
+    fn bitor_assign(&mut self, other: InterfaceFlags) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitAnd for InterfaceFlags {
+    type Output = InterfaceFlags;
+
+    fn bitand(self, other: InterfaceFlags) -> InterfaceFlags {
+        InterfaceFlags(self.0 & other.0)
+    }
+}
+
+impl fmt::Debug for InterfaceFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "InterfaceFlags({:#x})", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InterfaceFlags;
+    use crate::InterfaceFlag;
+    use luomu_libpcap_sys as libpcap;
+
+    #[test]
+    fn test_interface_flags() {
+        let flags = InterfaceFlags::from_bits_retain(
+            libpcap::PCAP_IF_UP
+                | libpcap::PCAP_IF_RUNNING
+                | libpcap::PCAP_IF_CONNECTION_STATUS_CONNECTED
+                | 0x100,
+        );
+        assert!(flags.contains(InterfaceFlags::UP | InterfaceFlags::RUNNING));
+        assert!(!flags.contains(InterfaceFlags::LOOPBACK));
+        assert_eq!(
+            flags.connection_status(),
+            libpcap::PCAP_IF_CONNECTION_STATUS_CONNECTED
+        );
+        assert_eq!(flags.bits() & 0x100, 0x100);
+        assert_eq!(
+            flags.iter().collect::<Vec<_>>(),
+            vec![InterfaceFlag::Up, InterfaceFlag::Running]
+        );
+
+        let known: InterfaceFlags = [InterfaceFlag::Loopback, InterfaceFlag::Up]
+            .into_iter()
+            .collect();
+        assert_eq!(known, InterfaceFlags::LOOPBACK | InterfaceFlags::UP);
+        assert!(InterfaceFlags::empty().is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+use crate::{Direction, LinkType, Packet};
+
+// LINKTYPE_PKTAP of savefiles. Apple's libpcap uses DLT_PKTAP 149 for live
+// captures instead.
+const LINKTYPE_PKTAP: i32 = 258;
+#[cfg(target_os = "macos")]
+const DLT_PKTAP: i32 = 149;
+
+// Length of the interface name in PKTAP header and the direction flags.
+const PKTAP_IFXNAMESIZE: usize = 24;
+const PTH_FLAG_DIR_IN: u32 = 0x0001;
+const PTH_FLAG_DIR_OUT: u32 = 0x0002;
+
+// Packet type of Linux cooked capture headers for outgoing packets.
+const LINUX_SLL_OUTGOING: u16 = 4;
+
+/// Interface and direction of a captured packet.
+///
+/// Captures on the Linux "any" device or macOS PKTAP pseudo-interface have
+/// packets of many interfaces. Their link-layer headers tell which interface
+/// a packet was captured on and in which direction it was going:
+///
+/// * Linux cooked capture v1 (`LinkType::LINUX_SLL`) has the direction.
+/// * Linux cooked capture v2 (`LinkType::LINUX_SLL2`) has the interface index
+///   and direction.
+/// * PKTAP has the interface name and direction.
+///
+/// Interface information in pcapng Enhanced Packet Block options is not
+/// available through libpcap.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Ingress {
+    /// Index of the interface, if known.
+    pub interface_index: Option<u32>,
+    /// Name of the interface, if known.
+    pub interface: Option<String>,
+    /// Direction of the packet, `Direction::In` or `Direction::Out`, if
+    /// known.
+    pub direction: Option<Direction>,
+}
+
+impl Ingress {
+    /// Get ingress information of `packet` captured with link-layer header
+    /// type `linktype`. Returns `None` if the type has no such information or
+    /// the packet is too short.
+    pub fn from_packet<P: Packet>(linktype: LinkType, packet: &P) -> Option<Ingress> {
+        Ingress::from_bytes(linktype, packet.packet())
+    }
+
+    fn from_bytes(linktype: LinkType, packet: &[u8]) -> Option<Ingress> {
+        let be16 = |offset: usize| {
+            Some(u16::from_be_bytes(
+                packet.get(offset..offset + 2)?.try_into().ok()?,
+            ))
+        };
+        let direction = |pkttype: u16| match pkttype {
+            LINUX_SLL_OUTGOING => Direction::Out,
+            _ => Direction::In,
+        };
+        match linktype {
+            LinkType::LINUX_SLL if packet.len() >= 16 => Some(Ingress {
+                direction: Some(direction(be16(0)?)),
+                ..Ingress::default()
+            }),
+            LinkType::LINUX_SLL2 if packet.len() >= 20 => Some(Ingress {
+                interface_index: Some(u32::from_be_bytes(packet[4..8].try_into().ok()?)),
+                direction: Some(direction(packet[10] as u16)),
+                ..Ingress::default()
+            }),
+            LinkType(dlt) if is_pktap(dlt) => pktap(packet),
+            _ => None,
+        }
+    }
+
+    /// Name of the interface.
+    ///
+    /// If only the index of the interface is known, the name is looked up
+    /// from interfaces of this host, which is meaningful only for live
+    /// captures.
+    pub fn interface_name(&self) -> Option<String> {
+        if let Some(name) = &self.interface {
+            return Some(name.clone());
+        }
+        index_to_name(self.interface_index?)
+    }
+}
+
+fn is_pktap(dlt: i32) -> bool {
+    #[cfg(target_os = "macos")]
+    if dlt == DLT_PKTAP {
+        return true;
+    }
+    dlt == LINKTYPE_PKTAP
+}
+
+// PKTAP header is in host byte order of the capturing host, which is always
+// little-endian for Apple's hardware.
+fn pktap(packet: &[u8]) -> Option<Ingress> {
+    let le32 = |offset: usize| {
+        Some(u32::from_le_bytes(
+            packet.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    let name = packet.get(12..12 + PKTAP_IFXNAMESIZE)?;
+    let name = &name[..name.iter().position(|b| *b == 0).unwrap_or(name.len())];
+    let flags = le32(36)?;
+    let direction = if flags & PTH_FLAG_DIR_OUT != 0 {
+        Some(Direction::Out)
+    } else if flags & PTH_FLAG_DIR_IN != 0 {
+        Some(Direction::In)
+    } else {
+        None
+    };
+    Some(Ingress {
+        interface_index: None,
+        interface: Some(String::from_utf8_lossy(name).into_owned()).filter(|n| !n.is_empty()),
+        direction,
+    })
+}
+
+#[cfg(unix)]
+fn index_to_name(index: u32) -> Option<String> {
+    let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+    let ret = unsafe { libc::if_indextoname(index, name.as_mut_ptr()) };
+    if ret.is_null() {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(name.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(not(unix))]
+fn index_to_name(_index: u32) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Ingress;
+    use crate::{Direction, LinkType};
+
+    #[test]
+    fn test_ingress_sll2() {
+        let mut packet = vec![0x08, 0x00, 0, 0, 0, 0, 0, 3, 0, 1, 4, 6];
+        packet.resize(20, 0);
+        let ingress = Ingress::from_bytes(LinkType::LINUX_SLL2, &packet).unwrap();
+        assert_eq!(ingress.interface_index, Some(3));
+        assert_eq!(ingress.direction, Some(Direction::Out));
+
+        packet[10] = 0;
+        let ingress = Ingress::from_bytes(LinkType::LINUX_SLL2, &packet).unwrap();
+        assert_eq!(ingress.direction, Some(Direction::In));
+        assert!(Ingress::from_bytes(LinkType::LINUX_SLL2, &packet[..10]).is_none());
+        assert!(Ingress::from_bytes(LinkType::ETHERNET, &packet).is_none());
+    }
+
+    #[test]
+    fn test_ingress_pktap() {
+        let mut packet = vec![0u8; 108];
+        packet[12..15].copy_from_slice(b"en0");
+        packet[36] = 0x01;
+        let ingress = Ingress::from_bytes(LinkType(258), &packet).unwrap();
+        assert_eq!(ingress.interface.as_deref(), Some("en0"));
+        assert_eq!(ingress.interface_name().as_deref(), Some("en0"));
+        assert_eq!(ingress.direction, Some(Direction::In));
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::OnceLock;
+
+use luomu_libpcap_sys as libpcap;
+
+use crate::functions::pcap_init;
+use crate::{Error, Result};
+
+/// Character encoding libpcap uses for strings passed to and returned from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CharEncoding {
+    /// Strings are in the local character encoding (`PCAP_CHAR_ENC_LOCAL`).
+    Local,
+    /// Strings are in UTF-8 (`PCAP_CHAR_ENC_UTF_8`).
+    Utf8,
+}
+
+impl CharEncoding {
+    fn opts(self) -> u32 {
+        match self {
+            CharEncoding::Local => libpcap::PCAP_CHAR_ENC_LOCAL,
+            CharEncoding::Utf8 => libpcap::PCAP_CHAR_ENC_UTF_8,
+        }
+    }
+}
+
+// Outcome of the one and only pcap_init() call.
+static INIT: OnceLock<std::result::Result<CharEncoding, String>> = OnceLock::new();
+
+/// Initialize libpcap with library-wide options.
+///
+/// libpcap is initialized exactly once per process. If this isn't called,
+/// initialization happens lazily with [CharEncoding::Utf8] when the first
+/// capture handle is created or devices are listed, as Rust strings are UTF-8.
+///
+/// Call this before using anything else in the crate to select the encoding.
+/// Calling again with the same encoding is a no-op, but an error is returned
+/// if libpcap has already been initialized with a different encoding.
+///
+/// With `libpcap-dlopen` feature libpcap is loaded at initialization. If it
+/// can't be loaded, or is older than 1.10.0 and lacks `pcap_init()`, this and
+/// all functions opening handles or listing devices return an error.
+///
+/// On Windows the initialization also initializes Winsock, which is needed
+/// for remote capture and for resolving host names in filters. This replaces
+/// the deprecated `pcap_wsockinit()`, so applications don't need to
+/// initialize Winsock themselves.
+pub fn init(encoding: CharEncoding) -> Result<()> {
+    match INIT.get_or_init(|| do_init(encoding)) {
+        Ok(enc) if *enc == encoding => Ok(()),
+        Ok(enc) => Err(Error::PcapError(format!(
+            "libpcap already initialized with {:?} encoding",
+            enc
+        ))),
+        Err(err) => Err(Error::PcapError(err.clone())),
+    }
+}
+
+/// Initialize libpcap with defaults unless it has already been initialized.
+///
+/// Called by all functions opening handles or listing devices, so that
+/// Winsock is initialized on Windows before libpcap uses sockets.
+pub(crate) fn ensure_init() -> Result<()> {
+    match INIT.get_or_init(|| do_init(CharEncoding::Utf8)) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(Error::PcapError(err.clone())),
+    }
+}
+
+fn do_init(encoding: CharEncoding) -> std::result::Result<CharEncoding, String> {
+    #[cfg(feature = "libpcap-dlopen")]
+    libpcap::load()?;
+    match pcap_init(encoding.opts()) {
+        Ok(()) => Ok(encoding),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ensure_init, init, CharEncoding};
+
+    #[test]
+    fn test_init_once() {
+        ensure_init().unwrap();
+        init(CharEncoding::Utf8).unwrap();
+        assert!(init(CharEncoding::Local).is_err());
+    }
+}
@@ -27,14 +27,17 @@
 //! You probably want to use the `Pcap` struct and other things from root of
 //! this crate.
 
+use std::cell::{Cell, RefCell};
 use std::collections::{BTreeSet, HashSet};
 use std::convert::TryFrom;
 use std::default;
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
 use std::ops::Deref;
 use std::path::Path;
 use std::result;
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use luomu_common::{Address, MacAddr};
 use luomu_libpcap_sys as libpcap;
@@ -43,14 +46,214 @@ pub mod functions;
 use functions::*;
 
 mod error;
-pub use error::Error;
+pub use error::{Error, FilterError};
 
 mod packet;
-pub use packet::{BorrowedPacket, OwnedPacket, Packet};
+pub use packet::{
+    BorrowedPacket, OwnedPacket, Packet, PacketHeader, SharedPacket, WithHeader, INLINE_PACKET_LEN,
+};
+
+pub mod export;
+
+pub mod generator;
+
+pub mod golden;
+
+mod config;
+pub use config::CaptureConfig;
+
+mod idle;
+pub use idle::IdleBackoff;
+
+mod autotune;
+pub use autotune::{BufferTuner, TuneAction};
+
+mod multiqueue;
+pub use multiqueue::{FlowHash, MultiQueue};
+
+mod source;
+pub use source::PacketSource;
+
+mod mock;
+pub use mock::MockSource;
+
+mod faults;
+pub use faults::{FaultCounts, FaultInjector, Faults};
+
+mod fixture;
+pub use fixture::FilterFixture;
+
+mod sampling;
+pub use sampling::{Sampler, Sampling};
+
+mod tolerant;
+pub use tolerant::Tolerant;
+
+mod summary;
+pub use summary::{CaptureSummary, SummaryGuard};
+
+mod quota;
+pub use quota::{Limited, Quota, QuotaSummary};
+
+mod headers;
+pub use headers::{header_len, ip_offset, HeadersOnly, Layers};
+
+mod ingress;
+pub use ingress::Ingress;
+
+mod loopback;
+pub use loopback::{loopback_device, loopback_frame, NPCAP_LOOPBACK};
+
+mod supervisor;
+pub use supervisor::{Supervisor, SupervisorEvent};
+
+mod template;
+pub use template::{FilterTemplate, FilterValue};
+
+mod filterexpr;
+pub use filterexpr::FilterExpr;
+
+mod savedfilter;
+pub use savedfilter::SavedFilter;
+
+mod filterreport;
+pub use filterreport::FilterReport;
+
+mod demux;
+pub use demux::Demux;
+
+mod hotswap;
+pub use hotswap::FilterHandle;
+
+mod session;
+pub use session::{PacketSink, SessionManager, SessionStats, SessionStatus};
+
+mod history;
+pub use history::{StatsHistory, StatsSample};
+
+mod trigger;
+pub use trigger::TriggerDumper;
+
+mod schedule;
+pub use schedule::{CaptureWindow, Schedule, ScheduledCapture};
+
+mod oneshot;
+pub use oneshot::{capture_n, read_file, sniff, FilePackets};
+
+mod record;
+pub use record::{capture_to_file, RecordHandle, RecordOptions};
+
+mod init;
+pub use init::{init, CharEncoding};
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    target_os = "netbsd"
+))]
+mod cfile;
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    target_os = "netbsd"
+))]
+mod bpf;
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    target_os = "netbsd"
+))]
+pub use bpf::BpfDevice;
+
+mod linktype;
+pub use linktype::LinkType;
+
+mod version;
+pub use version::{libpcap_available, libpcap_version, Capabilities};
+
+mod ifflags;
+pub use ifflags::InterfaceFlags;
+
+mod query;
+pub use query::InterfaceQuery;
+
+mod health;
+use health::Activity;
+pub use health::Health;
+
+mod watchdog;
+pub use watchdog::StallWatchdog;
+
+mod progress;
+pub use progress::Progress;
+
+mod observer;
+pub use observer::CaptureObserver;
+
+mod harness;
+pub use harness::{live_capture, LiveCapture};
+
+mod verify;
+pub use verify::{verify_capture, CaptureReport, Check};
 
 #[cfg(feature = "async-tokio")]
 pub mod tokio;
 
+#[cfg(feature = "smoltcp")]
+pub mod smoltcp;
+
+#[cfg(feature = "pcap-file")]
+pub mod pcap_file;
+
+#[cfg(feature = "proptest")]
+pub mod proptest;
+
+// Savefile chunks are read through C streams made by `cfile`.
+#[cfg(all(
+    feature = "rayon",
+    any(
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    )
+))]
+pub mod rayon;
+
+#[cfg(feature = "mmap")]
+pub mod mmap;
+
+#[cfg(feature = "signal")]
+pub mod shutdown;
+
+#[cfg(feature = "link")]
+pub mod link;
+
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+
+#[cfg(feature = "registry")]
+pub mod registry;
+
+mod savefile;
+
+/// Time without packets after which `Pcap::health()` reports a stall.
+const DEFAULT_STALL_THRESHOLD: Duration = Duration::from_secs(60);
+
 /// A `Result` wrapping luomu-libpcap's errors in `Err` side
 pub type Result<T> = result::Result<T, Error>;
 
@@ -60,6 +263,10 @@ pub struct PcapT {
     #[allow(dead_code)]
     errbuf: Vec<u8>,
     interface: Option<String>,
+    activity: Activity,
+    id: u64,
+    // Size of the savefile opened with `pcap_open_offline()`.
+    savefile_size: Option<u64>,
 }
 
 // I assume the pcap_t pointer is safe to move between threads, but it can only
@@ -68,6 +275,29 @@ pub struct PcapT {
 unsafe impl Send for PcapT {}
 
 impl PcapT {
+    // Identifier for a new handle.
+    fn next_id() -> u64 {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// get the identifier of the handle
+    ///
+    /// Returns a number identifying the handle in logs, unique within the
+    /// process. Unlike the `pcap_t` pointer, it is not reused when the handle
+    /// is closed.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// get the activation state
+    ///
+    /// Returns true if the handle is activated. Handles for savefiles and
+    /// compiling filters are always activated.
+    pub fn is_activated(&self) -> bool {
+        !matches!(pcap_datalink(self), Err(Error::NotActivated(_)))
+    }
+
     /// get interface name
     ///
     /// `get_interface` returns the interface name if known or "<unknown>".
@@ -89,11 +319,134 @@ impl PcapT {
     pub fn get_error(&self) -> Result<Error> {
         get_error(self)
     }
+
+    /// get raw `pcap_t` pointer
+    ///
+    /// Returns the underlying `pcap_t` pointer which can be used to call
+    /// libpcap functions not wrapped by this crate. The pointer is valid as
+    /// long as this `PcapT` is alive and must not be closed by the caller.
+    pub fn as_ptr(&self) -> *mut libpcap::pcap_t {
+        self.pcap_t
+    }
+
+    /// get the file descriptor for a live capture
+    ///
+    /// Returns the file descriptor from which captured packets are read, or
+    /// for offline handles the file descriptor of the savefile. `None` is
+    /// returned if there is no file descriptor.
+    pub fn fileno(&self) -> Option<libc::c_int> {
+        pcap_fileno(self)
+    }
+
+    /// get the standard I/O stream for a savefile being read
+    ///
+    /// Returns the `FILE` stream of a savefile opened with `Pcap::offline()`
+    /// or `None` for live captures. The stream is owned by libpcap and must
+    /// not be closed.
+    pub fn file(&self) -> Option<*mut libpcap::FILE> {
+        pcap_file(self)
+    }
+
+    /// get the progress of reading a savefile
+    ///
+    /// Returns how many bytes of the savefile have been read, of the size it
+    /// had when opened, and how many packets, so long reads can show their
+    /// progress. `None` is returned for live captures and savefiles not
+    /// opened from a regular file by name, like standard input.
+    ///
+    /// ```no_run
+    /// use std::time::Instant;
+    /// use luomu_libpcap::Pcap;
+    ///
+    /// let pcap = Pcap::offline("capture.pcap")?;
+    /// let start = Instant::now();
+    /// for (n, _packet) in pcap.capture().enumerate() {
+    ///     if n % 100_000 == 0 {
+    ///         if let Some(progress) = pcap.progress() {
+    ///             let eta = progress.eta(start.elapsed()).unwrap_or_default();
+    ///             eprintln!("{:.0}%, {:?} left", progress.fraction() * 100.0, eta);
+    ///         }
+    ///     }
+    /// }
+    /// # Ok::<(), luomu_libpcap::Error>(())
+    /// ```
+    pub fn progress(&self) -> Option<Progress> {
+        let total = self.savefile_size?;
+        let file = pcap_file(self)?;
+        let bytes = unsafe { libc::ftell(file) };
+        Some(Progress {
+            bytes: u64::try_from(bytes).ok()?,
+            total,
+            packets: self.activity.packets_read(),
+        })
+    }
+
+    // Forget registrations which refer to the pcap_t, as it is about to be
+    // closed or given away.
+    fn unregister(&self) {
+        #[cfg(feature = "signal")]
+        shutdown::unregister_handle(self.id);
+    }
+
+    /// Give up ownership of the `pcap_t` pointer without closing it.
+    fn into_raw(self) -> *mut libpcap::pcap_t {
+        self.unregister();
+        let mut this = std::mem::ManuallyDrop::new(self);
+        // Free everything except the pcap_t itself. The pattern lists every
+        // field so a new field can't be added without deciding here how it is
+        // freed.
+        let PcapT {
+            pcap_t,
+            errbuf,
+            interface,
+            activity,
+            id: _,
+            savefile_size: _,
+        } = &mut *this;
+        // `this` is never used or dropped after this, so each field is
+        // dropped exactly once.
+        unsafe {
+            std::ptr::drop_in_place(errbuf);
+            std::ptr::drop_in_place(interface);
+            std::ptr::drop_in_place(activity);
+        }
+        *pcap_t
+    }
+}
+
+/// Shows the identifier, device and activation state of the handle, and the
+/// link-layer header type and snapshot length once activated.
+impl std::fmt::Debug for PcapT {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let activated = self.is_activated();
+        let mut debug = f.debug_struct("PcapT");
+        debug
+            .field("id", &self.id)
+            .field("pcap_t", &self.pcap_t)
+            .field("device", &self.interface)
+            .field("activated", &activated);
+        if activated {
+            debug
+                .field("datalink", &pcap_datalink(self).ok().map(LinkType))
+                .field("snaplen", &pcap_snapshot(self).ok());
+        }
+        debug.finish()
+    }
+}
+
+/// Returns the file descriptor from `pcap_fileno()` or -1 if the handle has no
+/// file descriptor.
+#[cfg(unix)]
+impl std::os::fd::AsRawFd for PcapT {
+    fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        self.fileno().unwrap_or(-1)
+    }
 }
 
 impl Drop for PcapT {
     fn drop(&mut self) {
         log::trace!("PcapT::drop({:p})", self.pcap_t);
+        self.unregister();
         unsafe { luomu_libpcap_sys::pcap_close(self.pcap_t) }
     }
 }
@@ -110,6 +463,42 @@ impl Drop for PcapT {
 /// packets.
 pub struct Pcap {
     pcap_t: PcapT,
+    // Options the capture was built with, if built with `PcapBuilder`.
+    config: Option<CaptureConfig>,
+    // Filter in effect and how it was installed.
+    filter: RefCell<Option<InstalledFilter>>,
+    // Recompile the filter when the link-layer header type is changed.
+    reapply_filter: Cell<bool>,
+    // Observer set with `PcapBuilder::set_observer()`.
+    observer: Option<Arc<dyn CaptureObserver>>,
+    // State of `health()`: when the handle was created, how long without
+    // packets is a stall, and drops seen on the previous call.
+    opened: Instant,
+    stall_threshold: Cell<Duration>,
+    last_dropped: Cell<Option<u32>>,
+    // Entry of a live capture in the registry.
+    #[cfg(feature = "registry")]
+    registration: Option<registry::Registration>,
+}
+
+// Filter installed on a capture, kept to install it again the same way.
+#[derive(Clone)]
+enum InstalledFilter {
+    // Expression set with `set_filter()`.
+    Expression(String),
+    // Expression set with `set_filter_with_netmask()`.
+    WithNetmask(String, Ipv4Addr),
+    // Program set with `set_filter_program()`.
+    Program(SavedFilter),
+}
+
+impl InstalledFilter {
+    fn expression(&self) -> &str {
+        match self {
+            InstalledFilter::Expression(filter) | InstalledFilter::WithNetmask(filter, _) => filter,
+            InstalledFilter::Program(filter) => filter.expression(),
+        }
+    }
 }
 
 impl Pcap {
@@ -119,17 +508,20 @@ impl Pcap {
     /// network. `source` is a string that specifies the network device to open.
     pub fn new(source: &str) -> Result<Pcap> {
         let pcap_t = pcap_create(source)?;
-        Ok(Pcap { pcap_t })
+        Ok(Pcap::from_pcap_t(pcap_t, None))
     }
 
     /// Create a capture handle for reading packets from given savefile.
     ///
     /// This function can be used to create handle to read packes from saved
     /// pcap -file. Use `capture()` to get iterator for packets in the file.
+    ///
+    /// Filters set with `set_filter()` or `set_filter_program()` work on
+    /// offline handles too: only packets matching the filter are returned
+    /// when reading the file. Filters set with `set_filter()` are compiled for
+    /// the link-layer header type and snapshot length of the file.
     pub fn offline<P: AsRef<Path>>(savefile: P) -> Result<Pcap> {
-        Ok(Pcap {
-            pcap_t: pcap_open_offline(savefile)?,
-        })
+        Ok(Pcap::from_pcap_t(pcap_open_offline(savefile)?, None))
     }
 
     /// Use builder to create a live capture handle
@@ -138,7 +530,157 @@ impl Pcap {
     /// network. source is a string that specifies the network device to open.
     pub fn builder(source: &str) -> Result<PcapBuilder> {
         let pcap_t = pcap_create(source)?;
-        Ok(PcapBuilder { pcap_t })
+        Ok(PcapBuilder {
+            pcap_t,
+            config: CaptureConfig::new(source),
+            observer: None,
+        })
+    }
+
+    fn from_pcap_t(pcap_t: PcapT, config: Option<CaptureConfig>) -> Pcap {
+        #[cfg(feature = "registry")]
+        let registration = pcap_t
+            .interface
+            .as_deref()
+            .map(|device| registry::Registration::new(pcap_t.id, device));
+        Pcap {
+            pcap_t,
+            config,
+            filter: RefCell::new(None),
+            reapply_filter: Cell::new(true),
+            observer: None,
+            opened: Instant::now(),
+            stall_threshold: Cell::new(DEFAULT_STALL_THRESHOLD),
+            last_dropped: Cell::new(None),
+            #[cfg(feature = "registry")]
+            registration,
+        }
+    }
+
+    // Record the filter installed on the capture.
+    fn filter_installed(&self, filter: InstalledFilter) {
+        let expression = filter.expression().to_string();
+        *self.filter.borrow_mut() = Some(filter);
+        #[cfg(feature = "registry")]
+        if let Some(registration) = &self.registration {
+            registration.set_filter(Some(&expression));
+        }
+        self.observe(|observer, source| observer.on_filter_set(source, &expression));
+    }
+
+    // Compile `filter` if needed and install it, without recording it.
+    fn install_filter(&self, filter: &InstalledFilter) -> Result<()> {
+        let mut bpf_program = match filter {
+            InstalledFilter::Expression(filter) => {
+                let netmask = match (uses_netmask(filter), &self.pcap_t.interface) {
+                    (true, Some(device)) => match pcap_lookupnet(device) {
+                        Ok((_, netmask)) => Some(netmask),
+                        Err(err) => {
+                            log::debug!("no netmask for {}: {}", device, err);
+                            None
+                        }
+                    },
+                    _ => None,
+                };
+                match netmask {
+                    Some(netmask) => pcap_compile_with_netmask(&self.pcap_t, filter, netmask)?,
+                    None => PcapFilter::compile_with_pcap_t(&self.pcap_t, filter)?,
+                }
+            }
+            InstalledFilter::WithNetmask(filter, netmask) => {
+                pcap_compile_with_netmask(&self.pcap_t, filter, *netmask)?
+            }
+            InstalledFilter::Program(filter) => {
+                filter.check(&self.pcap_t)?;
+                let snaplen = pcap_snapshot(&self.pcap_t)?;
+                if (filter.snaplen() as usize) < snaplen {
+                    log::debug!(
+                        "filter compiled for snaplen {}, capture has {}",
+                        filter.snaplen(),
+                        snaplen
+                    );
+                }
+                filter.filter().clone()
+            }
+        };
+        pcap_setfilter(&self.pcap_t, &mut bpf_program)
+    }
+
+    // Pass an event to the observer of the capture, if any.
+    pub(crate) fn observe<F: FnOnce(&dyn CaptureObserver, &str)>(&self, event: F) {
+        if let Some(observer) = &self.observer {
+            event(
+                observer.as_ref(),
+                self.pcap_t.interface.as_deref().unwrap_or(""),
+            );
+        }
+    }
+
+    /// get the configuration of the capture
+    ///
+    /// Returns the options the capture was built with and the filter
+    /// expression in effect, or `None` if the capture was not created with
+    /// `PcapBuilder`.
+    pub fn config(&self) -> Option<CaptureConfig> {
+        let mut config = self.config.clone()?;
+        config.filter = self
+            .filter
+            .borrow()
+            .as_ref()
+            .map(|filter| filter.expression().to_string());
+        Some(config)
+    }
+
+    /// close the capture and open it again with changed options
+    ///
+    /// Options like the snapshot length and buffer size can't be changed on
+    /// an activated capture. `reopen_with()` closes the capture, creates a
+    /// new one with the options the capture was built with, passes the
+    /// builder to `configure` to change them, and activates it. The filter
+    /// in effect, the observer and the stall threshold are carried over.
+    /// Returns the new capture and the duration of the gap during which no
+    /// packets were captured.
+    ///
+    /// ```no_run
+    /// use luomu_libpcap::Pcap;
+    ///
+    /// let pcap = Pcap::builder("eth0")?.set_snaplen(96)?.activate()?;
+    /// pcap.set_filter("tcp")?;
+    /// let (pcap, gap) = pcap.reopen_with(|builder| builder.set_snaplen(65535))?;
+    /// println!("{} bytes per packet after {:?}", pcap.snaplen()?, gap);
+    /// # Ok::<(), luomu_libpcap::Error>(())
+    /// ```
+    ///
+    /// Fails if the capture was not created with `PcapBuilder`. If creating
+    /// the new capture fails, the capture is closed regardless.
+    pub fn reopen_with<F>(self, configure: F) -> Result<(Pcap, Duration)>
+    where
+        F: FnOnce(PcapBuilder) -> Result<PcapBuilder>,
+    {
+        let config = self.config().ok_or_else(|| {
+            Error::PcapError(String::from("capture was not created with PcapBuilder"))
+        })?;
+        let observer = self.observer.clone();
+        let filter = self.filter.borrow().clone();
+        let reapply_filter = self.reapply_filter.get();
+        let stall_threshold = self.stall_threshold.get();
+        let start = Instant::now();
+        drop(self);
+
+        let mut builder = config.builder()?;
+        if let Some(observer) = observer {
+            builder = builder.set_observer(observer)?;
+        }
+        let pcap = configure(builder)?.activate()?;
+        pcap.set_reapply_filter(reapply_filter);
+        pcap.set_stall_threshold(stall_threshold);
+        if let Some(filter) = filter {
+            pcap.install_filter(&filter)?;
+            pcap.filter_installed(filter);
+        }
+        let gap = start.elapsed();
+        log::debug!("reopened capture on {} after {:?}", config.source, gap);
+        Ok((pcap, gap))
     }
 
     /// set a filter expression
@@ -146,9 +688,139 @@ impl Pcap {
     /// `Set a filter for capture. See
     /// [pcap-filter(7)](https://www.tcpdump.org/manpages/pcap-filter.7.html)
     /// for the syntax of that string.
+    ///
+    /// If the filter uses `broadcast` or `multicast` primitives, the IPv4
+    /// netmask of the capture device is looked up with `pcap_lookupnet()` so
+    /// that `ip broadcast` works. Use `set_filter_with_netmask()` to give the
+    /// netmask explicitly.
     pub fn set_filter(&self, filter: &str) -> Result<()> {
-        let mut bpf_program = PcapFilter::compile_with_pcap_t(&self.pcap_t, filter)?;
-        pcap_setfilter(&self.pcap_t, &mut bpf_program)
+        let filter = InstalledFilter::Expression(filter.to_string());
+        self.install_filter(&filter)?;
+        self.filter_installed(filter);
+        Ok(())
+    }
+
+    /// set a filter expression with IPv4 netmask
+    ///
+    /// Like `set_filter()`, but the filter is compiled with the given IPv4
+    /// netmask of the network packets are captured on.
+    pub fn set_filter_with_netmask(&self, filter: &str, netmask: Ipv4Addr) -> Result<()> {
+        let filter = InstalledFilter::WithNetmask(filter.to_string(), netmask);
+        self.install_filter(&filter)?;
+        self.filter_installed(filter);
+        Ok(())
+    }
+
+    /// set a precompiled filter
+    ///
+    /// Installs a filter compiled earlier, for example one loaded with
+    /// `SavedFilter::load()`. Fails if the filter was compiled for another
+    /// link-layer header type than the capture has, as the filter would not
+    /// work correctly. A filter compiled for a smaller snapshot length than
+    /// the capture has truncates matching packets to that length.
+    pub fn set_filter_program(&self, filter: &SavedFilter) -> Result<()> {
+        let filter = InstalledFilter::Program(filter.clone());
+        self.install_filter(&filter)?;
+        self.filter_installed(filter);
+        Ok(())
+    }
+
+    /// wait for the first packet matching a filter
+    ///
+    /// Installs `filter`, waits up to `timeout` for a packet matching it and
+    /// restores the filter set before, or no filter if none was set. Returns
+    /// `None` if no matching packet arrived in time, or the end of a
+    /// savefile was reached. Useful for probes, like waiting for the reply to
+    /// a packet sent with `inject()`.
+    ///
+    /// The temporary filter is not recorded as the filter of the capture, so
+    /// observers and the registry don't see it. Packets not matching
+    /// `filter` which arrived while waiting are not returned by later reads.
+    ///
+    /// For savefiles `timeout` is ignored, and packets are read until one
+    /// matches or the end of the file is reached.
+    pub fn first_matching(&self, filter: &str, timeout: Duration) -> Result<Option<OwnedPacket>> {
+        self.install_filter(&InstalledFilter::Expression(filter.to_string()))?;
+        let packet = self.wait_packet(timeout);
+        let restored = match &*self.filter.borrow() {
+            Some(previous) => self.install_filter(previous),
+            None => self.install_filter(&InstalledFilter::Expression(String::new())),
+        };
+        let packet = packet?;
+        restored?;
+        Ok(packet)
+    }
+
+    // Read one packet, waiting at most `timeout` for it regardless of the
+    // packet buffer timeout of the capture. Savefiles are read until a packet
+    // is found, as they can't be made non-blocking or polled.
+    fn wait_packet(&self, timeout: Duration) -> Result<Option<OwnedPacket>> {
+        if self.file().is_some() {
+            return loop {
+                match pcap_next_ex(&self.pcap_t) {
+                    Ok(packet) => break Ok(Some(OwnedPacket::from(packet))),
+                    Err(Error::Timeout) => continue,
+                    Err(Error::Break) => break Ok(None),
+                    Err(err) => break Err(err),
+                }
+            };
+        }
+        let deadline = Instant::now() + timeout;
+        let nonblocking = pcap_getnonblock(&self.pcap_t)?;
+        if !nonblocking {
+            pcap_setnonblock(&self.pcap_t, true)?;
+        }
+        let packet = loop {
+            match pcap_next_ex(&self.pcap_t) {
+                Ok(packet) => break Ok(Some(OwnedPacket::from(packet))),
+                Err(Error::Timeout) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break Ok(None);
+                    }
+                    if let Err(err) = idle::wait_readable(&self.pcap_t, deadline - now) {
+                        break Err(err);
+                    }
+                }
+                Err(Error::Break) => break Ok(None),
+                Err(err) => break Err(err),
+            }
+        };
+        if !nonblocking {
+            pcap_setnonblock(&self.pcap_t, false)?;
+        }
+        packet
+    }
+
+    /// stop capturing on Ctrl-C
+    ///
+    /// Installs handlers for SIGINT and SIGTERM, or Ctrl-C on Windows, which
+    /// break the loop of the capture with `pcap_breakloop()` so that
+    /// `capture()` and the other reading functions return. Dumpers
+    /// registered with `ShutdownGuard::with_dumper()` are flushed, so the
+    /// savefile being written is complete. A second signal terminates the
+    /// process. See [ShutdownToken](shutdown::ShutdownToken) for the details.
+    ///
+    /// Ctrl-C stops the capture until the returned guard is dropped or the
+    /// capture is closed.
+    ///
+    /// ```no_run
+    /// use std::sync::{Arc, Mutex};
+    /// use luomu_libpcap::{functions, Pcap};
+    ///
+    /// let pcap = Pcap::builder("eth0")?.activate()?;
+    /// let dumper = Arc::new(Mutex::new(functions::pcap_dump_open(&pcap, "out.pcap")?));
+    /// let _stop = pcap.stop_on_ctrl_c()?.with_dumper(&dumper);
+    /// for packet in pcap.capture() {
+    ///     dumper.lock().unwrap().dump(packet);
+    /// }
+    /// # Ok::<(), luomu_libpcap::Error>(())
+    /// ```
+    ///
+    /// Requires feature `signal`.
+    #[cfg(feature = "signal")]
+    pub fn stop_on_ctrl_c(&self) -> Result<shutdown::ShutdownGuard<'_>> {
+        Ok(shutdown::ShutdownToken::install()?.register(&self.pcap_t))
     }
 
     /// Start capturing packets
@@ -159,11 +831,118 @@ impl Pcap {
         PcapIter::new(&self.pcap_t)
     }
 
+    /// Start capturing packets in batches
+    ///
+    /// Like `capture()` but packets are read with `pcap_dispatch()` up to
+    /// `batch_size` packets at a time. Packets are copied into a buffer owned
+    /// by the iterator, so one call into libpcap yields many packets. A
+    /// `batch_size` of 0 reads all the packets from one bufferful of a live
+    /// capture.
+    pub fn capture_batched(&self, batch_size: usize) -> PcapBatchIter<'_> {
+        PcapBatchIter::new(&self.pcap_t, batch_size)
+    }
+
+    /// Capture packets until a quota is exhausted
+    ///
+    /// Calls `callback` for each packet fitting in `quota` and stops the
+    /// capture with `pcap_breakloop()` when the quota is exhausted. The
+    /// callback, and any sink it owns, is dropped before returning. Also
+    /// returns when the end of a savefile is reached.
+    pub fn capture_quota<F>(&self, quota: Quota, callback: F) -> Result<QuotaSummary>
+    where
+        F: FnMut(BorrowedPacket),
+    {
+        quota::loop_with_quota(&self.pcap_t, quota, callback)
+    }
+
+    /// Read next packet into a caller-provided buffer
+    ///
+    /// Copies the next captured packet into `buf` without allocating. If
+    /// `buf` is smaller than the captured packet only the beginning of it is
+    /// copied, see `PacketHeader::is_truncated()`. A buffer of snapshot length
+    /// (`Pcap::snaplen()`) is always large enough.
+    pub fn read_into(&self, buf: &mut [u8]) -> Result<PacketHeader> {
+        pcap_next_ex(&self.pcap_t).map(|p| p.copy_into(buf))
+    }
+
+    /// Read next packet into an existing `OwnedPacket`
+    ///
+    /// Replaces the contents of `packet` with the next captured packet,
+    /// reusing its allocation. Together with `OwnedPacket::with_capacity()`
+    /// this allows keeping owned packets without allocating for each packet.
+    pub fn read_owned(&self, packet: &mut OwnedPacket) -> Result<()> {
+        pcap_next_ex(&self.pcap_t).map(|p| p.copy_to_owned(packet))
+    }
+
     /// Transmit a packet
     pub fn inject(&self, buf: &[u8]) -> Result<usize> {
         pcap_inject(&self.pcap_t, buf)
     }
 
+    /// Transmit an IP packet on a loopback device
+    ///
+    /// Prepends the link-layer header of loopback devices, as described in
+    /// [loopback_frame()], and transmits the packet. This works on the
+    /// loopback interfaces of BSDs and macOS, and the Npcap loopback adapter
+    /// on Windows.
+    pub fn inject_ip(&self, packet: &[u8]) -> Result<usize> {
+        let frame = loopback_frame(self.datalink()?, packet)?;
+        pcap_inject(&self.pcap_t, &frame)
+    }
+
+    /// Get the event signaled when packets are available
+    ///
+    /// The returned handle can be waited on with `WaitForSingleObject()`,
+    /// `WaitForMultipleObjects()` or registered with an event loop, like the
+    /// file descriptor of `as_raw_fd()` on Unix. Returns `None` for savefiles.
+    /// The handle is owned by the capture and stays valid until it is closed.
+    #[cfg(windows)]
+    pub fn event_handle(&self) -> Option<std::os::windows::io::RawHandle> {
+        pcap_getevent(&self.pcap_t)
+    }
+
+    /// Start writing captured packets into savefile `path` in the kernel
+    ///
+    /// Packets are written by the driver without passing through the
+    /// application, which allows archiving traffic at rates userspace can't
+    /// keep up with. Dumping stops once the file has `maxsize` bytes or
+    /// `maxpacks` packets, zero meaning no limit. Use `live_dump_ended()` to
+    /// check whether it has stopped. Only available on Windows, and not
+    /// supported by all drivers.
+    #[cfg(windows)]
+    pub fn live_dump<P: AsRef<Path>>(
+        &self,
+        path: P,
+        maxsize: usize,
+        maxpacks: usize,
+    ) -> Result<()> {
+        pcap_live_dump(&self.pcap_t, path, maxsize, maxpacks)
+    }
+
+    /// Check whether the kernel dump started with `live_dump()` has ended
+    ///
+    /// If `wait` is true, blocks until the dump has ended.
+    #[cfg(windows)]
+    pub fn live_dump_ended(&self, wait: bool) -> Result<bool> {
+        pcap_live_dump_ended(&self.pcap_t, wait)
+    }
+
+    /// Get the BPF device of a live capture
+    ///
+    /// Allows changing settings of the device on BSDs and macOS, such as
+    /// immediate mode, while capturing. See [BpfDevice].
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    ))]
+    pub fn bpf(&self) -> Result<BpfDevice<'_>> {
+        BpfDevice::new(&self.pcap_t)
+    }
+
     /// activate a capture
     ///
     /// This is used to activate a packet capture to look at packets on the
@@ -179,10 +958,225 @@ impl Pcap {
     pub fn stats(&self) -> Result<PcapStat> {
         let mut stats: PcapStat = Default::default();
         match pcap_stats(&self.pcap_t, &mut stats) {
-            Ok(()) => Ok(stats),
-            Err(e) => Err(e),
+            Ok(()) => {
+                #[cfg(feature = "registry")]
+                if let Some(registration) = &self.registration {
+                    registration.set_stats(&stats);
+                }
+                self.observe(|observer, source| observer.on_stats(source, &stats));
+                Ok(stats)
+            }
+            Err(e) => {
+                self.observe(|observer, source| observer.on_error(source, &e));
+                Err(e)
+            }
         }
     }
+
+    /// check the health of a live capture
+    ///
+    /// Combines the state of reading packets, the time since the last packet
+    /// was read and the number of packets dropped since the previous call
+    /// into a [Health], so supervisors can restart captures which have
+    /// failed or stalled:
+    ///
+    ///   * `Dead` if reading packets has failed, the capture has no file
+    ///     descriptor on Unix, or its statistics can't be read.
+    ///   * `Stalled` if no packets have been read for longer than the stall
+    ///     threshold, 60 seconds by default, or packets were dropped since the
+    ///     previous call.
+    ///   * `Healthy` otherwise.
+    ///
+    /// Only packets read from the capture are counted; packets filtered out
+    /// by libpcap don't reset the stall threshold.
+    pub fn health(&self) -> Health {
+        if self.pcap_t.activity.has_failed() {
+            return Health::Dead;
+        }
+        #[cfg(unix)]
+        if self.pcap_t.fileno().is_none() {
+            return Health::Dead;
+        }
+        let dropped = match self.stats() {
+            Ok(stats) => stats.packets_dropped(),
+            Err(_) => return Health::Dead,
+        };
+        let dropping = self
+            .last_dropped
+            .replace(Some(dropped))
+            .is_some_and(|previous| previous != dropped);
+        let last_packet = self.pcap_t.activity.last_packet().unwrap_or(self.opened);
+        if dropping || last_packet.elapsed() > self.stall_threshold.get() {
+            Health::Stalled
+        } else {
+            Health::Healthy
+        }
+    }
+
+    /// set how long a capture can be without packets before it is stalled
+    ///
+    /// See `health()`. Quiet networks need a longer threshold than the
+    /// default of 60 seconds.
+    pub fn set_stall_threshold(&self, threshold: Duration) {
+        self.stall_threshold.set(threshold);
+    }
+
+    /// get the snapshot length
+    ///
+    /// Returns the snapshot length in effect for an activated live capture, or
+    /// the snapshot length from the capture file for an offline capture.
+    pub fn snaplen(&self) -> Result<usize> {
+        pcap_snapshot(&self.pcap_t)
+    }
+
+    /// get the size of the capture buffer
+    ///
+    /// Returns the size of the buffer in bytes actually used for the capture.
+    /// This can differ from the size requested with
+    /// `PcapBuilder::set_buffer_size()`.
+    pub fn buffer_size(&self) -> Result<usize> {
+        pcap_bufsize(&self.pcap_t)
+    }
+
+    /// get the time stamp precision returned in captures
+    pub fn timestamp_precision(&self) -> Result<TimestampPrecision> {
+        pcap_get_tstamp_precision(&self.pcap_t)
+    }
+
+    /// get the state of non-blocking mode
+    ///
+    /// Returns true if the capture is in "non-blocking" mode. Always false for
+    /// offline captures.
+    pub fn is_nonblocking(&self) -> Result<bool> {
+        pcap_getnonblock(&self.pcap_t)
+    }
+
+    /// get the link-layer header type
+    ///
+    /// Returns the link-layer header type in effect.
+    pub fn datalink(&self) -> Result<LinkType> {
+        pcap_datalink(&self.pcap_t).map(LinkType)
+    }
+
+    /// set the link-layer header type
+    ///
+    /// Sets the link-layer header type of the capture to one of the types
+    /// returned by `pcap_list_datalinks()`. A filter program is compiled for
+    /// a link-layer header type, so the filter in effect is installed again
+    /// the way it was set: an expression set with `set_filter()` or
+    /// `set_filter_with_netmask()` is compiled again for the new type, with
+    /// the same netmask. A program set with `set_filter_program()` for
+    /// another type is replaced by its expression compiled for the new type
+    /// and the snapshot length of the program, and a warning is logged. If
+    /// the filter can't be compiled for the new type, the old type is
+    /// restored and the error is returned.
+    ///
+    /// Use `set_reapply_filter(false)` to only change the type. The filter
+    /// in effect has to be replaced then.
+    pub fn set_datalink(&self, linktype: LinkType) -> Result<()> {
+        let old = pcap_datalink(&self.pcap_t)?;
+        pcap_set_datalink(&self.pcap_t, linktype.value())?;
+        if !self.reapply_filter.get() {
+            return Ok(());
+        }
+        let filter = self.filter.borrow().clone();
+        let Some(filter) = filter else {
+            return Ok(());
+        };
+        let filter = match filter {
+            InstalledFilter::Program(program) if program.linktype() != linktype => {
+                log::warn!(
+                    "filter program for {} replaced by \"{}\" compiled for {}",
+                    program.linktype(),
+                    program.expression(),
+                    linktype
+                );
+                SavedFilter::compile(program.expression(), linktype, program.snaplen())
+                    .map(InstalledFilter::Program)
+            }
+            filter => Ok(filter),
+        };
+        match filter.and_then(|filter| self.install_filter(&filter).map(|()| filter)) {
+            Ok(filter) => {
+                *self.filter.borrow_mut() = Some(filter);
+                Ok(())
+            }
+            Err(err) => {
+                log::debug!("filter not valid for {}: {}", linktype, err);
+                pcap_set_datalink(&self.pcap_t, old)?;
+                Err(err)
+            }
+        }
+    }
+
+    /// set whether to recompile the filter when link-layer type changes
+    ///
+    /// Filter is recompiled by `set_datalink()` by default.
+    pub fn set_reapply_filter(&self, reapply: bool) {
+        self.reapply_filter.set(reapply);
+    }
+
+    /// get metadata of a savefile
+    ///
+    /// Returns information about the savefile opened with `Pcap::offline()`
+    /// or `None` if this is not an offline capture.
+    pub fn savefile_info(&self) -> Option<SavefileInfo> {
+        self.file()?;
+        Some(SavefileInfo {
+            swapped: pcap_is_swapped(&self.pcap_t).ok()?,
+            version_major: pcap_major_version(&self.pcap_t),
+            version_minor: pcap_minor_version(&self.pcap_t),
+            snaplen: pcap_snapshot(&self.pcap_t).ok()?,
+            datalink: LinkType(pcap_datalink(&self.pcap_t).ok()?),
+        })
+    }
+
+    /// Consume the `Pcap` and return the raw `pcap_t` pointer
+    ///
+    /// The handle is not closed. The caller becomes responsible for closing
+    /// it with `pcap_close()` or turning it back into `Pcap` with
+    /// `Pcap::from_raw()`.
+    pub fn into_raw(self) -> *mut libpcap::pcap_t {
+        self.pcap_t.into_raw()
+    }
+
+    /// Construct a `Pcap` from raw `pcap_t` pointer
+    ///
+    /// The returned `Pcap` takes ownership of the handle and closes it when
+    /// dropped.
+    ///
+    /// # Safety
+    ///
+    /// `pcap_t` must be a valid, non-null pointer returned by libpcap (for
+    /// example from `Pcap::into_raw()`) and it must not be used or closed by
+    /// anyone else afterwards.
+    pub unsafe fn from_raw(pcap_t: *mut libpcap::pcap_t) -> Pcap {
+        debug_assert!(!pcap_t.is_null(), "null pointer");
+        let pcap_t = PcapT {
+            pcap_t,
+            errbuf: vec![0; libpcap::PCAP_ERRBUF_SIZE as usize],
+            interface: None,
+            activity: Activity::default(),
+            id: PcapT::next_id(),
+            savefile_size: None,
+        };
+        Pcap::from_pcap_t(pcap_t, None)
+    }
+}
+
+// Returns true if `filter` has primitives which need the netmask of the
+// network to be compiled correctly.
+fn uses_netmask(filter: &str) -> bool {
+    filter
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|word| word == "broadcast" || word == "multicast")
+}
+
+#[cfg(unix)]
+impl std::os::fd::AsRawFd for Pcap {
+    fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        self.pcap_t.as_raw_fd()
+    }
 }
 
 impl Deref for Pcap {
@@ -193,9 +1187,113 @@ impl Deref for Pcap {
     }
 }
 
+/// Shows the identifier, device and activation state of the handle, the
+/// filter expression set with `set_filter()`, and the link-layer header type
+/// and snapshot length of an activated capture.
+impl std::fmt::Debug for Pcap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pcap")
+            .field("id", &self.pcap_t.id)
+            .field("device", &self.pcap_t.interface)
+            .field("activated", &self.pcap_t.is_activated())
+            .field(
+                "filter",
+                &self
+                    .filter
+                    .borrow()
+                    .as_ref()
+                    .map(InstalledFilter::expression),
+            )
+            .field("datalink", &self.datalink().ok())
+            .field("snaplen", &self.snaplen().ok())
+            .finish()
+    }
+}
+
+/// Precision of packet time stamps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TimestampPrecision {
+    /// Time stamps with microsecond precision (`PCAP_TSTAMP_PRECISION_MICRO`).
+    Micro,
+    /// Time stamps with nanosecond precision (`PCAP_TSTAMP_PRECISION_NANO`).
+    Nano,
+}
+
+impl TimestampPrecision {
+    pub(crate) fn as_raw(self) -> u32 {
+        match self {
+            TimestampPrecision::Micro => libpcap::PCAP_TSTAMP_PRECISION_MICRO,
+            TimestampPrecision::Nano => libpcap::PCAP_TSTAMP_PRECISION_NANO,
+        }
+    }
+}
+
+/// Direction of packets to capture. See `functions::pcap_setdirection()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Capture packets received by and sent by the device (`PCAP_D_INOUT`).
+    InOut,
+    /// Capture only packets received by the device (`PCAP_D_IN`).
+    In,
+    /// Capture only packets sent by the device (`PCAP_D_OUT`).
+    Out,
+}
+
+impl Direction {
+    pub(crate) fn as_raw(self) -> libpcap::pcap_direction_t {
+        match self {
+            Direction::InOut => libpcap::pcap_direction_t_PCAP_D_INOUT,
+            Direction::In => libpcap::pcap_direction_t_PCAP_D_IN,
+            Direction::Out => libpcap::pcap_direction_t_PCAP_D_OUT,
+        }
+    }
+}
+
+/// Profile of capture options. See `PcapBuilder::preset()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Profile {
+    /// Deliver packets as soon as they arrive.
+    ///
+    /// Immediate mode, 2 MiB buffer, snapshot length of 65535 and 10
+    /// millisecond packet buffer timeout. Every packet wakes up the reader,
+    /// which costs CPU time at high packet rates, and the small buffer fills
+    /// up quickly if the reader falls behind.
+    LowLatency,
+    /// Capture high packet rates with little overhead.
+    ///
+    /// Packets are buffered, 64 MiB buffer, snapshot length of 65535 and 100
+    /// millisecond packet buffer timeout. Packets are delivered in batches,
+    /// so they may be delayed up to the timeout.
+    HighThroughput,
+    /// Capture complete packets without losing any.
+    ///
+    /// Packets are buffered, 256 MiB buffer, snapshot length of 262144 and 1
+    /// second packet buffer timeout. The large buffer rides out bursts and
+    /// slow writers at the cost of memory, and packets may be delayed up to
+    /// the timeout.
+    Forensics,
+}
+
+/// Metadata of a savefile. See `Pcap::savefile_info()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SavefileInfo {
+    /// True if savefile uses different byte order than the current system.
+    pub swapped: bool,
+    /// Major version number of the savefile format.
+    pub version_major: i32,
+    /// Minor version number of the savefile format.
+    pub version_minor: i32,
+    /// Snapshot length of the savefile.
+    pub snaplen: usize,
+    /// Link-layer header type of the savefile.
+    pub datalink: LinkType,
+}
+
 /// Builder for a `Pcap`. Call `Pcap::builder()` to get started.
 pub struct PcapBuilder {
     pcap_t: PcapT,
+    config: CaptureConfig,
+    observer: Option<Arc<dyn CaptureObserver>>,
 }
 
 impl PcapBuilder {
@@ -204,8 +1302,9 @@ impl PcapBuilder {
     /// `set_buffer_size()` sets the buffer size that will be used on a capture
     /// handle when the handle is activated to buffer_size, which is in units of
     /// bytes.
-    pub fn set_buffer_size(self, buffer_size: usize) -> Result<PcapBuilder> {
+    pub fn set_buffer_size(mut self, buffer_size: usize) -> Result<PcapBuilder> {
         pcap_set_buffer_size(&self.pcap_t, buffer_size)?;
+        self.config.buffer_size = Some(buffer_size);
         Ok(self)
     }
 
@@ -213,8 +1312,9 @@ impl PcapBuilder {
     ///
     /// `set_promisc()` sets whether promiscuous mode should be set on a capture
     /// handle when the handle is activated.
-    pub fn set_promiscuous(self, promiscuous: bool) -> Result<PcapBuilder> {
+    pub fn set_promiscuous(mut self, promiscuous: bool) -> Result<PcapBuilder> {
         pcap_set_promisc(&self.pcap_t, promiscuous)?;
+        self.config.promiscuous = Some(promiscuous);
         Ok(self)
     }
 
@@ -223,8 +1323,9 @@ impl PcapBuilder {
     /// `set_immediate_mode()` sets whether immediate mode should be set on a
     /// capture handle when the handle is activated. In immediate mode, packets
     /// are always delivered as soon as they arrive, with no buffering.
-    pub fn set_immediate(self, immediate: bool) -> Result<PcapBuilder> {
+    pub fn set_immediate(mut self, immediate: bool) -> Result<PcapBuilder> {
         pcap_set_immediate_mode(&self.pcap_t, immediate)?;
+        self.config.immediate = Some(immediate);
         Ok(self)
     }
 
@@ -233,11 +1334,12 @@ impl PcapBuilder {
     /// `pcap_set_timeout()` sets the packet buffer timeout that will be used on a
     /// capture handle when the handle is activated to to_ms, which is in units of
     /// milliseconds.
-    pub fn set_timeout(self, to_ms: Duration) -> Result<PcapBuilder> {
+    pub fn set_timeout(mut self, to_ms: Duration) -> Result<PcapBuilder> {
         pcap_set_timeout(
             &self.pcap_t,
             (to_ms.as_millis().min(i32::MAX as u128)) as i32,
         )?;
+        self.config.timeout = Some(to_ms);
         Ok(self)
     }
 
@@ -247,8 +1349,36 @@ impl PcapBuilder {
     /// when the handle is activated to snaplen.
     ///
     /// `libpcap` says 65535 bytes should be enough for everyone.
-    pub fn set_snaplen(self, snaplen: usize) -> Result<PcapBuilder> {
+    pub fn set_snaplen(mut self, snaplen: usize) -> Result<PcapBuilder> {
         pcap_set_snaplen(&self.pcap_t, snaplen)?;
+        self.config.snaplen = Some(snaplen);
+        Ok(self)
+    }
+
+    /// set options of a profile
+    ///
+    /// Sets immediate mode, buffer size, snapshot length and packet buffer
+    /// timeout to the values of `profile`. See [Profile] for the values and
+    /// their trade-offs. Options can be changed after setting the profile,
+    /// e.g. to capture only the headers of packets.
+    pub fn preset(self, profile: Profile) -> Result<PcapBuilder> {
+        let (immediate, buffer_size, snaplen, timeout) = match profile {
+            Profile::LowLatency => (true, 2 << 20, 65535, Duration::from_millis(10)),
+            Profile::HighThroughput => (false, 64 << 20, 65535, Duration::from_millis(100)),
+            Profile::Forensics => (false, 256 << 20, 262144, Duration::from_secs(1)),
+        };
+        self.set_immediate(immediate)?
+            .set_buffer_size(buffer_size)?
+            .set_snaplen(snaplen)?
+            .set_timeout(timeout)
+    }
+
+    /// set an observer for the capture
+    ///
+    /// `observer` is told of lifecycle events of the capture from activating
+    /// it on, see [CaptureObserver].
+    pub fn set_observer(mut self, observer: Arc<dyn CaptureObserver>) -> Result<PcapBuilder> {
+        self.observer = Some(observer);
         Ok(self)
     }
 
@@ -258,10 +1388,18 @@ impl PcapBuilder {
     /// the network, with the options that were set on the handle being in
     /// effect.
     pub fn activate(self) -> Result<Pcap> {
-        pcap_activate(&self.pcap_t)?;
-        Ok(Pcap {
-            pcap_t: self.pcap_t,
-        })
+        let mut pcap = Pcap::from_pcap_t(self.pcap_t, Some(self.config));
+        pcap.observer = self.observer;
+        match pcap_activate(&pcap.pcap_t) {
+            Ok(()) => {
+                pcap.observe(|observer, source| observer.on_activated(source));
+                Ok(pcap)
+            }
+            Err(err) => {
+                pcap.observe(|observer, source| observer.on_error(source, &err));
+                Err(err)
+            }
+        }
     }
 }
 
@@ -270,6 +1408,17 @@ pub struct PcapFilter {
     bpf_program: libpcap::bpf_program,
 }
 
+// SAFETY: `bpf_program` is the only field. Its `bf_len` is a plain integer
+// and `bf_insns` points to instructions allocated for this `PcapFilter` alone
+// (by `pcap_compile()`, `from_insns()` or `clone()`), freed only in `drop()`.
+// The instructions are not tied to the thread or the `pcap_t` they were
+// compiled with, so the filter can be sent to other threads. Methods taking
+// `&self` only read the instructions, the only function writing
+// `bpf_program`, `pcap_setfilter()`, takes `&mut PcapFilter`, so sharing
+// references between threads is safe too.
+unsafe impl Send for PcapFilter {}
+unsafe impl Sync for PcapFilter {}
+
 impl PcapFilter {
     /// compile a filter expression
     ///
@@ -281,6 +1430,20 @@ impl PcapFilter {
         pcap_compile(&pcap, filter)
     }
 
+    /// validate a filter expression
+    ///
+    /// Checks that `filter` compiles for link-layer header type `linktype`
+    /// without opening a capture. On failure the error has libpcap's error
+    /// message and, if the message refers to a token in the expression, its
+    /// position.
+    pub fn validate(filter: &str, linktype: LinkType) -> result::Result<(), FilterError> {
+        match pcap_compile_nopcap(65535, linktype.value(), filter) {
+            Ok(_) => Ok(()),
+            Err(Error::PcapError(message)) => Err(FilterError::new(filter, message)),
+            Err(err) => Err(FilterError::new(filter, err.to_string())),
+        }
+    }
+
     /// compile a filter expression with `PcapT`
     ///
     /// `compile_with_pcap_t()` is used to compile the filter into a filter
@@ -291,6 +1454,113 @@ impl PcapFilter {
         pcap_compile(pcap_t, filter_str)
     }
 
+    /// check a packet against the filter
+    ///
+    /// Returns true if the packet matches the filter.
+    pub fn matches<P: Packet>(&self, packet: &P) -> bool {
+        pcap_offline_filter(self, packet.pkthdr(), packet.packet())
+    }
+
+    /// run the filter over a capture file
+    ///
+    /// Reads all packets of savefile `path` and counts how many of them match
+    /// the filter. Useful for developing and verifying filters against
+    /// recorded traffic. The filter should be compiled for the link-layer
+    /// header type of the file.
+    pub fn test_on_file<P: AsRef<Path>>(&self, path: P) -> Result<FilterReport> {
+        filterreport::test_on_file(self, path, 0)
+    }
+
+    /// run the filter over a capture file keeping matching packets
+    ///
+    /// Like `test_on_file()`, but the report also has copies of the first
+    /// `keep` packets matching the filter.
+    pub fn test_on_file_keep<P: AsRef<Path>>(&self, path: P, keep: usize) -> Result<FilterReport> {
+        filterreport::test_on_file(self, path, keep)
+    }
+
+    /// check a batch of packets against the filter
+    ///
+    /// Returns a vector with an element for each packet telling whether the
+    /// packet matched the filter. Classifying packets in batches avoids
+    /// per-packet overhead of `matches()` such as logging.
+    pub fn filter_batch(&self, packets: &[(libpcap::pcap_pkthdr, &[u8])]) -> Vec<bool> {
+        let mut matches = Vec::with_capacity(packets.len());
+        self.filter_batch_into(packets, &mut matches);
+        matches
+    }
+
+    /// check a batch of packets against the filter into existing vector
+    ///
+    /// Like `filter_batch()` but results are appended into `matches`, which
+    /// allows reusing its allocation between batches.
+    pub fn filter_batch_into(
+        &self,
+        packets: &[(libpcap::pcap_pkthdr, &[u8])],
+        matches: &mut Vec<bool>,
+    ) {
+        log::trace!(
+            "PcapFilter::filter_batch({:p}, {} packets)",
+            &self.bpf_program,
+            packets.len()
+        );
+        matches.reserve(packets.len());
+        matches.extend(packets.iter().map(|(pkthdr, bytes)| {
+            // libpcap reads caplen bytes from the packet, make sure it won't
+            // read past the end of given slice.
+            let mut pkthdr = *pkthdr;
+            pkthdr.caplen = pkthdr.caplen.min(bytes.len() as u32);
+            let ret =
+                unsafe { libpcap::pcap_offline_filter(&self.bpf_program, &pkthdr, bytes.as_ptr()) };
+            ret != 0
+        }));
+    }
+
+    // Construct a filter from a copy of instructions. The copy is allocated
+    // with malloc() so pcap_freecode() can free it.
+    pub(crate) fn from_insns(insns: &[libpcap::bpf_insn]) -> PcapFilter {
+        let size = std::mem::size_of_val(insns);
+        let ptr = match size {
+            0 => std::ptr::null_mut(),
+            _ => {
+                let ptr = unsafe { libc::malloc(size) } as *mut libpcap::bpf_insn;
+                assert!(!ptr.is_null(), "malloc() failed");
+                unsafe { std::ptr::copy_nonoverlapping(insns.as_ptr(), ptr, insns.len()) };
+                ptr
+            }
+        };
+        PcapFilter {
+            bpf_program: libpcap::bpf_program {
+                bf_len: insns.len() as libc::c_uint,
+                bf_insns: ptr,
+            },
+        }
+    }
+
+    // Instructions of the compiled filter.
+    pub(crate) fn insns(&self) -> &[libpcap::bpf_insn] {
+        if self.bpf_program.bf_insns.is_null() {
+            return &[];
+        }
+        unsafe {
+            std::slice::from_raw_parts(self.bpf_program.bf_insns, self.bpf_program.bf_len as usize)
+        }
+    }
+
+    /// disassemble the filter program
+    ///
+    /// Returns a human-readable representation of each instruction of the
+    /// program, as returned by `bpf_image()`. `Display` of `PcapFilter`
+    /// prints the whole program one instruction per line, like
+    /// `tcpdump -d`.
+    pub fn image(&self) -> Result<Vec<String>> {
+        self.insns()
+            .iter()
+            .enumerate()
+            .map(|(n, insn)| bpf_image(insn, n))
+            .collect()
+    }
+
     /// Get length of the compiled filter
     pub fn get_raw_filter_len(&self) -> u32 {
         self.bpf_program.bf_len
@@ -309,6 +1579,26 @@ impl PcapFilter {
     }
 }
 
+impl Clone for PcapFilter {
+    /// Make a deep copy of the compiled filter program.
+    ///
+    /// The copy doesn't depend on the original or the handle it was compiled
+    /// with in any way.
+    fn clone(&self) -> Self {
+        PcapFilter::from_insns(self.insns())
+    }
+}
+
+impl std::fmt::Display for PcapFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (n, insn) in self.insns().iter().enumerate() {
+            let image = bpf_image(insn, n).map_err(|_| std::fmt::Error)?;
+            writeln!(f, "{}", image)?;
+        }
+        Ok(())
+    }
+}
+
 impl Drop for PcapFilter {
     fn drop(&mut self) {
         log::trace!("PcapFilter::drop({:p})", &self.bpf_program);
@@ -317,19 +1607,152 @@ impl Drop for PcapFilter {
 }
 
 /// A PcapDumper
+///
+/// By default packets are written into the savefile's standard I/O stream
+/// one by one. `PcapDumper::set_buffer_size()` enables an internal write
+/// buffer, which collects packets and writes them with a single call when the
+/// buffer gets full. This avoids the overhead of writing millions of small
+/// packets individually.
 pub struct PcapDumper {
     pcap_dumper_t: *mut libpcap::pcap_dumper_t,
+    // Records waiting to be written into the savefile.
+    buf: Vec<u8>,
+    buffer_size: usize,
+    // Time stamp precision of the handle the savefile was opened with.
+    precision: TimestampPrecision,
 }
 
+// PcapDumper only wraps the savefile's stream, which can be written from any
+// thread.
+unsafe impl Send for PcapDumper {}
+
 impl PcapDumper {
+    pub(crate) fn new(
+        pcap_dumper_t: *mut libpcap::pcap_dumper_t,
+        precision: TimestampPrecision,
+    ) -> Self {
+        PcapDumper {
+            pcap_dumper_t,
+            buf: Vec::new(),
+            buffer_size: 0,
+            precision,
+        }
+    }
+
     /// Dump (save) a [Packet] to a savefile.
     pub fn dump<P: Packet>(&mut self, packet: P) {
         self.dump_raw(packet.pkthdr(), packet.packet())
     }
 
+    /// Write a packet with header `pkthdr` and contents `bytes` to a
+    /// savefile.
+    ///
+    /// Contents can be any bytes, like a `Vec<u8>`, an array or a captured
+    /// packet's `packet()`. If `bytes` is shorter than the captured length in
+    /// `pkthdr`, the captured length is reduced to fit.
+    pub fn write<B: AsRef<[u8]>>(&mut self, pkthdr: &luomu_libpcap_sys::pcap_pkthdr, bytes: B) {
+        let bytes = bytes.as_ref();
+        let mut pkthdr = *pkthdr;
+        pkthdr.caplen = pkthdr.caplen.min(bytes.len() as u32);
+        self.dump_raw(&pkthdr, bytes)
+    }
+
+    /// Write a packet with contents `bytes` to a savefile, timestamped with
+    /// the current time.
+    ///
+    /// Both the captured length and the length on the wire are the length of
+    /// `bytes`. The time stamp has the precision of the handle the savefile
+    /// was opened with. Useful for writing synthesized packets.
+    pub fn write_now<B: AsRef<[u8]>>(&mut self, bytes: B) {
+        let bytes = bytes.as_ref();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let pkthdr = luomu_libpcap_sys::pcap_pkthdr {
+            ts: timeval(now, self.precision),
+            caplen: bytes.len() as u32,
+            len: bytes.len() as u32,
+        };
+        self.dump_raw(&pkthdr, bytes)
+    }
+
     /// Dump (save) a header and bytes to a savefile.
+    //
+    // The fields of timeval are not i64 on all platforms.
+    #[allow(clippy::unnecessary_cast)]
     pub fn dump_raw(&mut self, pkthdr: &luomu_libpcap_sys::pcap_pkthdr, bytes: &[u8]) {
-        pcap_dump(self, pkthdr, bytes)
+        if self.buffer_size == 0 {
+            return pcap_dump(self, pkthdr, bytes);
+        }
+
+        // Same record as pcap_dump() writes, in host byte order.
+        let bytes = &bytes[..bytes.len().min(pkthdr.caplen as usize)];
+        self.buf
+            .extend_from_slice(&(pkthdr.ts.tv_sec as i32).to_ne_bytes());
+        self.buf
+            .extend_from_slice(&(pkthdr.ts.tv_usec as i32).to_ne_bytes());
+        self.buf
+            .extend_from_slice(&(bytes.len() as u32).to_ne_bytes());
+        self.buf.extend_from_slice(&pkthdr.len.to_ne_bytes());
+        self.buf.extend_from_slice(bytes);
+
+        if self.buf.len() >= self.buffer_size {
+            if let Err(err) = self.write_buffered() {
+                log::warn!("PcapDumper::dump_raw(): {}", err);
+            }
+        }
+    }
+
+    /// Set the size of the write buffer in bytes
+    ///
+    /// Packets are collected into the buffer until it's full. Size of 0, the
+    /// default, disables the buffer. Packets already in the buffer are written
+    /// out first.
+    pub fn set_buffer_size(&mut self, buffer_size: usize) -> Result<()> {
+        self.write_buffered()?;
+        self.buffer_size = buffer_size;
+        self.buf = Vec::with_capacity(buffer_size);
+        Ok(())
+    }
+
+    /// Get the size of the write buffer in bytes
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    /// Write buffered packets into the savefile and flush it
+    pub fn flush(&mut self) -> Result<()> {
+        pcap_dump_flush(self)
+    }
+
+    // Write records in buffer into the savefile's stream.
+    fn write_buffered(&mut self) -> Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+
+        let fp = unsafe { libpcap::pcap_dump_file(self.pcap_dumper_t) };
+        let len = self.buf.len();
+        // Records not written are discarded like pcap_dump() does.
+        let written = unsafe { libc::fwrite(self.buf.as_ptr() as *const libc::c_void, 1, len, fp) };
+        self.buf.clear();
+        if written != len {
+            return Err(Error::IO(std::io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+}
+
+// Time stamp for packet header from time since the epoch. The fraction of the
+// second is in nanoseconds with nanosecond precision.
+fn timeval(time: Duration, precision: TimestampPrecision) -> libc::timeval {
+    let fraction = match precision {
+        TimestampPrecision::Micro => time.subsec_micros(),
+        TimestampPrecision::Nano => time.subsec_nanos(),
+    };
+    libc::timeval {
+        tv_sec: time.as_secs() as _,
+        tv_usec: fraction as _,
     }
 }
 
@@ -350,6 +1773,84 @@ impl<'p> PcapIter<'p> {
     fn new(pcap_t: &'p PcapT) -> Self {
         PcapIter { pcap_t }
     }
+
+    /// Get the next packet as a view borrowing the iterator
+    ///
+    /// Unlike packets returned by `next()`, the compiler makes sure a
+    /// `PacketView` is not used after the next packet has been read from this
+    /// iterator, as reading requires the view to be dropped first:
+    ///
+    /// ```compile_fail
+    /// use luomu_libpcap::{Packet, Pcap};
+    ///
+    /// let pcap = Pcap::offline("capture.pcap").unwrap();
+    /// let mut iter = pcap.capture();
+    /// let first = iter.next_view().unwrap();
+    /// let second = iter.next_view(); // `iter` is still borrowed by `first`
+    /// println!("{}", first.len());
+    /// ```
+    ///
+    /// Reading packets from the same capture by other means, like another
+    /// iterator from `capture()` or `pcap_next_ex()`, also overwrites the
+    /// contents of the view, and the compiler does not prevent that. Read
+    /// only through this iterator while a view is in use.
+    ///
+    /// Returns `None` on error, like `next()`.
+    pub fn next_view(&mut self) -> Option<PacketView<'_>> {
+        let packet = pcap_next_ex(self.pcap_t).ok()?;
+        Some(PacketView {
+            packet,
+            _iter: std::marker::PhantomData,
+        })
+    }
+}
+
+/// Packet borrowing the iterator it was read from
+///
+/// Returned by `PcapIter::next_view()`. The packet's contents are in a buffer
+/// owned by libpcap and valid only until the next packet is read from the
+/// capture. Holding the borrow of the iterator makes the compiler enforce that
+/// for reads through the same iterator, but not for other reads from the
+/// capture.
+pub struct PacketView<'i> {
+    packet: BorrowedPacket,
+    _iter: std::marker::PhantomData<&'i mut ()>,
+}
+
+impl<'i> PacketView<'i> {
+    /// Copy the contents of the packet into `OwnedPacket`, which can be kept
+    /// after reading further packets.
+    pub fn to_owned(&self) -> OwnedPacket {
+        let mut packet = OwnedPacket::with_capacity(self.len());
+        self.packet.copy_to_owned(&mut packet);
+        packet
+    }
+}
+
+impl<'i> Packet for PacketView<'i> {
+    fn timestamp(&self) -> std::time::SystemTime {
+        self.packet.timestamp()
+    }
+
+    fn packet(&self) -> &[u8] {
+        self.packet.packet()
+    }
+
+    fn to_vec(self) -> Vec<u8> {
+        self.packet.to_vec()
+    }
+
+    fn len(&self) -> usize {
+        self.packet.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.packet.is_empty()
+    }
+
+    fn pkthdr(&self) -> &libpcap::pcap_pkthdr {
+        self.packet.pkthdr()
+    }
 }
 
 impl<'p> Iterator for PcapIter<'p> {
@@ -360,6 +1861,65 @@ impl<'p> Iterator for PcapIter<'p> {
     }
 }
 
+/// Pcap capture iterator reading packets in batches
+///
+/// Returned by `Pcap::capture_batched()`. Like with `PcapIter`, a
+/// `BorrowedPacket` returned from the iterator is valid only until next call
+/// of `next()`. Iteration ends on error or when a batch has no packets, that
+/// is at the end of a savefile or when the packet buffer timeout expires.
+pub struct PcapBatchIter<'p> {
+    pcap_t: &'p PcapT,
+    batch_size: usize,
+    // Contents of all the packets in current batch.
+    data: Vec<u8>,
+    // Headers and offsets into `data` of packets in current batch.
+    headers: Vec<(libpcap::pcap_pkthdr, usize)>,
+    pos: usize,
+}
+
+impl<'p> PcapBatchIter<'p> {
+    fn new(pcap_t: &'p PcapT, batch_size: usize) -> Self {
+        PcapBatchIter {
+            pcap_t,
+            batch_size,
+            data: Vec::new(),
+            headers: Vec::with_capacity(batch_size),
+            pos: 0,
+        }
+    }
+
+    // Read the next batch of packets. Returns false if no packets were read.
+    fn fill(&mut self) -> bool {
+        self.data.clear();
+        self.headers.clear();
+        self.pos = 0;
+
+        let data = &mut self.data;
+        let headers = &mut self.headers;
+        let cnt = i32::try_from(self.batch_size).unwrap_or(i32::MAX);
+        let ret = pcap_dispatch(self.pcap_t, cnt, |packet| {
+            headers.push((*packet.pkthdr(), data.len()));
+            data.extend_from_slice(packet.packet());
+        });
+
+        matches!(ret, Ok(n) if n > 0) && !self.headers.is_empty()
+    }
+}
+
+impl<'p> Iterator for PcapBatchIter<'p> {
+    type Item = BorrowedPacket;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos == self.headers.len() && !self.fill() {
+            return None;
+        }
+
+        let (pkthdr, offset) = &self.headers[self.pos];
+        self.pos += 1;
+        Some(BorrowedPacket::new(pkthdr, self.data[*offset..].as_ptr()))
+    }
+}
+
 /// Pcap capture statistics
 pub struct PcapStat {
     stats: libpcap::pcap_stat,
@@ -456,6 +2016,24 @@ impl Drop for PcapIfT {
     }
 }
 
+/// get a list of capture devices sorted by name
+///
+/// Like `PcapIfT::new()` and `PcapIfT::get_interfaces()`, but the devices
+/// are sorted by name. Printing the list with their index gives the output of
+/// `tcpdump -D`:
+///
+/// ```no_run
+/// for (n, interface) in luomu_libpcap::interfaces()?.iter().enumerate() {
+///     println!("{}.{}", n + 1, interface);
+/// }
+/// # Ok::<(), luomu_libpcap::Error>(())
+/// ```
+pub fn interfaces() -> Result<Vec<Interface>> {
+    let mut interfaces: Vec<Interface> = PcapIfT::new()?.iter().collect();
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(interfaces)
+}
+
 /// A network device that can be opened with `Pcap::new()` and
 /// `Pcap::builder()`.
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -466,24 +2044,28 @@ pub struct Interface {
     pub description: Option<String>,
     /// All addresses found from device
     pub addresses: BTreeSet<InterfaceAddress>,
-    /// Flags set for device
+    /// Flags set for device which have an [InterfaceFlag]. Derived from
+    /// `raw_flags`, which is what the methods of `Interface` check.
     pub flags: BTreeSet<InterfaceFlag>,
+    /// All flags reported for device, including the ones without an
+    /// [InterfaceFlag]
+    pub raw_flags: InterfaceFlags,
 }
 
 impl Interface {
     /// True if interface is up
     pub fn is_up(&self) -> bool {
-        self.flags.get(&InterfaceFlag::Up).is_some()
+        self.raw_flags.contains(InterfaceFlags::UP)
     }
 
     /// True if interface is running
     pub fn is_running(&self) -> bool {
-        self.flags.get(&InterfaceFlag::Running).is_some()
+        self.raw_flags.contains(InterfaceFlags::RUNNING)
     }
 
     /// True if interface is loopback
     pub fn is_loopback(&self) -> bool {
-        self.flags.get(&InterfaceFlag::Loopback).is_some()
+        self.raw_flags.contains(InterfaceFlags::LOOPBACK)
     }
 
     /// True if interface is has name `name`
@@ -511,7 +2093,49 @@ impl Interface {
 
     /// True if interface is has IP address `ip`
     pub fn has_address(&self, ip: &IpAddr) -> bool {
-        self.get_ip_addresses().get(ip).is_some()
+        self.get_ip_addresses().contains(ip)
+    }
+
+    /// Return IP addresses of the peers of a point-to-point interface
+    ///
+    /// Point-to-point interfaces like tun and ppp devices have a destination
+    /// address, the address of the other end of the link, for their
+    /// addresses. Returns an empty set for other interfaces.
+    pub fn peer_addresses(&self) -> HashSet<IpAddr> {
+        self.addresses
+            .iter()
+            .filter_map(|i| i.destination()?.as_ip())
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Interface {
+    /// Formats the interface like `tcpdump -D` does: name, description in
+    /// parentheses and flags in brackets, e.g. `lo [Loopback, Up, Running]`.
+    ///
+    /// The alternate form `{:#}` lists the addresses of the interface too,
+    /// one per line after the interface:
+    ///
+    /// ```text
+    /// lo [Loopback, Up, Running]
+    ///     127.0.0.1/8
+    ///     ::1/128
+    /// ```
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(description) = &self.description {
+            write!(f, " ({})", description)?;
+        }
+        let flags: Vec<String> = self.raw_flags.iter().map(|flag| flag.to_string()).collect();
+        if !flags.is_empty() {
+            write!(f, " [{}]", flags.join(", "))?;
+        }
+        if f.alternate() {
+            for address in &self.addresses {
+                write!(f, "\n    {}", address)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -571,6 +2195,141 @@ pub struct InterfaceAddress {
     /// The destination address corresponding to the address pointed to by addr;
     /// may be `None` if the device isn't a point-to-point interface.
     dstaddr: Option<Address>,
+    /// Scope id of an IPv6 address, `None` for addresses without a scope,
+    /// like global addresses.
+    scope_id: Option<u32>,
+}
+
+impl InterfaceAddress {
+    /// Address of the interface
+    pub fn addr(&self) -> &Address {
+        &self.addr
+    }
+
+    /// Scope id of the address
+    ///
+    /// Link-local IPv6 addresses like `fe80::1` are the same on every link
+    /// and are only unique together with the scope id, which is the index of
+    /// the interface the address is on. Returns `None` for IPv4 addresses
+    /// and IPv6 addresses without a scope.
+    pub fn scope_id(&self) -> Option<u32> {
+        self.scope_id
+    }
+
+    /// Socket address of the address and port `port`, with the scope id of
+    /// IPv6 addresses
+    ///
+    /// Returns `None` if the address is not an IP address.
+    pub fn socket_addr(&self, port: u16) -> Option<SocketAddr> {
+        match &self.addr {
+            Address::Ipv6(addr) => {
+                Some(SocketAddrV6::new(*addr, port, 0, self.scope_id.unwrap_or(0)).into())
+            }
+            addr => Some(SocketAddr::new(addr.as_ip()?, port)),
+        }
+    }
+
+    /// Netmask of the address, if any
+    pub fn netmask(&self) -> Option<&Address> {
+        self.netmask.as_ref()
+    }
+
+    /// Destination address of a point-to-point interface, if any
+    ///
+    /// This is the address of the peer on the other end of the link, e.g.
+    /// the remote end of a tunnel, and `None` if the interface isn't a
+    /// point-to-point interface.
+    pub fn destination(&self) -> Option<&Address> {
+        self.dstaddr.as_ref()
+    }
+
+    /// Network of the address
+    ///
+    /// Returns the address with the host bits cleared by the netmask, or
+    /// `None` if the address is not an IP address or has no netmask of the
+    /// same family.
+    pub fn network(&self) -> Option<IpAddr> {
+        match (&self.addr, self.netmask.as_ref()?) {
+            (Address::Ipv4(addr), Address::Ipv4(mask)) => {
+                Some(Ipv4Addr::from(u32::from(*addr) & u32::from(*mask)).into())
+            }
+            (Address::Ipv6(addr), Address::Ipv6(mask)) => {
+                Some(Ipv6Addr::from(u128::from(*addr) & u128::from(*mask)).into())
+            }
+            _ => None,
+        }
+    }
+
+    /// Broadcast address of the network
+    ///
+    /// Returns the broadcast address reported for the interface, or for
+    /// IPv4 addresses with a netmask the address with all host bits set if
+    /// none was reported. IPv6 has no broadcast addresses.
+    pub fn broadcast(&self) -> Option<IpAddr> {
+        if let Some(broadaddr) = self.broadaddr.as_ref().and_then(Address::as_ip) {
+            return Some(broadaddr);
+        }
+        match (&self.addr, self.netmask.as_ref()?) {
+            (Address::Ipv4(addr), Address::Ipv4(mask)) => {
+                Some(Ipv4Addr::from(u32::from(*addr) | !u32::from(*mask)).into())
+            }
+            _ => None,
+        }
+    }
+
+    /// True if `ip` is in the network of the address
+    ///
+    /// Compares `ip` against the network given by the address and its
+    /// netmask, answering whether traffic from `ip` is local to the
+    /// interface. Without a netmask only the address itself is in the
+    /// network.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        let addr = match self.addr.as_ip() {
+            Some(addr) => addr,
+            None => return false,
+        };
+        match (ip, self.netmask.as_ref()) {
+            (IpAddr::V4(ip), Some(Address::Ipv4(mask))) => {
+                let addr = IpAddr::V4(Ipv4Addr::from(u32::from(*ip) & u32::from(*mask)));
+                self.network() == Some(addr)
+            }
+            (IpAddr::V6(ip), Some(Address::Ipv6(mask))) => {
+                let addr = IpAddr::V6(Ipv6Addr::from(u128::from(*ip) & u128::from(*mask)));
+                self.network() == Some(addr)
+            }
+            _ => addr == *ip,
+        }
+    }
+}
+
+impl std::fmt::Display for InterfaceAddress {
+    /// Formats the address with the prefix length of its netmask, and the
+    /// broadcast or point-to-point destination address if any, e.g.
+    /// `192.168.1.20/24 broadcast 192.168.1.255`. IPv6 addresses with a scope
+    /// id are followed by it, like `fe80::1%2/64`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.addr)?;
+        if let Some(scope_id) = self.scope_id {
+            write!(f, "%{}", scope_id)?;
+        }
+        let prefix = match self.netmask.as_ref() {
+            Some(Address::Ipv4(mask)) if self.addr.is_ipv4() => Some(u32::from(*mask).count_ones()),
+            Some(Address::Ipv6(mask)) if self.addr.is_ipv6() => {
+                Some(u128::from(*mask).count_ones())
+            }
+            _ => None,
+        };
+        if let Some(prefix) = prefix {
+            write!(f, "/{}", prefix)?;
+        }
+        if let Some(broadaddr) = &self.broadaddr {
+            write!(f, " broadcast {}", broadaddr)?;
+        }
+        if let Some(dstaddr) = &self.dstaddr {
+            write!(f, " peer {}", dstaddr)?;
+        }
+        Ok(())
+    }
 }
 
 /// Iterator for network device's addresses.
@@ -624,3 +2383,83 @@ pub enum InterfaceFlag {
     /// set if the interface is running
     Running,
 }
+
+impl std::fmt::Display for InterfaceFlag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            InterfaceFlag::Loopback => "Loopback",
+            InterfaceFlag::Up => "Up",
+            InterfaceFlag::Running => "Running",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::BTreeSet;
+    use std::time::Duration;
+
+    use luomu_common::Address;
+
+    use crate::savefile::tests::savefile;
+    use crate::{Interface, InterfaceAddress, InterfaceFlags, Packet, Pcap};
+
+    // Interface `name` with flags `raw_flags` and no addresses.
+    pub(crate) fn interface(name: &str, raw_flags: InterfaceFlags) -> Interface {
+        Interface {
+            name: name.to_string(),
+            description: None,
+            addresses: BTreeSet::new(),
+            flags: raw_flags.iter().collect(),
+            raw_flags,
+        }
+    }
+
+    // Address `addr` with netmask `netmask` and no other addresses or scope.
+    pub(crate) fn interface_address(addr: Address, netmask: Option<Address>) -> InterfaceAddress {
+        InterfaceAddress {
+            addr,
+            netmask,
+            broadaddr: None,
+            dstaddr: None,
+            scope_id: None,
+        }
+    }
+
+    #[test]
+    fn test_first_matching_savefile() {
+        let path = std::env::temp_dir().join(format!(
+            "luomu-libpcap-first-matching-{}.pcap",
+            std::process::id()
+        ));
+        let packets: &[&[u8]] = &[b"Hello", b"world!", b"longer packet", b"tail"];
+        std::fs::write(&path, savefile(false, false, packets)).unwrap();
+
+        let pcap = Pcap::offline(&path).unwrap();
+        pcap.set_filter("less 30").unwrap();
+        let packet = pcap
+            .first_matching("greater 20", Duration::ZERO)
+            .unwrap()
+            .unwrap();
+        assert_eq!(packet.packet(), b"longer packet");
+        // The previous filter is installed again, but not recorded anew.
+        assert_eq!(
+            pcap.filter.borrow().as_ref().map(|f| f.expression()),
+            Some("less 30")
+        );
+        assert_eq!(pcap.capture().next().unwrap().packet(), b"tail");
+        assert!(pcap
+            .first_matching("greater 20", Duration::ZERO)
+            .unwrap()
+            .is_none());
+
+        let pcap = Pcap::offline(&path).unwrap();
+        let packet = pcap.first_matching("len = 16", Duration::ZERO).unwrap();
+        assert_eq!(packet.unwrap().packet(), b"world!");
+        assert!(pcap.filter.borrow().is_none());
+        assert_eq!(pcap.capture().count(), 2);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
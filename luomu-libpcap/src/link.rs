@@ -0,0 +1,142 @@
+//! Link-layer header fields of packets
+//!
+//! [LinkLayer] adds accessors for link-layer fields to all [Packet]s, so that
+//! simple tools can look at MAC addresses, VLAN tags and EtherTypes without a
+//! packet parsing crate. Packets don't know their link-layer header type, so
+//! accessors other than `ethernet()` take it as an argument, as returned by
+//! `Pcap::datalink()`.
+
+use luomu_common::MacAddr;
+
+use crate::headers::link_header;
+use crate::{LinkType, Packet};
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+
+/// Fields of an Ethernet header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Ethernet {
+    /// Destination MAC address.
+    pub destination: MacAddr,
+    /// Source MAC address.
+    pub source: MacAddr,
+    /// VLAN ID of the outermost 802.1Q or 802.1ad tag, if any.
+    pub vlan: Option<u16>,
+    /// VLAN ID of the second tag of a double-tagged (QinQ) frame, if any.
+    pub inner_vlan: Option<u16>,
+    /// EtherType of the payload, after any VLAN tags.
+    pub ethertype: u16,
+    /// Offset of the payload from the start of the packet.
+    pub payload_offset: usize,
+}
+
+/// Accessors for link-layer header fields of packets.
+pub trait LinkLayer: Packet {
+    /// Fields of the Ethernet header of a packet captured on an Ethernet
+    /// link (`LinkType::ETHERNET`), or `None` if the packet is too short.
+    fn ethernet(&self) -> Option<Ethernet> {
+        let packet = self.packet();
+        let (payload_offset, ethertype) = link_header(LinkType::ETHERNET, packet)?;
+        let vlan_id = |offset: usize| {
+            (payload_offset > offset + 2)
+                .then(|| u16::from_be_bytes([packet[offset + 2], packet[offset + 3]]) & 0x0fff)
+        };
+        Some(Ethernet {
+            destination: MacAddr::from(<[u8; 6]>::try_from(&packet[0..6]).ok()?),
+            source: MacAddr::from(<[u8; 6]>::try_from(&packet[6..12]).ok()?),
+            vlan: vlan_id(12),
+            inner_vlan: vlan_id(16),
+            ethertype: ethertype?,
+            payload_offset,
+        })
+    }
+
+    /// Offset of the network layer header from the start of a packet with
+    /// link-layer header type `linktype`, or `None` if the type is not
+    /// supported or the packet is too short.
+    ///
+    /// Ethernet, Linux cooked capture (v1 and v2), BSD loopback and raw IP
+    /// are supported.
+    fn network_offset(&self, linktype: LinkType) -> Option<usize> {
+        link_header(linktype, self.packet()).map(|(offset, _)| offset)
+    }
+
+    /// EtherType of the network layer protocol of a packet with link-layer
+    /// header type `linktype`.
+    ///
+    /// For link-layer header types without a protocol field, like raw IP,
+    /// the EtherType of IPv4 or IPv6 is returned based on the IP version.
+    fn ethertype(&self, linktype: LinkType) -> Option<u16> {
+        let packet = self.packet();
+        match link_header(linktype, packet)? {
+            (_, Some(ethertype)) => Some(ethertype),
+            (offset, None) => match packet.get(offset)? >> 4 {
+                4 => Some(ETHERTYPE_IPV4),
+                6 => Some(ETHERTYPE_IPV6),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl<P: Packet + ?Sized> LinkLayer for P {}
+
+#[cfg(test)]
+mod tests {
+    use luomu_common::MacAddr;
+
+    use super::LinkLayer;
+    use crate::{LinkType, OwnedPacket};
+
+    fn owned(bytes: &[u8]) -> OwnedPacket {
+        let header = luomu_libpcap_sys::pcap_pkthdr {
+            ts: libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            caplen: bytes.len() as u32,
+            len: bytes.len() as u32,
+        };
+        OwnedPacket::new(header, bytes.to_vec())
+    }
+
+    #[test]
+    fn test_link_ethernet() {
+        let mut bytes = vec![0xff; 6];
+        bytes.extend_from_slice(&[2, 0, 0, 0, 0, 1]);
+        bytes.extend_from_slice(&[0x88, 0xa8, 0x00, 0x64, 0x81, 0x00, 0x20, 0x0a]);
+        bytes.extend_from_slice(&[0x86, 0xdd, 0x60, 0, 0, 0]);
+        let packet = owned(&bytes);
+
+        let ethernet = packet.ethernet().unwrap();
+        assert!(ethernet.destination.is_broadcast());
+        assert_eq!(ethernet.source, MacAddr::from([2, 0, 0, 0, 0, 1]));
+        assert_eq!(ethernet.vlan, Some(100));
+        assert_eq!(ethernet.inner_vlan, Some(10));
+        assert_eq!(ethernet.ethertype, 0x86dd);
+        assert_eq!(ethernet.payload_offset, 22);
+        assert_eq!(packet.network_offset(LinkType::ETHERNET), Some(22));
+
+        let untagged = super::Ethernet {
+            vlan: None,
+            inner_vlan: None,
+            ethertype: 0x0800,
+            payload_offset: 14,
+            ..ethernet
+        };
+        bytes.drain(12..20);
+        bytes[12..14].copy_from_slice(&[0x08, 0x00]);
+        assert_eq!(owned(&bytes).ethernet(), Some(untagged));
+
+        assert_eq!(owned(&bytes[..10]).ethernet(), None);
+    }
+
+    #[test]
+    fn test_link_ethertype() {
+        let packet = owned(&[0x45, 0, 0, 20]);
+        assert_eq!(packet.ethertype(LinkType::RAW), Some(0x0800));
+        assert_eq!(packet.network_offset(LinkType::RAW), Some(0));
+        assert_eq!(packet.ethertype(LinkType::IEEE802_11), None);
+    }
+}
@@ -0,0 +1,151 @@
+use std::fmt;
+use std::str::FromStr;
+
+use luomu_libpcap_sys as libpcap;
+
+use crate::functions::{
+    pcap_datalink_name_to_val, pcap_datalink_val_to_description,
+    pcap_datalink_val_to_description_or_dlt, pcap_datalink_val_to_name,
+};
+use crate::Error;
+
+/// Link-layer header type of a capture handle or a savefile.
+///
+/// Wraps the `DLT_*` value used by libpcap. `LinkType` is displayed with its
+/// libpcap name (for example "EN10MB") and can be parsed from either a name
+/// or a plain number, which makes it suitable for configuration files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LinkType(pub i32);
+
+impl LinkType {
+    /// BSD loopback encapsulation (`DLT_NULL`).
+    pub const NULL: LinkType = LinkType(libpcap::DLT_NULL as i32);
+    /// Ethernet (`DLT_EN10MB`).
+    pub const ETHERNET: LinkType = LinkType(libpcap::DLT_EN10MB as i32);
+    /// Raw IP (`DLT_RAW`).
+    pub const RAW: LinkType = LinkType(libpcap::DLT_RAW as i32);
+    /// IEEE 802.11 wireless LAN (`DLT_IEEE802_11`).
+    pub const IEEE802_11: LinkType = LinkType(libpcap::DLT_IEEE802_11 as i32);
+    /// OpenBSD loopback encapsulation (`DLT_LOOP`).
+    pub const LOOP: LinkType = LinkType(libpcap::DLT_LOOP as i32);
+    /// Linux "cooked" capture encapsulation (`DLT_LINUX_SLL`).
+    pub const LINUX_SLL: LinkType = LinkType(libpcap::DLT_LINUX_SLL as i32);
+    /// Radiotap link-layer information followed by 802.11 header
+    /// (`DLT_IEEE802_11_RADIO`).
+    pub const IEEE802_11_RADIO: LinkType = LinkType(libpcap::DLT_IEEE802_11_RADIO as i32);
+    /// Linux "cooked" capture encapsulation v2 (`DLT_LINUX_SLL2`).
+    pub const LINUX_SLL2: LinkType = LinkType(libpcap::DLT_LINUX_SLL2 as i32);
+    /// Raw IPv4 (`DLT_IPV4`).
+    pub const IPV4: LinkType = LinkType(libpcap::DLT_IPV4 as i32);
+    /// Raw IPv6 (`DLT_IPV6`).
+    pub const IPV6: LinkType = LinkType(libpcap::DLT_IPV6 as i32);
+
+    /// Returns true if packets have no link-layer header but start with an
+    /// IPv4 or IPv6 header, as on tun devices and many VPN interfaces.
+    ///
+    /// Besides [LinkType::RAW], [LinkType::IPV4] and [LinkType::IPV6],
+    /// `LINKTYPE_RAW` of savefiles (101) is raw IP, and on OpenBSD also the
+    /// value of its `DLT_RAW` (14). Elsewhere 14 is another link-layer header
+    /// type.
+    pub fn is_raw_ip(self) -> bool {
+        matches!(self, LinkType::RAW | LinkType::IPV4 | LinkType::IPV6)
+            || self.0 == 101
+            || (cfg!(target_os = "openbsd") && self.0 == 14)
+    }
+
+    /// Returns the `DLT_*` value of this link-layer header type.
+    pub fn value(self) -> i32 {
+        self.0
+    }
+
+    /// Returns the libpcap name of the link-layer header type, which is the
+    /// `DLT_` name with the `DLT_` removed. `None` is returned if the type is
+    /// not known to libpcap.
+    pub fn name(self) -> Option<String> {
+        pcap_datalink_val_to_name(self.0)
+    }
+
+    /// Returns a short description of the link-layer header type. `None` is
+    /// returned if the type is not known to libpcap.
+    pub fn description(self) -> Option<String> {
+        pcap_datalink_val_to_description(self.0)
+    }
+
+    /// Returns a short description of the link-layer header type, or "DLT n"
+    /// if the type is not known to libpcap.
+    pub fn description_or_dlt(self) -> String {
+        pcap_datalink_val_to_description_or_dlt(self.0)
+    }
+}
+
+impl From<i32> for LinkType {
+    fn from(v: i32) -> Self {
+        LinkType(v)
+    }
+}
+
+impl From<LinkType> for i32 {
+    fn from(v: LinkType) -> Self {
+        v.0
+    }
+}
+
+impl fmt::Display for LinkType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(&name),
+            None => write!(f, "DLT {}", self.0),
+        }
+    }
+}
+
+impl FromStr for LinkType {
+    type Err = Error;
+
+    /// Parses a link-layer header type from a libpcap name (case-insensitive,
+    /// with or without the `DLT_` prefix) or from a `DLT_*` number.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(v) = s.parse::<i32>() {
+            return Ok(LinkType(v));
+        }
+        let name = match s.get(..4) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("DLT_") => &s[4..],
+            _ => s,
+        };
+        pcap_datalink_name_to_val(name).map(LinkType)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LinkType;
+
+    #[test]
+    fn test_linktype_from_number() {
+        assert_eq!("1".parse::<LinkType>().unwrap(), LinkType::ETHERNET);
+        assert_eq!(" 101 ".parse::<LinkType>().unwrap(), LinkType(101));
+        assert_eq!(i32::from(LinkType::ETHERNET), 1);
+    }
+
+    #[test]
+    fn test_linktype_is_raw_ip() {
+        assert!(LinkType::RAW.is_raw_ip());
+        assert!(LinkType(101).is_raw_ip());
+        assert!(LinkType::IPV6.is_raw_ip());
+        assert!(!LinkType::ETHERNET.is_raw_ip());
+        assert!(!LinkType::NULL.is_raw_ip());
+        assert_eq!(LinkType(14).is_raw_ip(), cfg!(target_os = "openbsd"));
+    }
+
+    #[test]
+    fn test_linktype_names() {
+        assert_eq!("EN10MB".parse::<LinkType>().unwrap(), LinkType::ETHERNET);
+        assert_eq!("dlt_raw".parse::<LinkType>().unwrap(), LinkType::RAW);
+        assert!("no-such-link".parse::<LinkType>().is_err());
+        assert_eq!(LinkType::ETHERNET.to_string(), "EN10MB");
+        assert_eq!(LinkType(-42).to_string(), "DLT -42");
+        assert_eq!(LinkType(-42).description_or_dlt(), "DLT -42");
+        assert!(LinkType::ETHERNET.description().is_some());
+    }
+}
@@ -0,0 +1,82 @@
+use crate::{Error, LinkType, PcapIfT, Result};
+
+/// Name of the Npcap loopback adapter on Windows.
+///
+/// Npcap captures traffic to and from localhost on this adapter, with
+/// `LinkType::NULL` framing like the loopback interfaces of BSDs and macOS.
+pub const NPCAP_LOOPBACK: &str = "\\Device\\NPF_Loopback";
+
+const AF_INET: u32 = 2;
+#[cfg(unix)]
+const AF_INET6: u32 = libc::AF_INET6 as u32;
+#[cfg(windows)]
+const AF_INET6: u32 = 23;
+
+/// Name of the loopback capture device.
+///
+/// Returns the first device flagged as loopback, such as `lo` on Linux,
+/// `lo0` on BSDs and macOS, and the Npcap loopback adapter on Windows.
+pub fn loopback_device() -> Result<String> {
+    PcapIfT::new()?
+        .iter()
+        .find(|interface| interface.is_loopback() || interface.name == NPCAP_LOOPBACK)
+        .map(|interface| interface.name)
+        .ok_or_else(|| Error::NoSuchDevice(String::from("loopback")))
+}
+
+/// Frame an IPv4 or IPv6 packet for injecting on a link with link-layer
+/// header type `linktype`.
+///
+/// Loopback links don't have Ethernet headers. Packets on `LinkType::NULL`
+/// links, like the Npcap loopback adapter, start with the address family in
+/// host byte order, and on `LinkType::LOOP` links in network byte order.
+/// Packets for raw IP links, see `LinkType::is_raw_ip()`, are returned as
+/// they are.
+pub fn loopback_frame(linktype: LinkType, packet: &[u8]) -> Result<Vec<u8>> {
+    let family = match packet.first().map(|b| b >> 4) {
+        Some(4) => AF_INET,
+        Some(6) => AF_INET6,
+        _ => return Err(Error::PcapError(String::from("not an IP packet"))),
+    };
+    let header = match linktype {
+        LinkType::NULL => family.to_ne_bytes(),
+        LinkType::LOOP => family.to_be_bytes(),
+        _ if linktype.is_raw_ip() => return Ok(packet.to_vec()),
+        _ => {
+            return Err(Error::PcapError(format!(
+                "can't frame IP packet for link-layer header type {}",
+                linktype.value()
+            )))
+        }
+    };
+    let mut frame = Vec::with_capacity(header.len() + packet.len());
+    frame.extend_from_slice(&header);
+    frame.extend_from_slice(packet);
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{loopback_frame, AF_INET6};
+    use crate::LinkType;
+
+    #[test]
+    fn test_loopback_frame() {
+        let ipv4 = [0x45, 0, 0, 20];
+        let frame = loopback_frame(LinkType::NULL, &ipv4).unwrap();
+        assert_eq!(&frame[..4], &2u32.to_ne_bytes());
+        assert_eq!(&frame[4..], &ipv4);
+
+        let ipv6 = [0x60, 0, 0, 0];
+        let frame = loopback_frame(LinkType::LOOP, &ipv6).unwrap();
+        assert_eq!(&frame[..4], &AF_INET6.to_be_bytes());
+
+        assert_eq!(loopback_frame(LinkType::RAW, &ipv4).unwrap(), ipv4);
+        assert!(loopback_frame(LinkType::ETHERNET, &ipv4).is_err());
+        assert_eq!(
+            loopback_frame(LinkType(14), &ipv4).is_ok(),
+            cfg!(target_os = "openbsd")
+        );
+        assert!(loopback_frame(LinkType::NULL, &[0x10]).is_err());
+    }
+}
@@ -0,0 +1,248 @@
+//! Memory-mapped savefile reader
+//!
+//! [MmapSavefile] reads savefiles in classic pcap format without libpcap. The
+//! file is mapped into memory and packets are returned as slices into the
+//! mapping, so reading a packet involves neither a system call nor a copy.
+//! This is considerably faster than `Pcap::offline()` for scanning large
+//! files.
+//!
+//! pcapng files are not supported, use `Pcap::offline()` for them.
+
+use std::fs::File;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use luomu_libpcap_sys::pcap_pkthdr;
+use memmap2::Mmap;
+
+use crate::export::MAX_CAPLEN;
+use crate::savefile::{invalid_data, FileHeader, FILE_HEADER_LEN, RECORD_HEADER_LEN};
+use crate::{Packet, Result};
+
+/// A savefile mapped into memory.
+#[derive(Debug)]
+pub struct MmapSavefile {
+    mmap: Mmap,
+    header: FileHeader,
+}
+
+impl MmapSavefile {
+    /// Map a savefile into memory.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it is mapped, for
+    /// example by a capture still writing into it. Modifying the file
+    /// changes the contents of the returned packets under their feet, and
+    /// truncating it can crash the process.
+    pub unsafe fn open<P: AsRef<Path>>(savefile: P) -> Result<MmapSavefile> {
+        let file = File::open(savefile)?;
+        let mmap = Mmap::map(&file)?;
+        let header = FileHeader::parse(&mmap)?;
+        Ok(MmapSavefile { mmap, header })
+    }
+
+    /// Returns true if savefile uses different byte order than the current
+    /// system.
+    pub fn is_swapped(&self) -> bool {
+        self.header.swapped
+    }
+
+    /// Returns true if time stamps in savefile have nanosecond precision.
+    pub fn is_nanosecond(&self) -> bool {
+        self.header.nsec
+    }
+
+    /// Returns the version number of the savefile format as `(major, minor)`.
+    pub fn version(&self) -> (u16, u16) {
+        (self.header.version_major, self.header.version_minor)
+    }
+
+    /// Snapshot length of the savefile.
+    pub fn snaplen(&self) -> usize {
+        self.header.snaplen as usize
+    }
+
+    /// Link-layer header type of the savefile as `LINKTYPE_*` value.
+    ///
+    /// Note that `LINKTYPE_*` values stored in files are not always equal to
+    /// `DLT_*` values used by libpcap.
+    pub fn linktype(&self) -> u32 {
+        self.header.linktype
+    }
+
+    /// Returns iterator over packets in the savefile.
+    pub fn packets(&self) -> MmapPackets<'_> {
+        MmapPackets {
+            data: &self.mmap,
+            offset: FILE_HEADER_LEN,
+            header: &self.header,
+        }
+    }
+}
+
+/// Iterator over packets in [MmapSavefile].
+///
+/// Returns an error and stops if the savefile ends in the middle of a packet
+/// or a packet record is corrupted.
+#[derive(Debug)]
+pub struct MmapPackets<'a> {
+    data: &'a [u8],
+    offset: usize,
+    header: &'a FileHeader,
+}
+
+impl<'a> Iterator for MmapPackets<'a> {
+    type Item = Result<MmapPacket<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.data[self.offset..];
+        if rest.is_empty() {
+            return None;
+        }
+
+        // Stop after the first error.
+        self.offset = self.data.len();
+
+        if rest.len() < RECORD_HEADER_LEN {
+            return Some(Err(invalid_data("truncated packet record header")));
+        }
+        let record = self.header.record(rest);
+        let caplen = record.caplen as usize;
+        if caplen > MAX_CAPLEN {
+            return Some(Err(invalid_data("packet record too large")));
+        }
+        let end = RECORD_HEADER_LEN + caplen;
+        if rest.len() < end {
+            return Some(Err(invalid_data("truncated packet record")));
+        }
+
+        let nanos = if self.header.nsec {
+            record.ts_frac
+        } else {
+            record.ts_frac.saturating_mul(1000)
+        };
+        let packet = MmapPacket {
+            pkthdr: pcap_pkthdr {
+                ts: libc::timeval {
+                    tv_sec: record.ts_sec as _,
+                    tv_usec: (nanos / 1000) as _,
+                },
+                caplen: record.caplen,
+                len: record.len,
+            },
+            nanos,
+            data: &rest[RECORD_HEADER_LEN..end],
+        };
+
+        self.offset = self.data.len() - rest.len() + end;
+        Some(Ok(packet))
+    }
+}
+
+/// A packet borrowed from [MmapSavefile].
+#[derive(Clone, Copy, Debug)]
+pub struct MmapPacket<'a> {
+    pkthdr: pcap_pkthdr,
+    nanos: u32,
+    data: &'a [u8],
+}
+
+impl<'a> MmapPacket<'a> {
+    /// Get the contents of a packet with the lifetime of the mapping.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+impl Packet for MmapPacket<'_> {
+    /// Returns the time stamp with full precision of the savefile.
+    fn timestamp(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::new(self.pkthdr.ts.tv_sec as u64, self.nanos)
+    }
+
+    fn packet(&self) -> &[u8] {
+        self.data
+    }
+
+    fn to_vec(self) -> Vec<u8> {
+        self.data.to_vec()
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the packet header. The time stamp in the header has microsecond
+    /// precision.
+    fn pkthdr(&self) -> &pcap_pkthdr {
+        &self.pkthdr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::MmapSavefile;
+    use crate::savefile::tests::savefile;
+    use crate::Packet;
+
+    fn write_savefile(name: &str, data: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "luomu-libpcap-mmap-{}-{}.pcap",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_mmap_packets() {
+        for (swapped, nsec) in [(false, false), (true, true)] {
+            let data = savefile(swapped, nsec, &[b"Hello", b"world!"]);
+            let path = write_savefile(&format!("packets-{}-{}", swapped, nsec), &data);
+            let file = unsafe { MmapSavefile::open(&path) }.unwrap();
+
+            assert_eq!(file.is_swapped(), swapped);
+            assert_eq!(file.is_nanosecond(), nsec);
+            assert_eq!(file.version(), (2, 4));
+            assert_eq!(file.snaplen(), 65535);
+            assert_eq!(file.linktype(), 1);
+
+            let packets = file.packets().collect::<crate::Result<Vec<_>>>().unwrap();
+            assert_eq!(packets.len(), 2);
+            assert_eq!(packets[0].bytes(), b"Hello");
+            assert_eq!(packets[1].packet(), b"world!");
+            assert_eq!(packets[1].pkthdr().len, 16);
+
+            let nanos = if nsec { 500 } else { 500_000 };
+            assert_eq!(packets[0].timestamp(), UNIX_EPOCH + Duration::new(1, nanos));
+
+            drop(file);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_mmap_truncated() {
+        let mut data = savefile(false, false, &[b"Hello", b"world!"]);
+        data.pop();
+        let path = write_savefile("truncated", &data);
+        let file = unsafe { MmapSavefile::open(&path) }.unwrap();
+
+        let mut packets = file.packets();
+        assert!(packets.next().unwrap().is_ok());
+        assert!(packets.next().unwrap().is_err());
+        assert!(packets.next().is_none());
+
+        drop(file);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use luomu_libpcap_sys::pcap_pkthdr;
+
+use crate::{Error, OwnedPacket, PacketSource, Result};
+
+/// In-memory [PacketSource] returning a scripted sequence of packets and
+/// errors.
+///
+/// Code generic over `PacketSource` can be tested with `MockSource` without
+/// privileges or real traffic. Packets and errors are returned in the order
+/// they were added, followed by `Error::Break` like at the end of a savefile.
+///
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// use luomu_libpcap::{Error, MockSource, Packet, PacketSource};
+///
+/// let mut source = MockSource::new()
+///     .packet([0xff; 60])
+///     .timeout()
+///     .packet_at(UNIX_EPOCH + Duration::from_secs(10), [0xff; 42]);
+///
+/// assert_eq!(source.next_packet()?.len(), 60);
+/// assert!(matches!(source.next_packet(), Err(Error::Timeout)));
+/// assert_eq!(source.next_packet()?.len(), 42);
+/// assert!(matches!(source.next_packet(), Err(Error::Break)));
+/// # Ok::<(), Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct MockSource {
+    script: VecDeque<Result<OwnedPacket>>,
+    // Timestamp of the last packet added.
+    timestamp: Option<SystemTime>,
+}
+
+impl MockSource {
+    /// Construct a new `MockSource` with nothing to return.
+    pub fn new() -> MockSource {
+        MockSource::default()
+    }
+
+    /// Add a packet with contents `bytes`. The packet is timestamped 1
+    /// millisecond after the packet added before it, or at the Unix epoch if
+    /// it's the first one.
+    pub fn packet<B: AsRef<[u8]>>(self, bytes: B) -> MockSource {
+        let timestamp = match self.timestamp {
+            Some(timestamp) => timestamp + Duration::from_millis(1),
+            None => UNIX_EPOCH,
+        };
+        self.packet_at(timestamp, bytes)
+    }
+
+    /// Add a packet with contents `bytes` and timestamp `timestamp`.
+    pub fn packet_at<B: AsRef<[u8]>>(mut self, timestamp: SystemTime, bytes: B) -> MockSource {
+        let bytes = bytes.as_ref();
+        let since_epoch = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let pkthdr = pcap_pkthdr {
+            ts: libc::timeval {
+                tv_sec: since_epoch.as_secs() as _,
+                tv_usec: since_epoch.subsec_micros() as _,
+            },
+            caplen: bytes.len() as u32,
+            len: bytes.len() as u32,
+        };
+        self.script
+            .push_back(Ok(OwnedPacket::new(pkthdr, bytes.to_vec())));
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Add packets with contents from `packets`, timestamped like with
+    /// `packet()`.
+    pub fn packets<I>(self, packets: I) -> MockSource
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        packets.into_iter().fold(self, MockSource::packet)
+    }
+
+    /// Add an `Error::Timeout`, like a packet buffer timeout on a quiet
+    /// interface.
+    pub fn timeout(self) -> MockSource {
+        self.error(Error::Timeout)
+    }
+
+    /// Add an error.
+    pub fn error(mut self, err: Error) -> MockSource {
+        self.script.push_back(Err(err));
+        self
+    }
+
+    /// Number of packets and errors not yet returned.
+    pub fn remaining(&self) -> usize {
+        self.script.len()
+    }
+}
+
+impl PacketSource for MockSource {
+    type Packet = OwnedPacket;
+
+    fn next_packet(&mut self) -> Result<OwnedPacket> {
+        self.script.pop_front().unwrap_or(Err(Error::Break))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::MockSource;
+    use crate::{Error, Packet, PacketSource};
+
+    #[test]
+    fn test_mock_source() {
+        let mut source = MockSource::new()
+            .packets([[1u8; 14], [2u8; 14]])
+            .error(Error::NoSuchDevice(String::from("eth0")))
+            .packet_at(UNIX_EPOCH + Duration::from_micros(1_500_000), [3u8; 20]);
+        assert_eq!(source.remaining(), 4);
+
+        let first = source.next_packet().unwrap();
+        assert_eq!(first.packet(), [1u8; 14]);
+        assert_eq!(first.timestamp(), UNIX_EPOCH);
+        let second = source.next_packet().unwrap();
+        assert_eq!(second.timestamp(), UNIX_EPOCH + Duration::from_millis(1));
+        assert!(matches!(source.next_packet(), Err(Error::NoSuchDevice(_))));
+        let third = source.next_packet().unwrap();
+        assert_eq!(third.len(), 20);
+        assert_eq!(third.pkthdr().len, 20);
+        assert_eq!(
+            third.timestamp(),
+            UNIX_EPOCH + Duration::from_micros(1_500_000)
+        );
+        assert!(matches!(source.next_packet(), Err(Error::Break)));
+        assert_eq!(source.remaining(), 0);
+    }
+}
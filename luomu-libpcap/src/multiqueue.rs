@@ -0,0 +1,163 @@
+use crate::{CaptureConfig, Pcap, Result};
+
+/// Packet fields hashed by [MultiQueue] to select a queue.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FlowHash {
+    /// Source and destination IP addresses.
+    Addresses,
+    /// Source and destination IP addresses, and ports of TCP, UDP and SCTP
+    /// packets.
+    ///
+    /// Non-first fragments of IPv4 packets don't have ports and are hashed by
+    /// addresses only, so they may end up in a different queue than the first
+    /// fragment.
+    #[default]
+    AddressesAndPorts,
+}
+
+/// Multi-queue capture balancing flows over several handles.
+///
+/// Opens a number of captures on the same device, each with a BPF filter
+/// selecting a disjoint slice of traffic by hashing fields of the packets.
+/// Reading each capture in its own thread spreads the packet processing over
+/// several cores without support for kernel fanout in libpcap.
+///
+/// The hash is a sum of the hashed fields, so both directions of a flow end
+/// up in the same queue. The last 32 bits of IPv6 addresses are hashed.
+/// Traffic other than IPv4 and IPv6, as well as VLAN tagged traffic, is all
+/// captured by the first queue.
+///
+/// Every packet is filtered by all the captures, so the cost of copying
+/// packets from the kernel is not divided.
+#[derive(Clone, Debug)]
+pub struct MultiQueue {
+    config: CaptureConfig,
+    queues: usize,
+    hash: FlowHash,
+}
+
+impl MultiQueue {
+    /// Construct a `MultiQueue` opening `queues` captures with options from
+    /// `config`.
+    ///
+    /// The filter in `config`, if any, is combined with the filters selecting
+    /// traffic of each queue. Values of `queues` less than 1 are treated as 1.
+    pub fn new(config: CaptureConfig, queues: usize) -> MultiQueue {
+        MultiQueue {
+            config,
+            queues: queues.max(1),
+            hash: FlowHash::default(),
+        }
+    }
+
+    /// Set the fields hashed to select a queue.
+    pub fn with_hash(mut self, hash: FlowHash) -> MultiQueue {
+        self.hash = hash;
+        self
+    }
+
+    /// Number of queues.
+    pub fn queues(&self) -> usize {
+        self.queues
+    }
+
+    /// Filter expression selecting traffic of `queue`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `queue` is not less than the number of queues.
+    pub fn filter(&self, queue: usize) -> Option<String> {
+        assert!(queue < self.queues, "queue {} out of range", queue);
+        if self.queues == 1 {
+            return self.config.filter.clone();
+        }
+
+        let slice = queue_filter(self.hash, queue, self.queues);
+        match &self.config.filter {
+            Some(filter) => Some(format!("({}) and ({})", filter, slice)),
+            None => Some(slice),
+        }
+    }
+
+    /// Open and activate the captures, one for each queue.
+    pub fn open(&self) -> Result<Vec<Pcap>> {
+        (0..self.queues)
+            .map(|queue| {
+                let mut config = self.config.clone();
+                config.filter = self.filter(queue);
+                log::debug!(
+                    "{}: opening queue {}/{}",
+                    config.source,
+                    queue + 1,
+                    self.queues
+                );
+                config.open()
+            })
+            .collect()
+    }
+}
+
+// Build filter expression selecting packets whose hash modulo `queues` is
+// `queue`.
+fn queue_filter(hash: FlowHash, queue: usize, queues: usize) -> String {
+    const IP_ADDRS: &str = "ip[12:4] + ip[16:4]";
+    const IP6_ADDRS: &str = "ip6[20:4] + ip6[36:4]";
+    // Ports are at the start of the transport header for all of these.
+    const IP_PORTS: &str = "ip[((ip[0] & 0xf) << 2):2] + ip[((ip[0] & 0xf) << 2) + 2:2]";
+    const IP6_PORTS: &str = "ip6[40:2] + ip6[42:2]";
+    const IP_HAS_PORTS: &str = "(ip[9] = 6 or ip[9] = 17 or ip[9] = 132) and ip[6:2] & 0x1fff = 0";
+    const IP6_HAS_PORTS: &str = "ip6[6] = 6 or ip6[6] = 17 or ip6[6] = 132";
+
+    let slice = |sum: &str| format!("({}) % {} = {}", sum, queues, queue);
+
+    let mut filter = match hash {
+        FlowHash::Addresses => format!(
+            "(ip and {}) or (ip6 and {})",
+            slice(IP_ADDRS),
+            slice(IP6_ADDRS)
+        ),
+        FlowHash::AddressesAndPorts => format!(
+            "(ip and ({has_ports}) and {ports}) or \
+             (ip and not ({has_ports}) and {addrs}) or \
+             (ip6 and ({has6_ports}) and {ports6}) or \
+             (ip6 and not ({has6_ports}) and {addrs6})",
+            has_ports = IP_HAS_PORTS,
+            ports = slice(&format!("{} + {}", IP_ADDRS, IP_PORTS)),
+            addrs = slice(IP_ADDRS),
+            has6_ports = IP6_HAS_PORTS,
+            ports6 = slice(&format!("{} + {}", IP6_ADDRS, IP6_PORTS)),
+            addrs6 = slice(IP6_ADDRS),
+        ),
+    };
+    if queue == 0 {
+        filter.push_str(" or not (ip or ip6)");
+    }
+    filter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FlowHash, MultiQueue};
+    use crate::CaptureConfig;
+
+    #[test]
+    fn test_multiqueue_filter() {
+        let mq = MultiQueue::new(CaptureConfig::new("eth0"), 4).with_hash(FlowHash::Addresses);
+        assert_eq!(
+            mq.filter(1).unwrap(),
+            "(ip and (ip[12:4] + ip[16:4]) % 4 = 1) or \
+             (ip6 and (ip6[20:4] + ip6[36:4]) % 4 = 1)"
+        );
+        assert!(mq.filter(0).unwrap().ends_with(" or not (ip or ip6)"));
+        assert!(mq.filter(3).unwrap().contains("% 4 = 3"));
+
+        let mut config = CaptureConfig::new("eth0");
+        config.filter = Some("tcp port 80".to_string());
+        let mq = MultiQueue::new(config, 2);
+        assert!(mq.filter(1).unwrap().starts_with("(tcp port 80) and ("));
+
+        let mq = MultiQueue::new(CaptureConfig::new("eth0"), 0);
+        assert_eq!(mq.queues(), 1);
+        assert_eq!(mq.filter(0), None);
+    }
+}
@@ -0,0 +1,40 @@
+use crate::{Error, PcapStat};
+
+/// Observer of lifecycle events of a capture.
+///
+/// An observer attached to a capture with `PcapBuilder::set_observer()` or
+/// to a session with `SessionManager::set_observer()` is told when the
+/// capture is activated, a filter is set, statistics are read and capturing
+/// fails, so management layers can follow captures without parsing logs.
+/// Each event comes with the name of the device of the capture, so one
+/// observer can be attached to many captures.
+///
+/// All methods do nothing by default. They are called from the thread using
+/// the capture, and should not block.
+pub trait CaptureObserver: Send + Sync {
+    /// The capture on `source` was activated.
+    fn on_activated(&self, source: &str) {
+        let _ = source;
+    }
+
+    /// Filter `filter` was installed on the capture on `source`. An empty
+    /// expression means the filter was removed.
+    fn on_filter_set(&self, source: &str, filter: &str) {
+        let _ = (source, filter);
+    }
+
+    /// Activating or capturing on `source` failed with `error`.
+    ///
+    /// Errors of reading packets are reported when they are read with
+    /// `PacketSource::next_packet()` or by a session. Timeouts and the end of
+    /// a capture are not errors.
+    fn on_error(&self, source: &str, error: &Error) {
+        let _ = (source, error);
+    }
+
+    /// Statistics `stats` were read from the capture on `source` with
+    /// `Pcap::stats()`.
+    fn on_stats(&self, source: &str, stats: &PcapStat) {
+        let _ = (source, stats);
+    }
+}
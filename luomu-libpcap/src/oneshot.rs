@@ -0,0 +1,165 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::functions::{pcap_datalink, pcap_next_ex, pcap_open_offline};
+use crate::{CaptureConfig, Error, LinkType, OwnedPacket, Pcap, PcapT, Progress, Result};
+
+// Packet buffer timeout of one-shot captures, which is how often the deadline
+// is checked when no packets arrive.
+const POLL_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Options of the captures opened by the helpers here.
+pub(crate) fn defaults(device: &str) -> CaptureConfig {
+    let mut config = CaptureConfig::new(device);
+    config.promiscuous = Some(false);
+    config.immediate = Some(true);
+    config.snaplen = Some(65535);
+    config
+}
+
+/// Open a capture on `device` with sensible defaults.
+///
+/// The capture is activated with promiscuous mode off, immediate mode on,
+/// snapshot length of 65535 and packet buffer timeout of 1 second. This is
+/// the capture for the common case of just looking at the packets of the
+/// host.
+///
+/// ```no_run
+/// use luomu_libpcap::Packet;
+///
+/// for packet in luomu_libpcap::sniff("eth0")?.capture() {
+///     println!("{} bytes", packet.len());
+/// }
+/// # Ok::<(), luomu_libpcap::Error>(())
+/// ```
+pub fn sniff(device: &str) -> Result<Pcap> {
+    let mut config = defaults(device);
+    config.timeout = Some(Duration::from_secs(1));
+    config.open()
+}
+
+/// Capture `n` packets matching `filter` on `device`.
+///
+/// Opens a capture on `device` with the defaults of [sniff()], sets `filter`,
+/// collects packets until `n` have been captured or `timeout` has passed, and
+/// closes the capture. If the time runs out, the packets captured by then are
+/// returned, so the result may have less than `n` packets. An empty `filter`
+/// captures all packets.
+///
+/// This is meant for scripts and tests. Long running captures should use
+/// [Pcap] directly.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// let packets = luomu_libpcap::capture_n("eth0", "udp port 53", 10, Duration::from_secs(5))?;
+/// println!("captured {} DNS packets", packets.len());
+/// # Ok::<(), luomu_libpcap::Error>(())
+/// ```
+pub fn capture_n(
+    device: &str,
+    filter: &str,
+    n: usize,
+    timeout: Duration,
+) -> Result<Vec<OwnedPacket>> {
+    let mut packets = Vec::new();
+    if n == 0 {
+        return Ok(packets);
+    }
+    let deadline = Instant::now() + timeout;
+
+    let mut config = defaults(device);
+    config.timeout = Some(POLL_TIMEOUT.min(timeout).max(Duration::from_millis(1)));
+    if !filter.is_empty() {
+        config.filter = Some(filter.to_string());
+    }
+    let pcap = config.open()?;
+
+    while packets.len() < n && Instant::now() < deadline {
+        match pcap_next_ex(&pcap) {
+            Ok(packet) => packets.push(OwnedPacket::from(packet)),
+            Err(Error::Timeout) => continue,
+            Err(Error::Break) => break,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(packets)
+}
+
+/// Read the packets of savefile `path`.
+///
+/// Returns an iterator of the packets in the file, copied into
+/// [OwnedPacket]s with their time stamps. The link-layer header type of the
+/// packets is given by `FilePackets::linktype()`. Iteration stops at the end
+/// of the file, or after an error reading the file is returned.
+///
+/// This is meant for scripts and tests. Going through large files is faster
+/// with `Pcap::offline()` and borrowed packets, or `mmap::MmapSavefile`.
+///
+/// ```no_run
+/// use luomu_libpcap::Packet;
+///
+/// let packets = luomu_libpcap::read_file("capture.pcap")?;
+/// let linktype = packets.linktype();
+/// for packet in packets {
+///     let packet = packet?;
+///     println!("{:?}: {:?}", packet.timestamp(), packet.layers(linktype));
+/// }
+/// # Ok::<(), luomu_libpcap::Error>(())
+/// ```
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<FilePackets> {
+    let pcap_t = pcap_open_offline(path)?;
+    let linktype = LinkType(pcap_datalink(&pcap_t)?);
+    Ok(FilePackets {
+        pcap_t: Some(pcap_t),
+        linktype,
+    })
+}
+
+/// Iterator of the packets of a savefile. See [read_file()].
+pub struct FilePackets {
+    // None after the end of the file or an error.
+    pcap_t: Option<PcapT>,
+    linktype: LinkType,
+}
+
+impl FilePackets {
+    /// Link-layer header type of the packets.
+    pub fn linktype(&self) -> LinkType {
+        self.linktype
+    }
+
+    /// Progress of reading the file, see `PcapT::progress()`. `None` after
+    /// the end of the file or an error.
+    pub fn progress(&self) -> Option<Progress> {
+        self.pcap_t.as_ref()?.progress()
+    }
+}
+
+impl Iterator for FilePackets {
+    type Item = Result<OwnedPacket>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = pcap_next_ex(self.pcap_t.as_ref()?).map(OwnedPacket::from);
+        match result {
+            Ok(packet) => Some(Ok(packet)),
+            Err(Error::Break) => {
+                self.pcap_t = None;
+                None
+            }
+            Err(err) => {
+                self.pcap_t = None;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for FilePackets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FilePackets")
+            .field("linktype", &self.linktype)
+            .field("finished", &self.pcap_t.is_none())
+            .finish()
+    }
+}
@@ -1,6 +1,17 @@
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use luomu_libpcap_sys::pcap_pkthdr;
+use smallvec::SmallVec;
+
+use crate::{Layers, LinkType};
+
+/// Packets up to this many bytes are stored inline in `OwnedPacket` without
+/// heap allocation.
+pub const INLINE_PACKET_LEN: usize = 128;
+
+type PacketBuf = SmallVec<[u8; INLINE_PACKET_LEN]>;
 
 /// A trait for handling Borrowed and Owned Packet data from libpcap.
 pub trait Packet {
@@ -28,16 +39,68 @@ pub trait Packet {
 
     /// Return a reference to the [pcap_pkthdr] structure of a packet.
     fn pkthdr(&self) -> &pcap_pkthdr;
+
+    /// Split the packet into link-layer, network and transport layers.
+    ///
+    /// `linktype` is the link-layer header type of the capture, as returned
+    /// by `Pcap::datalink()`.
+    fn layers(&self, linktype: LinkType) -> Layers<'_> {
+        Layers::new(linktype, self.packet())
+    }
+
+    /// Offset of the IPv4 or IPv6 header of the packet, or `None` if the
+    /// packet is not an IP packet. See [ip_offset()](crate::ip_offset).
+    fn ip_offset(&self, linktype: LinkType) -> Option<usize> {
+        crate::ip_offset(linktype, self.packet())
+    }
 }
 
 /// A network packet with ownership of the underlying bytes.
 ///
-/// Calling `OwnedPacket::to_vec()` consumes the type and returns the packet
-/// contents in `Vec<u8>` without doing a copy.
+/// Packets of at most [INLINE_PACKET_LEN] bytes, such as TCP ACKs and most DNS
+/// queries, are stored inline without allocating. Larger packets are stored
+/// on the heap and calling `OwnedPacket::to_vec()` consumes the type and
+/// returns the packet contents in `Vec<u8>` without doing a copy.
 #[derive(Clone, Debug)]
 pub struct OwnedPacket {
     header: pcap_pkthdr,
-    packet: Vec<u8>,
+    packet: PacketBuf,
+}
+
+impl OwnedPacket {
+    /// Construct a new `OwnedPacket` from header and packet contents.
+    pub(crate) fn new(header: pcap_pkthdr, packet: Vec<u8>) -> Self {
+        OwnedPacket {
+            header,
+            packet: SmallVec::from_vec(packet),
+        }
+    }
+
+    /// Truncate the captured bytes of the packet to `len` bytes. The length
+    /// of the packet on the wire is not changed.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.packet.truncate(len);
+        self.header.caplen = self.packet.len() as u32;
+    }
+
+    /// Construct an empty `OwnedPacket` with room for `capacity` bytes.
+    ///
+    /// The packet can be filled with `BorrowedPacket::copy_to_owned()` or
+    /// `Pcap::read_owned()` repeatedly without allocating once the capacity
+    /// is large enough for the captured packets, e.g. snapshot length.
+    pub fn with_capacity(capacity: usize) -> Self {
+        OwnedPacket {
+            header: pcap_pkthdr {
+                ts: libc::timeval {
+                    tv_sec: 0,
+                    tv_usec: 0,
+                },
+                caplen: 0,
+                len: 0,
+            },
+            packet: SmallVec::with_capacity(capacity),
+        }
+    }
 }
 
 impl Packet for OwnedPacket {
@@ -51,7 +114,7 @@ impl Packet for OwnedPacket {
     }
 
     fn to_vec(self) -> Vec<u8> {
-        self.packet
+        self.packet.into_vec()
     }
 
     fn len(&self) -> usize {
@@ -67,6 +130,80 @@ impl Packet for OwnedPacket {
     }
 }
 
+/// A network packet with contents shared by reference counting.
+///
+/// Cloning a `SharedPacket` doesn't copy the packet contents, so one captured
+/// packet can be handed to several consumers, like a dumper, an analyzer and
+/// a forwarder, possibly running in other threads.
+#[derive(Clone, Debug)]
+pub struct SharedPacket {
+    header: pcap_pkthdr,
+    packet: Arc<[u8]>,
+}
+
+impl SharedPacket {
+    /// Get the shared contents of the packet.
+    pub fn shared_bytes(&self) -> &Arc<[u8]> {
+        &self.packet
+    }
+}
+
+impl Packet for SharedPacket {
+    fn timestamp(&self) -> SystemTime {
+        let ts: libc::timeval = self.header.ts;
+        UNIX_EPOCH + Duration::new(ts.tv_sec as u64, (ts.tv_usec as u32) * 1000)
+    }
+
+    fn packet(&self) -> &[u8] {
+        &self.packet
+    }
+
+    fn to_vec(self) -> Vec<u8> {
+        self.packet.to_vec()
+    }
+
+    fn len(&self) -> usize {
+        self.packet.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.packet.is_empty()
+    }
+
+    fn pkthdr(&self) -> &pcap_pkthdr {
+        &self.header
+    }
+}
+
+impl PartialEq for SharedPacket {
+    fn eq(&self, other: &Self) -> bool {
+        self.packet() == other.packet()
+    }
+}
+
+impl Eq for SharedPacket {}
+
+impl Hash for SharedPacket {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.packet().hash(state)
+    }
+}
+
+impl From<OwnedPacket> for SharedPacket {
+    fn from(p: OwnedPacket) -> Self {
+        SharedPacket {
+            header: p.header,
+            packet: Arc::from(p.packet.as_slice()),
+        }
+    }
+}
+
+impl From<BorrowedPacket> for SharedPacket {
+    fn from(p: BorrowedPacket) -> Self {
+        p.to_shared()
+    }
+}
+
 /// A network packet captured by libpcap.
 ///
 /// This structure contains memory owned by `libpcap`. The `libpcap` owned data
@@ -90,9 +227,37 @@ impl BorrowedPacket {
     pub fn to_owned(self) -> OwnedPacket {
         OwnedPacket {
             header: unsafe { *(self.pkthdr) },
-            packet: self.to_vec(),
+            packet: SmallVec::from_slice(self.packet()),
         }
     }
+
+    /// Copy the contents of `BorrowedPacket` and turn it into `SharedPacket`.
+    pub fn to_shared(self) -> SharedPacket {
+        SharedPacket {
+            header: *self.pkthdr(),
+            packet: Arc::from(self.packet()),
+        }
+    }
+
+    /// Copy the contents of `BorrowedPacket` into `buf`.
+    ///
+    /// At most `buf.len()` bytes are copied; use `PacketHeader::copied()` to
+    /// get the number of bytes written and `PacketHeader::is_truncated()` to
+    /// check if the packet did not fit.
+    pub fn copy_into(&self, buf: &mut [u8]) -> PacketHeader {
+        let packet = self.packet();
+        let copied = packet.len().min(buf.len());
+        buf[..copied].copy_from_slice(&packet[..copied]);
+        PacketHeader::new(*self.pkthdr(), copied)
+    }
+
+    /// Copy the contents of `BorrowedPacket` into `packet`, reusing the
+    /// allocation of `packet`.
+    pub fn copy_to_owned(&self, packet: &mut OwnedPacket) {
+        packet.header = *self.pkthdr();
+        packet.packet.clear();
+        packet.packet.extend_from_slice(self.packet());
+    }
 }
 
 impl Packet for BorrowedPacket {
@@ -123,6 +288,130 @@ impl Packet for BorrowedPacket {
     }
 }
 
+/// Header of a packet copied into a caller-provided buffer.
+///
+/// Returned by `BorrowedPacket::copy_into()` and `Pcap::read_into()`.
+#[derive(Clone, Copy, Debug)]
+pub struct PacketHeader {
+    header: pcap_pkthdr,
+    copied: usize,
+}
+
+impl PacketHeader {
+    pub(crate) fn new(header: pcap_pkthdr, copied: usize) -> Self {
+        PacketHeader { header, copied }
+    }
+
+    /// get a timestamp of a packet
+    pub fn timestamp(&self) -> SystemTime {
+        let ts: libc::timeval = self.header.ts;
+        UNIX_EPOCH + Duration::new(ts.tv_sec as u64, (ts.tv_usec as u32) * 1000)
+    }
+
+    /// Length of captured packet.
+    pub fn caplen(&self) -> usize {
+        self.header.caplen as usize
+    }
+
+    /// Length of the packet on the wire.
+    pub fn len(&self) -> usize {
+        self.header.len as usize
+    }
+
+    /// Returns true if the packet on the wire had no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.header.len == 0
+    }
+
+    /// Number of bytes copied into the buffer.
+    pub fn copied(&self) -> usize {
+        self.copied
+    }
+
+    /// Returns true if the buffer was too small for the captured packet.
+    pub fn is_truncated(&self) -> bool {
+        self.copied < self.caplen()
+    }
+
+    /// Return a reference to the [pcap_pkthdr] structure of a packet.
+    pub fn pkthdr(&self) -> &pcap_pkthdr {
+        &self.header
+    }
+}
+
+// Packets are equal if their contents are equal, regardless of when they
+// were captured. `WithHeader` compares headers too.
+
+impl PartialEq for OwnedPacket {
+    fn eq(&self, other: &Self) -> bool {
+        self.packet() == other.packet()
+    }
+}
+
+impl Eq for OwnedPacket {}
+
+impl Hash for OwnedPacket {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.packet().hash(state)
+    }
+}
+
+impl PartialEq for BorrowedPacket {
+    fn eq(&self, other: &Self) -> bool {
+        self.packet() == other.packet()
+    }
+}
+
+impl Eq for BorrowedPacket {}
+
+impl Hash for BorrowedPacket {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.packet().hash(state)
+    }
+}
+
+impl PartialEq<BorrowedPacket> for OwnedPacket {
+    fn eq(&self, other: &BorrowedPacket) -> bool {
+        self.packet() == other.packet()
+    }
+}
+
+impl PartialEq<OwnedPacket> for BorrowedPacket {
+    fn eq(&self, other: &OwnedPacket) -> bool {
+        self.packet() == other.packet()
+    }
+}
+
+/// Packet compared and hashed by both its header and contents.
+///
+/// Plain packets are equal if their contents are equal. Wrapping them in
+/// `WithHeader` makes the timestamp, captured length and length on the wire
+/// significant too, for example to tell retransmissions apart.
+#[derive(Clone, Debug)]
+pub struct WithHeader<P>(pub P);
+
+impl<P: Packet> WithHeader<P> {
+    fn header(&self) -> (SystemTime, u32, u32) {
+        let pkthdr = self.0.pkthdr();
+        (self.0.timestamp(), pkthdr.caplen, pkthdr.len)
+    }
+}
+
+impl<P: Packet> PartialEq for WithHeader<P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.header() == other.header() && self.0.packet() == other.0.packet()
+    }
+}
+
+impl<P: Packet> Eq for WithHeader<P> {}
+
+impl<P: Packet> Hash for WithHeader<P> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.header().hash(state);
+        self.0.packet().hash(state);
+    }
+}
+
 impl From<BorrowedPacket> for OwnedPacket {
     fn from(p: BorrowedPacket) -> Self {
         p.to_owned()
@@ -135,7 +424,7 @@ mod tests {
 
     use luomu_libpcap_sys::pcap_pkthdr;
 
-    use crate::{BorrowedPacket, Packet};
+    use crate::{BorrowedPacket, OwnedPacket, Packet};
 
     const BUF: &[u8] = b"Hello world";
     const LEN: usize = BUF.len();
@@ -195,4 +484,82 @@ mod tests {
         assert!(!borrowed_packet().is_empty());
         assert!(!borrowed_packet().to_owned().is_empty());
     }
+
+    #[test]
+    fn test_packet_inline() {
+        let packet = borrowed_packet().to_owned();
+        assert!(!packet.packet.spilled());
+
+        let large = vec![0u8; super::INLINE_PACKET_LEN + 1];
+        let packet = OwnedPacket::new(PKTHDR, large.clone());
+        assert!(packet.packet.spilled());
+        assert_eq!(packet.to_vec(), large);
+    }
+
+    #[test]
+    fn test_packet_copy_into() {
+        let mut buf = [0u8; 64];
+        let hdr = borrowed_packet().copy_into(&mut buf);
+        assert_eq!(hdr.copied(), LEN);
+        assert!(!hdr.is_truncated());
+        assert_eq!(&buf[..hdr.copied()], BUF);
+        assert_eq!(hdr.timestamp(), timestamp());
+
+        let mut small = [0u8; 5];
+        let hdr = borrowed_packet().copy_into(&mut small);
+        assert_eq!(hdr.copied(), 5);
+        assert_eq!(hdr.caplen(), LEN);
+        assert!(hdr.is_truncated());
+        assert_eq!(&small, b"Hello");
+    }
+
+    #[test]
+    fn test_packet_eq() {
+        use std::collections::HashSet;
+
+        use super::WithHeader;
+
+        let owned = borrowed_packet().to_owned();
+        assert_eq!(owned, borrowed_packet().to_owned());
+        assert!(owned == borrowed_packet());
+        assert!(borrowed_packet() == borrowed_packet());
+
+        let mut later = PKTHDR;
+        later.ts.tv_sec += 1;
+        let later = OwnedPacket::new(later, BUF.to_vec());
+        assert_eq!(owned, later);
+        assert_ne!(WithHeader(owned.clone()), WithHeader(later.clone()));
+        assert_ne!(owned, OwnedPacket::new(PKTHDR, b"Hello".to_vec()));
+
+        let set: HashSet<_> = [owned.clone(), later.clone()].into_iter().collect();
+        assert_eq!(set.len(), 1);
+        let set: HashSet<_> = [WithHeader(owned), WithHeader(later)].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn test_packet_shared() {
+        use super::SharedPacket;
+
+        let shared = borrowed_packet().to_shared();
+        let clone = shared.clone();
+        assert!(std::sync::Arc::ptr_eq(
+            shared.shared_bytes(),
+            clone.shared_bytes()
+        ));
+        assert_eq!(clone.packet(), BUF);
+        assert_eq!(clone.timestamp(), timestamp());
+        assert_eq!(SharedPacket::from(borrowed_packet().to_owned()), shared);
+    }
+
+    #[test]
+    fn test_packet_copy_to_owned() {
+        let mut packet = OwnedPacket::with_capacity(128);
+        let ptr = packet.packet().as_ptr();
+        borrowed_packet().copy_to_owned(&mut packet);
+        borrowed_packet().copy_to_owned(&mut packet);
+        assert_eq!(packet.packet(), BUF);
+        assert_eq!(packet.timestamp(), timestamp());
+        assert_eq!(packet.packet().as_ptr(), ptr);
+    }
 }
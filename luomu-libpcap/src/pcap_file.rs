@@ -0,0 +1,213 @@
+//! pcap-file support for libpcap
+//!
+//! This module converts packets between this crate and the record types of
+//! the [pcap_file] crate: [PcapPacket] of pcap files, and
+//! [EnhancedPacketBlock] and [SimplePacketBlock] of pcapng files. Projects
+//! using pcap-file for reading and writing files can this way mix in packets
+//! captured live with this crate.
+//!
+//! Packets are converted to records borrowing their contents. Records are
+//! converted to [OwnedPacket]s, which have time stamps with microsecond
+//! precision, so nanoseconds of the time stamp of a record are dropped.
+//!
+//! ```no_run
+//! use luomu_libpcap::pcap_file::to_enhanced_packet_block;
+//! use luomu_libpcap::Pcap;
+//! use pcap_file::pcapng::PcapNgWriter;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let pcap = Pcap::builder("eth0")?.activate()?;
+//! let mut writer = PcapNgWriter::new(std::fs::File::create("out.pcapng")?)?;
+//! for packet in pcap.capture().take(10) {
+//!     writer.write_pcapng_block(to_enhanced_packet_block(&packet, 0))?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Requires feature `pcap-file`.
+
+use std::borrow::Cow;
+use std::time::{Duration, UNIX_EPOCH};
+
+use luomu_libpcap_sys::pcap_pkthdr;
+use pcap_file::pcap::PcapPacket;
+use pcap_file::pcapng::blocks::enhanced_packet::EnhancedPacketBlock;
+use pcap_file::pcapng::blocks::simple_packet::SimplePacketBlock;
+use pcap_file::pcapng::Block;
+
+use crate::{Error, OwnedPacket, Packet, SharedPacket, TimestampPrecision};
+
+/// Construct a [PcapPacket] borrowing the contents of `packet`.
+pub fn to_pcap_packet<P: Packet>(packet: &P) -> PcapPacket<'_> {
+    PcapPacket {
+        timestamp: since_epoch(packet),
+        orig_len: packet.pkthdr().len,
+        data: Cow::Borrowed(packet.packet()),
+    }
+}
+
+/// Construct an [EnhancedPacketBlock] borrowing the contents of `packet`.
+///
+/// `interface_id` is the index of the interface description block of the
+/// interface the packet was captured on.
+pub fn to_enhanced_packet_block<P: Packet>(
+    packet: &P,
+    interface_id: u32,
+) -> EnhancedPacketBlock<'_> {
+    EnhancedPacketBlock {
+        interface_id,
+        timestamp: since_epoch(packet),
+        original_len: packet.pkthdr().len,
+        data: Cow::Borrowed(packet.packet()),
+        options: Vec::new(),
+    }
+}
+
+/// Construct a [SimplePacketBlock] borrowing the contents of `packet`. The
+/// block has no time stamp.
+pub fn to_simple_packet_block<P: Packet>(packet: &P) -> SimplePacketBlock<'_> {
+    SimplePacketBlock {
+        original_len: packet.pkthdr().len,
+        data: Cow::Borrowed(packet.packet()),
+    }
+}
+
+impl<'a> From<&'a OwnedPacket> for PcapPacket<'a> {
+    fn from(packet: &'a OwnedPacket) -> Self {
+        to_pcap_packet(packet)
+    }
+}
+
+impl<'a> From<&'a SharedPacket> for PcapPacket<'a> {
+    fn from(packet: &'a SharedPacket) -> Self {
+        to_pcap_packet(packet)
+    }
+}
+
+impl From<PcapPacket<'_>> for OwnedPacket {
+    fn from(packet: PcapPacket<'_>) -> Self {
+        owned_packet(packet.timestamp, packet.orig_len, packet.data)
+    }
+}
+
+impl From<&PcapPacket<'_>> for OwnedPacket {
+    fn from(packet: &PcapPacket<'_>) -> Self {
+        owned_packet(packet.timestamp, packet.orig_len, packet.data.clone())
+    }
+}
+
+impl From<EnhancedPacketBlock<'_>> for OwnedPacket {
+    fn from(block: EnhancedPacketBlock<'_>) -> Self {
+        owned_packet(block.timestamp, block.original_len, block.data)
+    }
+}
+
+/// The packet has time stamp of zero, as simple packet blocks have no time
+/// stamps.
+impl From<SimplePacketBlock<'_>> for OwnedPacket {
+    fn from(block: SimplePacketBlock<'_>) -> Self {
+        owned_packet(Duration::ZERO, block.original_len, block.data)
+    }
+}
+
+/// Fails if the block is not an enhanced or a simple packet block.
+impl TryFrom<Block<'_>> for OwnedPacket {
+    type Error = Error;
+
+    fn try_from(block: Block<'_>) -> Result<Self, Self::Error> {
+        match block {
+            Block::EnhancedPacket(block) => Ok(block.into()),
+            Block::SimplePacket(block) => Ok(block.into()),
+            _ => Err(Error::PcapError(String::from("not a packet block"))),
+        }
+    }
+}
+
+fn since_epoch<P: Packet>(packet: &P) -> Duration {
+    packet
+        .timestamp()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+fn owned_packet(timestamp: Duration, len: u32, data: Cow<'_, [u8]>) -> OwnedPacket {
+    let header = pcap_pkthdr {
+        ts: crate::timeval(timestamp, TimestampPrecision::Micro),
+        caplen: data.len() as u32,
+        len,
+    };
+    OwnedPacket::new(header, data.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use pcap_file::pcap::PcapPacket;
+    use pcap_file::pcapng::blocks::interface_statistics::InterfaceStatisticsBlock;
+    use pcap_file::pcapng::Block;
+
+    use super::{to_enhanced_packet_block, to_pcap_packet, to_simple_packet_block};
+    use crate::{OwnedPacket, Packet, SharedPacket};
+
+    fn packet() -> OwnedPacket {
+        PcapPacket::new(Duration::new(1_700_000_000, 123_456_000), 60, b"Hello").into()
+    }
+
+    #[test]
+    fn test_pcap_packet() {
+        let packet = packet();
+        assert_eq!(
+            packet.timestamp(),
+            UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_000)
+        );
+        assert_eq!(packet.packet(), b"Hello");
+        assert_eq!(packet.pkthdr().caplen, 5);
+        assert_eq!(packet.pkthdr().len, 60);
+
+        let record = PcapPacket::from(&packet);
+        assert_eq!(record.timestamp, Duration::new(1_700_000_000, 123_456_000));
+        assert_eq!(record.orig_len, 60);
+        assert_eq!(&record.data[..], b"Hello");
+        assert_eq!(OwnedPacket::from(&record), packet);
+
+        let shared = SharedPacket::from(packet.clone());
+        assert_eq!(PcapPacket::from(&shared).data, to_pcap_packet(&packet).data);
+
+        // Nanoseconds don't fit in the time stamp of OwnedPacket.
+        let record = PcapPacket::new(Duration::new(1, 999_999_999), 1, b"x");
+        assert_eq!(
+            OwnedPacket::from(record).timestamp(),
+            UNIX_EPOCH + Duration::new(1, 999_999_000)
+        );
+    }
+
+    #[test]
+    fn test_pcapng_blocks() {
+        let packet = packet();
+
+        let block = to_enhanced_packet_block(&packet, 3);
+        assert_eq!(block.interface_id, 3);
+        assert_eq!(block.timestamp, Duration::new(1_700_000_000, 123_456_000));
+        assert_eq!(block.original_len, 60);
+        assert_eq!(&block.data[..], b"Hello");
+        assert_eq!(OwnedPacket::from(block.clone()), packet);
+        let owned = OwnedPacket::try_from(Block::EnhancedPacket(block)).unwrap();
+        assert_eq!(owned.timestamp(), packet.timestamp());
+
+        let block = to_simple_packet_block(&packet);
+        assert_eq!(block.original_len, 60);
+        let owned = OwnedPacket::try_from(Block::SimplePacket(block)).unwrap();
+        assert_eq!(owned, packet);
+        assert_eq!(owned.timestamp(), UNIX_EPOCH);
+        assert_eq!(owned.pkthdr().len, 60);
+
+        let block = InterfaceStatisticsBlock {
+            interface_id: 0,
+            timestamp: 0,
+            options: Vec::new(),
+        };
+        assert!(OwnedPacket::try_from(Block::InterfaceStatistics(block)).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+/// Progress of reading a savefile. See `PcapT::progress()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Progress {
+    /// Bytes of the file read so far.
+    pub bytes: u64,
+    /// Size of the file in bytes when it was opened.
+    pub total: u64,
+    /// Packets read so far.
+    pub packets: u64,
+}
+
+impl Progress {
+    /// Fraction of the file read, between 0 and 1.
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        (self.bytes as f64 / self.total as f64).min(1.0)
+    }
+
+    /// Estimated time left to read the rest of the file, given the time
+    /// `elapsed` reading it so far. `None` if nothing has been read yet.
+    pub fn eta(&self, elapsed: Duration) -> Option<Duration> {
+        if self.bytes == 0 {
+            return None;
+        }
+        let left = self.total.saturating_sub(self.bytes);
+        Some(elapsed.mul_f64(left as f64 / self.bytes as f64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Progress;
+
+    #[test]
+    fn test_progress() {
+        let progress = Progress {
+            bytes: 250,
+            total: 1000,
+            packets: 10,
+        };
+        assert_eq!(progress.fraction(), 0.25);
+        assert_eq!(
+            progress.eta(Duration::from_secs(10)),
+            Some(Duration::from_secs(30))
+        );
+
+        let start = Progress {
+            bytes: 0,
+            total: 0,
+            packets: 0,
+        };
+        assert_eq!(start.fraction(), 1.0);
+        assert_eq!(start.eta(Duration::from_secs(1)), None);
+    }
+}
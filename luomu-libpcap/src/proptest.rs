@@ -0,0 +1,179 @@
+//! proptest strategies for the types of the crate
+//!
+//! The strategies here generate addresses, packet headers and synthetic
+//! packets, so that code consuming them can be property-tested with
+//! [proptest] without writing generators for them first. Packets are built
+//! with [FrameBuilder] and are valid UDP or TCP packets over IPv4 or IPv6.
+//!
+//! ```
+//! use luomu_libpcap::proptest::owned_packet;
+//! use luomu_libpcap::{ip_offset, LinkType, Packet};
+//! use proptest::test_runner::TestRunner;
+//!
+//! let mut runner = TestRunner::default();
+//! runner
+//!     .run(&owned_packet(), |packet| {
+//!         assert!(packet.len() <= packet.pkthdr().len as usize);
+//!         if packet.len() == packet.pkthdr().len as usize {
+//!             assert_eq!(ip_offset(LinkType::ETHERNET, packet.packet()), Some(14));
+//!         }
+//!         Ok(())
+//!     })
+//!     .unwrap();
+//! ```
+//!
+//! Requires feature `proptest`.
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::time::Duration;
+
+use luomu_common::{Address, MacAddr};
+use luomu_libpcap_sys::pcap_pkthdr;
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use crate::generator::FrameBuilder;
+use crate::{OwnedPacket, PacketHeader, TimestampPrecision};
+
+// Largest payload of generated packets, small enough for any IP packet.
+const MAX_PAYLOAD: usize = 1024;
+
+// Largest length on the wire in generated packet headers.
+const MAX_LEN: u32 = 65535;
+
+/// Any Ethernet address.
+pub fn mac_addr() -> impl Strategy<Value = MacAddr> {
+    any::<[u8; 6]>().prop_map(MacAddr::from)
+}
+
+/// Any IPv4, IPv6 or Ethernet address.
+pub fn address() -> impl Strategy<Value = Address> {
+    prop_oneof![
+        any::<Ipv4Addr>().prop_map(Address::Ipv4),
+        any::<Ipv6Addr>().prop_map(Address::Ipv6),
+        mac_addr().prop_map(Address::Mac),
+    ]
+}
+
+/// Time stamp since the Unix epoch with microsecond precision, which fits
+/// in the time stamp of a packet header on all platforms.
+pub fn timestamp() -> impl Strategy<Value = Duration> {
+    (0..=i32::MAX as u64, 0..1_000_000u32)
+        .prop_map(|(secs, micros)| Duration::new(secs, micros * 1000))
+}
+
+/// Packet header with a captured length of at most the length on the wire.
+pub fn pkthdr() -> impl Strategy<Value = pcap_pkthdr> {
+    (timestamp(), 0..=MAX_LEN)
+        .prop_flat_map(|(ts, len)| (Just(ts), 0..=len, Just(len)))
+        .prop_map(|(ts, caplen, len)| pcap_pkthdr {
+            ts: crate::timeval(ts, TimestampPrecision::Micro),
+            caplen,
+            len,
+        })
+}
+
+/// Header of a packet copied into a buffer, with at most the captured
+/// length copied.
+pub fn packet_header() -> impl Strategy<Value = PacketHeader> {
+    pkthdr()
+        .prop_flat_map(|header| (Just(header), 0..=header.caplen as usize))
+        .prop_map(|(header, copied)| PacketHeader::new(header, copied))
+}
+
+// Source and destination of the same address family.
+fn endpoints() -> impl Strategy<Value = (SocketAddr, SocketAddr)> {
+    prop_oneof![
+        any::<(SocketAddrV4, SocketAddrV4)>()
+            .prop_map(|(src, dst)| (SocketAddr::V4(src), SocketAddr::V4(dst))),
+        any::<(SocketAddrV6, SocketAddrV6)>()
+            .prop_map(|(src, dst)| (SocketAddr::V6(src), SocketAddr::V6(dst))),
+    ]
+}
+
+/// Builder of a UDP packet or a TCP segment with any addresses, time to
+/// live, TCP flags and sequence numbers, and payload.
+pub fn frame_builder() -> impl Strategy<Value = FrameBuilder> {
+    let udp = (endpoints(), mac_addr(), mac_addr(), any::<u8>()).prop_map(
+        |((src, dst), src_mac, dst_mac, ttl)| {
+            FrameBuilder::udp(src, dst).macs(src_mac, dst_mac).ttl(ttl)
+        },
+    );
+    let tcp = (
+        endpoints(),
+        mac_addr(),
+        mac_addr(),
+        any::<(u8, u8, u32, u32)>(),
+    )
+        .prop_map(|((src, dst), src_mac, dst_mac, (ttl, flags, seq, ack))| {
+            FrameBuilder::tcp(src, dst)
+                .macs(src_mac, dst_mac)
+                .ttl(ttl)
+                .tcp_flags(flags)
+                .tcp_seq(seq, ack)
+        });
+    (prop_oneof![udp, tcp], vec(any::<u8>(), 0..=MAX_PAYLOAD))
+        .prop_map(|(builder, payload)| builder.payload(payload))
+}
+
+/// Ethernet frame carrying a UDP packet or a TCP segment.
+pub fn frame() -> impl Strategy<Value = Vec<u8>> {
+    frame_builder().prop_map(|builder| builder.build().expect("frame of valid size"))
+}
+
+/// IP packet without link-layer header carrying UDP or TCP, as captured on
+/// links with raw IP link-layer header type.
+pub fn ip_packet() -> impl Strategy<Value = Vec<u8>> {
+    frame_builder().prop_map(|builder| builder.build_ip().expect("packet of valid size"))
+}
+
+/// Packet of an Ethernet frame from [frame()], possibly truncated as if
+/// captured with a shorter snapshot length.
+pub fn owned_packet() -> impl Strategy<Value = OwnedPacket> {
+    (timestamp(), frame())
+        .prop_flat_map(|(ts, frame)| {
+            let len = frame.len();
+            (Just(ts), Just(frame), 0..=len)
+        })
+        .prop_map(|(ts, mut frame, caplen)| {
+            let header = pcap_pkthdr {
+                ts: crate::timeval(ts, TimestampPrecision::Micro),
+                caplen: caplen as u32,
+                len: frame.len() as u32,
+            };
+            frame.truncate(caplen);
+            OwnedPacket::new(header, frame)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{address, ip_packet, owned_packet, packet_header, pkthdr};
+    use crate::{ip_offset, LinkType, Packet};
+
+    proptest! {
+        #[test]
+        fn test_pkthdr(header in pkthdr(), copied in packet_header()) {
+            prop_assert!(header.caplen <= header.len);
+            prop_assert!(copied.copied() <= copied.caplen());
+            prop_assert!(copied.caplen() <= copied.len());
+        }
+
+        #[test]
+        fn test_address(addr in address()) {
+            prop_assert_eq!(addr.is_ip(), !addr.is_mac());
+            prop_assert_eq!(addr.as_ip().is_some(), addr.is_ip());
+        }
+
+        #[test]
+        fn test_packets(packet in owned_packet(), ip in ip_packet()) {
+            prop_assert!(packet.len() <= packet.pkthdr().len as usize);
+            if packet.len() == packet.pkthdr().len as usize {
+                prop_assert_eq!(ip_offset(LinkType::ETHERNET, packet.packet()), Some(14));
+            }
+            prop_assert_eq!(ip_offset(LinkType::RAW, &ip), Some(0));
+        }
+    }
+}
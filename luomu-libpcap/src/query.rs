@@ -0,0 +1,176 @@
+use crate::{Interface, PcapIfT};
+
+/// Query selecting capture devices.
+///
+/// Conditions are added with the builder methods and all of them must hold
+/// for an interface to match. A query without conditions matches every
+/// interface.
+///
+/// ```no_run
+/// use luomu_libpcap::{InterfaceQuery, PcapIfT};
+///
+/// let interfaces = PcapIfT::new()?;
+/// let query = InterfaceQuery::new()
+///     .up()
+///     .running()
+///     .not_loopback()
+///     .with_ipv4()
+///     .name_matches("eth*");
+/// for interface in query.find(&interfaces) {
+///     println!("{}", interface);
+/// }
+/// # Ok::<(), luomu_libpcap::Error>(())
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InterfaceQuery {
+    up: bool,
+    running: bool,
+    not_loopback: bool,
+    ipv4: bool,
+    names: Vec<String>,
+}
+
+impl InterfaceQuery {
+    /// Construct a new `InterfaceQuery` matching every interface.
+    pub fn new() -> InterfaceQuery {
+        InterfaceQuery::default()
+    }
+
+    /// Match interfaces which are up.
+    pub fn up(mut self) -> InterfaceQuery {
+        self.up = true;
+        self
+    }
+
+    /// Match interfaces which are running.
+    pub fn running(mut self) -> InterfaceQuery {
+        self.running = true;
+        self
+    }
+
+    /// Match interfaces which are not loopback interfaces.
+    pub fn not_loopback(mut self) -> InterfaceQuery {
+        self.not_loopback = true;
+        self
+    }
+
+    /// Match interfaces having an IPv4 address.
+    pub fn with_ipv4(mut self) -> InterfaceQuery {
+        self.ipv4 = true;
+        self
+    }
+
+    /// Match interfaces with name matching the glob pattern `pattern`. In the
+    /// pattern `*` matches any number of characters and `?` matches one
+    /// character, like `"eth*"` or `"wlp?s0"`.
+    pub fn name_matches(mut self, pattern: &str) -> InterfaceQuery {
+        self.names.push(pattern.to_string());
+        self
+    }
+
+    /// Returns true if `interface` matches the query.
+    pub fn matches(&self, interface: &Interface) -> bool {
+        (!self.up || interface.is_up())
+            && (!self.running || interface.is_running())
+            && (!self.not_loopback || !interface.is_loopback())
+            && (!self.ipv4 || interface.addresses.iter().any(|a| a.addr.is_ipv4()))
+            && self
+                .names
+                .iter()
+                .all(|pattern| glob_match(pattern, &interface.name))
+    }
+
+    /// Find the interfaces of `interfaces` matching the query, sorted by
+    /// name.
+    pub fn find(&self, interfaces: &PcapIfT) -> Vec<Interface> {
+        let mut found: Vec<Interface> = interfaces
+            .iter()
+            .filter(|interface| self.matches(interface))
+            .collect();
+        found.sort_by(|a, b| a.name.cmp(&b.name));
+        log::trace!("InterfaceQuery::find({:?}) = {:?}", self, found);
+        found
+    }
+
+    /// Find the first interface of `interfaces` by name matching the query.
+    pub fn first(&self, interfaces: &PcapIfT) -> Option<Interface> {
+        self.find(interfaces).into_iter().next()
+    }
+}
+
+// Match `name` against glob `pattern` with `*` and `?` wildcards.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern and the name position it was
+    // tried at, to backtrack to when the rest fails to match.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use luomu_common::Address;
+
+    use super::{glob_match, InterfaceQuery};
+    use crate::tests::{interface, interface_address};
+    use crate::InterfaceFlags;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("eth*", "eth0"));
+        assert!(glob_match("eth*", "eth"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("wlp?s0", "wlp2s0"));
+        assert!(glob_match("*0*1", "en0p1"));
+        assert!(!glob_match("eth*", "wlan0"));
+        assert!(!glob_match("eth?", "eth"));
+        assert!(!glob_match("lo", "lo0"));
+    }
+
+    #[test]
+    fn test_interface_query() {
+        let mut interface = interface("eth0", InterfaceFlags::UP | InterfaceFlags::RUNNING);
+        let query = InterfaceQuery::new()
+            .up()
+            .running()
+            .not_loopback()
+            .name_matches("eth*");
+        assert!(InterfaceQuery::new().matches(&interface));
+        assert!(query.matches(&interface));
+        assert!(!query.clone().with_ipv4().matches(&interface));
+        assert!(!query.clone().name_matches("wl*").matches(&interface));
+
+        interface.addresses.insert(interface_address(
+            Address::Ipv4(Ipv4Addr::new(192, 0, 2, 1)),
+            None,
+        ));
+        assert!(query.clone().with_ipv4().matches(&interface));
+
+        interface.raw_flags |= InterfaceFlags::LOOPBACK;
+        assert!(!query.matches(&interface));
+    }
+}
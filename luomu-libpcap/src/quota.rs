@@ -0,0 +1,245 @@
+use std::time::{Duration, Instant};
+
+use crate::functions::{pcap_breakloop, pcap_loop};
+use crate::{BorrowedPacket, Error, Packet, PacketSource, PcapT, Result};
+
+/// Budget of packets and bytes for a capture.
+///
+/// A packet is passed on only if it fits in both budgets, counting the
+/// captured bytes of packets. The capture stops at the first packet which
+/// doesn't fit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Quota {
+    /// Maximum number of packets, or `None` for no limit.
+    pub packets: Option<u64>,
+    /// Maximum number of bytes, or `None` for no limit.
+    pub bytes: Option<u64>,
+}
+
+impl Quota {
+    /// Quota of `packets` packets.
+    pub fn packets(packets: u64) -> Quota {
+        Quota {
+            packets: Some(packets),
+            bytes: None,
+        }
+    }
+
+    /// Quota of `bytes` bytes.
+    pub fn bytes(bytes: u64) -> Quota {
+        Quota {
+            packets: None,
+            bytes: Some(bytes),
+        }
+    }
+
+    /// Set the maximum number of packets.
+    pub fn with_packets(mut self, packets: u64) -> Quota {
+        self.packets = Some(packets);
+        self
+    }
+
+    /// Set the maximum number of bytes.
+    pub fn with_bytes(mut self, bytes: u64) -> Quota {
+        self.bytes = Some(bytes);
+        self
+    }
+}
+
+/// Summary of a capture limited by [Quota].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuotaSummary {
+    /// Packets passed on.
+    pub packets: u64,
+    /// Bytes of packets passed on.
+    pub bytes: u64,
+    /// Time from the start to the end of the capture.
+    pub duration: Duration,
+    /// True if the capture stopped because the quota was exhausted, false if
+    /// the source ended first.
+    pub exhausted: bool,
+}
+
+// Counting of packets and bytes against a quota.
+#[derive(Debug)]
+struct Usage {
+    quota: Quota,
+    packets: u64,
+    bytes: u64,
+    started: Instant,
+    exhausted: bool,
+}
+
+impl Usage {
+    fn new(quota: Quota) -> Usage {
+        Usage {
+            quota,
+            packets: 0,
+            bytes: 0,
+            started: Instant::now(),
+            exhausted: false,
+        }
+    }
+
+    // Count a packet of `len` bytes. Returns false if it doesn't fit.
+    fn take(&mut self, len: usize) -> bool {
+        let packets = self.packets + 1;
+        let bytes = self.bytes + len as u64;
+        if self.exhausted
+            || self.quota.packets.is_some_and(|max| packets > max)
+            || self.quota.bytes.is_some_and(|max| bytes > max)
+        {
+            self.exhausted = true;
+            return false;
+        }
+        self.packets = packets;
+        self.bytes = bytes;
+        // Stop without waiting for another packet once the quota is used up.
+        self.exhausted = self.quota.packets == Some(packets) || self.quota.bytes == Some(bytes);
+        true
+    }
+
+    fn summary(&self) -> QuotaSummary {
+        QuotaSummary {
+            packets: self.packets,
+            bytes: self.bytes,
+            duration: self.started.elapsed(),
+            exhausted: self.exhausted,
+        }
+    }
+}
+
+/// Adapter ending a [PacketSource] when a [Quota] is exhausted.
+///
+/// After the quota is exhausted `next_packet()` returns `Error::Break`, like
+/// a source at the end of a savefile.
+#[derive(Debug)]
+pub struct Limited<S> {
+    source: S,
+    usage: Usage,
+}
+
+impl<S: PacketSource> Limited<S> {
+    /// Construct a new `Limited` over `source`.
+    pub fn new(source: S, quota: Quota) -> Limited<S> {
+        Limited {
+            source,
+            usage: Usage::new(quota),
+        }
+    }
+
+    /// Returns true if the quota has been exhausted.
+    pub fn is_exhausted(&self) -> bool {
+        self.usage.exhausted
+    }
+
+    /// Summary of the capture so far.
+    pub fn summary(&self) -> QuotaSummary {
+        self.usage.summary()
+    }
+
+    /// Get a reference to the underlying source.
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Get the underlying source back.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S: PacketSource> PacketSource for Limited<S> {
+    type Packet = S::Packet;
+
+    fn next_packet(&mut self) -> Result<S::Packet> {
+        if self.usage.exhausted {
+            return Err(Error::Break);
+        }
+        let packet = self.source.next_packet()?;
+        if !self.usage.take(packet.len()) {
+            return Err(Error::Break);
+        }
+        Ok(packet)
+    }
+}
+
+// pcap_loop() calling callback until quota is exhausted.
+pub(crate) fn loop_with_quota<F>(
+    pcap_t: &PcapT,
+    quota: Quota,
+    mut callback: F,
+) -> Result<QuotaSummary>
+where
+    F: FnMut(BorrowedPacket),
+{
+    let mut usage = Usage::new(quota);
+    let ret = pcap_loop(pcap_t, -1, |packet| {
+        if usage.take(packet.len()) {
+            callback(packet);
+        }
+        if usage.exhausted {
+            pcap_breakloop(pcap_t);
+        }
+    });
+    drop(callback);
+
+    match ret {
+        Ok(()) => (),
+        Err(Error::Break) if usage.exhausted => (),
+        Err(err) => return Err(err),
+    }
+    let summary = usage.summary();
+    log::debug!(
+        "capture quota: {} packets, {} bytes in {:?}",
+        summary.packets,
+        summary.bytes,
+        summary.duration
+    );
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Limited, Quota};
+    use crate::{Error, OwnedPacket, PacketSource, Result};
+
+    // Source of packets of 100 bytes.
+    struct Endless;
+
+    impl PacketSource for Endless {
+        type Packet = OwnedPacket;
+
+        fn next_packet(&mut self) -> Result<OwnedPacket> {
+            let header = luomu_libpcap_sys::pcap_pkthdr {
+                ts: libc::timeval {
+                    tv_sec: 0,
+                    tv_usec: 0,
+                },
+                caplen: 100,
+                len: 100,
+            };
+            Ok(OwnedPacket::new(header, vec![0; 100]))
+        }
+    }
+
+    #[test]
+    fn test_limited_quota() {
+        let mut limited = Limited::new(Endless, Quota::packets(3).with_bytes(1000));
+        for _ in 0..3 {
+            assert!(limited.next_packet().is_ok());
+        }
+        assert!(limited.is_exhausted());
+        assert!(matches!(limited.next_packet(), Err(Error::Break)));
+        let summary = limited.summary();
+        assert_eq!((summary.packets, summary.bytes), (3, 300));
+        assert!(summary.exhausted);
+
+        let mut limited = Limited::new(Endless, Quota::bytes(250));
+        assert!(limited.next_packet().is_ok());
+        assert!(limited.next_packet().is_ok());
+        assert!(!limited.is_exhausted());
+        assert!(matches!(limited.next_packet(), Err(Error::Break)));
+        assert_eq!(limited.summary().bytes, 200);
+    }
+}
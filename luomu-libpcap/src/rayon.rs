@@ -0,0 +1,241 @@
+//! Parallel offline processing with rayon
+//!
+//! [ParallelOffline] splits a savefile into chunks at packet record
+//! boundaries and reads the chunks in parallel in rayon's thread pool. Each
+//! chunk is read with its own libpcap handle so filters and timestamps work
+//! exactly like with `Pcap::offline()`.
+//!
+//! Only savefiles in classic pcap format are supported; pcapng files can't be
+//! split without parsing all the blocks.
+//!
+//! Not available on Windows, where savefile chunks can't be read through C
+//! streams.
+
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::functions::{pcap_compile, pcap_fopen_offline_reader, pcap_next_ex, pcap_setfilter};
+use crate::savefile::{FileHeader, FILE_HEADER_LEN, RECORD_HEADER_LEN};
+use crate::{BorrowedPacket, Error, PcapT, Result};
+
+/// Savefile processor reading chunks of the file in parallel.
+#[derive(Debug)]
+pub struct ParallelOffline {
+    path: PathBuf,
+    header: [u8; FILE_HEADER_LEN],
+    chunks: Vec<Range<u64>>,
+    filter: Option<String>,
+}
+
+impl ParallelOffline {
+    /// Open a savefile for parallel processing.
+    ///
+    /// The file is split into as many chunks as there are threads in the
+    /// current rayon thread pool.
+    pub fn open<P: AsRef<Path>>(savefile: P) -> Result<ParallelOffline> {
+        ParallelOffline::with_chunks(savefile, rayon::current_num_threads())
+    }
+
+    /// Open a savefile for parallel processing, splitting it into at most
+    /// `chunks` chunks of roughly equal size.
+    ///
+    /// The whole file is scanned for record boundaries, reading only the
+    /// record headers.
+    pub fn with_chunks<P: AsRef<Path>>(savefile: P, chunks: usize) -> Result<ParallelOffline> {
+        let path = savefile.as_ref().to_path_buf();
+        let (header, chunks) = scan(&path, chunks.max(1))?;
+        Ok(ParallelOffline {
+            path,
+            header,
+            chunks,
+            filter: None,
+        })
+    }
+
+    /// set a filter expression
+    ///
+    /// Only packets matching the filter are passed to the processing
+    /// functions. See
+    /// [pcap-filter(7)](https://www.tcpdump.org/manpages/pcap-filter.7.html)
+    /// for the syntax of that string.
+    pub fn set_filter(mut self, filter: &str) -> Result<ParallelOffline> {
+        self.filter = Some(filter.to_string());
+        // Fail early if the filter doesn't compile.
+        self.open_chunk(&self.chunks[0])?;
+        Ok(self)
+    }
+
+    /// Number of chunks the savefile was split into.
+    pub fn chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Fold packets of each chunk in parallel and reduce the results.
+    ///
+    /// `identity` produces the initial value for each chunk, `fold` is called
+    /// for each packet in a chunk and `reduce` merges results of two chunks.
+    /// Results are merged in file order, so `reduce` needs to be associative
+    /// but not commutative.
+    pub fn fold<T, ID, F, R>(&self, identity: ID, fold: F, reduce: R) -> Result<T>
+    where
+        T: Send,
+        ID: Fn() -> T + Sync + Send,
+        F: Fn(T, BorrowedPacket) -> T + Sync + Send,
+        R: Fn(T, T) -> T + Sync + Send,
+    {
+        self.chunks
+            .par_iter()
+            .map(|range| self.fold_chunk(range, identity(), &fold))
+            .try_reduce(&identity, |a, b| Ok(reduce(a, b)))
+    }
+
+    /// Call `f` for each packet in parallel.
+    pub fn for_each<F>(&self, f: F) -> Result<()>
+    where
+        F: Fn(BorrowedPacket) + Sync + Send,
+    {
+        self.fold(|| (), |(), packet| f(packet), |(), ()| ())
+    }
+
+    /// Count packets in parallel.
+    pub fn count(&self) -> Result<usize> {
+        self.fold(|| 0, |n, _| n + 1, |a, b| a + b)
+    }
+
+    fn fold_chunk<T, F>(&self, range: &Range<u64>, init: T, fold: &F) -> Result<T>
+    where
+        F: Fn(T, BorrowedPacket) -> T,
+    {
+        let pcap_t = self.open_chunk(range)?;
+        let mut acc = init;
+        loop {
+            match pcap_next_ex(&pcap_t) {
+                Ok(packet) => acc = fold(acc, packet),
+                // End of the savefile
+                Err(Error::Break) => return Ok(acc),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Open a libpcap handle reading from the savefile header followed by the
+    // records in range.
+    fn open_chunk(&self, range: &Range<u64>) -> Result<PcapT> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(range.start))?;
+        let reader = Cursor::new(self.header).chain(file.take(range.end - range.start));
+
+        let pcap_t = pcap_fopen_offline_reader(reader)?;
+        if let Some(filter) = &self.filter {
+            let mut bpf_program = pcap_compile(&pcap_t, filter)?;
+            pcap_setfilter(&pcap_t, &mut bpf_program)?;
+        }
+        Ok(pcap_t)
+    }
+}
+
+// Read the savefile header and split the records into at most `chunks`
+// ranges of file offsets.
+fn scan(path: &Path, chunks: usize) -> Result<([u8; FILE_HEADER_LEN], Vec<Range<u64>>)> {
+    let file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+
+    let mut header = [0u8; FILE_HEADER_LEN];
+    reader.read_exact(&mut header)?;
+    let file_header = FileHeader::parse(&header)?;
+
+    let target = ((size - FILE_HEADER_LEN as u64) / chunks as u64).max(1);
+    let mut ranges = Vec::with_capacity(chunks);
+    let mut start = FILE_HEADER_LEN as u64;
+    let mut offset = start;
+
+    loop {
+        let mut record = [0u8; RECORD_HEADER_LEN];
+        match reader.read_exact(&mut record) {
+            Ok(()) => (),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(Error::IO(e)),
+        }
+
+        let caplen = file_header.record(&record).caplen;
+
+        let next = offset + RECORD_HEADER_LEN as u64 + u64::from(caplen);
+        if next > size {
+            // Truncated record. Leave it into the last chunk for libpcap to
+            // report.
+            break;
+        }
+        reader.seek_relative(i64::from(caplen))?;
+        offset = next;
+
+        if offset - start >= target && ranges.len() + 1 < chunks {
+            ranges.push(start..offset);
+            start = offset;
+        }
+    }
+
+    if start < size || ranges.is_empty() {
+        ranges.push(start..size);
+    }
+    Ok((header, ranges))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use super::scan;
+    use crate::savefile::tests::savefile;
+
+    fn write_savefile(name: &str, data: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "luomu-libpcap-rayon-{}-{}.pcap",
+            std::process::id(),
+            name
+        ));
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(data)
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_scan_chunks() {
+        for swapped in [false, true] {
+            let packets: &[&[u8]] = &[b"aaaa", b"bbbbbbbb", b"cc", b"dddd"];
+            let path = write_savefile(
+                &format!("chunks-{}", swapped),
+                &savefile(swapped, false, packets),
+            );
+            let (_, chunks) = scan(&path, 2).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(chunks, vec![24..68, 68..106]);
+        }
+    }
+
+    #[test]
+    fn test_scan_more_chunks_than_packets() {
+        let path = write_savefile("single", &savefile(false, false, &[b"aaaa"]));
+        let (_, chunks) = scan(&path, 8).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(chunks, vec![24..44]);
+    }
+
+    #[test]
+    fn test_scan_invalid_magic() {
+        let mut data = savefile(false, false, &[]);
+        data[0] = 0x0a;
+        let path = write_savefile("magic", &data);
+        assert!(scan(&path, 2).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,289 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::functions::pcap_dump_open;
+use crate::savefile::{FILE_HEADER_LEN, RECORD_HEADER_LEN};
+use crate::{Error, Limited, Packet, PacketSource, PcapDumper, PcapT, Quota, QuotaSummary, Result};
+
+// Packet buffer timeout of the capture, which is how often stopping is
+// checked when no packets arrive.
+const POLL_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Options of [capture_to_file()].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RecordOptions {
+    /// Snapshot length, or `None` for 65535.
+    pub snaplen: Option<usize>,
+    /// Promiscuous mode.
+    pub promiscuous: bool,
+    /// Stop after a number of packets or bytes of packets.
+    pub quota: Quota,
+    /// Stop after capturing for this long.
+    pub duration: Option<Duration>,
+    /// Start a new file when the current one would grow larger than this
+    /// many bytes, like `tcpdump -C`. The first file has the name given,
+    /// the following ones have a number appended: `dump.pcap`,
+    /// `dump.pcap1`, `dump.pcap2` and so on.
+    pub file_size: Option<u64>,
+    /// Keep at most this many files, overwriting the oldest ones, like
+    /// `tcpdump -W`. Only used with `file_size`.
+    pub files: Option<usize>,
+}
+
+/// Handle of a capture started with [capture_to_file()].
+///
+/// Dropping the handle stops the capture and waits for it to finish.
+#[derive(Debug)]
+pub struct RecordHandle {
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<Result<QuotaSummary>>>,
+}
+
+impl RecordHandle {
+    /// Stop the capture. The capture stops within 100 milliseconds; use
+    /// `wait()` to wait for it.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if the capture has stopped.
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().map_or(true, |t| t.is_finished())
+    }
+
+    /// Wait for the capture to stop, because of `stop()` or a stop condition
+    /// of the options, and get a summary of it.
+    ///
+    /// The summary tells the number of packets and bytes written. It's
+    /// `exhausted` if the quota or duration of the options ran out.
+    pub fn wait(mut self) -> Result<QuotaSummary> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<QuotaSummary> {
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .unwrap_or_else(|_| Err(Error::PcapError(String::from("capture panicked")))),
+            None => Err(Error::PcapError(String::from("capture already finished"))),
+        }
+    }
+}
+
+impl Drop for RecordHandle {
+    fn drop(&mut self) {
+        if self.thread.is_some() {
+            self.stop();
+            if let Err(err) = self.join() {
+                log::warn!("capture to file failed: {}", err);
+            }
+        }
+    }
+}
+
+/// Capture packets matching `filter` on `device` into savefile `path`.
+///
+/// The capture runs in a thread of its own until it's stopped with the
+/// returned handle, or a quota or duration in `options` runs out. Errors
+/// opening the capture, setting the filter or creating the file are returned
+/// right away. An empty `filter` captures all packets.
+///
+/// This is what `tcpdump -w` does:
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use luomu_libpcap::{capture_to_file, RecordOptions};
+///
+/// let options = RecordOptions {
+///     duration: Some(Duration::from_secs(60)),
+///     file_size: Some(10_000_000),
+///     ..Default::default()
+/// };
+/// let handle = capture_to_file("eth0", "tcp port 443", "tls.pcap", options)?;
+/// let summary = handle.wait()?;
+/// println!("{} packets captured", summary.packets);
+/// # Ok::<(), luomu_libpcap::Error>(())
+/// ```
+pub fn capture_to_file<P: AsRef<Path>>(
+    device: &str,
+    filter: &str,
+    path: P,
+    options: RecordOptions,
+) -> Result<RecordHandle> {
+    let mut config = crate::oneshot::defaults(device);
+    config.promiscuous = Some(options.promiscuous);
+    config.snaplen = Some(options.snaplen.unwrap_or(65535));
+    config.timeout = Some(POLL_TIMEOUT);
+    if !filter.is_empty() {
+        config.filter = Some(filter.to_string());
+    }
+    let path = path.as_ref().to_path_buf();
+    let stop = Arc::new(AtomicBool::new(false));
+    let (opened_tx, opened_rx) = mpsc::channel();
+
+    let thread_stop = Arc::clone(&stop);
+    let thread = thread::Builder::new()
+        .name(format!("capture {}", device))
+        .spawn(move || {
+            let mut files = Files::new(path, &options);
+            let opened = config.open().and_then(|pcap| {
+                let dumper = files.open(&pcap)?;
+                Ok((pcap, dumper))
+            });
+            match opened {
+                Ok((mut pcap, dumper)) => {
+                    _ = opened_tx.send(Ok(()));
+                    record(&mut pcap, dumper, files, &options, &thread_stop)
+                }
+                Err(err) => {
+                    _ = opened_tx.send(Err(err));
+                    Err(Error::PcapError(String::from("capture not opened")))
+                }
+            }
+        })?;
+
+    let opened = opened_rx
+        .recv()
+        .unwrap_or_else(|_| Err(Error::PcapError(String::from("capture panicked"))));
+    match opened {
+        Ok(()) => Ok(RecordHandle {
+            stop,
+            thread: Some(thread),
+        }),
+        Err(err) => {
+            _ = thread.join();
+            Err(err)
+        }
+    }
+}
+
+fn record(
+    pcap: &mut crate::Pcap,
+    mut dumper: PcapDumper,
+    mut files: Files,
+    options: &RecordOptions,
+    stop: &AtomicBool,
+) -> Result<QuotaSummary> {
+    let started = Instant::now();
+    let mut source = Limited::new(&mut *pcap, options.quota);
+    let mut out_of_time = false;
+
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        if options.duration.is_some_and(|d| started.elapsed() >= d) {
+            out_of_time = true;
+            break;
+        }
+        let packet = match source.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::Timeout) => continue,
+            Err(Error::Break) => break,
+            Err(err) => return Err(err),
+        };
+        let len = RECORD_HEADER_LEN as u64 + packet.packet().len() as u64;
+        if files.is_full(len) {
+            dumper.flush()?;
+            files.advance();
+            dumper = files.open(source.get_ref())?;
+        }
+        dumper.dump(packet);
+        files.written += len;
+    }
+    dumper.flush()?;
+
+    let mut summary = source.summary();
+    summary.exhausted |= out_of_time;
+    Ok(summary)
+}
+
+// Names and sizes of the files written.
+struct Files {
+    path: PathBuf,
+    file_size: Option<u64>,
+    files: Option<usize>,
+    index: usize,
+    written: u64,
+}
+
+impl Files {
+    fn new(path: PathBuf, options: &RecordOptions) -> Files {
+        Files {
+            path,
+            file_size: options.file_size,
+            files: options.files.filter(|files| *files > 0),
+            index: 0,
+            written: 0,
+        }
+    }
+
+    // Returns true if a record of `len` bytes doesn't fit in the current file.
+    // At least one packet is written into every file.
+    fn is_full(&self, len: u64) -> bool {
+        self.file_size
+            .is_some_and(|size| self.written > FILE_HEADER_LEN as u64 && self.written + len > size)
+    }
+
+    fn name(&self) -> PathBuf {
+        if self.index == 0 {
+            return self.path.clone();
+        }
+        let mut name = OsString::from(self.path.as_os_str());
+        name.push(self.index.to_string());
+        PathBuf::from(name)
+    }
+
+    fn open(&mut self, pcap_t: &PcapT) -> Result<PcapDumper> {
+        let dumper = pcap_dump_open(pcap_t, self.name())?;
+        self.written = FILE_HEADER_LEN as u64;
+        Ok(dumper)
+    }
+
+    // Move on to the next file, or back to the first one if the maximum
+    // number of files has been reached.
+    fn advance(&mut self) {
+        self.index += 1;
+        if self.files.is_some_and(|files| self.index >= files) {
+            self.index = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{Files, RecordOptions};
+
+    #[test]
+    fn test_record_files() {
+        let options = RecordOptions {
+            file_size: Some(100),
+            files: Some(3),
+            ..Default::default()
+        };
+        let mut files = Files::new(PathBuf::from("dump.pcap"), &options);
+        files.written = 24;
+        assert!(!files.is_full(200));
+        files.written = 80;
+        assert!(!files.is_full(20));
+        assert!(files.is_full(21));
+
+        let mut names = Vec::new();
+        for _ in 0..4 {
+            names.push(files.name());
+            files.advance();
+        }
+        let names: Vec<_> = names.iter().map(|n| n.to_str().unwrap()).collect();
+        assert_eq!(
+            names,
+            ["dump.pcap", "dump.pcap1", "dump.pcap2", "dump.pcap"]
+        );
+    }
+}
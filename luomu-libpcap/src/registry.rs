@@ -0,0 +1,166 @@
+//! Registry of live captures
+//!
+//! Every live [Pcap](crate::Pcap) handle is registered here while it's
+//! open, with its device, filter and the statistics it last read.
+//! [captures()] lists them, so a daemon running many captures can show them
+//! in a diagnostic command without keeping track of them itself:
+//!
+//! ```no_run
+//! use luomu_libpcap::{registry, Pcap};
+//!
+//! let pcap = Pcap::builder("eth0")?.activate()?;
+//! pcap.set_filter("tcp port 80")?;
+//! pcap.stats()?;
+//!
+//! for capture in registry::captures() {
+//!     println!("{}", capture);
+//! }
+//! # Ok::<(), luomu_libpcap::Error>(())
+//! ```
+//!
+//! A handle can only be used from the thread owning it, so the statistics
+//! are the ones read with `Pcap::stats()` by that thread, not read when
+//! listing captures. Savefiles are not registered.
+//!
+//! Requires feature `registry`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::{PcapStat, StatsSample};
+
+static CAPTURES: Mutex<BTreeMap<u64, Entry>> = Mutex::new(BTreeMap::new());
+
+struct Entry {
+    device: String,
+    filter: Option<String>,
+    opened: Instant,
+    stats: Option<StatsSample>,
+}
+
+/// A live capture in the registry. See [captures()].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaptureInfo {
+    /// Identifier of the capture, see `PcapT::id()`.
+    pub id: u64,
+    /// Device captured on.
+    pub device: String,
+    /// Filter expression set with `Pcap::set_filter()`, if any.
+    pub filter: Option<String>,
+    /// Time since the capture was opened.
+    pub uptime: Duration,
+    /// Statistics the capture last read, if any.
+    pub stats: Option<StatsSample>,
+}
+
+impl fmt::Display for CaptureInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} {} up {}s",
+            self.id,
+            self.device,
+            self.uptime.as_secs()
+        )?;
+        if let Some(filter) = &self.filter {
+            write!(f, " filter \"{}\"", filter)?;
+        }
+        if let Some(stats) = &self.stats {
+            write!(
+                f,
+                " received {} dropped {} dropped by interface {}",
+                stats.received, stats.dropped, stats.dropped_interface
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// List the live captures open in the process, in the order they were
+/// opened.
+pub fn captures() -> Vec<CaptureInfo> {
+    let captures = lock();
+    captures
+        .iter()
+        .map(|(id, entry)| CaptureInfo {
+            id: *id,
+            device: entry.device.clone(),
+            filter: entry.filter.clone(),
+            uptime: entry.opened.elapsed(),
+            stats: entry.stats,
+        })
+        .collect()
+}
+
+fn lock() -> std::sync::MutexGuard<'static, BTreeMap<u64, Entry>> {
+    match CAPTURES.lock() {
+        Ok(captures) => captures,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+// Registration of a capture, removed from the registry when dropped.
+#[derive(Debug)]
+pub(crate) struct Registration {
+    id: u64,
+}
+
+impl Registration {
+    pub(crate) fn new(id: u64, device: &str) -> Registration {
+        let entry = Entry {
+            device: device.to_string(),
+            filter: None,
+            opened: Instant::now(),
+            stats: None,
+        };
+        lock().insert(id, entry);
+        Registration { id }
+    }
+
+    pub(crate) fn set_filter(&self, filter: Option<&str>) {
+        if let Some(entry) = lock().get_mut(&self.id) {
+            entry.filter = filter.map(String::from);
+        }
+    }
+
+    pub(crate) fn set_stats(&self, stats: &PcapStat) {
+        if let Some(entry) = lock().get_mut(&self.id) {
+            entry.stats = Some(StatsSample::new(SystemTime::now(), stats));
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        lock().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{captures, Registration};
+    use crate::PcapStat;
+
+    #[test]
+    fn test_registry() {
+        let registration = Registration::new(u64::MAX, "registry-test0");
+        registration.set_filter(Some("udp"));
+        registration.set_stats(&PcapStat::default());
+        let info = captures()
+            .into_iter()
+            .find(|capture| capture.device == "registry-test0")
+            .unwrap();
+        assert_eq!(info.filter.as_deref(), Some("udp"));
+        assert_eq!(info.stats.unwrap().received, 0);
+        assert!(info
+            .to_string()
+            .contains("registry-test0 up 0s filter \"udp\""));
+
+        drop(registration);
+        assert!(!captures()
+            .iter()
+            .any(|capture| capture.device == "registry-test0"));
+    }
+}
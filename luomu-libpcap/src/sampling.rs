@@ -0,0 +1,153 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use crate::{PacketSource, Result};
+
+/// How [Sampler] selects packets.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sampling {
+    /// Select every nth packet, starting from the first one.
+    EveryNth(u64),
+    /// Select each packet independently with given probability between 0
+    /// and 1.
+    Probability(f64),
+}
+
+/// Adapter passing through a sample of packets of a [PacketSource].
+///
+/// For links where the rate of packets is too high to process all of them,
+/// `Sampler` drops all but a sample of the packets as early as possible.
+/// Counters of sampled and skipped packets allow scaling results up to the
+/// whole traffic.
+#[derive(Debug)]
+pub struct Sampler<S> {
+    source: S,
+    sampling: Sampling,
+    // State of xorshift64* generator for probabilistic sampling.
+    rng: u64,
+    sampled: u64,
+    skipped: u64,
+}
+
+impl<S: PacketSource> Sampler<S> {
+    /// Construct a new `Sampler` over `source`.
+    ///
+    /// `Sampling::EveryNth(0)` is treated like `Sampling::EveryNth(1)`, and
+    /// probabilities are clamped between 0 and 1.
+    pub fn new(source: S, sampling: Sampling) -> Sampler<S> {
+        let sampling = match sampling {
+            Sampling::EveryNth(n) => Sampling::EveryNth(n.max(1)),
+            Sampling::Probability(p) => Sampling::Probability(p.clamp(0.0, 1.0)),
+        };
+        // Seed with the per-process random keys of std's HashMap.
+        let seed = RandomState::new().build_hasher().finish();
+        Sampler {
+            source,
+            sampling,
+            rng: seed | 1,
+            sampled: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Number of packets passed through.
+    pub fn sampled(&self) -> u64 {
+        self.sampled
+    }
+
+    /// Number of packets skipped.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Get a reference to the underlying source.
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Get the underlying source back.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    fn select(&mut self) -> bool {
+        match self.sampling {
+            Sampling::EveryNth(n) => (self.sampled + self.skipped) % n == 0,
+            Sampling::Probability(p) => {
+                self.rng ^= self.rng >> 12;
+                self.rng ^= self.rng << 25;
+                self.rng ^= self.rng >> 27;
+                let random = self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+                (random as f64 / (1u64 << 53) as f64) < p
+            }
+        }
+    }
+}
+
+impl<S: PacketSource> PacketSource for Sampler<S> {
+    type Packet = S::Packet;
+
+    /// Get the next sampled packet. Errors from the source are returned as
+    /// they are.
+    fn next_packet(&mut self) -> Result<S::Packet> {
+        loop {
+            let packet = self.source.next_packet()?;
+            if self.select() {
+                self.sampled += 1;
+                return Ok(packet);
+            }
+            self.skipped += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Sampler, Sampling};
+    use crate::{Error, OwnedPacket, PacketSource, Result};
+
+    // Source of n empty packets.
+    struct Counted(usize);
+
+    impl PacketSource for Counted {
+        type Packet = OwnedPacket;
+
+        fn next_packet(&mut self) -> Result<OwnedPacket> {
+            if self.0 == 0 {
+                return Err(Error::Break);
+            }
+            self.0 -= 1;
+            Ok(OwnedPacket::with_capacity(0))
+        }
+    }
+
+    fn drain<S: PacketSource>(sampler: &mut Sampler<S>) -> usize {
+        let mut n = 0;
+        while sampler.next_packet().is_ok() {
+            n += 1;
+        }
+        n
+    }
+
+    #[test]
+    fn test_sampler_every_nth() {
+        let mut sampler = Sampler::new(Counted(10), Sampling::EveryNth(3));
+        assert_eq!(drain(&mut sampler), 4);
+        assert_eq!(sampler.sampled(), 4);
+        assert_eq!(sampler.skipped(), 6);
+    }
+
+    #[test]
+    fn test_sampler_probability() {
+        let mut sampler = Sampler::new(Counted(100), Sampling::Probability(0.0));
+        assert_eq!(drain(&mut sampler), 0);
+        assert_eq!(sampler.skipped(), 100);
+
+        let mut sampler = Sampler::new(Counted(100), Sampling::Probability(2.0));
+        assert_eq!(drain(&mut sampler), 100);
+
+        let mut sampler = Sampler::new(Counted(10000), Sampling::Probability(0.5));
+        let n = drain(&mut sampler);
+        assert!((4000..6000).contains(&n), "{} sampled", n);
+    }
+}
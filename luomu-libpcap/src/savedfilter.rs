@@ -0,0 +1,383 @@
+use std::fs;
+use std::path::Path;
+
+use luomu_libpcap_sys as libpcap;
+
+use crate::functions::{pcap_compile_nopcap, pcap_datalink, pcap_snapshot};
+use crate::{Error, LinkType, PcapFilter, PcapT, Result};
+
+// Identifies a saved filter, followed by format version.
+const MAGIC: &[u8; 4] = b"LPCF";
+const VERSION: u16 = 1;
+
+// Limits for programs loaded from untrusted input. The kernel doesn't accept
+// longer programs either.
+const MAX_INSNS: usize = 4096;
+const MEMWORDS: u32 = 16;
+
+/// Compiled filter which can be saved and loaded back.
+///
+/// Together with the filter program a `SavedFilter` has the link-layer header
+/// type and snapshot length it was compiled for, and the filter expression.
+/// This allows compiling filters centrally and distributing them to agents
+/// which can't or shouldn't compile filters themselves.
+///
+/// The binary format is stable and independent of the platform: all values
+/// are little-endian and the format is versioned. Loaded programs are checked
+/// before they are used like `bpf_validate()` of libpcap does, and to only
+/// have instructions the BPF interpreter of libpcap can run.
+pub struct SavedFilter {
+    linktype: LinkType,
+    snaplen: u32,
+    expression: String,
+    filter: PcapFilter,
+}
+
+impl SavedFilter {
+    /// Compile `filter` for link-layer header type `linktype` and snapshot
+    /// length `snaplen`.
+    pub fn compile(filter: &str, linktype: LinkType, snaplen: u32) -> Result<SavedFilter> {
+        let compiled = pcap_compile_nopcap(snaplen as usize, linktype.value(), filter)?;
+        Ok(SavedFilter {
+            linktype,
+            snaplen,
+            expression: filter.to_string(),
+            filter: compiled,
+        })
+    }
+
+    /// Compile `filter` for the link-layer header type and snapshot length
+    /// of `pcap_t`.
+    pub fn compile_for(pcap_t: &PcapT, filter: &str) -> Result<SavedFilter> {
+        let linktype = LinkType(pcap_datalink(pcap_t)?);
+        SavedFilter::compile(filter, linktype, pcap_snapshot(pcap_t)? as u32)
+    }
+
+    /// Link-layer header type the filter was compiled for.
+    pub fn linktype(&self) -> LinkType {
+        self.linktype
+    }
+
+    /// Snapshot length the filter was compiled for.
+    pub fn snaplen(&self) -> u32 {
+        self.snaplen
+    }
+
+    /// Filter expression the filter was compiled from.
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// The compiled filter program.
+    pub fn filter(&self) -> &PcapFilter {
+        &self.filter
+    }
+
+    /// Get the compiled filter program.
+    pub fn into_filter(self) -> PcapFilter {
+        self.filter
+    }
+
+    /// Check the filter was compiled for the link-layer header type of
+    /// `pcap_t`. A filter compiled for another type gives wrong results.
+    pub fn check(&self, pcap_t: &PcapT) -> Result<()> {
+        let linktype = LinkType(pcap_datalink(pcap_t)?);
+        if linktype != self.linktype {
+            return Err(Error::PcapError(format!(
+                "filter compiled for {}, capture has {}",
+                self.linktype, linktype
+            )));
+        }
+        Ok(())
+    }
+
+    /// Serialize the filter.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let insns = self.filter.insns();
+        let mut bytes = Vec::with_capacity(22 + self.expression.len() + insns.len() * 8);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.linktype.value().to_le_bytes());
+        bytes.extend_from_slice(&self.snaplen.to_le_bytes());
+        bytes.extend_from_slice(&(self.expression.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(self.expression.as_bytes());
+        bytes.extend_from_slice(&(insns.len() as u32).to_le_bytes());
+        for insn in insns {
+            bytes.extend_from_slice(&insn.code.to_le_bytes());
+            bytes.push(insn.jt);
+            bytes.push(insn.jf);
+            bytes.extend_from_slice(&insn.k.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Deserialize a filter serialized with `to_bytes()`.
+    ///
+    /// Fails if the data is not a saved filter or the filter program is not
+    /// valid.
+    pub fn from_bytes(bytes: &[u8]) -> Result<SavedFilter> {
+        let mut reader = Reader { bytes };
+        if reader.take(4)? != MAGIC {
+            return Err(invalid("not a saved filter"));
+        }
+        let version = u16::from_le_bytes(reader.array()?);
+        if version != VERSION {
+            return Err(invalid(&format!("unsupported version {}", version)));
+        }
+        let linktype = LinkType(i32::from_le_bytes(reader.array()?));
+        let snaplen = u32::from_le_bytes(reader.array()?);
+        let len = u32::from_le_bytes(reader.array()?) as usize;
+        let expression = std::str::from_utf8(reader.take(len)?)
+            .map_err(|_| invalid("filter expression is not UTF-8"))?
+            .to_string();
+
+        let count = u32::from_le_bytes(reader.array()?) as usize;
+        if count > MAX_INSNS {
+            return Err(invalid("filter program too long"));
+        }
+        let mut insns = Vec::with_capacity(count);
+        for _ in 0..count {
+            let insn: [u8; 8] = reader.array()?;
+            insns.push(libpcap::bpf_insn {
+                code: u16::from_le_bytes([insn[0], insn[1]]),
+                jt: insn[2],
+                jf: insn[3],
+                k: u32::from_le_bytes([insn[4], insn[5], insn[6], insn[7]]),
+            });
+        }
+        if !reader.bytes.is_empty() {
+            return Err(invalid("trailing data"));
+        }
+        validate_program(&insns)?;
+
+        Ok(SavedFilter {
+            linktype,
+            snaplen,
+            expression,
+            filter: PcapFilter::from_insns(&insns),
+        })
+    }
+
+    /// Save the filter into file `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Load a filter saved with `save()` from file `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<SavedFilter> {
+        SavedFilter::from_bytes(&fs::read(path)?)
+    }
+}
+
+impl Clone for SavedFilter {
+    fn clone(&self) -> Self {
+        SavedFilter {
+            linktype: self.linktype,
+            snaplen: self.snaplen,
+            expression: self.expression.clone(),
+            filter: self.filter.clone(),
+        }
+    }
+}
+
+impl std::fmt::Debug for SavedFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SavedFilter")
+            .field("linktype", &self.linktype)
+            .field("snaplen", &self.snaplen)
+            .field("expression", &self.expression)
+            .field("instructions", &self.filter.get_raw_filter_len())
+            .finish()
+    }
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::PcapError(format!("invalid saved filter: {}", reason))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(invalid("truncated data"));
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+}
+
+// Opcodes run by the BPF interpreter of libpcap, `bpf_filter()`, which
+// aborts the process on any other opcode.
+#[rustfmt::skip]
+const OPCODES: &[u16] = &[
+    // ret #k, ret a
+    0x06, 0x16,
+    // ld [k], ldh [k], ldb [k]
+    0x20, 0x28, 0x30,
+    // ld [x + k], ldh [x + k], ldb [x + k]
+    0x40, 0x48, 0x50,
+    // ld #len, ldx #len
+    0x80, 0x81,
+    // ldxb 4*([k]&0xf)
+    0xb1,
+    // ld #k, ldx #k, ld M[k], ldx M[k]
+    0x00, 0x01, 0x60, 0x61,
+    // st M[k], stx M[k]
+    0x02, 0x03,
+    // ja, and jeq, jgt, jge and jset with k and with x
+    0x05, 0x15, 0x25, 0x35, 0x45, 0x1d, 0x2d, 0x3d, 0x4d,
+    // add, sub, mul, div, or, and, lsh, rsh, mod and xor with k
+    0x04, 0x14, 0x24, 0x34, 0x44, 0x54, 0x64, 0x74, 0x94, 0xa4,
+    // add, sub, mul, div, or, and, lsh, rsh, mod and xor with x
+    0x0c, 0x1c, 0x2c, 0x3c, 0x4c, 0x5c, 0x6c, 0x7c, 0x9c, 0xac,
+    // neg
+    0x84,
+    // tax, txa
+    0x07, 0x87,
+];
+
+// Check the program is safe to run, like bpf_validate() of libpcap: only
+// opcodes `bpf_filter()` knows are used, jumps stay within the program,
+// scratch memory accesses are in bounds, there are no divisions by constant
+// zero and the program ends with a return.
+fn validate_program(insns: &[libpcap::bpf_insn]) -> Result<()> {
+    const BPF_LD: u16 = 0x00;
+    const BPF_LDX: u16 = 0x01;
+    const BPF_ST: u16 = 0x02;
+    const BPF_STX: u16 = 0x03;
+    const BPF_ALU: u16 = 0x04;
+    const BPF_JMP: u16 = 0x05;
+    const BPF_RET: u16 = 0x06;
+    const BPF_MEM: u16 = 0x60;
+    const BPF_JA: u16 = 0x00;
+    const BPF_DIV: u16 = 0x30;
+    const BPF_MOD: u16 = 0x90;
+    const BPF_K: u16 = 0x00;
+
+    if insns.is_empty() {
+        return Ok(());
+    }
+    for (i, insn) in insns.iter().enumerate() {
+        if !OPCODES.contains(&insn.code) {
+            return Err(invalid(&format!(
+                "unknown opcode {:#04x} at {}",
+                insn.code, i
+            )));
+        }
+        let class = insn.code & 0x07;
+        let remaining = (insns.len() - i - 1) as u64;
+        let valid = match class {
+            BPF_LD | BPF_LDX if insn.code & 0xe0 == BPF_MEM => insn.k < MEMWORDS,
+            BPF_ST | BPF_STX => insn.k < MEMWORDS,
+            BPF_ALU => {
+                let op = insn.code & 0xf0;
+                !(matches!(op, BPF_DIV | BPF_MOD) && insn.code & 0x08 == BPF_K && insn.k == 0)
+            }
+            BPF_JMP if insn.code & 0xf0 == BPF_JA => (insn.k as u64) < remaining,
+            BPF_JMP => (insn.jt as u64) < remaining && (insn.jf as u64) < remaining,
+            _ => true,
+        };
+        if !valid {
+            return Err(invalid(&format!("bad instruction at {}", i)));
+        }
+    }
+    if insns[insns.len() - 1].code & 0x07 != BPF_RET {
+        return Err(invalid("program doesn't end with return"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use luomu_libpcap_sys as libpcap;
+
+    use super::SavedFilter;
+    use crate::{LinkType, PcapFilter};
+
+    fn insn(code: u16, jt: u8, jf: u8, k: u32) -> libpcap::bpf_insn {
+        libpcap::bpf_insn { code, jt, jf, k }
+    }
+
+    #[test]
+    fn test_saved_filter_roundtrip() {
+        // ldh [12]; jeq #0x800 jt 0 jf 1; ret #65535; ret #0
+        let insns = [
+            insn(0x28, 0, 0, 12),
+            insn(0x15, 0, 1, 0x800),
+            insn(0x06, 0, 0, 65535),
+            insn(0x06, 0, 0, 0),
+        ];
+        let saved = SavedFilter {
+            linktype: LinkType::ETHERNET,
+            snaplen: 1500,
+            expression: "ip".to_string(),
+            filter: PcapFilter::from_insns(&insns),
+        };
+        let bytes = saved.to_bytes();
+        let loaded = SavedFilter::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.linktype(), LinkType::ETHERNET);
+        assert_eq!(loaded.snaplen(), 1500);
+        assert_eq!(loaded.expression(), "ip");
+        let codes: Vec<_> = loaded
+            .filter()
+            .insns()
+            .iter()
+            .map(|i| (i.code, i.k))
+            .collect();
+        assert_eq!(
+            codes,
+            insns.iter().map(|i| (i.code, i.k)).collect::<Vec<_>>()
+        );
+        assert_eq!(loaded.to_bytes(), bytes);
+
+        assert!(SavedFilter::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(SavedFilter::from_bytes(&[bytes.as_slice(), &[0]].concat()).is_err());
+        assert!(SavedFilter::from_bytes(b"LPCX").is_err());
+    }
+
+    #[test]
+    fn test_saved_filter_invalid_program() {
+        let programs = [
+            // Jump past the end.
+            vec![insn(0x15, 5, 0, 0x800), insn(0x06, 0, 0, 0)],
+            // Scratch memory out of bounds.
+            vec![insn(0x02, 0, 0, 16), insn(0x06, 0, 0, 0)],
+            // Division by zero.
+            vec![insn(0x34, 0, 0, 0), insn(0x06, 0, 0, 0)],
+            // No return at the end.
+            vec![insn(0x28, 0, 0, 12)],
+            // Return with an undefined return value.
+            vec![insn(0x0e, 0, 0, 0)],
+            // Undefined ALU operation.
+            vec![insn(0xb4, 0, 0, 1), insn(0x06, 0, 0, 0)],
+            // Undefined jump.
+            vec![insn(0x55, 0, 0, 0), insn(0x06, 0, 0, 0)],
+            // Load with an undefined mode.
+            vec![insn(0xe0, 0, 0, 0), insn(0x06, 0, 0, 0)],
+            // Load of an undefined size.
+            vec![insn(0x38, 0, 0, 0), insn(0x06, 0, 0, 0)],
+            // Undefined misc operation.
+            vec![insn(0x0f, 0, 0, 0), insn(0x06, 0, 0, 0)],
+        ];
+        for insns in programs {
+            let saved = SavedFilter {
+                linktype: LinkType::ETHERNET,
+                snaplen: 1500,
+                expression: String::new(),
+                filter: PcapFilter::from_insns(&insns),
+            };
+            assert!(SavedFilter::from_bytes(&saved.to_bytes()).is_err());
+        }
+    }
+}
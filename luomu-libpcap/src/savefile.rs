@@ -0,0 +1,164 @@
+//! Parsing of classic pcap savefile headers for the pure Rust readers.
+
+use std::io;
+
+use crate::{Error, Result};
+
+/// Size of the savefile header.
+pub(crate) const FILE_HEADER_LEN: usize = 24;
+/// Size of the packet record header.
+pub(crate) const RECORD_HEADER_LEN: usize = 16;
+
+pub(crate) const MAGIC_USEC: u32 = 0xa1b2_c3d4;
+pub(crate) const MAGIC_NSEC: u32 = 0xa1b2_3c4d;
+
+/// Header of a savefile in classic pcap format.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct FileHeader {
+    /// Savefile was written on a host with different byte order.
+    pub(crate) swapped: bool,
+    /// Time stamps have nanosecond precision.
+    pub(crate) nsec: bool,
+    pub(crate) version_major: u16,
+    pub(crate) version_minor: u16,
+    pub(crate) snaplen: u32,
+    pub(crate) linktype: u32,
+}
+
+/// Header of a packet record.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RecordHeader {
+    pub(crate) ts_sec: u32,
+    /// Microseconds or nanoseconds, depending on `FileHeader::nsec`.
+    pub(crate) ts_frac: u32,
+    pub(crate) caplen: u32,
+    pub(crate) len: u32,
+}
+
+impl FileHeader {
+    /// Parse the savefile header.
+    pub(crate) fn parse(header: &[u8]) -> Result<FileHeader> {
+        if header.len() < FILE_HEADER_LEN {
+            return Err(invalid_data("truncated savefile header"));
+        }
+
+        let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let (swapped, nsec) = match magic {
+            MAGIC_USEC => (false, false),
+            MAGIC_NSEC => (false, true),
+            m if m.swap_bytes() == MAGIC_USEC => (true, false),
+            m if m.swap_bytes() == MAGIC_NSEC => (true, true),
+            _ => return Err(invalid_data("not a savefile in pcap format")),
+        };
+
+        let mut file_header = FileHeader {
+            swapped,
+            nsec,
+            version_major: 0,
+            version_minor: 0,
+            snaplen: 0,
+            linktype: 0,
+        };
+        let u16_at = |i: usize| {
+            let b = [header[i], header[i + 1]];
+            if swapped {
+                u16::from_be_bytes(b)
+            } else {
+                u16::from_le_bytes(b)
+            }
+        };
+        file_header.version_major = u16_at(4);
+        file_header.version_minor = u16_at(6);
+        file_header.snaplen = file_header.u32_at(header, 16);
+        file_header.linktype = file_header.u32_at(header, 20);
+        Ok(file_header)
+    }
+
+    /// Parse the packet record header from the beginning of `record`.
+    pub(crate) fn record(&self, record: &[u8]) -> RecordHeader {
+        RecordHeader {
+            ts_sec: self.u32_at(record, 0),
+            ts_frac: self.u32_at(record, 4),
+            caplen: self.u32_at(record, 8),
+            len: self.u32_at(record, 12),
+        }
+    }
+
+    // Read u32 in the byte order of the savefile.
+    fn u32_at(&self, bytes: &[u8], i: usize) -> u32 {
+        let b = [bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]];
+        if self.swapped {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        }
+    }
+}
+
+pub(crate) fn invalid_data(msg: &str) -> Error {
+    Error::IO(io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{FileHeader, FILE_HEADER_LEN, MAGIC_NSEC, MAGIC_USEC};
+
+    /// Build a savefile in classic pcap format.
+    pub(crate) fn savefile(swapped: bool, nsec: bool, packets: &[&[u8]]) -> Vec<u8> {
+        let u32_bytes = |v: u32| {
+            if swapped {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&u32_bytes(if nsec { MAGIC_NSEC } else { MAGIC_USEC }));
+        if swapped {
+            data.extend_from_slice(&[0, 2, 0, 4]);
+        } else {
+            data.extend_from_slice(&[2, 0, 4, 0]);
+        }
+        data.extend_from_slice(&[0u8; 8]);
+        data.extend_from_slice(&u32_bytes(65535));
+        data.extend_from_slice(&u32_bytes(1));
+        assert_eq!(data.len(), FILE_HEADER_LEN);
+
+        for (i, packet) in packets.iter().enumerate() {
+            data.extend_from_slice(&u32_bytes(i as u32 + 1));
+            data.extend_from_slice(&u32_bytes(500));
+            data.extend_from_slice(&u32_bytes(packet.len() as u32));
+            data.extend_from_slice(&u32_bytes(packet.len() as u32 + 10));
+            data.extend_from_slice(packet);
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_file_header() {
+        for swapped in [false, true] {
+            let data = savefile(swapped, true, &[b"abc"]);
+            let header = FileHeader::parse(&data).unwrap();
+            assert_eq!(header.swapped, swapped);
+            assert!(header.nsec);
+            assert_eq!((header.version_major, header.version_minor), (2, 4));
+            assert_eq!(header.snaplen, 65535);
+            assert_eq!(header.linktype, 1);
+
+            let record = header.record(&data[FILE_HEADER_LEN..]);
+            assert_eq!(record.ts_sec, 1);
+            assert_eq!(record.ts_frac, 500);
+            assert_eq!(record.caplen, 3);
+            assert_eq!(record.len, 13);
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid_file_header() {
+        let mut data = savefile(false, false, &[]);
+        assert!(FileHeader::parse(&data[..10]).is_err());
+        data[0] = 0x0a;
+        assert!(FileHeader::parse(&data).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use crate::functions::{pcap_dump_open, pcap_next_ex};
+use crate::{CaptureConfig, Error, Pcap, PcapDumper, Result};
+
+/// Longest time `ScheduledCapture::step()` sleeps waiting for a window.
+const MAX_SLEEP: Duration = Duration::from_secs(1);
+
+/// A window of time, optionally recurring, during which to capture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CaptureWindow {
+    /// Start of the first window.
+    pub start: SystemTime,
+    /// Length of each window.
+    pub duration: Duration,
+    /// Interval between starts of windows, `None` for a single window.
+    pub every: Option<Duration>,
+}
+
+impl CaptureWindow {
+    /// Construct a single window.
+    pub fn once(start: SystemTime, duration: Duration) -> CaptureWindow {
+        CaptureWindow {
+            start,
+            duration,
+            every: None,
+        }
+    }
+
+    /// Construct a window recurring every `every`, e.g. daily.
+    pub fn recurring(start: SystemTime, duration: Duration, every: Duration) -> CaptureWindow {
+        CaptureWindow {
+            start,
+            duration,
+            every: Some(every),
+        }
+    }
+
+    /// Start of the latest occurrence starting at or before `now`.
+    fn latest_start(&self, now: SystemTime) -> Option<SystemTime> {
+        let elapsed = now.duration_since(self.start).ok()?;
+        match self.every {
+            Some(every) if !every.is_zero() => {
+                let n = elapsed.as_nanos() / every.as_nanos();
+                Some(self.start + every * u32::try_from(n).ok()?)
+            }
+            _ => Some(self.start),
+        }
+    }
+
+    /// Start of the occurrence active at `now`, if any.
+    pub fn active_at(&self, now: SystemTime) -> Option<SystemTime> {
+        let start = self.latest_start(now)?;
+        (now < start + self.duration).then_some(start)
+    }
+
+    /// Start of the first occurrence starting after `now`, if any.
+    pub fn next_start(&self, now: SystemTime) -> Option<SystemTime> {
+        match (self.latest_start(now), self.every) {
+            (None, _) => Some(self.start),
+            (Some(start), Some(every)) if !every.is_zero() => Some(start + every),
+            (Some(_), _) => None,
+        }
+    }
+}
+
+/// Set of windows during which [ScheduledCapture] captures.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Schedule {
+    windows: Vec<CaptureWindow>,
+}
+
+impl Schedule {
+    /// Construct an empty schedule.
+    pub fn new() -> Schedule {
+        Schedule::default()
+    }
+
+    /// Add a window into the schedule.
+    pub fn with_window(mut self, window: CaptureWindow) -> Schedule {
+        self.windows.push(window);
+        self
+    }
+
+    /// Start and end of the window active at `now`, if any.
+    ///
+    /// If several windows overlap, the one ending last is returned.
+    pub fn active_at(&self, now: SystemTime) -> Option<(SystemTime, SystemTime)> {
+        self.windows
+            .iter()
+            .filter_map(|window| {
+                let start = window.active_at(now)?;
+                Some((start, start + window.duration))
+            })
+            .max_by_key(|(_, end)| *end)
+    }
+
+    /// Start of the next window starting after `now`, if any.
+    pub fn next_start(&self, now: SystemTime) -> Option<SystemTime> {
+        self.windows
+            .iter()
+            .filter_map(|window| window.next_start(now))
+            .min()
+    }
+}
+
+/// Capture running only during scheduled windows.
+///
+/// The capture is opened from `config` when a window of the [Schedule]
+/// starts and closed when it ends. Packets of each window are written into a
+/// savefile of their own, named by calling `path` with the start of the
+/// window.
+///
+/// Call `ScheduledCapture::step()` repeatedly, for example from a loop of a
+/// dedicated thread.
+pub struct ScheduledCapture {
+    config: CaptureConfig,
+    schedule: Schedule,
+    path: Box<dyn FnMut(SystemTime) -> PathBuf + Send>,
+    // Capture and dumper of the current window, and end of the window.
+    current: Option<(Pcap, PcapDumper, SystemTime)>,
+}
+
+impl ScheduledCapture {
+    /// Construct a new `ScheduledCapture`.
+    pub fn new<F>(config: CaptureConfig, schedule: Schedule, path: F) -> ScheduledCapture
+    where
+        F: FnMut(SystemTime) -> PathBuf + Send + 'static,
+    {
+        ScheduledCapture {
+            config,
+            schedule,
+            path: Box::new(path),
+            current: None,
+        }
+    }
+
+    /// Returns true if a window is active and the capture is open.
+    pub fn is_active(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Returns true if there are no more windows to capture.
+    pub fn is_finished(&self) -> bool {
+        let now = SystemTime::now();
+        self.current.is_none()
+            && self.schedule.active_at(now).is_none()
+            && self.schedule.next_start(now).is_none()
+    }
+
+    /// Capture a packet, or wait for the next window.
+    ///
+    /// Opens the capture and its savefile when a window starts and closes
+    /// them when it ends. Outside windows this sleeps until the next window
+    /// starts, but at most a second. Returns true if a packet was captured.
+    pub fn step(&mut self) -> Result<bool> {
+        let now = SystemTime::now();
+
+        if matches!(&self.current, Some((_, _, end)) if now >= *end) {
+            log::info!("{}: capture window ended", self.config.source);
+            self.current = None;
+        }
+
+        if self.current.is_none() {
+            let Some((start, end)) = self.schedule.active_at(now) else {
+                let sleep = self
+                    .schedule
+                    .next_start(now)
+                    .and_then(|next| next.duration_since(now).ok())
+                    .unwrap_or(MAX_SLEEP)
+                    .min(MAX_SLEEP);
+                std::thread::sleep(sleep);
+                return Ok(false);
+            };
+            self.open(start, end)?;
+        }
+
+        let Some((pcap, dumper, _)) = &mut self.current else {
+            return Ok(false);
+        };
+        match pcap_next_ex(pcap) {
+            Ok(packet) => {
+                dumper.dump(packet);
+                Ok(true)
+            }
+            Err(Error::Timeout) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Close the capture of the current window, if any.
+    ///
+    /// The capture is opened again by the next `step()` if the window is
+    /// still active.
+    pub fn close(&mut self) {
+        self.current = None;
+    }
+
+    fn open(&mut self, start: SystemTime, end: SystemTime) -> Result<()> {
+        let mut config = self.config.clone();
+        // Wake up to close the window when no packets arrive.
+        if config.timeout.is_none() {
+            config.timeout = Some(MAX_SLEEP);
+        }
+        let pcap = config.open()?;
+        let path = (self.path)(start);
+        log::info!(
+            "{}: capture window started, writing into {}",
+            self.config.source,
+            path.display()
+        );
+        let dumper = pcap_dump_open(&pcap, path)?;
+        self.current = Some((pcap, dumper, end));
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for ScheduledCapture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScheduledCapture")
+            .field("config", &self.config)
+            .field("schedule", &self.schedule)
+            .field("active", &self.is_active())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::{CaptureWindow, Schedule};
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_capture_window() {
+        let once = CaptureWindow::once(at(100), Duration::from_secs(10));
+        assert_eq!(once.active_at(at(99)), None);
+        assert_eq!(once.active_at(at(100)), Some(at(100)));
+        assert_eq!(once.active_at(at(110)), None);
+        assert_eq!(once.next_start(at(50)), Some(at(100)));
+        assert_eq!(once.next_start(at(105)), None);
+
+        let recurring =
+            CaptureWindow::recurring(at(100), Duration::from_secs(10), Duration::from_secs(60));
+        assert_eq!(recurring.active_at(at(165)), Some(at(160)));
+        assert_eq!(recurring.active_at(at(175)), None);
+        assert_eq!(recurring.next_start(at(175)), Some(at(220)));
+    }
+
+    #[test]
+    fn test_schedule() {
+        let schedule = Schedule::new()
+            .with_window(CaptureWindow::once(at(100), Duration::from_secs(10)))
+            .with_window(CaptureWindow::once(at(105), Duration::from_secs(10)))
+            .with_window(CaptureWindow::once(at(200), Duration::from_secs(10)));
+        assert_eq!(schedule.active_at(at(107)), Some((at(105), at(115))));
+        assert_eq!(schedule.active_at(at(150)), None);
+        assert_eq!(schedule.next_start(at(150)), Some(at(200)));
+        assert_eq!(schedule.next_start(at(250)), None);
+    }
+}
@@ -0,0 +1,455 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::functions::pcap_next_ex;
+use crate::{
+    BorrowedPacket, CaptureConfig, CaptureObserver, Error, FilterHandle, Packet, Pcap, Result,
+    StatsHistory, StatsSample,
+};
+
+/// Packet buffer timeout used for sessions configured without one, so the
+/// capture thread notices when the session is stopped.
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Destination of packets captured by a session of [SessionManager].
+pub trait PacketSink: Send {
+    /// Handle a captured packet.
+    fn packet(&mut self, packet: BorrowedPacket);
+
+    /// Called when the session stops. Does nothing by default.
+    fn stopped(&mut self) {}
+}
+
+impl<F> PacketSink for F
+where
+    F: FnMut(BorrowedPacket) + Send,
+{
+    fn packet(&mut self, packet: BorrowedPacket) {
+        self(packet)
+    }
+}
+
+/// Status of a session of [SessionManager].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SessionStatus {
+    /// Session has not been started or was stopped.
+    Stopped,
+    /// Session is capturing packets.
+    Running,
+    /// Capturing failed. The capture thread exited with the error, followed
+    /// by the `Debug` output of the capture with its filter.
+    Failed(String),
+}
+
+/// Statistics of a session, or sum of statistics of all sessions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SessionStats {
+    /// Packets passed to the sink.
+    pub packets: u64,
+    /// Bytes of packets passed to the sink.
+    pub bytes: u64,
+    /// Packets received as reported by libpcap.
+    pub received: u64,
+    /// Packets dropped as reported by libpcap.
+    pub dropped: u64,
+}
+
+impl std::ops::Add for SessionStats {
+    type Output = SessionStats;
+
+    fn add(self, other: SessionStats) -> SessionStats {
+        SessionStats {
+            packets: self.packets + other.packets,
+            bytes: self.bytes + other.bytes,
+            received: self.received + other.received,
+            dropped: self.dropped + other.dropped,
+        }
+    }
+}
+
+// State shared between a session and its capture thread.
+#[derive(Default)]
+struct Shared {
+    stop: AtomicBool,
+    packets: AtomicU64,
+    bytes: AtomicU64,
+    received: AtomicU64,
+    dropped: AtomicU64,
+    error: Mutex<Option<String>>,
+    history: Mutex<StatsHistory>,
+}
+
+impl Shared {
+    fn stats(&self) -> SessionStats {
+        SessionStats {
+            packets: self.packets.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            received: self.received.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+struct Session {
+    config: CaptureConfig,
+    sink: Arc<Mutex<Box<dyn PacketSink>>>,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+    // Filter replaceable while running, and its expression to use on start.
+    filter: Option<FilterHandle>,
+    hot_filter: Option<String>,
+    observer: Option<Arc<dyn CaptureObserver>>,
+    history: usize,
+}
+
+impl Session {
+    fn status(&self) -> SessionStatus {
+        if let Some(error) = self.shared.error.lock().unwrap().clone() {
+            return SessionStatus::Failed(error);
+        }
+        match &self.thread {
+            Some(thread) if !thread.is_finished() => SessionStatus::Running,
+            _ => SessionStatus::Stopped,
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.shared.stop.store(true, Ordering::SeqCst);
+            if thread.join().is_err() {
+                log::error!("{}: capture thread panicked", self.config.source);
+            }
+        }
+    }
+}
+
+/// Manager of named capture sessions.
+///
+/// Each session captures packets with its own [CaptureConfig], that is from
+/// a device with options and a filter, and passes them to its
+/// [PacketSink]. A started session runs in a thread of its own until it's
+/// stopped or capturing fails.
+///
+/// The capture thread checks whether the session has been stopped after each
+/// packet and packet buffer timeout. If the configuration doesn't set the
+/// timeout, 500 milliseconds is used.
+#[derive(Default)]
+pub struct SessionManager {
+    sessions: BTreeMap<String, Session>,
+}
+
+impl SessionManager {
+    /// Construct a `SessionManager` without sessions.
+    pub fn new() -> SessionManager {
+        SessionManager::default()
+    }
+
+    /// Add a new stopped session.
+    ///
+    /// Fails if a session with the same name exists.
+    pub fn add<S>(&mut self, name: &str, config: CaptureConfig, sink: S) -> Result<()>
+    where
+        S: PacketSink + 'static,
+    {
+        if self.sessions.contains_key(name) {
+            return Err(Error::PcapError(format!("session {} already exists", name)));
+        }
+        let session = Session {
+            config,
+            sink: Arc::new(Mutex::new(Box::new(sink))),
+            shared: Arc::default(),
+            thread: None,
+            filter: None,
+            hot_filter: None,
+            observer: None,
+            history: 0,
+        };
+        self.sessions.insert(name.to_string(), session);
+        Ok(())
+    }
+
+    /// Stop and remove a session.
+    pub fn remove(&mut self, name: &str) -> Result<()> {
+        let mut session = self
+            .sessions
+            .remove(name)
+            .ok_or_else(|| no_such_session(name))?;
+        session.stop();
+        Ok(())
+    }
+
+    /// Names of the sessions in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.sessions.keys().map(String::as_str)
+    }
+
+    /// Start a session.
+    ///
+    /// The capture is opened before returning, so errors in opening it or
+    /// setting its filter are returned here. Starting a running session does
+    /// nothing.
+    pub fn start(&mut self, name: &str) -> Result<()> {
+        let session = self
+            .sessions
+            .get_mut(name)
+            .ok_or_else(|| no_such_session(name))?;
+        if session.status() == SessionStatus::Running {
+            return Ok(());
+        }
+        session.stop();
+
+        let mut config = session.config.clone();
+        if config.timeout.is_none() {
+            config.timeout = Some(DEFAULT_TIMEOUT);
+        }
+        let mut builder = config.builder()?;
+        if let Some(observer) = &session.observer {
+            builder = builder.set_observer(observer.clone())?;
+        }
+        let pcap = builder.activate()?;
+        if let Some(filter) = &config.filter {
+            pcap.set_filter(filter)?;
+        }
+        let filter = FilterHandle::new(&pcap, session.hot_filter.as_deref())?;
+
+        let shared = Arc::new(Shared {
+            history: Mutex::new(StatsHistory::new(session.history)),
+            ..Shared::default()
+        });
+        let sink = session.sink.clone();
+        let thread_shared = shared.clone();
+        let thread_filter = filter.clone();
+        let thread = std::thread::Builder::new()
+            .name(format!("luomu-libpcap-{}", name))
+            .spawn(move || run(pcap, thread_filter, sink, thread_shared))?;
+
+        log::debug!("session {}: started capturing on {}", name, config.source);
+        session.shared = shared;
+        session.thread = Some(thread);
+        session.filter = Some(filter);
+        Ok(())
+    }
+
+    /// Replace the filter of a session without stopping it.
+    ///
+    /// The filter is applied on top of the filter of the session's
+    /// [CaptureConfig], see [FilterHandle] for details. Each packet is
+    /// evaluated either with the old or the new filter. `None` removes the
+    /// filter. If the session is not running, the filter is taken into use
+    /// when it's started.
+    pub fn set_filter(&mut self, name: &str, filter: Option<&str>) -> Result<()> {
+        let session = self
+            .sessions
+            .get_mut(name)
+            .ok_or_else(|| no_such_session(name))?;
+        if let Some(handle) = &session.filter {
+            match filter {
+                Some(filter) => handle.set(filter)?,
+                None => handle.clear(),
+            }
+        }
+        session.hot_filter = filter.map(String::from);
+        Ok(())
+    }
+
+    /// Set the observer of a session.
+    ///
+    /// `observer` is told of lifecycle events of the capture of the session,
+    /// see [CaptureObserver], including errors ending the capture thread and
+    /// statistics read after each packet buffer timeout. Takes effect when
+    /// the session is started next.
+    pub fn set_observer(&mut self, name: &str, observer: Arc<dyn CaptureObserver>) -> Result<()> {
+        let session = self
+            .sessions
+            .get_mut(name)
+            .ok_or_else(|| no_such_session(name))?;
+        session.observer = Some(observer);
+        Ok(())
+    }
+
+    /// Keep the last `samples` statistics samples of a session.
+    ///
+    /// The capture thread of the session reads statistics after each packet
+    /// buffer timeout, and keeps the latest of them in a [StatsHistory].
+    /// Zero disables the history. Takes effect when the session is started
+    /// next.
+    pub fn set_stats_history(&mut self, name: &str, samples: usize) -> Result<()> {
+        let session = self
+            .sessions
+            .get_mut(name)
+            .ok_or_else(|| no_such_session(name))?;
+        session.history = samples;
+        Ok(())
+    }
+
+    /// Statistics samples of a session from the oldest to the newest.
+    ///
+    /// See `set_stats_history()`. Returns `None` if there is no such session.
+    pub fn stats_history(&self, name: &str) -> Option<Vec<StatsSample>> {
+        let session = self.sessions.get(name)?;
+        let history = session.shared.history.lock().unwrap();
+        Some(history.samples().copied().collect())
+    }
+
+    /// Handle for replacing the filter of a session from other threads.
+    ///
+    /// Returns `None` if the session has never been started. A new handle is
+    /// created each time the session is started.
+    pub fn filter_handle(&self, name: &str) -> Option<FilterHandle> {
+        self.sessions.get(name)?.filter.clone()
+    }
+
+    /// Stop a session and wait for its capture thread to exit.
+    pub fn stop(&mut self, name: &str) -> Result<()> {
+        let session = self
+            .sessions
+            .get_mut(name)
+            .ok_or_else(|| no_such_session(name))?;
+        session.stop();
+        log::debug!("session {}: stopped", name);
+        Ok(())
+    }
+
+    /// Start all stopped sessions.
+    ///
+    /// Stops at the first session failing to start.
+    pub fn start_all(&mut self) -> Result<()> {
+        let names = self.sessions.keys().cloned().collect::<Vec<_>>();
+        names.iter().try_for_each(|name| self.start(name))
+    }
+
+    /// Stop all sessions.
+    pub fn stop_all(&mut self) {
+        self.sessions.values_mut().for_each(Session::stop);
+    }
+
+    /// Status of a session.
+    pub fn status(&self, name: &str) -> Option<SessionStatus> {
+        self.sessions.get(name).map(Session::status)
+    }
+
+    /// Statistics of a session since it was last started.
+    pub fn session_stats(&self, name: &str) -> Option<SessionStats> {
+        self.sessions
+            .get(name)
+            .map(|session| session.shared.stats())
+    }
+
+    /// Sum of statistics of all sessions.
+    pub fn stats(&self) -> SessionStats {
+        self.sessions
+            .values()
+            .map(|session| session.shared.stats())
+            .fold(SessionStats::default(), |a, b| a + b)
+    }
+}
+
+impl Drop for SessionManager {
+    fn drop(&mut self) {
+        self.stop_all();
+    }
+}
+
+impl std::fmt::Debug for SessionManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(
+                self.sessions
+                    .iter()
+                    .map(|(name, session)| (name, session.status())),
+            )
+            .finish()
+    }
+}
+
+fn no_such_session(name: &str) -> Error {
+    Error::PcapError(format!("no such session: {}", name))
+}
+
+// Capture packets into sink until the session is stopped or capturing fails.
+fn run(
+    pcap: Pcap,
+    filter: FilterHandle,
+    sink: Arc<Mutex<Box<dyn PacketSink>>>,
+    shared: Arc<Shared>,
+) {
+    let mut sink = match sink.lock() {
+        Ok(sink) => sink,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    while !shared.stop.load(Ordering::SeqCst) {
+        match pcap_next_ex(&pcap) {
+            Ok(packet) if !filter.matches(&packet) => (),
+            Ok(packet) => {
+                shared.packets.fetch_add(1, Ordering::Relaxed);
+                shared
+                    .bytes
+                    .fetch_add(packet.len() as u64, Ordering::Relaxed);
+                sink.packet(packet);
+            }
+            Err(Error::Timeout) => {
+                if let Ok(stats) = pcap.stats() {
+                    shared
+                        .received
+                        .store(stats.packets_received().into(), Ordering::Relaxed);
+                    shared
+                        .dropped
+                        .store(stats.packets_dropped().into(), Ordering::Relaxed);
+                    shared.history.lock().unwrap().record(&stats);
+                }
+            }
+            Err(Error::Break) => break,
+            Err(err) => {
+                let message = format!("{} ({:?})", err, pcap);
+                log::warn!("capture failed: {}", message);
+                pcap.observe(|observer, source| observer.on_error(source, &err));
+                *shared.error.lock().unwrap() = Some(message);
+                break;
+            }
+        }
+    }
+    sink.stopped();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SessionManager, SessionStats, SessionStatus};
+    use crate::CaptureConfig;
+
+    #[test]
+    fn test_session_manager_sessions() {
+        let mut manager = SessionManager::new();
+        manager
+            .add("b", CaptureConfig::new("eth1"), |_packet| ())
+            .unwrap();
+        manager
+            .add("a", CaptureConfig::new("eth0"), |_packet| ())
+            .unwrap();
+        assert!(manager
+            .add("a", CaptureConfig::new("eth0"), |_packet| ())
+            .is_err());
+
+        assert_eq!(manager.names().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(manager.status("a"), Some(SessionStatus::Stopped));
+        assert_eq!(manager.status("c"), None);
+        assert_eq!(manager.stats(), SessionStats::default());
+
+        manager.stop("a").unwrap();
+        assert!(manager.stop("c").is_err());
+        manager.remove("a").unwrap();
+        assert_eq!(manager.names().collect::<Vec<_>>(), vec!["b"]);
+
+        manager.set_filter("b", Some("tcp")).unwrap();
+        assert!(manager.set_filter("a", None).is_err());
+        assert!(manager.filter_handle("b").is_none());
+
+        manager.set_stats_history("b", 10).unwrap();
+        assert!(manager.set_stats_history("a", 10).is_err());
+        assert_eq!(manager.stats_history("b"), Some(Vec::new()));
+        assert_eq!(manager.stats_history("a"), None);
+    }
+}
@@ -0,0 +1,386 @@
+//! Graceful shutdown on SIGINT and SIGTERM
+//!
+//! [ShutdownToken::install()] hooks SIGINT and SIGTERM on Unix, or console
+//! control events such as Ctrl-C on Windows. When the first one arrives, a
+//! background thread breaks the loops of all registered captures with
+//! `pcap_breakloop()` and flushes all registered dumpers, so a tool can
+//! finish writing its output and exit cleanly. A second signal terminates the
+//! process like the default signal handler would.
+//!
+//! ```no_run
+//! use std::sync::{Arc, Mutex};
+//!
+//! use luomu_libpcap::shutdown::ShutdownToken;
+//! use luomu_libpcap::{functions, Pcap};
+//!
+//! # fn main() -> luomu_libpcap::Result<()> {
+//! let token = ShutdownToken::install()?;
+//! let pcap = Pcap::builder("eth0")?.activate()?;
+//! let dumper = Arc::new(Mutex::new(functions::pcap_dump_open(&pcap, "out.pcap")?));
+//! let _guard = token.register(&pcap);
+//! token.register_dumper(&dumper);
+//!
+//! for packet in pcap.capture() {
+//!     dumper.lock().unwrap().dump(packet);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, Weak};
+
+use crate::functions::pcap_breakloop;
+use crate::{Error, PcapDumper, PcapT, Result};
+
+static TOKEN: OnceLock<std::result::Result<ShutdownToken, String>> = OnceLock::new();
+
+#[derive(Default)]
+struct Inner {
+    shutdown: AtomicBool,
+    // Captures registered with ShutdownToken::register().
+    captures: Mutex<Vec<Capture>>,
+    next_id: Mutex<u64>,
+    dumpers: Mutex<Vec<Weak<Mutex<PcapDumper>>>>,
+}
+
+impl Inner {
+    fn captures(&self) -> MutexGuard<'_, Vec<Capture>> {
+        self.captures
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn remove_handle(&self, id: u64) {
+        self.captures().retain(|capture| capture.handle != id);
+    }
+}
+
+struct Capture {
+    // Id of the ShutdownGuard of the registration.
+    guard: u64,
+    // `PcapT::id()` of the handle.
+    handle: u64,
+    pcap_t: *mut luomu_libpcap_sys::pcap_t,
+}
+
+// The pointer is only used for pcap_breakloop(), which may be called from
+// other threads. It is valid while it is in `Inner::captures`: PcapT removes
+// its captures with `unregister_handle()` before closing or giving away the
+// pcap_t, and `ShutdownToken::shutdown()` holds the lock of the list while
+// breaking the loops.
+unsafe impl Send for Capture {}
+
+/// Remove the captures of handle `id` from the process wide token. Called by
+/// `PcapT` when the handle is closed or its `pcap_t` is given away, so the
+/// registration ends with the handle even if its `ShutdownGuard` was leaked.
+pub(crate) fn unregister_handle(id: u64) {
+    if let Some(Ok(token)) = TOKEN.get() {
+        token.inner.remove_handle(id);
+    }
+}
+
+/// Token for observing and requesting shutdown.
+///
+/// The token is shared by the whole process, clones refer to the same token.
+#[derive(Clone)]
+pub struct ShutdownToken {
+    inner: Arc<Inner>,
+}
+
+impl ShutdownToken {
+    /// Install the signal handlers and return the token.
+    ///
+    /// The handlers are installed only once, later calls return the same
+    /// token.
+    pub fn install() -> Result<ShutdownToken> {
+        TOKEN
+            .get_or_init(|| {
+                let token = ShutdownToken {
+                    inner: Arc::new(Inner::default()),
+                };
+                sys::install(token.clone())
+                    .map(|()| token)
+                    .map_err(|err| err.to_string())
+            })
+            .clone()
+            .map_err(Error::PcapError)
+    }
+
+    /// Returns true if shutdown has been requested.
+    pub fn is_shutdown(&self) -> bool {
+        self.inner.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// Request shutdown as if a signal had arrived.
+    pub fn shutdown(&self) {
+        if self.inner.shutdown.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        log::info!("shutdown requested");
+
+        for capture in self.inner.captures().iter() {
+            unsafe { luomu_libpcap_sys::pcap_breakloop(capture.pcap_t) };
+        }
+        self.flush_dumpers();
+    }
+
+    /// Break the loop of `pcap_t` on shutdown.
+    ///
+    /// The capture stays registered until the returned guard is dropped or
+    /// the capture is closed, whichever happens first. If shutdown was
+    /// already requested, the loop is broken immediately.
+    pub fn register<'p>(&self, pcap_t: &'p PcapT) -> ShutdownGuard<'p> {
+        let id = {
+            let mut next_id = self.inner.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id
+        };
+        self.inner.captures().push(Capture {
+            guard: id,
+            handle: pcap_t.id(),
+            pcap_t: pcap_t.pcap_t,
+        });
+        if self.is_shutdown() {
+            pcap_breakloop(pcap_t);
+        }
+        ShutdownGuard {
+            inner: self.inner.clone(),
+            id,
+            _pcap_t: PhantomData,
+        }
+    }
+
+    /// Flush `dumper` on shutdown.
+    ///
+    /// The dumper stays registered until it's dropped.
+    pub fn register_dumper(&self, dumper: &Arc<Mutex<PcapDumper>>) {
+        let mut dumpers = self.inner.dumpers.lock().unwrap();
+        dumpers.retain(|dumper| dumper.strong_count() > 0);
+        dumpers.push(Arc::downgrade(dumper));
+    }
+
+    /// Flush all registered dumpers.
+    pub fn flush_dumpers(&self) {
+        for dumper in self.inner.dumpers.lock().unwrap().iter() {
+            let Some(dumper) = dumper.upgrade() else {
+                continue;
+            };
+            let mut dumper = match dumper.lock() {
+                Ok(dumper) => dumper,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            if let Err(err) = dumper.flush() {
+                log::warn!("flushing dumper on shutdown failed: {}", err);
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for ShutdownToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShutdownToken")
+            .field("shutdown", &self.is_shutdown())
+            .finish()
+    }
+}
+
+/// Registration of a capture with [ShutdownToken].
+///
+/// Unregisters the capture when dropped. Closing the capture unregisters it
+/// too, so leaking the guard doesn't leave a closed capture registered.
+pub struct ShutdownGuard<'p> {
+    inner: Arc<Inner>,
+    id: u64,
+    _pcap_t: PhantomData<&'p PcapT>,
+}
+
+impl ShutdownGuard<'_> {
+    /// Flush `dumper` on shutdown too, see `ShutdownToken::register_dumper()`.
+    pub fn with_dumper(self, dumper: &Arc<Mutex<PcapDumper>>) -> Self {
+        ShutdownToken {
+            inner: self.inner.clone(),
+        }
+        .register_dumper(dumper);
+        self
+    }
+}
+
+impl Drop for ShutdownGuard<'_> {
+    fn drop(&mut self) {
+        self.inner
+            .captures()
+            .retain(|capture| capture.guard != self.id);
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::io;
+    use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+    use super::ShutdownToken;
+
+    // Write end of the pipe waking up the shutdown thread.
+    static PIPE: AtomicI32 = AtomicI32::new(-1);
+    static SIGNALED: AtomicBool = AtomicBool::new(false);
+
+    // Only async-signal-safe functions may be called here, so the work is
+    // left to the shutdown thread.
+    extern "C" fn handler(signum: libc::c_int) {
+        if SIGNALED.swap(true, Ordering::SeqCst) {
+            unsafe {
+                libc::signal(signum, libc::SIG_DFL);
+                libc::raise(signum);
+            }
+            return;
+        }
+        let byte = 1u8;
+        unsafe {
+            libc::write(
+                PIPE.load(Ordering::SeqCst),
+                &byte as *const u8 as *const libc::c_void,
+                1,
+            )
+        };
+    }
+
+    pub(super) fn install(token: ShutdownToken) -> io::Result<()> {
+        let mut fds = [0 as libc::c_int; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let [read_fd, write_fd] = fds;
+        PIPE.store(write_fd, Ordering::SeqCst);
+
+        std::thread::Builder::new()
+            .name("luomu-libpcap-shutdown".to_string())
+            .spawn(move || loop {
+                let mut byte = 0u8;
+                let ret =
+                    unsafe { libc::read(read_fd, &mut byte as *mut u8 as *mut libc::c_void, 1) };
+                if ret == 1 {
+                    token.shutdown();
+                    return;
+                }
+                if ret < 0 && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return;
+            })?;
+
+        for signum in [libc::SIGINT, libc::SIGTERM] {
+            let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+            action.sa_sigaction = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            if unsafe { libc::sigaction(signum, &action, std::ptr::null_mut()) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::io;
+    use std::sync::OnceLock;
+
+    use super::ShutdownToken;
+
+    const CTRL_C_EVENT: u32 = 0;
+    const CTRL_BREAK_EVENT: u32 = 1;
+
+    static HANDLER_TOKEN: OnceLock<ShutdownToken> = OnceLock::new();
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetConsoleCtrlHandler(
+            handler: Option<unsafe extern "system" fn(u32) -> i32>,
+            add: i32,
+        ) -> i32;
+    }
+
+    // Windows runs console control handlers in a thread of their own, so the
+    // shutdown can be done right here.
+    unsafe extern "system" fn handler(event: u32) -> i32 {
+        let Some(token) = HANDLER_TOKEN.get() else {
+            return 0;
+        };
+        if token.is_shutdown() {
+            // Let the default handler terminate the process.
+            return 0;
+        }
+        token.shutdown();
+        // Console close, logoff and shutdown events terminate the process
+        // when the handler returns no matter what.
+        i32::from(event == CTRL_C_EVENT || event == CTRL_BREAK_EVENT)
+    }
+
+    pub(super) fn install(token: ShutdownToken) -> io::Result<()> {
+        _ = HANDLER_TOKEN.set(token);
+        if unsafe { SetConsoleCtrlHandler(Some(handler), 1) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+    use std::sync::Arc;
+
+    use super::{Capture, Inner, ShutdownGuard, ShutdownToken};
+
+    // Token which is not the process wide one, so the tests don't install
+    // signal handlers or shut down other tests.
+    fn token() -> ShutdownToken {
+        ShutdownToken {
+            inner: Arc::new(Inner::default()),
+        }
+    }
+
+    #[test]
+    fn test_shutdown_token() {
+        let token = token();
+        let clone = token.clone();
+        assert!(!token.is_shutdown());
+        clone.shutdown();
+        clone.shutdown();
+        assert!(token.is_shutdown());
+        assert!(!self::token().is_shutdown());
+    }
+
+    #[test]
+    fn test_unregister_leaked_guard() {
+        let token = token();
+        for (guard, handle) in [(1, 7), (2, 8)] {
+            // Not a real capture, the pointer is never used as shutdown is
+            // not requested.
+            token.inner.captures().push(Capture {
+                guard,
+                handle,
+                pcap_t: std::ptr::null_mut(),
+            });
+        }
+        std::mem::forget(ShutdownGuard {
+            inner: token.inner.clone(),
+            id: 1,
+            _pcap_t: PhantomData,
+        });
+
+        // Closing handle 7 removes its capture even though its guard leaked.
+        token.inner.remove_handle(7);
+        let handles: Vec<u64> = token.inner.captures().iter().map(|c| c.handle).collect();
+        assert_eq!(handles, vec![8]);
+
+        drop(ShutdownGuard {
+            inner: token.inner.clone(),
+            id: 2,
+            _pcap_t: PhantomData,
+        });
+        assert!(token.inner.captures().is_empty());
+    }
+}
@@ -0,0 +1,117 @@
+//! smoltcp support for libpcap
+//!
+//! This module implements [smoltcp::phy::Device] on top of a live [Pcap]
+//! capture. Captured packets are handed out as received frames and frames
+//! transmitted by smoltcp are sent out with `pcap_inject()`. This makes it
+//! possible to run a user-space TCP/IP stack over any interface libpcap can
+//! open, which is handy for testing.
+//!
+//! The [Pcap] should be configured with a short packet buffer timeout (or
+//! immediate mode) as [Device::receive()] blocks until a packet arrives or the
+//! timeout expires.
+
+use smoltcp::phy::{self, Device, DeviceCapabilities, Medium};
+use smoltcp::time::Instant;
+
+use crate::functions::{pcap_inject, pcap_next_ex};
+use crate::{Error, Packet, Pcap, PcapT};
+
+/// A [smoltcp::phy::Device] built on top of [Pcap].
+pub struct PcapDevice {
+    pcap: Pcap,
+    medium: Medium,
+    mtu: usize,
+}
+
+impl PcapDevice {
+    /// Construct a new `PcapDevice` from activated [Pcap].
+    ///
+    /// `medium` tells smoltcp what kind of frames are captured and `mtu` is
+    /// the maximum size of a frame (including link-layer header for
+    /// Ethernet) smoltcp is allowed to transmit.
+    pub fn new(pcap: Pcap, medium: Medium, mtu: usize) -> Self {
+        PcapDevice { pcap, medium, mtu }
+    }
+
+    /// Return a reference to the underlying [Pcap].
+    pub fn pcap(&self) -> &Pcap {
+        &self.pcap
+    }
+
+    /// Consume the `PcapDevice` and return the underlying [Pcap].
+    pub fn into_inner(self) -> Pcap {
+        self.pcap
+    }
+}
+
+impl Device for PcapDevice {
+    type RxToken<'a> = RxToken;
+    type TxToken<'a> = TxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        match pcap_next_ex(&self.pcap.pcap_t) {
+            Ok(packet) => {
+                let rx = RxToken {
+                    buf: packet.to_vec(),
+                };
+                let tx = TxToken {
+                    pcap_t: &self.pcap.pcap_t,
+                };
+                Some((rx, tx))
+            }
+            Err(Error::Timeout) => None,
+            Err(err) => {
+                log::error!("PcapDevice::receive(): {}", err);
+                None
+            }
+        }
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(TxToken {
+            pcap_t: &self.pcap.pcap_t,
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = self.medium;
+        caps.max_transmission_unit = self.mtu;
+        caps
+    }
+}
+
+/// Token for a received frame. Owns a copy of the captured packet.
+pub struct RxToken {
+    buf: Vec<u8>,
+}
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(&mut self.buf)
+    }
+}
+
+/// Token for transmitting a frame with `pcap_inject()`.
+pub struct TxToken<'p> {
+    pcap_t: &'p PcapT,
+}
+
+impl<'p> phy::TxToken for TxToken<'p> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut buf = vec![0u8; len];
+        let ret = f(&mut buf);
+        match pcap_inject(self.pcap_t, &buf) {
+            Ok(n) if n != len => log::warn!("TxToken::consume(): short write {}/{}", n, len),
+            Ok(_) => (),
+            Err(err) => log::error!("TxToken::consume(): {}", err),
+        }
+        ret
+    }
+}
@@ -0,0 +1,48 @@
+use crate::functions::pcap_next_ex;
+use crate::{BorrowedPacket, Error, Packet, Pcap, Result, Supervisor};
+
+/// Source of packets, such as a live capture or a savefile.
+///
+/// `next_packet()` follows the conventions of `pcap_next_ex()`: it returns
+/// `Error::Timeout` if no packet arrived within the packet buffer timeout and
+/// `Error::Break` at the end of a savefile or after `pcap_breakloop()`.
+///
+/// Code generic over `PacketSource` can be composed with adapters, like
+/// [Sampler](crate::Sampler), and tested without capturing real traffic.
+pub trait PacketSource {
+    /// Type of packets returned by the source.
+    type Packet: Packet;
+
+    /// Get the next packet from the source.
+    fn next_packet(&mut self) -> Result<Self::Packet>;
+}
+
+impl PacketSource for Pcap {
+    type Packet = BorrowedPacket;
+
+    fn next_packet(&mut self) -> Result<BorrowedPacket> {
+        let packet = pcap_next_ex(self);
+        if let Err(err) = &packet {
+            if !matches!(err, Error::Timeout | Error::Break) {
+                self.observe(|observer, source| observer.on_error(source, err));
+            }
+        }
+        packet
+    }
+}
+
+impl PacketSource for Supervisor {
+    type Packet = BorrowedPacket;
+
+    fn next_packet(&mut self) -> Result<BorrowedPacket> {
+        Supervisor::next_packet(self)
+    }
+}
+
+impl<S: PacketSource + ?Sized> PacketSource for &mut S {
+    type Packet = S::Packet;
+
+    fn next_packet(&mut self) -> Result<S::Packet> {
+        (**self).next_packet()
+    }
+}
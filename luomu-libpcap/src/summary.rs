@@ -0,0 +1,186 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::{Packet, PacketSource, Pcap, Result};
+
+/// Final summary of a capture. See [SummaryGuard].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CaptureSummary {
+    /// Time from the start of the guard until the end of the capture.
+    pub duration: Duration,
+    /// Packets read through the guard.
+    pub packets: u64,
+    /// Captured bytes of packets read through the guard.
+    pub bytes: u64,
+    /// Packets received as reported by libpcap, or `None` if statistics are
+    /// not available, e.g. for savefiles.
+    pub received: Option<u64>,
+    /// Packets dropped by the operating system as reported by libpcap.
+    pub dropped: Option<u64>,
+    /// Packets dropped by the network interface or its driver as reported by
+    /// libpcap.
+    pub dropped_interface: Option<u64>,
+}
+
+impl fmt::Display for CaptureSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} packets ({} bytes) in {:.3}s",
+            self.packets,
+            self.bytes,
+            self.duration.as_secs_f64()
+        )?;
+        if let (Some(received), Some(dropped), Some(dropped_interface)) =
+            (self.received, self.dropped, self.dropped_interface)
+        {
+            write!(
+                f,
+                ", {} received, {} dropped, {} dropped by interface",
+                received, dropped, dropped_interface
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Guard summarizing a capture when it ends.
+///
+/// `SummaryGuard` owns a [Pcap] and counts the packets read through its
+/// `PacketSource` implementation. When the guard is dropped, on whichever
+/// path the application exits the capture on, the summary of the capture is
+/// logged at info level. Call `finish()` to get the summary instead.
+///
+/// The guard dereferences to the `Pcap`, so the capture can be configured
+/// and its statistics queried as usual. Packets read in other ways, like
+/// with `Pcap::capture()`, are not counted in `packets` and `bytes` of the
+/// summary, but are in the statistics of libpcap.
+///
+/// ```no_run
+/// use luomu_libpcap::{PacketSource, Pcap, SummaryGuard};
+///
+/// let mut pcap = SummaryGuard::new(Pcap::new("eth0")?);
+/// pcap.activate()?;
+/// while let Ok(packet) = pcap.next_packet() {
+///     // ...
+/// }
+/// println!("{}", pcap.finish());
+/// # Ok::<(), luomu_libpcap::Error>(())
+/// ```
+pub struct SummaryGuard {
+    // None after `finish()`.
+    pcap: Option<Pcap>,
+    started: Instant,
+    packets: u64,
+    bytes: u64,
+}
+
+impl SummaryGuard {
+    /// Start summarizing `pcap`.
+    pub fn new(pcap: Pcap) -> SummaryGuard {
+        SummaryGuard {
+            pcap: Some(pcap),
+            started: Instant::now(),
+            packets: 0,
+            bytes: 0,
+        }
+    }
+
+    /// Summary of the capture so far.
+    pub fn summary(&self) -> CaptureSummary {
+        let mut summary = CaptureSummary {
+            duration: self.started.elapsed(),
+            packets: self.packets,
+            bytes: self.bytes,
+            ..Default::default()
+        };
+        if let Some(Ok(stats)) = self.pcap.as_ref().map(Pcap::stats) {
+            summary.received = Some(stats.packets_received().into());
+            summary.dropped = Some(stats.packets_dropped().into());
+            summary.dropped_interface = Some(stats.packets_dropped_interface().into());
+        }
+        summary
+    }
+
+    /// Close the capture and return its summary without logging it.
+    pub fn finish(mut self) -> CaptureSummary {
+        let summary = self.summary();
+        self.pcap = None;
+        summary
+    }
+
+    /// Get the capture back without summarizing it.
+    pub fn into_inner(mut self) -> Pcap {
+        self.pcap.take().expect("capture of SummaryGuard")
+    }
+
+    fn pcap(&self) -> &Pcap {
+        self.pcap.as_ref().expect("capture of SummaryGuard")
+    }
+}
+
+impl std::ops::Deref for SummaryGuard {
+    type Target = Pcap;
+
+    fn deref(&self) -> &Pcap {
+        self.pcap()
+    }
+}
+
+impl PacketSource for SummaryGuard {
+    type Packet = <Pcap as PacketSource>::Packet;
+
+    fn next_packet(&mut self) -> Result<Self::Packet> {
+        let packet = crate::functions::pcap_next_ex(self.pcap())?;
+        self.packets += 1;
+        self.bytes += packet.len() as u64;
+        Ok(packet)
+    }
+}
+
+impl Drop for SummaryGuard {
+    fn drop(&mut self) {
+        if let Some(pcap) = &self.pcap {
+            log::info!(
+                "capture on {} ended: {}",
+                pcap.get_inteface(),
+                self.summary()
+            );
+        }
+    }
+}
+
+impl fmt::Debug for SummaryGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SummaryGuard")
+            .field("packets", &self.packets)
+            .field("bytes", &self.bytes)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::CaptureSummary;
+
+    #[test]
+    fn test_capture_summary_display() {
+        let mut summary = CaptureSummary {
+            duration: Duration::from_millis(1500),
+            packets: 10,
+            bytes: 640,
+            ..Default::default()
+        };
+        assert_eq!(summary.to_string(), "10 packets (640 bytes) in 1.500s");
+
+        summary.received = Some(12);
+        summary.dropped = Some(2);
+        summary.dropped_interface = Some(0);
+        assert_eq!(
+            summary.to_string(),
+            "10 packets (640 bytes) in 1.500s, 12 received, 2 dropped, 0 dropped by interface"
+        );
+    }
+}
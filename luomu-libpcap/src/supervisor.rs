@@ -0,0 +1,271 @@
+use std::time::{Duration, Instant};
+
+use crate::{BorrowedPacket, CaptureConfig, Error, Pcap, Result};
+
+/// Events emitted by [Supervisor].
+#[derive(Debug)]
+pub enum SupervisorEvent {
+    /// Reading from the capture failed and it was closed.
+    Down {
+        /// Error returned by libpcap.
+        error: Error,
+    },
+    /// Opening the capture again failed.
+    ReopenFailed {
+        /// Number of failed attempts since the capture went down.
+        attempts: u32,
+        /// Error returned by libpcap.
+        error: Error,
+    },
+    /// The capture was opened again.
+    Reopened {
+        /// Time the capture was down.
+        downtime: Duration,
+        /// Number of failed attempts before succeeding.
+        attempts: u32,
+    },
+}
+
+type EventHandler = Box<dyn FnMut(&SupervisorEvent) + Send>;
+
+/// Live capture which is reopened automatically when it fails.
+///
+/// When the interface of a capture goes down or the device is removed,
+/// reading from the capture fails and the handle is useless. `Supervisor`
+/// closes such a handle and tries to create and activate it again from the
+/// same [CaptureConfig], so options and filter are preserved. Attempts are
+/// made with an increasing interval, from 100 milliseconds up to 5 seconds by
+/// default, until the interface returns.
+///
+/// Interruptions are reported as [SupervisorEvent]s to the handler set with
+/// `Supervisor::on_event()`, and logged. Packets arriving while the capture
+/// is down are lost.
+pub struct Supervisor {
+    config: CaptureConfig,
+    pcap: Option<Pcap>,
+    min_retry: Duration,
+    max_retry: Duration,
+    retry: Duration,
+    down_since: Option<Instant>,
+    attempts: u32,
+    on_event: Option<EventHandler>,
+}
+
+impl Supervisor {
+    /// Open a capture with `config` and supervise it.
+    ///
+    /// Fails if the capture can't be opened the first time.
+    pub fn open(config: CaptureConfig) -> Result<Supervisor> {
+        let pcap = config.open()?;
+        Ok(Supervisor::new(config, Some(pcap)))
+    }
+
+    /// Supervise an activated capture created with `PcapBuilder`.
+    pub fn from_pcap(pcap: Pcap) -> Result<Supervisor> {
+        let config = pcap.config().ok_or_else(|| {
+            Error::PcapError("capture was not created with PcapBuilder".to_string())
+        })?;
+        Ok(Supervisor::new(config, Some(pcap)))
+    }
+
+    fn new(config: CaptureConfig, pcap: Option<Pcap>) -> Supervisor {
+        let min_retry = Duration::from_millis(100);
+        Supervisor {
+            config,
+            pcap,
+            min_retry,
+            max_retry: Duration::from_secs(5),
+            retry: min_retry,
+            down_since: None,
+            attempts: 0,
+            on_event: None,
+        }
+    }
+
+    /// Set the interval between reopen attempts, starting from `min` and
+    /// doubling after each failed attempt up to `max`.
+    pub fn with_retry_interval(mut self, min: Duration, max: Duration) -> Supervisor {
+        self.min_retry = min;
+        self.max_retry = max.max(min);
+        self.retry = min;
+        self
+    }
+
+    /// Set a handler called for every [SupervisorEvent].
+    pub fn on_event<F>(mut self, handler: F) -> Supervisor
+    where
+        F: FnMut(&SupervisorEvent) + Send + 'static,
+    {
+        self.on_event = Some(Box::new(handler));
+        self
+    }
+
+    /// Configuration the capture is opened with.
+    pub fn config(&self) -> &CaptureConfig {
+        &self.config
+    }
+
+    /// The current capture, or `None` while it's down.
+    pub fn pcap(&self) -> Option<&Pcap> {
+        self.pcap.as_ref()
+    }
+
+    /// Returns true if the capture is up.
+    pub fn is_up(&self) -> bool {
+        self.pcap.is_some()
+    }
+
+    /// Get the next packet from the capture.
+    ///
+    /// Returns `Error::Timeout` if the packet buffer timeout expired, or if
+    /// the capture is down and could not be reopened. While the capture is
+    /// down each call waits for the retry interval and makes one attempt to
+    /// reopen it. Other errors mean the capture went down.
+    pub fn next_packet(&mut self) -> Result<BorrowedPacket> {
+        let pcap = match &self.pcap {
+            Some(pcap) => pcap,
+            None => {
+                self.reopen();
+                return Err(Error::Timeout);
+            }
+        };
+
+        match crate::functions::pcap_next_ex(pcap) {
+            Ok(packet) => Ok(packet),
+            Err(error) if !is_down(&error) => Err(error),
+            Err(error) => {
+                log::warn!("capture went down: {} ({:?})", error, pcap);
+                self.pcap = None;
+                self.went_down(Instant::now());
+                let event = SupervisorEvent::Down { error };
+                self.emit(&event);
+                Err(Error::Timeout)
+            }
+        }
+    }
+
+    // Wait for the retry interval and try to open the capture again.
+    fn reopen(&mut self) {
+        std::thread::sleep(self.retry);
+        match self.config.open() {
+            Ok(pcap) => {
+                let (downtime, attempts) = self.reopened(Instant::now());
+                log::info!(
+                    "{}: capture reopened after {:?}",
+                    self.config.source,
+                    downtime
+                );
+                self.pcap = Some(pcap);
+                let event = SupervisorEvent::Reopened { downtime, attempts };
+                self.emit(&event);
+            }
+            Err(error) => {
+                let attempts = self.reopen_failed();
+                log::debug!(
+                    "{}: reopening capture failed: {}",
+                    self.config.source,
+                    error
+                );
+                let event = SupervisorEvent::ReopenFailed { attempts, error };
+                self.emit(&event);
+            }
+        }
+    }
+
+    // Record that the capture went down at `now`.
+    fn went_down(&mut self, now: Instant) {
+        self.down_since = Some(now);
+        self.attempts = 0;
+        self.retry = self.min_retry;
+    }
+
+    // Record a failed reopen attempt and back off. Returns the number of
+    // failed attempts since the capture went down.
+    fn reopen_failed(&mut self) -> u32 {
+        self.attempts += 1;
+        self.retry = (self.retry * 2).min(self.max_retry);
+        self.attempts
+    }
+
+    // Record that the capture was reopened at `now`. Returns the time it was
+    // down and the number of failed attempts before.
+    fn reopened(&mut self, now: Instant) -> (Duration, u32) {
+        let downtime = self
+            .down_since
+            .take()
+            .map(|since| now.saturating_duration_since(since))
+            .unwrap_or_default();
+        let attempts = self.attempts;
+        self.attempts = 0;
+        self.retry = self.min_retry;
+        (downtime, attempts)
+    }
+
+    fn emit(&mut self, event: &SupervisorEvent) {
+        if let Some(handler) = &mut self.on_event {
+            handler(event);
+        }
+    }
+}
+
+impl std::fmt::Debug for Supervisor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Supervisor")
+            .field("config", &self.config)
+            .field("up", &self.is_up())
+            .field("attempts", &self.attempts)
+            .finish()
+    }
+}
+
+// Returns true if reading failed with `error` because the capture went down,
+// and not because of a timeout or `pcap_breakloop()`.
+fn is_down(error: &Error) -> bool {
+    !matches!(error, Error::Timeout | Error::Break)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{is_down, Supervisor};
+    use crate::{CaptureConfig, Error};
+
+    #[test]
+    fn test_supervisor_backoff() {
+        let mut supervisor = Supervisor::new(CaptureConfig::new("eth0"), None)
+            .with_retry_interval(Duration::from_millis(100), Duration::from_millis(500));
+        let start = Instant::now();
+        supervisor.went_down(start);
+        assert_eq!(supervisor.retry, Duration::from_millis(100));
+
+        let retries: Vec<(u32, Duration)> = (0..4)
+            .map(|_| (supervisor.reopen_failed(), supervisor.retry))
+            .collect();
+        assert_eq!(
+            retries,
+            vec![
+                (1, Duration::from_millis(200)),
+                (2, Duration::from_millis(400)),
+                (3, Duration::from_millis(500)),
+                (4, Duration::from_millis(500)),
+            ]
+        );
+
+        let (downtime, attempts) = supervisor.reopened(start + Duration::from_secs(2));
+        assert_eq!(downtime, Duration::from_secs(2));
+        assert_eq!(attempts, 4);
+        assert_eq!(supervisor.retry, Duration::from_millis(100));
+
+        // Going down again starts a new series of attempts.
+        supervisor.went_down(start + Duration::from_secs(3));
+        assert_eq!(supervisor.reopen_failed(), 1);
+    }
+
+    #[test]
+    fn test_is_down() {
+        assert!(!is_down(&Error::Timeout));
+        assert!(!is_down(&Error::Break));
+        assert!(is_down(&Error::PcapError("The interface went down".into())));
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+
+use luomu_common::MacAddr;
+
+use crate::{Error, Result};
+
+// Reserved words of the filter language. A name substituted into a filter
+// must not be any of these, as it would change the meaning of the filter.
+const KEYWORDS: &str = "\
+    aarp action addr1 addr2 addr3 addr4 address1 address2 address3 address4 ah \
+    and arp atalk bcc broadcast byte carp clnp connectmsg csnp decnet dir \
+    direction dpc dst esis esp ether fddi fisu gateway geneve greater hdpc \
+    hfisu hlssu hmsu hopc host hsio hsls icmp icmp6 ifindex ifname igmp igrp \
+    iih ilmic inbound ip ip6 ipx isis iso l1 l2 lane lat len length less link \
+    llc lsp lssu lsu mask metac metaconnect mopdl moprc mpls msu multicast net \
+    netbeui not oam oamf4 oamf4ec oamf4sc on opc or outbound pim port \
+    portrange ppp pppoed pppoes proto protochain psnp ra radio rarp reason rnr \
+    rset rulenum ruleset sc sca sctp sio slip sls snp src srnr stp subrulenum \
+    subtype ta tcp tr type udp vci vlan vpi vrrp wlan";
+
+/// Typed value substituted into a [FilterTemplate].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FilterValue {
+    /// IPv4 or IPv6 address.
+    Ip(IpAddr),
+    /// Network as address and prefix length. Bits of the address outside
+    /// the prefix must be zero.
+    Net(IpAddr, u8),
+    /// Port number.
+    Port(u16),
+    /// Range of ports, inclusive.
+    PortRange(u16, u16),
+    /// Ethernet address.
+    Mac(MacAddr),
+    /// Number, such as a protocol number or packet length.
+    Number(u32),
+    /// Name of a host, protocol or interface.
+    ///
+    /// Only ASCII letters, digits, '.', '-' and '_' are allowed, and the
+    /// name can't be a reserved word of the filter language.
+    Name(String),
+}
+
+impl FilterValue {
+    // Check the value can be safely substituted.
+    pub(crate) fn validate(&self) -> Result<()> {
+        match self {
+            FilterValue::Net(IpAddr::V4(_), prefix) if *prefix > 32 => {
+                Err(invalid_value(self, "prefix length too long"))
+            }
+            FilterValue::Net(IpAddr::V6(_), prefix) if *prefix > 128 => {
+                Err(invalid_value(self, "prefix length too long"))
+            }
+            // libpcap rejects networks with bits set outside the prefix.
+            FilterValue::Net(IpAddr::V4(ip), prefix)
+                if u32::from(*ip).checked_shl(u32::from(*prefix)).unwrap_or(0) != 0 =>
+            {
+                Err(invalid_value(self, "host bits set in network"))
+            }
+            FilterValue::Net(IpAddr::V6(ip), prefix)
+                if u128::from(*ip).checked_shl(u32::from(*prefix)).unwrap_or(0) != 0 =>
+            {
+                Err(invalid_value(self, "host bits set in network"))
+            }
+            FilterValue::PortRange(start, end) if start > end => {
+                Err(invalid_value(self, "start of port range after its end"))
+            }
+            FilterValue::Name(name) => {
+                let valid_chars = name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+                if name.is_empty() || !valid_chars || name.starts_with('-') {
+                    return Err(invalid_value(self, "invalid characters in name"));
+                }
+                if KEYWORDS
+                    .split_whitespace()
+                    .any(|keyword| keyword.eq_ignore_ascii_case(name))
+                {
+                    return Err(invalid_value(self, "name is a reserved word"));
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for FilterValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterValue::Ip(ip) => write!(f, "{}", ip),
+            FilterValue::Net(ip, prefix) => write!(f, "{}/{}", ip, prefix),
+            FilterValue::Port(port) => write!(f, "{}", port),
+            FilterValue::PortRange(start, end) => write!(f, "{}-{}", start, end),
+            FilterValue::Mac(mac) => write!(f, "{}", mac),
+            FilterValue::Number(n) => write!(f, "{}", n),
+            FilterValue::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+impl From<IpAddr> for FilterValue {
+    fn from(ip: IpAddr) -> Self {
+        FilterValue::Ip(ip)
+    }
+}
+
+impl From<u16> for FilterValue {
+    fn from(port: u16) -> Self {
+        FilterValue::Port(port)
+    }
+}
+
+impl From<MacAddr> for FilterValue {
+    fn from(mac: MacAddr) -> Self {
+        FilterValue::Mac(mac)
+    }
+}
+
+fn invalid_value(value: &FilterValue, reason: &str) -> Error {
+    Error::PcapError(format!("invalid filter value {:?}: {}", value, reason))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Param(String),
+}
+
+/// Filter expression with named parameters.
+///
+/// Parameters are written as `{name}` in the template, for example
+/// `"host {ip} and port {port}"`, and literal braces as `{{` and `}}`.
+/// Values are given as typed [FilterValue]s, which are validated before
+/// substitution. Building filters from untrusted input this way can't change
+/// the structure of the filter, unlike concatenating strings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FilterTemplate {
+    parts: Vec<Part>,
+}
+
+impl FilterTemplate {
+    /// Parse a template.
+    pub fn parse(template: &str) -> Result<FilterTemplate> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest
+                        .find('}')
+                        .ok_or_else(|| template_error("unclosed parameter"))?;
+                    let name = &rest[..end];
+                    if name.is_empty()
+                        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                    {
+                        return Err(template_error("invalid parameter name"));
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Param(name.to_string()));
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => return Err(template_error("unmatched '}'")),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(FilterTemplate { parts })
+    }
+
+    /// Names of the parameters in the template.
+    pub fn params(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Param(name) => Some(name.as_str()),
+            Part::Literal(_) => None,
+        })
+    }
+
+    /// Substitute values to parameters and return the filter expression.
+    ///
+    /// Fails if a parameter has no value, a value is given for an unknown
+    /// parameter, or a value is not valid.
+    pub fn render(&self, values: &[(&str, FilterValue)]) -> Result<String> {
+        let values: HashMap<&str, &FilterValue> =
+            values.iter().map(|(name, value)| (*name, value)).collect();
+        if let Some(name) = values
+            .keys()
+            .find(|name| !self.params().any(|p| p == **name))
+        {
+            return Err(template_error(&format!("unknown parameter {}", name)));
+        }
+
+        let mut filter = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => filter.push_str(literal),
+                Part::Param(name) => {
+                    let value = values
+                        .get(name.as_str())
+                        .ok_or_else(|| template_error(&format!("no value for {}", name)))?;
+                    value.validate()?;
+                    filter.push_str(&value.to_string());
+                }
+            }
+        }
+        Ok(filter)
+    }
+}
+
+fn template_error(reason: &str) -> Error {
+    Error::PcapError(format!("invalid filter template: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::{FilterTemplate, FilterValue};
+
+    #[test]
+    fn test_filter_template_render() {
+        let template = FilterTemplate::parse("host {ip} and port {port}").unwrap();
+        assert_eq!(template.params().collect::<Vec<_>>(), vec!["ip", "port"]);
+
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let filter = template
+            .render(&[("ip", ip.into()), ("port", 53u16.into())])
+            .unwrap();
+        assert_eq!(filter, "host 192.0.2.1 and port 53");
+
+        assert!(template.render(&[("ip", ip.into())]).is_err());
+        assert!(template
+            .render(&[
+                ("ip", ip.into()),
+                ("port", 53u16.into()),
+                ("x", 1u16.into())
+            ])
+            .is_err());
+
+        let template = FilterTemplate::parse("net {net} or ip[0] & 0xf = {{n}}").unwrap();
+        let net = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0));
+        let filter = template
+            .render(&[("net", FilterValue::Net(net, 24))])
+            .unwrap();
+        assert_eq!(filter, "net 192.0.2.0/24 or ip[0] & 0xf = {n}");
+    }
+
+    #[test]
+    fn test_filter_template_invalid() {
+        assert!(FilterTemplate::parse("host {ip").is_err());
+        assert!(FilterTemplate::parse("host {}").is_err());
+        assert!(FilterTemplate::parse("host ip}").is_err());
+
+        let template = FilterTemplate::parse("host {host}").unwrap();
+        for name in ["", "a b", "x or tcp", "-v", "and", "TCP"] {
+            let value = FilterValue::Name(name.to_string());
+            assert!(template.render(&[("host", value)]).is_err(), "{}", name);
+        }
+        let value = FilterValue::Name("www.example.com".to_string());
+        assert!(template.render(&[("host", value)]).is_ok());
+
+        let template = FilterTemplate::parse("net {net}").unwrap();
+        let host = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        assert!(template
+            .render(&[("net", FilterValue::Net(host, 24))])
+            .is_err());
+        assert!(template
+            .render(&[("net", FilterValue::Net(host, 32))])
+            .is_ok());
+        let host: IpAddr = "2001:db8::1".parse().unwrap();
+        assert!(template
+            .render(&[("net", FilterValue::Net(host, 64))])
+            .is_err());
+        assert!(template
+            .render(&[("net", FilterValue::Net("2001:db8::".parse().unwrap(), 32))])
+            .is_ok());
+        assert!(template
+            .render(&[(
+                "net",
+                FilterValue::Net(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+            )])
+            .is_ok());
+
+        let template = FilterTemplate::parse("portrange {ports}").unwrap();
+        assert!(template
+            .render(&[("ports", FilterValue::PortRange(2, 1))])
+            .is_err());
+    }
+}
@@ -9,11 +9,13 @@ use tokio::sync::mpsc::{self, UnboundedSender};
 use tokio::task;
 
 use crate::functions as libpcap;
-use crate::{Error, OwnedPacket};
+use crate::{Error, IdleBackoff, OwnedPacket};
 
 /// Asynchronous Capture
 ///
-/// This type uses Tokio's blocking task to run a libpcap capture loop.
+/// This type uses Tokio's blocking task to run a libpcap capture loop. When
+/// the capture is idle the loop waits with [IdleBackoff] instead of retrying
+/// immediately.
 #[derive(Debug)]
 pub struct AsyncCapture {
     rx: mpsc::UnboundedReceiver<crate::Result<OwnedPacket>>,
@@ -49,10 +51,20 @@ impl Stream for AsyncCapture {
 }
 
 fn capture_loop(pcap: crate::Pcap, tx: UnboundedSender<crate::Result<OwnedPacket>>) {
+    let mut idle = IdleBackoff::default();
     loop {
         match next_packet(&pcap) {
-            Poll::Pending => continue,
+            Poll::Pending => {
+                if tx.is_closed() {
+                    return;
+                }
+                if let Err(e) = idle.wait(&pcap) {
+                    _ = tx.send(Err(e));
+                    return;
+                }
+            }
             Poll::Ready(ret) => {
+                idle.reset();
                 let is_err = ret.is_err();
                 if tx.send(ret).is_err() {
                     return;
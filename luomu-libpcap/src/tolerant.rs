@@ -0,0 +1,140 @@
+use std::io;
+
+use crate::{Error, PacketSource, Result};
+
+/// Adapter retrying transient errors of a [PacketSource].
+///
+/// Long running collectors should not stop because a read timed out or a
+/// system call was interrupted by a signal. `Tolerant` retries reads failing
+/// with such errors, logs them and counts them, and returns only packets and
+/// errors after which the capture can't continue.
+///
+/// Retried errors are:
+///
+/// - `Error::Timeout`, the packet buffer timeout expiring on a quiet
+///   interface
+/// - `Error::IO` of kind `Interrupted` or `WouldBlock`
+/// - errors of libpcap about an interrupted system call (`EINTR`)
+///
+/// `Error::Break` is returned as it is, so the end of a savefile and
+/// `pcap_breakloop()` still stop the capture.
+#[derive(Debug)]
+pub struct Tolerant<S> {
+    source: S,
+    timeouts: u64,
+    interrupted: u64,
+}
+
+impl<S: PacketSource> Tolerant<S> {
+    /// Construct a new `Tolerant` over `source`.
+    pub fn new(source: S) -> Tolerant<S> {
+        Tolerant {
+            source,
+            timeouts: 0,
+            interrupted: 0,
+        }
+    }
+
+    /// Number of timeouts retried.
+    pub fn timeouts(&self) -> u64 {
+        self.timeouts
+    }
+
+    /// Number of interrupted reads retried.
+    pub fn interrupted(&self) -> u64 {
+        self.interrupted
+    }
+
+    /// Total number of errors retried.
+    pub fn suppressed(&self) -> u64 {
+        self.timeouts + self.interrupted
+    }
+
+    /// Get a reference to the underlying source.
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Get the underlying source back.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S: PacketSource> PacketSource for Tolerant<S> {
+    type Packet = S::Packet;
+
+    /// Get the next packet, retrying transient errors.
+    fn next_packet(&mut self) -> Result<S::Packet> {
+        loop {
+            match self.source.next_packet() {
+                Ok(packet) => return Ok(packet),
+                Err(Error::Timeout) => {
+                    self.timeouts += 1;
+                    log::trace!("capture timed out, retrying");
+                }
+                Err(err) if is_interrupted(&err) => {
+                    self.interrupted += 1;
+                    log::debug!("capture interrupted, retrying: {}", err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+fn is_interrupted(err: &Error) -> bool {
+    match err {
+        Error::IO(err) => matches!(
+            err.kind(),
+            io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+        ),
+        Error::PcapError(message) => message.contains("Interrupted system call"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::io;
+
+    use super::Tolerant;
+    use crate::{Error, OwnedPacket, PacketSource, Result};
+
+    // Source returning given results, and Error::Break after them.
+    struct Scripted(VecDeque<Result<OwnedPacket>>);
+
+    impl PacketSource for Scripted {
+        type Packet = OwnedPacket;
+
+        fn next_packet(&mut self) -> Result<OwnedPacket> {
+            self.0.pop_front().unwrap_or(Err(Error::Break))
+        }
+    }
+
+    #[test]
+    fn test_tolerant() {
+        let mut tolerant = Tolerant::new(Scripted(VecDeque::from(vec![
+            Err(Error::Timeout),
+            Ok(OwnedPacket::with_capacity(0)),
+            Err(Error::IO(io::Error::from(io::ErrorKind::Interrupted))),
+            Err(Error::PcapError(String::from(
+                "read: Interrupted system call",
+            ))),
+            Err(Error::Timeout),
+            Ok(OwnedPacket::with_capacity(0)),
+            Err(Error::NoSuchDevice(String::from("eth0"))),
+        ])));
+        assert!(tolerant.next_packet().is_ok());
+        assert!(tolerant.next_packet().is_ok());
+        assert!(matches!(
+            tolerant.next_packet(),
+            Err(Error::NoSuchDevice(_))
+        ));
+        assert!(matches!(tolerant.next_packet(), Err(Error::Break)));
+        assert_eq!(tolerant.timeouts(), 2);
+        assert_eq!(tolerant.interrupted(), 2);
+        assert_eq!(tolerant.suppressed(), 4);
+    }
+}
@@ -0,0 +1,348 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::functions::{pcap_dump_open, pcap_open_dead_with_tstamp_precision};
+use crate::{OwnedPacket, Packet, Pcap, PcapDumper, PcapFilter, PcapT, Result, TimestampPrecision};
+
+/// Dumper writing packets around trigger events into savefiles.
+///
+/// `TriggerDumper` keeps the most recent packets in memory and starts writing
+/// a new savefile when a packet matches the trigger filter. The savefile gets
+/// the packets kept in memory before the trigger, the packet matching it, and
+/// all packets until `post_trigger` has passed since the last matching
+/// packet. Packets matching the trigger while writing extend the dump.
+///
+/// Durations are measured with the time stamps of the packets, so savefiles
+/// can be processed as well as live captures. By default 5 seconds or 10000
+/// packets of history is kept and dumping stops 5 seconds after the trigger.
+pub struct TriggerDumper {
+    trigger: PcapFilter,
+    // Dead handle with the link-layer header type, snapshot length and time
+    // stamp precision of the capture for opening dumpers.
+    pcap_t: PcapT,
+    precision: TimestampPrecision,
+    path: Box<dyn FnMut(usize) -> PathBuf + Send>,
+    history: History,
+    window: Window,
+    dumper: Option<PcapDumper>,
+    triggers: usize,
+}
+
+impl TriggerDumper {
+    /// Construct a `TriggerDumper` for packets captured with `pcap`.
+    ///
+    /// `trigger` is a filter expression, see
+    /// [pcap-filter(7)](https://www.tcpdump.org/manpages/pcap-filter.7.html)
+    /// for the syntax. `path` is called with the number of the trigger,
+    /// starting from 0, to get the path of the savefile to write.
+    pub fn new<F>(pcap: &Pcap, trigger: &str, path: F) -> Result<TriggerDumper>
+    where
+        F: FnMut(usize) -> PathBuf + Send + 'static,
+    {
+        let precision = pcap.timestamp_precision()?;
+        let pcap_t = pcap_open_dead_with_tstamp_precision(
+            pcap.datalink()?.value(),
+            pcap.snaplen()?,
+            precision,
+        )?;
+        let trigger = PcapFilter::compile_with_pcap_t(&pcap_t, trigger)?;
+        Ok(TriggerDumper {
+            trigger,
+            pcap_t,
+            precision,
+            path: Box::new(path),
+            history: History::new(Duration::from_secs(5), 10000),
+            window: Window::new(Duration::from_secs(5)),
+            dumper: None,
+            triggers: 0,
+        })
+    }
+
+    /// Set how long packets are kept in memory before the trigger.
+    pub fn with_history(mut self, history: Duration) -> TriggerDumper {
+        self.history.duration = history;
+        self
+    }
+
+    /// Set how many packets at most are kept in memory before the trigger.
+    pub fn with_history_packets(mut self, packets: usize) -> TriggerDumper {
+        self.history.max_packets = packets;
+        self
+    }
+
+    /// Set how long packets are dumped after the last packet matching the
+    /// trigger.
+    pub fn with_post_trigger(mut self, post_trigger: Duration) -> TriggerDumper {
+        self.window.post_trigger = post_trigger;
+        self
+    }
+
+    /// Returns true if packets are currently being written into a savefile.
+    pub fn is_dumping(&self) -> bool {
+        self.dumper.is_some()
+    }
+
+    /// Number of triggers so far, that is savefiles opened.
+    pub fn triggers(&self) -> usize {
+        self.triggers
+    }
+
+    /// Number of packets kept in memory.
+    pub fn history_len(&self) -> usize {
+        self.history.packets.len()
+    }
+
+    /// Handle a captured packet.
+    ///
+    /// Returns true if the packet was written into a savefile, false if it
+    /// was kept in memory.
+    pub fn handle<P: Packet>(&mut self, packet: &P) -> Result<bool> {
+        let timestamp = packet_time(packet, self.precision);
+        let matches = self.trigger.matches(packet);
+
+        match self.window.update(timestamp, matches) {
+            Step::Start => self.start()?,
+            Step::Stop => self.stop(),
+            Step::Dump | Step::Remember => (),
+        }
+
+        match &mut self.dumper {
+            Some(dumper) => {
+                dumper.dump_raw(packet.pkthdr(), packet.packet());
+                Ok(true)
+            }
+            None => {
+                self.history.remember(packet, timestamp, self.precision);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Stop writing the current savefile, if any.
+    pub fn stop(&mut self) {
+        self.window.stop();
+        // Dropping the dumper flushes and closes the savefile.
+        if self.dumper.take().is_some() {
+            log::debug!("trigger {}: dump finished", self.triggers - 1);
+        }
+    }
+
+    // Open a new savefile and write the history into it.
+    fn start(&mut self) -> Result<()> {
+        let path = (self.path)(self.triggers);
+        log::debug!(
+            "trigger {}: dumping {} packets of history into {}",
+            self.triggers,
+            self.history.packets.len(),
+            path.display()
+        );
+        let mut dumper = match pcap_dump_open(&self.pcap_t, path) {
+            Ok(dumper) => dumper,
+            Err(err) => {
+                self.window.stop();
+                return Err(err);
+            }
+        };
+        for packet in self.history.packets.drain(..) {
+            dumper.dump(packet);
+        }
+        self.dumper = Some(dumper);
+        self.triggers += 1;
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for TriggerDumper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TriggerDumper")
+            .field("history", &self.history.packets.len())
+            .field("dumping", &self.is_dumping())
+            .field("triggers", &self.triggers)
+            .finish()
+    }
+}
+
+// What to do with a packet, see `Window::update()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Step {
+    // Keep the packet in history.
+    Remember,
+    // Open a new savefile and dump the history and the packet into it.
+    Start,
+    // Dump the packet into the current savefile.
+    Dump,
+    // Close the current savefile and keep the packet in history.
+    Stop,
+}
+
+// Dump window after trigger matches.
+#[derive(Debug)]
+struct Window {
+    post_trigger: Duration,
+    // End of the current dump, if dumping.
+    deadline: Option<SystemTime>,
+}
+
+impl Window {
+    fn new(post_trigger: Duration) -> Window {
+        Window {
+            post_trigger,
+            deadline: None,
+        }
+    }
+
+    // Update the window with a packet captured at `timestamp`, which matched
+    // the trigger if `matches`.
+    fn update(&mut self, timestamp: SystemTime, matches: bool) -> Step {
+        let dumping = self.deadline.is_some();
+        if matches {
+            self.deadline = Some(timestamp + self.post_trigger);
+            return if dumping { Step::Dump } else { Step::Start };
+        }
+        match self.deadline {
+            Some(deadline) if timestamp > deadline => {
+                self.deadline = None;
+                Step::Stop
+            }
+            Some(_) => Step::Dump,
+            None => Step::Remember,
+        }
+    }
+
+    fn stop(&mut self) {
+        self.deadline = None;
+    }
+}
+
+// Packets kept in memory before the trigger.
+#[derive(Debug)]
+struct History {
+    packets: VecDeque<OwnedPacket>,
+    duration: Duration,
+    max_packets: usize,
+}
+
+impl History {
+    fn new(duration: Duration, max_packets: usize) -> History {
+        History {
+            packets: VecDeque::new(),
+            duration,
+            max_packets,
+        }
+    }
+
+    // Keep packet in history and forget packets too old or too many.
+    fn remember<P: Packet>(
+        &mut self,
+        packet: &P,
+        timestamp: SystemTime,
+        precision: TimestampPrecision,
+    ) {
+        if self.max_packets == 0 {
+            return;
+        }
+        if self.packets.len() == self.max_packets {
+            self.packets.pop_front();
+        }
+        self.packets
+            .push_back(OwnedPacket::new(*packet.pkthdr(), packet.packet().to_vec()));
+
+        let oldest = timestamp
+            .checked_sub(self.duration)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        while matches!(self.packets.front(), Some(p) if packet_time(p, precision) < oldest) {
+            self.packets.pop_front();
+        }
+    }
+}
+
+// Time stamp of `packet` from a capture with time stamp precision
+// `precision`. `Packet::timestamp()` assumes microseconds.
+fn packet_time<P: Packet>(packet: &P, precision: TimestampPrecision) -> SystemTime {
+    let ts = packet.pkthdr().ts;
+    let fraction = match precision {
+        TimestampPrecision::Micro => Duration::from_micros(ts.tv_usec as u64),
+        TimestampPrecision::Nano => Duration::from_nanos(ts.tv_usec as u64),
+    };
+    UNIX_EPOCH + Duration::from_secs(ts.tv_sec as u64) + fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::{packet_time, History, Step, Window};
+    use crate::{OwnedPacket, TimestampPrecision};
+
+    fn packet(secs: i64, fraction: i64) -> OwnedPacket {
+        let pkthdr = luomu_libpcap_sys::pcap_pkthdr {
+            ts: libc::timeval {
+                tv_sec: secs as _,
+                tv_usec: fraction as _,
+            },
+            caplen: 1,
+            len: 1,
+        };
+        OwnedPacket::new(pkthdr, vec![0])
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_trigger_window() {
+        let mut window = Window::new(Duration::from_secs(5));
+        assert_eq!(window.update(at(1), false), Step::Remember);
+        assert_eq!(window.update(at(2), true), Step::Start);
+        assert_eq!(window.update(at(6), false), Step::Dump);
+        // Matching packets while dumping extend the dump.
+        assert_eq!(window.update(at(7), true), Step::Dump);
+        assert_eq!(window.update(at(12), false), Step::Dump);
+        assert_eq!(window.update(at(13), false), Step::Stop);
+        assert_eq!(window.update(at(14), false), Step::Remember);
+        assert_eq!(window.update(at(15), true), Step::Start);
+        window.stop();
+        assert_eq!(window.update(at(16), false), Step::Remember);
+    }
+
+    #[test]
+    fn test_trigger_history() {
+        let precision = TimestampPrecision::Micro;
+        let mut history = History::new(Duration::from_secs(5), 3);
+        for secs in [1, 2, 3, 4] {
+            let p = packet(secs, 0);
+            history.remember(&p, packet_time(&p, precision), precision);
+        }
+        // At most 3 packets are kept.
+        assert_eq!(history.packets.len(), 3);
+        let p = packet(8, 0);
+        history.remember(&p, packet_time(&p, precision), precision);
+        // Packets older than 5 seconds are forgotten.
+        let kept: Vec<SystemTime> = history
+            .packets
+            .iter()
+            .map(|p| packet_time(p, precision))
+            .collect();
+        assert_eq!(kept, vec![at(3), at(4), at(8)]);
+
+        let mut disabled = History::new(Duration::from_secs(5), 0);
+        disabled.remember(&p, at(8), precision);
+        assert!(disabled.packets.is_empty());
+    }
+
+    #[test]
+    fn test_trigger_timestamp() {
+        let p = packet(1, 500_000_000);
+        assert_eq!(
+            packet_time(&p, TimestampPrecision::Nano),
+            at(1) + Duration::from_millis(500)
+        );
+        let p = packet(1, 500_000);
+        assert_eq!(
+            packet_time(&p, TimestampPrecision::Micro),
+            at(1) + Duration::from_millis(500)
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use crate::generator::FrameBuilder;
+use crate::{loopback_frame, Error, LinkType, Packet, Pcap, PcapIfT, Result};
+
+// Destination of the probe, the discard port.
+const PROBE_PORT: u16 = 9;
+const PROBE_PAYLOAD: &[u8] = b"luomu-libpcap capture probe";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Outcome of one step of [verify_capture()].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Check {
+    /// The step worked.
+    Passed,
+    /// The step failed with the given error.
+    Failed(String),
+    /// The step was not attempted, for the given reason.
+    Skipped(String),
+}
+
+impl Check {
+    /// Returns true if the step failed.
+    pub fn is_failed(&self) -> bool {
+        matches!(self, Check::Failed(_))
+    }
+
+    fn from_result<T>(result: Result<T>) -> Check {
+        match result {
+            Ok(_) => Check::Passed,
+            Err(err) => Check::Failed(err.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Check::Passed => write!(f, "ok"),
+            Check::Failed(err) => write!(f, "failed: {}", err),
+            Check::Skipped(reason) => write!(f, "skipped: {}", reason),
+        }
+    }
+}
+
+/// Report of [verify_capture()] on what works on a device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaptureReport {
+    /// Name of the device checked.
+    pub device: String,
+    /// Activating a live capture on the device.
+    pub activate: Check,
+    /// Link-layer header type of the activated capture.
+    pub linktype: Option<LinkType>,
+    /// Compiling and installing a filter.
+    pub filter: Check,
+    /// Injecting a probe packet. Only attempted on loopback devices.
+    pub inject: Check,
+    /// Capturing the injected probe packet.
+    pub capture: Check,
+}
+
+impl CaptureReport {
+    fn new(device: &str) -> CaptureReport {
+        let skipped = || Check::Skipped(String::from("capture not activated"));
+        CaptureReport {
+            device: device.to_string(),
+            activate: skipped(),
+            linktype: None,
+            filter: skipped(),
+            inject: skipped(),
+            capture: skipped(),
+        }
+    }
+
+    /// Returns true if none of the steps failed.
+    pub fn is_ok(&self) -> bool {
+        ![&self.activate, &self.filter, &self.inject, &self.capture]
+            .iter()
+            .any(|check| check.is_failed())
+    }
+}
+
+impl fmt::Display for CaptureReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "capture on {}:", self.device)?;
+        write!(f, "  activate: {}", self.activate)?;
+        if let Some(linktype) = self.linktype {
+            write!(f, " ({})", linktype.description_or_dlt())?;
+        }
+        writeln!(f)?;
+        writeln!(f, "  filter: {}", self.filter)?;
+        writeln!(f, "  inject: {}", self.inject)?;
+        write!(f, "  capture: {}", self.capture)
+    }
+}
+
+/// Check whether packets can be captured on `interface`.
+///
+/// Activates a live capture on the device and installs a filter on it. On a
+/// loopback device, a UDP packet is injected too and captured back, which
+/// exercises the whole path from sending to reading packets. The steps that
+/// worked are reported in [CaptureReport], so deployments can validate at
+/// install time that the privileges and drivers needed for capturing are in
+/// place:
+///
+/// ```no_run
+/// let report = luomu_libpcap::verify_capture("lo")?;
+/// println!("{}", report);
+/// assert!(report.is_ok());
+/// # Ok::<(), luomu_libpcap::Error>(())
+/// ```
+///
+/// Returns an error only if a capture handle can't be created for the
+/// device at all.
+pub fn verify_capture(interface: &str) -> Result<CaptureReport> {
+    let mut report = CaptureReport::new(interface);
+    let activated = Pcap::builder(interface)?
+        .set_immediate(true)
+        .and_then(|builder| builder.set_timeout(Duration::from_millis(100)))
+        .and_then(|builder| builder.activate());
+    let pcap = match activated {
+        Ok(pcap) => pcap,
+        Err(err) => {
+            report.activate = Check::Failed(err.to_string());
+            return Ok(report);
+        }
+    };
+    report.activate = Check::Passed;
+    report.linktype = pcap.datalink().ok();
+
+    report.filter = Check::from_result(pcap.set_filter("len >= 0"));
+    if report.filter.is_failed() {
+        report.inject = Check::Skipped(String::from("filter not installed"));
+        report.capture = Check::Skipped(String::from("filter not installed"));
+        return Ok(report);
+    }
+
+    if !is_loopback(interface) {
+        report.inject = Check::Skipped(String::from("not a loopback device"));
+        report.capture = Check::Skipped(String::from("not a loopback device"));
+        return Ok(report);
+    }
+    let frame = report
+        .linktype
+        .ok_or_else(|| Error::PcapError(String::from("unknown link-layer header type")))
+        .and_then(probe_frame);
+    let injected = frame.and_then(|frame| {
+        pcap.set_filter(&format!("udp dst port {}", PROBE_PORT))?;
+        pcap.inject(&frame)
+    });
+    report.inject = Check::from_result(injected);
+    if report.inject.is_failed() {
+        report.capture = Check::Skipped(String::from("probe not injected"));
+        return Ok(report);
+    }
+    report.capture = match capture_probe(&pcap) {
+        Ok(true) => Check::Passed,
+        Ok(false) => Check::Failed(String::from("probe not captured")),
+        Err(err) => Check::Failed(err.to_string()),
+    };
+    Ok(report)
+}
+
+fn is_loopback(interface: &str) -> bool {
+    PcapIfT::new()
+        .map(|interfaces| {
+            interfaces
+                .iter()
+                .any(|iface| iface.name == interface && iface.is_loopback())
+        })
+        .unwrap_or(false)
+}
+
+fn probe_frame(linktype: LinkType) -> Result<Vec<u8>> {
+    let addr = |port| SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let builder = FrameBuilder::udp(addr(PROBE_PORT), addr(PROBE_PORT)).payload(PROBE_PAYLOAD);
+    if linktype == LinkType::ETHERNET {
+        builder.build()
+    } else {
+        loopback_frame(linktype, &builder.build_ip()?)
+    }
+}
+
+// Read until the probe is seen or no more packets arrive in time.
+fn capture_probe(pcap: &Pcap) -> Result<bool> {
+    while let Some(packet) = pcap.wait_packet(PROBE_TIMEOUT)? {
+        if packet.packet().ends_with(PROBE_PAYLOAD) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{probe_frame, CaptureReport, Check, PROBE_PAYLOAD};
+    use crate::LinkType;
+
+    #[test]
+    fn test_capture_report() {
+        let mut report = CaptureReport::new("lo");
+        assert!(report.is_ok());
+        report.activate = Check::Passed;
+        report.filter = Check::Failed(String::from("syntax error"));
+        assert!(!report.is_ok());
+        let text = report.to_string();
+        assert!(text.contains("activate: ok"), "{}", text);
+        assert!(text.contains("filter: failed: syntax error"), "{}", text);
+        assert!(text.contains("inject: skipped"), "{}", text);
+    }
+
+    #[test]
+    fn test_probe_frame() {
+        let frame = probe_frame(LinkType::ETHERNET).unwrap();
+        assert_eq!(&frame[12..14], &[0x08, 0x00]);
+        assert!(frame.ends_with(PROBE_PAYLOAD));
+        let frame = probe_frame(LinkType::NULL).unwrap();
+        assert_eq!(frame[4], 0x45);
+        assert!(probe_frame(LinkType(147)).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::functions::pcap_lib_version;
+use crate::Result;
+
+/// Get the version information for libpcap.
+///
+/// Returns the string from `pcap_lib_version()`, for example "libpcap version
+/// 1.10.4 (with TPACKET_V3)". Note that it contains more information than
+/// just a version number.
+///
+/// With `libpcap-dlopen` feature fails if libpcap can't be loaded.
+pub fn libpcap_version() -> Result<String> {
+    pcap_lib_version()
+}
+
+/// Returns true if libpcap is available.
+///
+/// With `libpcap-dlopen` feature libpcap is loaded at runtime, and this
+/// returns false if it is not installed, in which case capturing is not
+/// possible. Otherwise libpcap is linked into the program and this always
+/// returns true.
+pub fn libpcap_available() -> bool {
+    loaded()
+}
+
+#[cfg(feature = "libpcap-dlopen")]
+fn loaded() -> bool {
+    luomu_libpcap_sys::load().is_ok()
+}
+
+#[cfg(not(feature = "libpcap-dlopen"))]
+fn loaded() -> bool {
+    true
+}
+
+/// Optional libpcap APIs available at runtime.
+///
+/// Different platforms ship different versions of libpcap (or Npcap on
+/// Windows). `Capabilities::probe()` checks the version of libpcap in use and
+/// tells which optional APIs can be expected to work so callers can degrade
+/// gracefully.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Version of libpcap as `(major, minor, patch)`.
+    pub version: (u32, u32, u32),
+    /// Time stamp types can be listed and set (libpcap 1.2.0 and later).
+    pub tstamp_types: bool,
+    /// Time stamp precision can be set (libpcap 1.5.0 and later).
+    pub tstamp_precision: bool,
+    /// Immediate mode can be set (libpcap 1.5.0 and later).
+    pub immediate_mode: bool,
+    /// `pcap_set_protocol_linux()` is available (libpcap 1.10.0 and later on
+    /// Linux).
+    pub set_protocol_linux: bool,
+}
+
+impl Capabilities {
+    /// Probe the capabilities of libpcap in use.
+    pub fn probe() -> Result<Capabilities> {
+        let version = parse_version(&libpcap_version()?).unwrap_or((0, 0, 0));
+        Ok(Capabilities::from_version(version))
+    }
+
+    fn from_version(version: (u32, u32, u32)) -> Capabilities {
+        let at_least = |major: u32, minor: u32| (version.0, version.1) >= (major, minor);
+        Capabilities {
+            version,
+            tstamp_types: at_least(1, 2),
+            tstamp_precision: at_least(1, 5),
+            immediate_mode: at_least(1, 5),
+            set_protocol_linux: cfg!(target_os = "linux") && at_least(1, 10),
+        }
+    }
+}
+
+// Parse version number out from pcap_lib_version() string. Npcap reports
+// something like "Npcap version 1.71, based on libpcap version 1.10.2-PRE-GIT"
+// so look for the last "libpcap version".
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    const PREFIX: &str = "libpcap version ";
+    let start = version.rfind(PREFIX)? + PREFIX.len();
+    let number = version[start..]
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .next()?;
+
+    let mut parts = number.split('.').map(|p| p.parse::<u32>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    Some((major, minor, patch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_version, Capabilities};
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            parse_version("libpcap version 1.10.4 (with TPACKET_V3)"),
+            Some((1, 10, 4))
+        );
+        assert_eq!(
+            parse_version("Npcap version 1.71, based on libpcap version 1.10.2-PRE-GIT"),
+            Some((1, 10, 2))
+        );
+        assert_eq!(parse_version("libpcap version 1.5"), Some((1, 5, 0)));
+        assert_eq!(parse_version("something else"), None);
+    }
+
+    #[test]
+    fn test_capabilities_from_version() {
+        let old = Capabilities::from_version((1, 1, 1));
+        assert!(!old.tstamp_types);
+        assert!(!old.immediate_mode);
+        assert!(!old.set_protocol_linux);
+
+        let new = Capabilities::from_version((1, 10, 4));
+        assert!(new.tstamp_types);
+        assert!(new.tstamp_precision);
+        assert!(new.immediate_mode);
+        assert_eq!(new.set_protocol_linux, cfg!(target_os = "linux"));
+    }
+}
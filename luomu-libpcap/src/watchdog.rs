@@ -0,0 +1,181 @@
+use std::time::{Duration, Instant};
+
+use crate::{Pcap, Result};
+
+type Reference = Box<dyn FnMut() -> Result<u32> + Send>;
+
+/// Watchdog detecting captures which stalled while the interface did not.
+///
+/// A capture reading no packets on a quiet network is fine, but one reading
+/// no packets while the interface keeps receiving them is likely
+/// misconfigured, for example with a filter which matches nothing or a
+/// wrong link-layer header type. `StallWatchdog` tells these apart with a
+/// second source of statistics, the reference, which counts packets
+/// received on the interface regardless of the capture. The capture is
+/// stalled if no packets have been read from it for `period` while the
+/// reference count increased.
+///
+/// The reference is a function returning a packet count which increases as
+/// packets are received, such as the `ps_recv` of another capture without a
+/// filter (see `StallWatchdog::with_reference_capture()`) or the receive
+/// counter of the interface from the operating system. The count is a `u32`
+/// which may wrap around, as `ps_recv` does.
+///
+/// Call `check()` periodically, e.g. on each packet buffer timeout:
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use luomu_libpcap::{Pcap, StallWatchdog};
+///
+/// let pcap = Pcap::builder("eth0")?.set_timeout(Duration::from_secs(1))?.activate()?;
+/// pcap.set_filter("udp port 53")?;
+/// let mut watchdog = StallWatchdog::with_reference_capture("eth0", Duration::from_secs(30))?;
+/// loop {
+///     for packet in pcap.capture() {
+///         // ...
+///     }
+///     if watchdog.check(&pcap)? {
+///         eprintln!("no DNS packets captured, check the filter");
+///     }
+/// }
+/// # Ok::<(), luomu_libpcap::Error>(())
+/// ```
+pub struct StallWatchdog {
+    period: Duration,
+    reference: Reference,
+    // Start of the current period and the reference count at it.
+    window: Option<(Instant, u32)>,
+    stalled: bool,
+}
+
+impl StallWatchdog {
+    /// Construct a new `StallWatchdog` with stall period `period` and
+    /// reference packet count from `reference`.
+    pub fn new<F>(period: Duration, reference: F) -> StallWatchdog
+    where
+        F: FnMut() -> Result<u32> + Send + 'static,
+    {
+        StallWatchdog {
+            period,
+            reference: Box::new(reference),
+            window: None,
+            stalled: false,
+        }
+    }
+
+    /// Construct a new `StallWatchdog` with stall period `period` and a
+    /// capture on `device` without a filter as the reference.
+    ///
+    /// The reference capture is never read from, only its statistics are.
+    /// It captures only a few bytes of each packet into a small buffer so it
+    /// uses little memory.
+    pub fn with_reference_capture(device: &str, period: Duration) -> Result<StallWatchdog> {
+        let reference = Pcap::builder(device)?
+            .set_snaplen(64)?
+            .set_buffer_size(64 * 1024)?
+            .activate()?;
+        Ok(StallWatchdog::new(period, move || {
+            Ok(reference.stats()?.packets_received())
+        }))
+    }
+
+    /// Check whether `pcap` has stalled.
+    ///
+    /// Returns true if no packets have been read from `pcap` for the stall
+    /// period while the reference count increased. A stalled capture stays
+    /// stalled until packets are read from it again. The first call starts
+    /// the first period.
+    pub fn check(&mut self, pcap: &Pcap) -> Result<bool> {
+        let count = (self.reference)()?;
+        let stalled = self.update(pcap.activity.last_packet(), Instant::now(), count);
+        Ok(stalled)
+    }
+
+    /// Returns true if the capture was stalled on the last `check()`.
+    pub fn is_stalled(&self) -> bool {
+        self.stalled
+    }
+
+    fn update(&mut self, last_packet: Option<Instant>, now: Instant, count: u32) -> bool {
+        let (start, start_count) = match self.window {
+            Some((start, _)) if last_packet.is_some_and(|last| last >= start) => {
+                if self.stalled {
+                    log::info!("capture is receiving packets again");
+                }
+                self.stalled = false;
+                self.window = Some((now, count));
+                return false;
+            }
+            Some(window) => window,
+            None => {
+                self.window = Some((now, count));
+                return false;
+            }
+        };
+        if now.duration_since(start) < self.period || self.stalled {
+            return self.stalled;
+        }
+        let received = count.wrapping_sub(start_count);
+        if received > 0 {
+            log::warn!(
+                "capture stalled: no packets read in {:?} while {} packets were received",
+                self.period,
+                received
+            );
+            self.stalled = true;
+        } else {
+            self.window = Some((now, count));
+        }
+        self.stalled
+    }
+}
+
+impl std::fmt::Debug for StallWatchdog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StallWatchdog")
+            .field("period", &self.period)
+            .field("stalled", &self.stalled)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::StallWatchdog;
+
+    #[test]
+    fn test_stall_watchdog() {
+        let mut watchdog = StallWatchdog::new(Duration::from_secs(10), || Ok(0));
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert!(!watchdog.update(None, at(0), 100));
+        // Quiet interface is not a stall.
+        assert!(!watchdog.update(None, at(11), 100));
+        // Packets read from the capture restart the period.
+        assert!(!watchdog.update(Some(at(12)), at(15), 200));
+        assert!(!watchdog.update(Some(at(12)), at(20), 300));
+        assert!(watchdog.update(Some(at(12)), at(26), 300));
+        assert!(watchdog.is_stalled());
+        assert!(watchdog.update(Some(at(12)), at(40), 300));
+        assert!(!watchdog.update(Some(at(41)), at(42), 400));
+        assert!(!watchdog.is_stalled());
+    }
+
+    #[test]
+    fn test_stall_watchdog_wrapping() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // Reference count wrapping around is still an increase.
+        let mut watchdog = StallWatchdog::new(Duration::from_secs(10), || Ok(0));
+        assert!(!watchdog.update(None, at(0), u32::MAX - 5));
+        assert!(watchdog.update(None, at(11), 10));
+
+        let mut watchdog = StallWatchdog::new(Duration::from_secs(10), || Ok(0));
+        assert!(!watchdog.update(None, at(0), u32::MAX));
+        assert!(!watchdog.update(None, at(11), u32::MAX));
+    }
+}
@@ -0,0 +1,117 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use luomu_libpcap::{
+    require_live_capture, verify_capture, CaptureObserver, Check, Health, Pcap, PcapStat, Result,
+};
+
+#[test]
+fn test_live_capture_loopback() -> Result<()> {
+    let device = require_live_capture!(Ok(()));
+    let pcap = Pcap::builder(&device)?
+        .set_timeout(Duration::from_millis(10))?
+        .activate()?;
+    assert!(pcap.datalink().is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_verify_capture_loopback() -> Result<()> {
+    let device = require_live_capture!(Ok(()));
+    let report = verify_capture(&device)?;
+    assert!(report.is_ok(), "{}", report);
+    assert_eq!(report.activate, Check::Passed);
+    Ok(())
+}
+
+#[derive(Default)]
+struct Events(Mutex<Vec<String>>);
+
+impl CaptureObserver for Events {
+    fn on_activated(&self, source: &str) {
+        self.0.lock().unwrap().push(format!("activated {}", source));
+    }
+
+    fn on_filter_set(&self, _source: &str, filter: &str) {
+        self.0.lock().unwrap().push(format!("filter {}", filter));
+    }
+
+    fn on_stats(&self, _source: &str, _stats: &PcapStat) {
+        self.0.lock().unwrap().push(String::from("stats"));
+    }
+}
+
+#[test]
+fn test_capture_observer() -> Result<()> {
+    let device = require_live_capture!(Ok(()));
+    let events = Arc::new(Events::default());
+    let pcap = Pcap::builder(&device)?
+        .set_timeout(Duration::from_millis(10))?
+        .set_observer(events.clone())?
+        .activate()?;
+    pcap.set_filter("udp")?;
+    assert!(pcap.set_filter("not a filter").is_err());
+    pcap.stats()?;
+    assert_eq!(
+        *events.0.lock().unwrap(),
+        vec![
+            format!("activated {}", device),
+            "filter udp".into(),
+            "stats".into()
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_reopen_with() -> Result<()> {
+    let device = require_live_capture!(Ok(()));
+    let pcap = Pcap::builder(&device)?
+        .set_timeout(Duration::from_millis(10))?
+        .set_snaplen(96)?
+        .activate()?;
+    pcap.set_filter("udp")?;
+    let (pcap, _gap) = pcap.reopen_with(|builder| builder.set_snaplen(1024))?;
+    assert_eq!(pcap.snaplen()?, 1024);
+    let config = pcap.config().unwrap();
+    assert_eq!(config.timeout, Some(Duration::from_millis(10)));
+    assert_eq!(config.filter.as_deref(), Some("udp"));
+
+    assert!(Pcap::new(&device)?.reopen_with(Ok).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_health() -> Result<()> {
+    let device = require_live_capture!(Ok(()));
+    let pcap = Pcap::builder(&device)?
+        .set_timeout(Duration::from_millis(10))?
+        .activate()?;
+    assert_eq!(pcap.health(), Health::Healthy);
+    pcap.set_stall_threshold(Duration::ZERO);
+    std::thread::sleep(Duration::from_millis(1));
+    assert_eq!(pcap.health(), Health::Stalled);
+    Ok(())
+}
+
+#[test]
+fn test_pcap_debug() -> Result<()> {
+    let device = require_live_capture!(Ok(()));
+    let pcap = Pcap::builder(&device)?
+        .set_timeout(Duration::from_millis(10))?
+        .set_snaplen(128)?
+        .activate()?;
+    pcap.set_filter("udp port 53")?;
+    let debug = format!("{:?}", pcap);
+    assert!(debug.contains("filter: Some(\"udp port 53\")"), "{}", debug);
+    assert!(debug.contains("snaplen: Some(128)"), "{}", debug);
+    assert!(debug.contains("activated: true"), "{}", debug);
+
+    let inactive = Pcap::new(&device)?;
+    assert!(!inactive.is_activated());
+    assert_ne!(inactive.id(), pcap.id());
+    let debug = format!("{:?}", *inactive);
+    assert!(debug.contains("activated: false"), "{}", debug);
+    assert!(!debug.contains("snaplen"), "{}", debug);
+    Ok(())
+}
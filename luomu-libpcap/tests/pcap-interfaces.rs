@@ -1,6 +1,7 @@
+use std::collections::BTreeSet;
 use std::net::IpAddr;
 
-use luomu_libpcap::{PcapFilter, PcapIfT, Result};
+use luomu_libpcap::{Interface, InterfaceFlag, InterfaceFlags, PcapFilter, PcapIfT, Result};
 
 #[test]
 fn test_get_interfaces() -> Result<()> {
@@ -9,6 +10,33 @@ fn test_get_interfaces() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_interfaces_sorted() -> Result<()> {
+    let interfaces = luomu_libpcap::interfaces()?;
+    assert!(interfaces.windows(2).all(|w| w[0].name <= w[1].name));
+    Ok(())
+}
+
+#[test]
+fn test_interface_display() {
+    let mut interface = Interface {
+        name: String::from("eth0"),
+        description: None,
+        addresses: BTreeSet::new(),
+        flags: BTreeSet::new(),
+        raw_flags: InterfaceFlags::empty(),
+    };
+    assert_eq!(interface.to_string(), "eth0");
+
+    interface.description = Some(String::from("Ethernet adapter"));
+    interface.flags.insert(InterfaceFlag::Running);
+    interface.flags.insert(InterfaceFlag::Up);
+    assert_eq!(
+        interface.to_string(),
+        "eth0 (Ethernet adapter) [Up, Running]"
+    );
+}
+
 #[test]
 fn test_find_localhost() -> Result<()> {
     let localhost: IpAddr = "127.0.0.1".parse().unwrap();
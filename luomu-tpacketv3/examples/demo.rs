@@ -169,10 +169,12 @@ mod linux {
             .spawn(move || packet_consumer(rx, c_stop))
             .unwrap();
 
-        let mut params: tpacketv3::ReaderParameters = Default::default();
-        params.block_count = blocks;
-        params.block_size = blocksize;
-        params.fanout = fanout_mode;
+        let params = tpacketv3::ReaderParameters {
+            block_count: blocks,
+            block_size: blocksize,
+            fanout: fanout_mode,
+            ..Default::default()
+        };
         let mut producers = Vec::new();
         for i in 0..workers {
             let flag = stop.clone();
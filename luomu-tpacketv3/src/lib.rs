@@ -405,7 +405,6 @@ impl<'a> Iterator for PacketIter<'a> {
 }
 
 #[cfg(test)]
-
 mod tests {
     #[test]
     fn it_works() {